@@ -510,6 +510,19 @@ in
       };
     });
 
+    "registry+https://github.com/rust-lang/crates.io-index".cmake."0.1.50" = overridableMkRustCrate (profileName: rec {
+      name = "cmake";
+      version = "0.1.50";
+      registry = "registry+https://github.com/rust-lang/crates.io-index";
+      src = fetchCratesIo {
+        inherit name version;
+        sha256 = "a31c789563b815f77f4250caee12365734369f942439b7defd71e18a48197130";
+      };
+      dependencies = {
+        cc = (rustPackages."registry+https://github.com/rust-lang/crates.io-index".cc."1.0.101" {inherit profileName;}).out;
+      };
+    });
+
     "registry+https://github.com/rust-lang/crates.io-index".colorchoice."1.0.1" = overridableMkRustCrate (profileName: rec {
       name = "colorchoice";
       version = "1.0.1";
@@ -1288,6 +1301,7 @@ in
         rand = (rustPackages."registry+https://github.com/rust-lang/crates.io-index".rand."0.8.5" {inherit profileName;}).out;
         rustsat = (rustPackages."registry+https://github.com/rust-lang/crates.io-index".rustsat."0.4.3" {inherit profileName;}).out;
         rustsat_kissat = (rustPackages."registry+https://github.com/rust-lang/crates.io-index".rustsat-kissat."0.1.6" {inherit profileName;}).out;
+        rustsat_minisat = (rustPackages."registry+https://github.com/rust-lang/crates.io-index".rustsat-minisat."0.2.3" {inherit profileName;}).out;
         volute = (rustPackages."registry+https://github.com/rust-lang/crates.io-index".volute."1.1.5" {inherit profileName;}).out;
       };
     });
@@ -1479,6 +1493,27 @@ in
       };
     });
 
+    "registry+https://github.com/rust-lang/crates.io-index".rustsat-minisat."0.2.3" = overridableMkRustCrate (profileName: rec {
+      name = "rustsat-minisat";
+      version = "0.2.3";
+      registry = "registry+https://github.com/rust-lang/crates.io-index";
+      src = fetchCratesIo {
+        inherit name version;
+        sha256 = "ff65af036bc3e3ee2e5ca024e1dd7ee8abba47746a4cc0a90f201718530d1dd7";
+      };
+      dependencies = {
+        cpu_time = (rustPackages."registry+https://github.com/rust-lang/crates.io-index".cpu-time."1.0.0" {inherit profileName;}).out;
+        rustsat = (rustPackages."registry+https://github.com/rust-lang/crates.io-index".rustsat."0.4.3" {inherit profileName;}).out;
+      };
+      buildDependencies = {
+        cc = (buildRustPackages."registry+https://github.com/rust-lang/crates.io-index".cc."1.0.101" {profileName = "__noProfile";}).out;
+        chrono = (buildRustPackages."registry+https://github.com/rust-lang/crates.io-index".chrono."0.4.38" {profileName = "__noProfile";}).out;
+        cmake = (buildRustPackages."registry+https://github.com/rust-lang/crates.io-index".cmake."0.1.50" {profileName = "__noProfile";}).out;
+        git2 = (buildRustPackages."registry+https://github.com/rust-lang/crates.io-index".git2."0.18.3" {profileName = "__noProfile";}).out;
+        glob = (buildRustPackages."registry+https://github.com/rust-lang/crates.io-index".glob."0.3.1" {profileName = "__noProfile";}).out;
+      };
+    });
+
     "registry+https://github.com/rust-lang/crates.io-index".ryu."1.0.18" = overridableMkRustCrate (profileName: rec {
       name = "ryu";
       version = "1.0.18";
@@ -5,12 +5,18 @@
       url = "github:cargo2nix/cargo2nix/release-0.11.0";
       inputs.nixpkgs.follows = "nixpkgs";
     };
+    # Minisat fork with the C interface used by rustsat-minisat, at the commit its build.rs pins
+    minisat = {
+      url = "github:chrjabs/minisat/f64a4f78eea61927dec9f151650504defba490c1";
+      flake = false;
+    };
   };
 
   outputs = {
     self,
     nixpkgs,
     cargo2nix,
+    minisat,
     ...
   }: {
     packages = nixpkgs.lib.genAttrs ["x86_64-linux" "aarch64-linux" "x86_64-darwin" "aarch64-darwin"] (system: let
@@ -18,6 +24,13 @@
         inherit system;
         overlays = [cargo2nix.overlays.default];
       };
+      minisat-lib = pkgs.stdenv.mkDerivation {
+        pname = "minisat-rustsat";
+        version = "f64a4f7";
+        src = minisat;
+        nativeBuildInputs = [pkgs.cmake];
+        buildInputs = [pkgs.zlib];
+      };
       rustPkgs = pkgs.rustBuilder.makePackageSet {
         rustVersion = "1.75.0";
         # You can regenerate Cargo.nix using this command:
@@ -41,6 +54,18 @@
                 NIX_KISSAT_DIR="${pkgs.kissat.lib}";
               };
             })
+            (pkgs.rustBuilder.rustLib.makeOverride {
+              name = "rustsat-minisat";
+              overrideAttrs = {
+                buildInputs = [
+                  minisat-lib
+                ];
+                patches = [
+                  ./nix/patches/rustsat-minisat.patch
+                ];
+                NIX_MINISAT_DIR="${minisat-lib}";
+              };
+            })
           ];
       };
       self = {
@@ -42,20 +42,13 @@ pub fn expose_dff(aig: &Network) -> Network {
     ret
 }
 
-/// Find a new test pattern for a specific fault using a SAT solver
+/// Modify a network to introduce a fault
 ///
-/// Each gate may be in one of two cases:
-///     * in the logic cone after the fault: those need to be duplicated with/without the fault
-///     * elsewhere, where they don't need to be duplicated
-/// To keep things simpler, we create the full network with/without the fault, and let basic
-/// deduplication handle the rest.
-fn find_pattern_detecting_fault(aig: &Network, fault: Fault) -> Option<Vec<bool>> {
-    assert!(aig.is_comb());
-
-    let mut fault_aig = aig.clone();
+/// The faulty connection is replaced by a constant, in place, so that node indices are unchanged.
+pub(crate) fn apply_fault(aig: &mut Network, fault: Fault) {
     match fault {
         Fault::OutputStuckAtFault { gate, value } => {
-            fault_aig.replace(gate, Gate::Buf(Signal::from(value)));
+            aig.replace(gate, Gate::Buf(Signal::from(value)));
         }
         Fault::InputStuckAtFault { gate, input, value } => {
             let g =
@@ -68,9 +61,24 @@ fn find_pattern_detecting_fault(aig: &Network, fault: Fault) -> Option<Vec<bool>
                         }
                     },
                 );
-            fault_aig.replace(gate, g);
+            aig.replace(gate, g);
         }
     };
+}
+
+/// Find a new test pattern for a specific fault using a SAT solver
+///
+/// Each gate may be in one of two cases:
+///     * in the logic cone after the fault: those need to be duplicated with/without the fault
+///     * elsewhere, where they don't need to be duplicated
+/// To keep things simpler, we create the full network with/without the fault, and let basic
+/// deduplication handle the rest.
+/// Returns None if the fault is untestable.
+pub(crate) fn find_pattern_detecting_fault(aig: &Network, fault: Fault) -> Option<Vec<bool>> {
+    assert!(aig.is_comb());
+
+    let mut fault_aig = aig.clone();
+    apply_fault(&mut fault_aig, fault);
 
     let mut diff = difference(aig, &fault_aig);
     diff.make_canonical();
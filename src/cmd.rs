@@ -4,12 +4,22 @@ use crate::atpg::{
     expose_dff, generate_comb_test_patterns, generate_random_seq_patterns,
     report_comb_test_patterns,
 };
-use crate::equiv::check_equivalence_bounded;
-use crate::io::{read_network_file, read_pattern_file, write_network_file, write_pattern_file};
-use crate::optim;
+use crate::equiv::{
+    check_equivalence_bounded, check_equivalence_unbounded, check_property, check_property_bounded,
+    ProofResult, PropertyResult,
+};
+use crate::io::{
+    read_library_file, read_network_file, read_pattern_file, write_mapped_file, write_network_file,
+    write_pattern_file, Error,
+};
+use crate::network::area::AreaParameters;
+use crate::network::timing::DelayParameters;
+use crate::optim::{self, Script};
 use crate::sim::simulate;
-use clap::{Args, Parser, Subcommand};
+use crate::{Gate, Network};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
+use std::time::Instant;
 
 /// Command line arguments
 #[derive(Parser)]
@@ -25,17 +35,34 @@ pub struct Cli {
 pub enum Commands {
     /// Show statistics about a logic network
     ///
-    /// Will print statistics on the number of inputs, outputs and gates in the network.
+    /// Will print statistics on the number of inputs, outputs and gates in the network, and its
+    /// logic depth.
     #[clap()]
     Show(ShowArgs),
 
     /// Optimize a logic network
     ///
-    /// At the moment this is simple constant propagation and deduplication,
-    /// but will grow in power over time.
+    /// This performs constant propagation, deduplication and logic sharing,
+    /// and will grow in power over time. A custom sequence of passes can be given with --script,
+    /// and the area, depth and runtime are reported after each pass.
     #[clap(visible_alias = "opt")]
     Optimize(OptArgs),
 
+    /// Map a logic network to a technology
+    ///
+    /// Either K-input LUTs for FPGAs, or the cells of a standard cell library in .genlib or
+    /// Liberty format. LUT mapping minimizes depth first, then area. Cell mapping minimizes area,
+    /// without exceeding the delay target if one is given.
+    #[clap()]
+    Map(MapArgs),
+
+    /// Retime a sequential network
+    ///
+    /// Flip-flops without enable or reset are moved forward across the logic, to minimize the
+    /// logic depth or the number of flip-flops, and the logic depth achieved is reported.
+    #[clap()]
+    Retime(RetimeArgs),
+
     /// Simulate a logic network
     ///
     /// This uses the same test pattern format as Atalanta, with one bit per input:
@@ -63,15 +90,40 @@ pub enum Commands {
     /// Check equivalence between two logic networks
     ///
     /// The command will fail if the two networks are not equivalent, and will output the
-    /// failing test pattern.
+    /// failing test pattern. By default, sequential networks are only compared for a fixed
+    /// number of cycles; use --unbounded for a full proof by k-induction.
     #[clap(visible_alias = "equiv")]
     CheckEquivalence(EquivArgs),
 
+    /// Check that the outputs of a sequential network can never be 1
+    ///
+    /// Each output is a property violation, starting from the all-zero state of the flip-flops.
+    /// The check uses property-directed reachability (IC3/PDR). The command will fail if a
+    /// violation is reachable, and will output the sequence of inputs leading to it.
+    #[clap()]
+    CheckProperty(PropertyArgs),
+
+    /// Bounded model checking of a sequential network
+    ///
+    /// Search for a sequence of inputs that sets an output to 1, starting from the all-zero
+    /// state of the flip-flops, for an increasing number of cycles. The command will fail if
+    /// such a sequence is found, and will output it.
+    #[clap()]
+    Bmc(BmcArgs),
+
     /// Read a logic network and write it in another format
     #[clap()]
     Convert(ConvertArgs),
 }
 
+/// Unwrap the result of a file operation, or report the error and exit
+fn or_exit<T>(res: Result<T, Error>) -> T {
+    res.unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    })
+}
+
 /// Command arguments for equivalence checking
 #[derive(Args)]
 pub struct EquivArgs {
@@ -87,12 +139,45 @@ pub struct EquivArgs {
     /// Use only the Sat solver, skipping internal optimizations
     #[arg(long)]
     sat_only: bool,
+
+    /// Prove equivalence for any number of cycles, using k-induction
+    #[arg(long)]
+    unbounded: bool,
+
+    /// Maximum induction depth for unbounded equivalence checking
+    #[arg(long, default_value_t = 20)]
+    max_depth: usize,
+}
+
+/// Print a failing test pattern, one line per cycle, after the names of the inputs
+fn print_pattern(aig: &Network, pattern: &[Vec<bool>]) {
+    let names: Vec<String> = (0..aig.nb_inputs())
+        .map(|i| match aig.input_name(i) {
+            Some(name) => name.to_string(),
+            None => aig.input(i).to_string(),
+        })
+        .collect();
+    println!("Test pattern ({}):", names.join(", "));
+    print!("{}", format_pattern(pattern));
+}
+
+/// Format a test pattern, one line per cycle with one bit per input
+fn format_pattern(pattern: &[Vec<bool>]) -> String {
+    let mut ret = String::new();
+    for v in pattern {
+        ret.push('\t');
+        for b in v {
+            ret.push(if *b { '1' } else { '0' });
+        }
+        ret.push('\n');
+    }
+    ret
 }
 
 impl EquivArgs {
     pub fn run(&self) {
-        let aig1 = read_network_file(&self.file1);
-        let aig2 = read_network_file(&self.file2);
+        let aig1 = or_exit(read_network_file(&self.file1));
+        let aig2 = or_exit(read_network_file(&self.file2));
         if aig1.nb_inputs() != aig2.nb_inputs() {
             println!(
                 "Different number of inputs: {} vs {}. Networks are not equivalent",
@@ -109,20 +194,34 @@ impl EquivArgs {
             );
             std::process::exit(1);
         }
+        if self.unbounded {
+            match check_equivalence_unbounded(&aig1, &aig2, self.max_depth) {
+                ProofResult::Proved(depth) => {
+                    println!("Networks are equivalent (proved by induction at depth {depth})");
+                    std::process::exit(0);
+                }
+                ProofResult::Counterexample(pattern) => {
+                    println!(
+                        "Networks are not equivalent (difference at cycle {})",
+                        pattern.len() - 1
+                    );
+                    print_pattern(&aig1, &pattern);
+                    std::process::exit(1);
+                }
+                ProofResult::Unknown(depth) => {
+                    println!(
+                        "Equivalence is unknown: no difference found, but induction failed up to depth {depth}"
+                    );
+                    std::process::exit(2);
+                }
+            }
+        }
         let res = check_equivalence_bounded(&aig1, &aig2, self.num_cycles, !self.sat_only);
         let is_comb = aig1.is_comb() && aig2.is_comb();
         match res {
             Err(err) => {
                 println!("Networks are not equivalent");
-                println!("Test pattern:");
-                // TODO: extract the names here
-                for v in err {
-                    print!("\t");
-                    for b in v {
-                        print!("{}", if b { "0" } else { "1" });
-                    }
-                    println!();
-                }
+                print_pattern(&aig1, &err);
                 std::process::exit(1);
             }
             Ok(()) => {
@@ -137,6 +236,87 @@ impl EquivArgs {
     }
 }
 
+/// Command arguments for property checking
+#[derive(Args)]
+pub struct PropertyArgs {
+    /// Network to check
+    file: PathBuf,
+
+    /// Output file for the inductive invariant, as a network with one input per flip-flop
+    #[arg(long)]
+    invariant: Option<PathBuf>,
+
+    /// Output file for the counterexample, in test pattern format
+    #[arg(short = 'o', long)]
+    output: Option<PathBuf>,
+
+    /// Maximum number of frames explored
+    #[arg(long, default_value_t = 100)]
+    max_depth: usize,
+}
+
+impl PropertyArgs {
+    pub fn run(&self) {
+        let aig = or_exit(read_network_file(&self.file));
+        match check_property(&aig, self.max_depth) {
+            PropertyResult::Proved(invariant) => {
+                println!("Property holds");
+                if let Some(f) = &self.invariant {
+                    or_exit(write_network_file(f, &invariant));
+                }
+                std::process::exit(0);
+            }
+            PropertyResult::Counterexample(pattern) => {
+                println!("Property fails at cycle {}", pattern.len() - 1);
+                print_pattern(&aig, &pattern);
+                if let Some(f) = &self.output {
+                    or_exit(write_pattern_file(f, &vec![pattern]));
+                }
+                std::process::exit(1);
+            }
+            PropertyResult::Unknown(depth) => {
+                println!("Property is unknown: no conclusion after {depth} frames");
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
+/// Command arguments for bounded model checking
+#[derive(Args)]
+pub struct BmcArgs {
+    /// Network to check
+    file: PathBuf,
+
+    /// Output file for the counterexample, in test pattern format
+    #[arg(short = 'o', long)]
+    output: Option<PathBuf>,
+
+    /// Number of clock cycles considered
+    #[arg(short = 'c', long, default_value_t = 20)]
+    num_cycles: usize,
+}
+
+impl BmcArgs {
+    pub fn run(&self) {
+        let aig = or_exit(read_network_file(&self.file));
+        match check_property_bounded(&aig, self.num_cycles) {
+            Ok(()) => {
+                println!("No output can be 1 up to {} cycles", self.num_cycles);
+                std::process::exit(0);
+            }
+            Err(pattern) => {
+                println!("Output can be 1 at cycle {}", pattern.len() - 1);
+                print_pattern(&aig, &pattern);
+                if let Some(f) = &self.output {
+                    or_exit(write_pattern_file(f, &vec![pattern]));
+                }
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
 /// Command arguments for optimization
 #[derive(Args)]
 pub struct OptArgs {
@@ -154,23 +334,213 @@ pub struct OptArgs {
     /// Seed for randomized algorithms
     #[arg(long)]
     seed: Option<u64>,
+
+    /// Optimization objective
+    #[arg(long, value_enum, default_value_t = Objective::Area)]
+    objective: Objective,
+
+    /// Optimization script to run instead of the default recipe
+    ///
+    /// Passes are separated by semicolons or newlines, with optional integer arguments, for
+    /// example "canon; share 64; infer-xor; infer-dffe; share". The effort and objective are
+    /// ignored when a script is given.
+    #[arg(long, conflicts_with = "script_file")]
+    script: Option<String>,
+
+    /// File containing the optimization script to run instead of the default recipe
+    #[arg(long)]
+    script_file: Option<PathBuf>,
+}
+
+/// Objective for logic optimization
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Objective {
+    /// Minimize the number of gates
+    Area,
+    /// Minimize the logic depth, sharing logic when it does not increase the depth
+    Depth,
 }
 
 impl OptArgs {
+    /// Default optimization recipe, depending on the objective and the effort
+    fn default_script(&self) -> String {
+        let restructure = match self.objective {
+            Objective::Area => "share 64",
+            Objective::Depth => "balance 64",
+        };
+        let mut script = format!("canon; {}", restructure);
+        for _ in 0..self.effort {
+            script.push_str(&format!("; infer-xor; infer-dffe; {}", restructure));
+        }
+        script
+    }
+
+    /// Parse the script given by the user, or the default one
+    fn script(&self) -> Result<Script, Error> {
+        if let Some(text) = &self.script {
+            Script::parse(text)
+        } else if let Some(path) = &self.script_file {
+            let text = std::fs::read_to_string(path).map_err(|e| Error::from(e).with_file(path))?;
+            Script::parse(&text).map_err(|e| e.with_file(path))
+        } else {
+            Ok(Script::parse(&self.default_script()).unwrap())
+        }
+    }
+
     pub fn run(&self) {
-        let mut aig = read_network_file(&self.file);
+        let script = self.script().unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            if matches!(err, Error::Syntax { .. }) {
+                eprintln!("Available passes:\n{}", Script::help());
+            }
+            std::process::exit(1);
+        });
+        let mut aig = or_exit(read_network_file(&self.file));
         if let Some(s) = self.seed {
             aig.shuffle(s);
         }
+        let report = |name: &str, aig: &Network, time: &str| {
+            println!(
+                "{:<24}{:>12}{:>8}{:>10}",
+                name,
+                report_area(aig),
+                DelayParameters::unit().max_delay(aig),
+                time
+            );
+        };
+        println!("{:<24}{:>12}{:>8}{:>10}", "Pass", "Area", "Depth", "Time");
+        report("(input)", &aig, "");
+        for pass in script.passes() {
+            let start = Instant::now();
+            pass.run(&mut aig);
+            let time = format!("{:.2}s", start.elapsed().as_secs_f64());
+            report(&pass.to_string(), &aig, &time);
+        }
+        or_exit(write_network_file(&self.output, &aig));
+    }
+}
+
+/// Area of a network for reports, with Lut gates counted separately since their area is not
+/// modeled
+fn report_area(aig: &Network) -> String {
+    let params = AreaParameters::vlsi();
+    let mut area = 0;
+    let mut luts = 0;
+    for i in 0..aig.nb_nodes() {
+        match aig.gate(i) {
+            Gate::Lut(_) => luts += 1,
+            g => area += params.gate_area(g),
+        }
+    }
+    if luts == 0 {
+        area.to_string()
+    } else {
+        format!("{}+{}LUT", area, luts)
+    }
+}
+
+/// Command arguments for retiming
+#[derive(Args)]
+pub struct RetimeArgs {
+    /// Network to retime
+    file: PathBuf,
+
+    /// Output file for retimed network
+    #[arg(short = 'o', long)]
+    output: PathBuf,
+
+    /// Retiming objective
+    #[arg(long, value_enum, default_value_t = RetimeObjective::Depth)]
+    objective: RetimeObjective,
+}
+
+/// Objective for retiming
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RetimeObjective {
+    /// Minimize the number of flip-flops, regardless of the logic depth
+    Area,
+    /// Minimize the logic depth between flip-flops
+    Depth,
+}
+
+impl RetimeArgs {
+    pub fn run(&self) {
+        let mut aig = or_exit(read_network_file(&self.file));
         aig.cleanup();
         aig.make_canonical();
-        optim::share_logic(&mut aig, 64);
-        for _ in 0..self.effort {
-            optim::infer_xor_mux(&mut aig);
-            optim::infer_dffe(&mut aig);
-            optim::share_logic(&mut aig, 64);
+        let depth = match self.objective {
+            RetimeObjective::Area => optim::retime_min_area(&mut aig),
+            RetimeObjective::Depth => optim::retime_min_period(&mut aig),
+        };
+        println!("Depth after retiming: {}", depth);
+        or_exit(write_network_file(&self.output, &aig));
+    }
+}
+
+/// Command arguments for technology mapping
+#[derive(Args)]
+pub struct MapArgs {
+    /// Network to map
+    file: PathBuf,
+
+    /// Output file for mapped network
+    #[arg(short = 'o', long)]
+    output: PathBuf,
+
+    /// Map to LUTs with the given number of inputs
+    #[arg(long)]
+    lut: Option<usize>,
+
+    /// Map to the cells of a library (.genlib or .lib)
+    #[arg(long, conflicts_with = "lut")]
+    library: Option<PathBuf>,
+
+    /// Delay target for the mapping to library cells
+    #[arg(long, requires = "library")]
+    delay: Option<f64>,
+
+    /// Lower flip-flop enables into logic, for targets without flip-flops with enable
+    #[arg(long)]
+    lower_enable: bool,
+
+    /// Lower flip-flop resets into logic, for targets without flip-flops with reset
+    #[arg(long)]
+    lower_reset: bool,
+}
+
+impl MapArgs {
+    pub fn run(&self) {
+        let mut aig = or_exit(read_network_file(&self.file));
+        if self.lower_enable || self.lower_reset {
+            optim::lower_dffe(&mut aig, self.lower_enable, self.lower_reset);
+        }
+        if let Some(path) = &self.library {
+            let library = or_exit(read_library_file(path));
+            if !library.is_complete() {
+                eprintln!(
+                    "Error: the library must contain an inverter and a two-input And, Or, Nand or Nor cell"
+                );
+                std::process::exit(1);
+            }
+            let mapped = optim::map_cells(&aig, &library, self.delay);
+            println!("{}", mapped.report(&library));
+            or_exit(write_mapped_file(&self.output, &mapped, &library));
+            return;
+        }
+        match self.lut {
+            Some(k) => {
+                if !(3..=8).contains(&k) {
+                    eprintln!("Error: LUT size must be between 3 and 8");
+                    std::process::exit(1);
+                }
+                optim::map_luts(&mut aig, k);
+            }
+            None => {
+                eprintln!("Error: no mapping target given; use --lut or --library");
+                std::process::exit(1);
+            }
         }
-        write_network_file(&self.output, &aig);
+        or_exit(write_network_file(&self.output, &aig));
     }
 }
 
@@ -179,13 +549,36 @@ impl OptArgs {
 pub struct ShowArgs {
     /// Network to show
     file: PathBuf,
+
+    /// Show the slack histogram and the critical paths
+    #[arg(long)]
+    timing: bool,
 }
 
 impl ShowArgs {
     pub fn run(&self) {
         use crate::network::stats::stats;
-        let aig = read_network_file(&self.file);
-        println!("Network stats:\n{}\n\n", stats(&aig));
+        use crate::network::timing::{DelayParameters, TimingAnalysis};
+        let aig = or_exit(read_network_file(&self.file));
+        let timing = TimingAnalysis::new(&aig, &DelayParameters::unit());
+        println!(
+            "Network stats:\n{}  Depth: {}\n\n",
+            stats(&aig),
+            timing.max_delay()
+        );
+        if self.timing {
+            println!("Network timing:\n{}", timing);
+            for path in timing.critical_paths(&aig, 3) {
+                let names: Vec<String> = path
+                    .iter()
+                    .map(|i| match aig.node_name(*i) {
+                        Some(name) => name.to_string(),
+                        None => aig.node(*i).to_string(),
+                    })
+                    .collect();
+                println!("Critical path: {}", names.join(" -> "));
+            }
+        }
     }
 }
 
@@ -201,8 +594,8 @@ pub struct ConvertArgs {
 
 impl ConvertArgs {
     pub fn run(&self) {
-        let aig = read_network_file(&self.file);
-        write_network_file(&self.destination, &aig);
+        let aig = or_exit(read_network_file(&self.file));
+        or_exit(write_network_file(&self.destination, &aig));
     }
 }
 
@@ -227,16 +620,16 @@ pub struct SimulateArgs {
 
 impl SimulateArgs {
     pub fn run(&self) {
-        let mut aig = read_network_file(&self.network);
+        let mut aig = or_exit(read_network_file(&self.network));
         if self.expose_ff {
             aig = expose_dff(&aig);
         }
-        let input_values = read_pattern_file(&self.input);
+        let input_values = or_exit(read_pattern_file(&self.input));
         let mut output_values = Vec::new();
         for pattern in &input_values {
             output_values.push(simulate(&aig, pattern));
         }
-        write_pattern_file(&self.output, &output_values);
+        or_exit(write_pattern_file(&self.output, &output_values));
     }
 }
 
@@ -269,7 +662,7 @@ pub struct AtpgArgs {
 
 impl AtpgArgs {
     pub fn run(&self) {
-        let mut aig = read_network_file(&self.network);
+        let mut aig = or_exit(read_network_file(&self.network));
 
         if self.num_cycles.is_none() && self.num_random.is_none() {
             if !aig.is_comb() {
@@ -278,14 +671,14 @@ impl AtpgArgs {
             }
             let patterns = generate_comb_test_patterns(&aig, self.seed, self.with_redundant_faults);
             let seq_patterns = patterns.iter().map(|p| vec![p.clone()]).collect();
-            write_pattern_file(&self.output, &seq_patterns);
+            or_exit(write_pattern_file(&self.output, &seq_patterns));
         } else {
             println!("Generating only random patterns for multiple cycles");
             let nb_timesteps = self.num_cycles.unwrap_or(1);
             let nb_patterns = self.num_random.unwrap_or(4 * (aig.nb_inputs() + 1));
             let seq_patterns =
                 generate_random_seq_patterns(aig.nb_inputs(), nb_timesteps, nb_patterns, self.seed);
-            write_pattern_file(&self.output, &seq_patterns);
+            or_exit(write_pattern_file(&self.output, &seq_patterns));
         }
     }
 }
@@ -306,14 +699,36 @@ pub struct AtpgReportArgs {
 
 impl AtpgReportArgs {
     pub fn run(&self) {
-        let mut aig = read_network_file(&self.network);
+        let mut aig = or_exit(read_network_file(&self.network));
 
         if !aig.is_comb() {
             println!("Exposing flip-flops for a sequential network");
             aig = expose_dff(&aig);
         }
-        let seq_patterns = read_pattern_file(&self.patterns);
+        let seq_patterns = or_exit(read_pattern_file(&self.patterns));
         let patterns = seq_patterns.iter().map(|p| p[0].clone()).collect();
         report_comb_test_patterns(&aig, patterns, self.with_redundant_faults);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::format_pattern;
+    use crate::equiv::check_equivalence_bounded;
+    use crate::{Network, Signal};
+
+    #[test]
+    fn test_pattern_polarity() {
+        // The only difference between And(a, !b) and zero is for a=1, b=0
+        let mut a = Network::new();
+        let i0 = a.add_input();
+        let i1 = a.add_input();
+        let x = a.and(i0, !i1);
+        a.add_output(x);
+        let mut b = Network::new();
+        b.add_inputs(2);
+        b.add_output(Signal::zero());
+        let pattern = check_equivalence_bounded(&a, &b, 1, false).unwrap_err();
+        assert_eq!(format_pattern(&pattern), "\t10\n");
+    }
+}
@@ -1,5 +1,9 @@
 //! Equivalence checking
 
+mod bmc;
+mod pdr;
+mod solver;
+
 use std::collections::HashMap;
 
 use rustsat::solvers::Solve;
@@ -11,8 +15,13 @@ use rustsat_kissat::Kissat;
 use volute::Lut;
 
 use crate::network::{BinaryType, NaryType, TernaryType};
+use crate::optim;
 use crate::{Gate, Network, Signal};
 
+pub use bmc::check_property_bounded;
+pub use pdr::{check_property, PropertyResult};
+pub(crate) use solver::Prover;
+
 // TODO: have clean clause builder object to encapsulate this part
 
 /// Add clauses for And-type n-ary function
@@ -79,13 +88,35 @@ fn add_lut_clauses(clauses: &mut Vec<Vec<Signal>>, v: &[Signal], n: Signal, lut:
 
 /// Export a combinatorial network to a CNF formula
 fn to_cnf(aig: &Network) -> Vec<Vec<Signal>> {
-    use Gate::*;
     assert!(aig.is_comb());
-    let mut ret = Vec::<Vec<Signal>>::new();
     let mut var = aig.nb_nodes() as u32;
-    for i in 0..aig.nb_nodes() {
-        let n = aig.node(i);
-        match aig.gate(i) {
+    to_cnf_nodes(aig, 0..aig.nb_nodes(), &mut var)
+}
+
+/// Export a subset of the nodes of a network to a CNF formula
+///
+/// Additional variables are numbered starting from var, which is updated.
+/// Flip-flops are not allowed in the subset.
+fn to_cnf_nodes(
+    aig: &Network,
+    nodes: impl IntoIterator<Item = usize>,
+    var: &mut u32,
+) -> Vec<Vec<Signal>> {
+    to_cnf_gates(nodes.into_iter().map(|i| (aig.node(i), aig.gate(i))), var)
+}
+
+/// Export gates to a CNF formula, each with the signal of its output
+///
+/// Additional variables are numbered starting from var, which is updated.
+/// Flip-flops are not allowed.
+fn to_cnf_gates<'a>(
+    gates: impl IntoIterator<Item = (Signal, &'a Gate)>,
+    var: &mut u32,
+) -> Vec<Vec<Signal>> {
+    use Gate::*;
+    let mut ret = Vec::<Vec<Signal>>::new();
+    for (n, g) in gates {
+        match g {
             Binary([a, b], BinaryType::And) => {
                 // 3 clauses, 7 literals
                 ret.push(vec![*a, !n]);
@@ -108,8 +139,8 @@ fn to_cnf(aig: &Network) -> Vec<Vec<Signal>> {
             }
             Ternary([a, b, c], TernaryType::Xor) => {
                 // 8 clauses, 24 literals, one new variable
-                let v = Signal::from_var(var);
-                var += 1;
+                let v = Signal::from_var(*var);
+                *var += 1;
                 // First Xor to new variable
                 ret.push(vec![*a, *b, !v]);
                 ret.push(vec![!a, !b, !v]);
@@ -146,8 +177,8 @@ fn to_cnf(aig: &Network) -> Vec<Vec<Signal>> {
                 NaryType::Or => add_and_clauses(&mut ret, v, n, true, true),
                 NaryType::Nand => add_and_clauses(&mut ret, v, n, false, true),
                 NaryType::Nor => add_and_clauses(&mut ret, v, n, true, false),
-                NaryType::Xor => add_xor_clauses(&mut ret, &mut var, v, n, false),
-                NaryType::Xnor => add_xor_clauses(&mut ret, &mut var, v, n, true),
+                NaryType::Xor => add_xor_clauses(&mut ret, var, v, n, false),
+                NaryType::Xnor => add_xor_clauses(&mut ret, var, v, n, true),
             },
             Buf(s) => {
                 ret.push(vec![*s, !n]);
@@ -170,7 +201,7 @@ fn to_cnf(aig: &Network) -> Vec<Vec<Signal>> {
 }
 
 /// Copy the gates from one network to another and fill the existing translation table
-fn extend_aig_helper(
+pub(crate) fn extend_aig_helper(
     a: &mut Network,
     b: &Network,
     t: &mut HashMap<Signal, Signal>,
@@ -202,53 +233,126 @@ fn extend_aig_helper(
     }
 }
 
-/// Copy the gates from one network to another and fill the translation table
+/// Copy the gates, including flip-flops, from one network to another and fill the translation table
 fn extend_aig(a: &mut Network, b: &Network) -> HashMap<Signal, Signal> {
     let mut t = HashMap::<Signal, Signal>::new();
+
+    // Flip-flops are created first, as they may depend on any gate
+    let mut dffs = Vec::new();
+    for i in 0..b.nb_nodes() {
+        if !b.gate(i).is_comb() {
+            let s = a.add(Gate::dff(Signal::zero(), Signal::one(), Signal::zero()));
+            t.insert(b.node(i), s);
+            t.insert(!b.node(i), !s);
+            dffs.push((i, s));
+        }
+    }
     extend_aig_helper(a, b, &mut t, true);
+    for (i, s) in dffs {
+        a.replace(s.var() as usize, b.gate(i).remap(|x| t[x]));
+    }
     t
 }
 
-/// Unroll a sequential network over a fixed number of steps, making a larger combinatorial networks
-pub fn unroll(aig: &Network, nb_steps: usize) -> Network {
-    use Gate::*;
-    let mut ret = Network::new();
-
-    let mut t_prev = HashMap::new();
-    for step in 0..nb_steps {
-        let mut t = HashMap::new();
-
-        // Convert flip-flops for this step
-        for i in 0..aig.nb_nodes() {
-            if let Dff([d, en, res]) = aig.gate(i) {
-                let ff = aig.node(i);
-                let unroll_ff = if step == 0 {
-                    Signal::zero()
-                } else {
-                    let mx = ret.add_canonical(Gate::mux(t_prev[en], t_prev[d], t_prev[&ff]));
-                    ret.and(mx, !t_prev[res])
-                };
-                t.insert(ff, unroll_ff);
-                t.insert(!ff, !unroll_ff);
-            }
+/// Unrolled copy of a sequential network
+struct Unrolling {
+    /// Combinatorial network, without outputs
+    net: Network,
+    /// Value of the flip-flops at each step, optionally including the state after the last step
+    states: Vec<Vec<Signal>>,
+    /// Value of the outputs at each step
+    outputs: Vec<Vec<Signal>>,
+}
+
+/// Compute the value of the flip-flops of a sequential network for one step of an unrolling
+///
+/// The value is computed from the translation table of the previous step. For the first step,
+/// the value is zero, or a new input with free_init.
+/// Returns the translation table for the flip-flops.
+pub(crate) fn unroll_state(
+    net: &mut Network,
+    aig: &Network,
+    t_prev: Option<&HashMap<Signal, Signal>>,
+    free_init: bool,
+) -> HashMap<Signal, Signal> {
+    let mut t = HashMap::new();
+    for i in 0..aig.nb_nodes() {
+        if let Gate::Dff([d, en, res]) = aig.gate(i) {
+            let ff = aig.node(i);
+            let unroll_ff = if let Some(t_prev) = t_prev {
+                let mx = net.add_canonical(Gate::mux(t_prev[en], t_prev[d], t_prev[&ff]));
+                net.and(mx, !t_prev[res])
+            } else if free_init {
+                net.add_input()
+            } else {
+                Signal::zero()
+            };
+            t.insert(ff, unroll_ff);
+            t.insert(!ff, !unroll_ff);
         }
+    }
+    t
+}
+
+/// Value of the flip-flops in a translation table, in order
+pub(crate) fn state_values(aig: &Network, t: &HashMap<Signal, Signal>) -> Vec<Signal> {
+    (0..aig.nb_nodes())
+        .filter(|i| !aig.gate(*i).is_comb())
+        .map(|i| t[&aig.node(i)])
+        .collect()
+}
+
+/// Unroll a sequential network, from the zero state or from a free state
+///
+/// With a free initial state, the value of each flip-flop at the first step is given by an
+/// additional input, placed before the inputs of the first step.
+/// With final_state, the state after the last step is computed as well.
+fn unroll_helper(aig: &Network, nb_steps: usize, free_init: bool, final_state: bool) -> Unrolling {
+    let mut ret = Unrolling {
+        net: Network::new(),
+        states: Vec::new(),
+        outputs: Vec::new(),
+    };
+
+    let mut t_prev = None;
+    for _ in 0..nb_steps {
+        let mut t = unroll_state(&mut ret.net, aig, t_prev.as_ref(), free_init);
+        ret.states.push(state_values(aig, &t));
 
         // Convert inputs and nodes
-        extend_aig_helper(&mut ret, aig, &mut t, false);
+        extend_aig_helper(&mut ret.net, aig, &mut t, false);
 
-        for o in 0..aig.nb_outputs() {
-            ret.add_output(t[&aig.output(o)]);
-        }
-        std::mem::swap(&mut t, &mut t_prev);
+        ret.outputs
+            .push((0..aig.nb_outputs()).map(|o| t[&aig.output(o)]).collect());
+        t_prev = Some(t);
+    }
+    if final_state {
+        let t = unroll_state(&mut ret.net, aig, t_prev.as_ref(), free_init);
+        ret.states.push(state_values(aig, &t));
+    }
+    ret
+}
+
+/// Unroll a sequential network over a fixed number of steps, making a larger combinatorial networks
+pub fn unroll(aig: &Network, nb_steps: usize) -> Network {
+    let Unrolling {
+        net: mut ret,
+        outputs,
+        ..
+    } = unroll_helper(aig, nb_steps, false, false);
+    for s in outputs.into_iter().flatten() {
+        ret.add_output(s);
     }
     assert_eq!(ret.nb_inputs(), aig.nb_inputs() * nb_steps);
     assert_eq!(ret.nb_outputs(), aig.nb_outputs() * nb_steps);
     ret
 }
 
-/// Create a network with a single output, representing whether two combinatorial networks give different outputs
+/// Create a network with a single output, representing whether two networks give different outputs
+///
+/// For sequential networks, this is a miter: the flip-flops of both networks are kept, and the
+/// output is 1 whenever the outputs differ at the current cycle.
 pub fn difference(a: &Network, b: &Network) -> Network {
-    assert!(a.is_comb() && b.is_comb());
     assert_eq!(a.nb_inputs(), b.nb_inputs());
     assert_eq!(a.nb_outputs(), b.nb_outputs());
 
@@ -269,64 +373,116 @@ pub fn difference(a: &Network, b: &Network) -> Network {
     eq
 }
 
-/// Find an assignment of the inputs that sets the single output to 1
+/// Solve a set of clauses over signals
 ///
-/// Returns the assignment, or None if no such assignment exists.
-pub fn prove(a: &Network) -> Option<Vec<bool>> {
-    assert_eq!(a.nb_outputs(), 1);
-
-    let clauses = to_cnf(a);
-
+/// Returns the value of the requested signals if the clauses are satisfiable, or None otherwise.
+/// Constant signals are allowed in the clauses.
+fn solve_clauses(clauses: &[Vec<Signal>], read: &[Signal]) -> Option<Vec<bool>> {
     let mut all_lits: Vec<Signal> = clauses
         .iter()
         .flatten()
+        .chain(read.iter())
+        .filter(|s| !s.is_constant())
         .map(|s| s.without_inversion())
         .collect();
-    for i in 0..a.nb_inputs() {
-        all_lits.push(Signal::from_input(i as u32));
-    }
     all_lits.sort();
     all_lits.dedup();
     let mut t = HashMap::new();
-    let mut i: u32 = 0;
-    for s in all_lits {
-        t.insert(s, Lit::new(i, false));
-        t.insert(!s, Lit::new(i, true));
-        i += 1;
+    for (i, s) in all_lits.into_iter().enumerate() {
+        t.insert(s, Lit::new(i as u32, false));
+        t.insert(!s, Lit::new(i as u32, true));
     }
 
     let mut solver = Kissat::default();
     for c in clauses {
-        let cl = Clause::from_iter(c.iter().map(|s| t[s]));
-        solver.add_clause(cl).unwrap();
-    }
-    let out = a.output(0);
-    if out == Signal::one() {
-        return Some(vec![false; a.nb_inputs()]);
-    } else if out == Signal::zero() {
-        return None;
+        if c.contains(&Signal::one()) {
+            continue;
+        }
+        let lits: Vec<Lit> = c
+            .iter()
+            .filter(|s| **s != Signal::zero())
+            .map(|s| t[s])
+            .collect();
+        if lits.is_empty() {
+            return None;
+        }
+        solver.add_clause(Clause::from_iter(lits)).unwrap();
     }
-    solver.add_unit(t[&out]).unwrap();
 
     let res = solver.solve().unwrap();
     match res {
         SolverResult::Sat => {
             let sol = solver.full_solution().unwrap();
-            let mut v = Vec::new();
-            for inp in 0..a.nb_inputs() {
-                let b = match sol.lit_value(t[&Signal::from_input(inp as u32)]) {
-                    TernaryVal::True => true,
-                    _ => false,
-                };
-                v.push(b);
-            }
-            Some(v)
+            let value = |s: &Signal| {
+                if s.is_constant() {
+                    *s == Signal::one()
+                } else {
+                    sol.lit_value(t[s]) == TernaryVal::True
+                }
+            };
+            Some(read.iter().map(value).collect())
         }
         SolverResult::Unsat => None,
         SolverResult::Interrupted => panic!("Sat solver couldn't run to completion"),
     }
 }
 
+/// Find an assignment of the inputs that sets the single output to 1
+///
+/// Returns the assignment, or None if no such assignment exists.
+pub fn prove(a: &Network) -> Option<Vec<bool>> {
+    assert_eq!(a.nb_outputs(), 1);
+
+    let mut clauses = to_cnf(a);
+    clauses.push(vec![a.output(0)]);
+    let inputs: Vec<Signal> = (0..a.nb_inputs()).map(|i| a.input(i)).collect();
+    solve_clauses(&clauses, &inputs)
+}
+
+/// Find an assignment such that two signals of a network have different values
+///
+/// Only the logic in the transitive fanin of the signals is encoded, and flip-flops are
+/// considered as free variables.
+/// Returns the values of the inputs and of the flip-flops, or None if the signals are equivalent.
+pub(crate) fn find_difference(
+    aig: &Network,
+    a: Signal,
+    b: Signal,
+) -> Option<(Vec<bool>, Vec<bool>)> {
+    let mut visited = vec![false; aig.nb_nodes()];
+    let mut to_visit: Vec<u32> = [a, b]
+        .iter()
+        .filter(|s| s.is_var())
+        .map(|s| s.var())
+        .collect();
+    while let Some(v) = to_visit.pop() {
+        let i = v as usize;
+        if visited[i] {
+            continue;
+        }
+        visited[i] = true;
+        if aig.gate(i).is_comb() {
+            to_visit.extend(aig.gate(i).vars());
+        }
+    }
+    let cone = (0..aig.nb_nodes()).filter(|i| visited[*i] && aig.gate(*i).is_comb());
+    let mut var = aig.nb_nodes() as u32;
+    let mut clauses = to_cnf_nodes(aig, cone, &mut var);
+    clauses.push(vec![a, b]);
+    clauses.push(vec![!a, !b]);
+    let dffs: Vec<Signal> = (0..aig.nb_nodes())
+        .filter(|i| !aig.gate(*i).is_comb())
+        .map(|i| aig.node(i))
+        .collect();
+    let read: Vec<Signal> = (0..aig.nb_inputs())
+        .map(|i| aig.input(i))
+        .chain(dffs)
+        .collect();
+    let values = solve_clauses(&clauses, &read)?;
+    let (inputs, dffs) = values.split_at(aig.nb_inputs());
+    Some((inputs.to_vec(), dffs.to_vec()))
+}
+
 /// Perform equivalence checking on two combinatorial networks
 pub fn check_equivalence_comb(a: &Network, b: &Network, optimize: bool) -> Result<(), Vec<bool>> {
     assert!(a.is_comb() && b.is_comb());
@@ -334,6 +490,14 @@ pub fn check_equivalence_comb(a: &Network, b: &Network, optimize: bool) -> Resul
     if optimize {
         diff.make_canonical();
         diff.cleanup();
+        optim::fraig(&mut diff);
+        // Sat sweeping usually reduces the miter to a constant, which needs no further proof
+        if diff.output(0) == Signal::zero() {
+            return Ok(());
+        }
+        if diff.output(0) == Signal::one() {
+            return Err(vec![false; diff.nb_inputs()]);
+        }
     }
     let res = prove(&diff);
     match res {
@@ -371,6 +535,93 @@ pub fn check_equivalence_bounded(
     }
 }
 
+/// Result of an unbounded proof on a sequential network
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofResult {
+    /// The output can never be 1; the proof succeeded by induction at the given depth
+    Proved(usize),
+    /// The output can be 1; the sequence of input values to reach it, one per cycle
+    Counterexample(Vec<Vec<bool>>),
+    /// No conclusion could be reached up to the given depth
+    Unknown(usize),
+}
+
+/// Split a vector of input values into one vector per cycle
+fn split_steps(v: &[bool], nb_inputs: usize, nb_steps: usize) -> Vec<Vec<bool>> {
+    assert_eq!(v.len(), nb_inputs * nb_steps);
+    if nb_inputs == 0 {
+        return vec![Vec::new(); nb_steps];
+    }
+    v.chunks(nb_inputs).map(|c| c.to_vec()).collect()
+}
+
+/// Check whether a single signal of a combinatorial network can be 1
+fn prove_signal(mut net: Network, s: Signal) -> Option<Vec<bool>> {
+    net.add_output(s);
+    net.cleanup();
+    prove(&net)
+}
+
+/// Prove that the single output of a sequential network is never 1, using k-induction
+///
+/// At each depth k, the base case checks that the output cannot be 1 at cycle k from the
+/// zero initial state. The induction step checks that, from any state, k cycles with the
+/// output at 0 cannot be followed by a cycle with the output at 1. Simple-path constraints
+/// require all states in the induction step to be different, so that the proof always
+/// succeeds at a depth bounded by the number of reachable states.
+pub fn prove_kinduction(aig: &Network, max_depth: usize) -> ProofResult {
+    assert_eq!(aig.nb_outputs(), 1);
+    let nb_dffs = (0..aig.nb_nodes())
+        .filter(|i| !aig.gate(*i).is_comb())
+        .count();
+    for k in 0..=max_depth {
+        // Base case: the output is reachable at cycle k from the initial state
+        let base = unroll_helper(aig, k + 1, false, false);
+        if let Some(v) = prove_signal(base.net, base.outputs[k][0]) {
+            return ProofResult::Counterexample(split_steps(&v, aig.nb_inputs(), k + 1));
+        }
+
+        // Induction step: k good cycles followed by a bad one along a simple path
+        let mut step = unroll_helper(aig, k + 1, true, false);
+        assert_eq!(step.net.nb_inputs(), nb_dffs + aig.nb_inputs() * (k + 1));
+        let mut constraints = vec![step.outputs[k][0]];
+        for i in 0..k {
+            constraints.push(!step.outputs[i][0]);
+        }
+        for i in 0..=k {
+            for j in (i + 1)..=k {
+                let diff: Vec<Signal> = (0..nb_dffs)
+                    .map(|f| step.net.xor(step.states[i][f], step.states[j][f]))
+                    .collect();
+                constraints.push(
+                    step.net
+                        .add_canonical(Gate::Nary(diff.into(), NaryType::Or)),
+                );
+            }
+        }
+        let cex = step
+            .net
+            .add_canonical(Gate::Nary(constraints.into(), NaryType::And));
+        if prove_signal(step.net, cex).is_none() {
+            return ProofResult::Proved(k);
+        }
+    }
+    ProofResult::Unknown(max_depth)
+}
+
+/// Perform unbounded equivalence checking on two sequential networks
+///
+/// The two networks are combined in a miter, whose output is proved to be always 0 using
+/// k-induction up to the given depth.
+pub fn check_equivalence_unbounded(a: &Network, b: &Network, max_depth: usize) -> ProofResult {
+    assert_eq!(a.nb_inputs(), b.nb_inputs());
+    assert_eq!(a.nb_outputs(), b.nb_outputs());
+    let mut diff = difference(a, b);
+    diff.make_canonical();
+    diff.cleanup();
+    prove_kinduction(&diff, max_depth)
+}
+
 #[cfg(test)]
 mod tests {
     use volute::Lut;
@@ -441,6 +692,8 @@ mod tests {
         b.add_output(Signal::zero());
         let res = check_equivalence_comb(&a, &b, false);
         assert_ne!(res, Ok(()));
+        let res = check_equivalence_comb(&a, &b, true);
+        assert_eq!(res, Err(vec![false, false]));
     }
 
     #[test]
@@ -758,4 +1011,104 @@ mod tests {
         check_equivalence_comb(&a, &b, false).unwrap();
         check_equivalence_comb(&a, &b, true).unwrap();
     }
+
+    #[test]
+    fn test_unbounded_toggle() {
+        use super::{check_equivalence_unbounded, ProofResult};
+
+        // Single toggle flip-flop
+        let mut a = Network::new();
+        a.add_input();
+        let fa = a.add(Gate::dff(Signal::zero(), Signal::one(), Signal::zero()));
+        a.replace(0, Gate::dff(!fa, Signal::one(), Signal::zero()));
+        a.add_output(fa);
+
+        // Two toggle flip-flops, always equal
+        let mut b = Network::new();
+        b.add_input();
+        let f1 = b.add(Gate::dff(Signal::zero(), Signal::one(), Signal::zero()));
+        let f2 = b.add(Gate::dff(Signal::zero(), Signal::one(), Signal::zero()));
+        b.replace(0, Gate::dff(!f1, Signal::one(), Signal::zero()));
+        b.replace(1, Gate::dff(!f2, Signal::one(), Signal::zero()));
+        let o = b.and(f1, f2);
+        b.add_output(o);
+
+        assert!(matches!(
+            check_equivalence_unbounded(&a, &b, 10),
+            ProofResult::Proved(_)
+        ));
+        assert_eq!(
+            check_equivalence_unbounded(&a, &b, 0),
+            ProofResult::Unknown(0)
+        );
+
+        // Constant zero differs at the second cycle
+        let mut c = Network::new();
+        c.add_input();
+        c.add_output(Signal::zero());
+        assert_eq!(
+            check_equivalence_unbounded(&a, &c, 10),
+            ProofResult::Counterexample(vec![vec![false]; 2])
+        );
+    }
+
+    #[test]
+    fn test_unbounded_comb() {
+        use super::{check_equivalence_unbounded, ProofResult};
+
+        let mut a = Network::new();
+        let l1 = a.add_input();
+        let l2 = a.add_input();
+        let aa = a.and(l1, l2);
+        a.add_output(aa);
+        let mut b = Network::new();
+        b.add_input();
+        b.add_input();
+        let ab = !b.add(Gate::Nary([!l1, !l2].into(), NaryType::Or));
+        b.add_output(!ab);
+        assert_eq!(
+            check_equivalence_unbounded(&a, &a, 4),
+            ProofResult::Proved(0)
+        );
+        assert!(matches!(
+            check_equivalence_unbounded(&a, &b, 4),
+            ProofResult::Counterexample(v) if v.len() == 1
+        ));
+    }
+
+    #[test]
+    fn test_unbounded_shift_register() {
+        use super::{check_equivalence_unbounded, ProofResult};
+
+        // Shift register of length 3, compared to a version with the last stage duplicated
+        let mut a = Network::new();
+        let i0 = a.add_input();
+        let s1 = a.dff(i0, Signal::one(), Signal::zero());
+        let s2 = a.dff(s1, Signal::one(), Signal::zero());
+        let s3 = a.dff(s2, Signal::one(), Signal::zero());
+        a.add_output(s3);
+
+        let mut b = Network::new();
+        b.add_input();
+        let t1 = b.dff(i0, Signal::one(), Signal::zero());
+        let t2 = b.dff(t1, Signal::one(), Signal::zero());
+        let t3 = b.dff(t2, Signal::one(), Signal::zero());
+        let t3b = b.dff(t2, Signal::one(), Signal::zero());
+        let o = b.and(t3, t3b);
+        b.add_output(o);
+
+        assert!(matches!(
+            check_equivalence_unbounded(&a, &b, 10),
+            ProofResult::Proved(_)
+        ));
+        let mut c = Network::new();
+        c.add_input();
+        let u1 = c.dff(i0, Signal::one(), Signal::zero());
+        let u2 = c.dff(u1, Signal::one(), Signal::zero());
+        c.add_output(u2);
+        assert!(matches!(
+            check_equivalence_unbounded(&a, &c, 10),
+            ProofResult::Counterexample(v) if v.len() == 3
+        ));
+    }
 }
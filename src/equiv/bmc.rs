@@ -0,0 +1,154 @@
+//! Bounded model checking
+
+use std::collections::HashMap;
+
+use crate::{Network, Signal};
+
+use super::solver::IncrementalSolver;
+use super::{extend_aig_helper, split_steps, unroll_state};
+
+/// Unrolling of a sequential network that is extended one step at a time
+///
+/// The clauses of each step are added once to an incremental solver, and kept for all
+/// subsequent depths.
+struct Bmc<'a> {
+    /// Network being checked
+    aig: &'a Network,
+    /// Unrolled combinatorial network
+    net: Network,
+    /// Translation table of the last step
+    t: Option<HashMap<Signal, Signal>>,
+    /// Solver with the clauses of all steps unrolled so far
+    solver: IncrementalSolver,
+    /// Value of the outputs at each step
+    outputs: Vec<Vec<Signal>>,
+}
+
+impl<'a> Bmc<'a> {
+    fn new(aig: &'a Network) -> Bmc<'a> {
+        Bmc {
+            aig,
+            net: Network::new(),
+            t: None,
+            solver: IncrementalSolver::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    /// Unroll one more step and add its clauses
+    fn add_step(&mut self) {
+        let first_node = self.net.nb_nodes();
+        let mut t = unroll_state(&mut self.net, self.aig, self.t.as_ref(), false);
+        extend_aig_helper(&mut self.net, self.aig, &mut t, false);
+        self.outputs.push(
+            (0..self.aig.nb_outputs())
+                .map(|o| t[&self.aig.output(o)])
+                .collect(),
+        );
+        self.t = Some(t);
+        self.solver
+            .add_nodes(&self.net, first_node..self.net.nb_nodes());
+    }
+
+    /// Find input values for all steps so far such that an output is 1 at the last step
+    fn solve_last_step(&mut self) -> Option<Vec<Vec<bool>>> {
+        let bad = self.outputs.last().unwrap().clone();
+        // The clause requiring a bad output only holds for this query
+        let act = self.solver.new_lit();
+        self.solver.add_guarded_clause(Some(act), &bad);
+        let ret = if self.solver.solve(&[], &[act]) {
+            // Inputs that do not appear in the clauses are left at 0
+            let values: Vec<bool> = (0..self.net.nb_inputs())
+                .map(|i| self.solver.value(self.net.input(i)))
+                .collect();
+            Some(split_steps(
+                &values,
+                self.aig.nb_inputs(),
+                self.outputs.len(),
+            ))
+        } else {
+            None
+        };
+        self.solver.release(act);
+        if ret.is_none() {
+            // The outputs are known to be 0 at this step for all subsequent queries
+            for s in bad {
+                self.solver.add_clause(&[!s]);
+            }
+        }
+        ret
+    }
+}
+
+/// Bounded model checking of a sequential network, starting from the all-zero state
+///
+/// Each output is considered as a bad state monitor. The network is unrolled one cycle at a time,
+/// and the first cycle where an output can be 1 is reported, as a sequence of input values
+/// with one vector per cycle.
+/// Returns Ok if no output can be 1 in the given number of cycles.
+pub fn check_property_bounded(aig: &Network, nb_steps: usize) -> Result<(), Vec<Vec<bool>>> {
+    let mut bmc = Bmc::new(aig);
+    for _ in 0..nb_steps {
+        bmc.add_step();
+        if let Some(trace) = bmc.solve_last_step() {
+            return Err(trace);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_property_bounded;
+    use crate::sim::simulate;
+    use crate::{Gate, Network, Signal};
+
+    /// Shift register of the given length, with an output on the last bit
+    fn shift_register(len: usize) -> Network {
+        let mut aig = Network::new();
+        let mut s = aig.add_input();
+        for _ in 0..len {
+            s = aig.add(Gate::dff(s, Signal::one(), Signal::zero()));
+        }
+        aig.add_output(s);
+        aig
+    }
+
+    #[test]
+    fn test_shift_register() {
+        for len in 0..5 {
+            let aig = shift_register(len);
+            assert!(check_property_bounded(&aig, len).is_ok());
+            let trace = check_property_bounded(&aig, len + 3).unwrap_err();
+            assert_eq!(trace.len(), len + 1);
+            assert_eq!(simulate(&aig, &trace).last().unwrap(), &vec![true]);
+        }
+    }
+
+    #[test]
+    fn test_constant_outputs() {
+        let mut aig = Network::new();
+        aig.add_input();
+        aig.add_output(Signal::zero());
+        assert!(check_property_bounded(&aig, 3).is_ok());
+        aig.add_output(Signal::one());
+        assert_eq!(check_property_bounded(&aig, 3), Err(vec![vec![false]]));
+    }
+
+    #[test]
+    fn test_xor3() {
+        // Xor3 gates use additional variables in the clauses
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let x = aig.add(Gate::xor3(a, b, c));
+        let d = aig.add(Gate::dff(x, Signal::one(), Signal::zero()));
+        let e = aig.add(Gate::dff(d, Signal::one(), Signal::zero()));
+        let o = aig.add(Gate::and3(e, !d, a));
+        aig.add_output(o);
+        let trace = check_property_bounded(&aig, 5).unwrap_err();
+        assert_eq!(trace.len(), 3);
+        assert_eq!(simulate(&aig, &trace).last().unwrap(), &vec![true]);
+    }
+}
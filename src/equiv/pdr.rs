@@ -0,0 +1,419 @@
+//! Property-directed reachability (IC3/PDR)
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use rustsat::types::Lit;
+
+use crate::network::NaryType;
+use crate::{Gate, Network, Signal};
+
+use super::solver::IncrementalSolver;
+use super::unroll_helper;
+
+/// Result of a safety property check
+#[derive(Debug, Clone)]
+pub enum PropertyResult {
+    /// The outputs can never be 1; the inductive invariant is given as a network
+    ///
+    /// The invariant has one input per flip-flop, in the order of the flip-flops in the design,
+    /// and a single output that is 1 for all states satisfying the invariant.
+    Proved(Network),
+    /// An output can be 1; the sequence of input values to reach it, one per cycle
+    Counterexample(Vec<Vec<bool>>),
+    /// No conclusion could be reached with the given number of frames
+    Unknown(usize),
+}
+
+/// Conjunction of flip-flop values, as (flip-flop index, value) sorted by index
+type Cube = Vec<(usize, bool)>;
+
+/// Proof obligation: a state that must be shown unreachable
+struct Obligation {
+    /// State to block
+    cube: Cube,
+    /// Input values leading from this state to the next obligation, or to a bad state
+    inputs: Vec<bool>,
+    /// Next obligation in the trace
+    next: Option<usize>,
+}
+
+/// State of the PDR algorithm
+///
+/// A single incremental solver holds the transition relation and the lemmas of all frames.
+/// Each frame has an activation literal guarding its lemmas, and a query on a frame assumes the
+/// activation literals of this frame and all subsequent ones.
+struct Pdr {
+    /// Solver for the transition relation and the lemmas
+    solver: IncrementalSolver,
+    /// Activation literal of each frame; the one of frame 0 guards the initial state
+    acts: Vec<Lit>,
+    /// Signals for the current value of the flip-flops
+    state: Vec<Signal>,
+    /// Signals for the next value of the flip-flops
+    next: Vec<Signal>,
+    /// Signals for the primary inputs
+    inputs: Vec<Signal>,
+    /// Signal that is 1 when any output is 1
+    bad: Signal,
+    /// Lemmas at each frame, as blocked cubes; a lemma holds at its frame and all previous ones
+    lemmas: Vec<Vec<Cube>>,
+    /// Names of the flip-flops, if any
+    names: Vec<Option<String>>,
+}
+
+/// Whether the first cube contains all literals of the second
+fn contains(a: &Cube, b: &Cube) -> bool {
+    b.iter().all(|l| a.contains(l))
+}
+
+/// Whether a cube contains the all-zero initial state
+fn intersects_init(c: &Cube) -> bool {
+    c.iter().all(|(_, v)| !v)
+}
+
+impl Pdr {
+    fn new(aig: &Network) -> Pdr {
+        let mut u = unroll_helper(aig, 1, true, true);
+        let nb_dffs = u.states[0].len();
+        let outputs = u.outputs.pop().unwrap();
+        let bad = u
+            .net
+            .add_canonical(Gate::Nary(outputs.into(), NaryType::Or));
+        u.net.add_output(bad);
+        for s in &u.states[1] {
+            u.net.add_output(*s);
+        }
+        u.net.cleanup();
+        let net = u.net;
+        let state: Vec<Signal> = (0..nb_dffs).map(|i| net.input(i)).collect();
+        let mut solver = IncrementalSolver::new();
+        solver.add_nodes(&net, 0..net.nb_nodes());
+        let init = solver.new_lit();
+        for s in &state {
+            solver.add_guarded_clause(Some(init), &[!s]);
+        }
+        let act = solver.new_lit();
+        Pdr {
+            solver,
+            acts: vec![init, act],
+            state,
+            next: (1..=nb_dffs).map(|i| net.output(i)).collect(),
+            inputs: (nb_dffs..net.nb_inputs()).map(|i| net.input(i)).collect(),
+            bad: net.output(0),
+            lemmas: vec![Vec::new(), Vec::new()],
+            names: (0..aig.nb_nodes())
+                .filter(|i| !aig.gate(*i).is_comb())
+                .map(|i| aig.node_name(i).map(|n| n.to_owned()))
+                .collect(),
+        }
+    }
+
+    /// Literal that is true when a flip-flop has the given value
+    fn lit(signals: &[Signal], (i, v): (usize, bool)) -> Signal {
+        signals[i] ^ !v
+    }
+
+    /// Clause blocking a cube
+    fn blocking_clause(signals: &[Signal], c: &Cube) -> Vec<Signal> {
+        c.iter().map(|l| !Self::lit(signals, *l)).collect()
+    }
+
+    /// Add a new frame at the end
+    fn add_frame(&mut self) {
+        let act = self.solver.new_lit();
+        self.acts.push(act);
+        self.lemmas.push(Vec::new());
+    }
+
+    /// Solve with the transition relation and the given frame, under assumptions on the signals
+    /// and with an additional clause
+    ///
+    /// Returns the current state and the inputs if satisfiable.
+    fn solve(
+        &mut self,
+        level: usize,
+        assumptions: &[Signal],
+        extra: Option<Vec<Signal>>,
+    ) -> Option<(Cube, Vec<bool>)> {
+        let mut acts = if level == 0 {
+            vec![self.acts[0]]
+        } else {
+            self.acts[level..].to_vec()
+        };
+        let extra_act = extra.map(|c| {
+            let a = self.solver.new_lit();
+            self.solver.add_guarded_clause(Some(a), &c);
+            a
+        });
+        acts.extend(extra_act);
+        let sat = self.solver.solve(assumptions, &acts);
+        let ret = if sat {
+            let state = self.state.iter().map(|s| self.solver.value(*s));
+            let inputs = self.inputs.iter().map(|s| self.solver.value(*s));
+            Some((state.enumerate().collect(), inputs.collect()))
+        } else {
+            None
+        };
+        if let Some(a) = extra_act {
+            self.solver.release(a);
+        }
+        ret
+    }
+
+    /// Find a state of the frame where the bad signal can be 1
+    fn get_bad(&mut self, level: usize) -> Option<(Cube, Vec<bool>)> {
+        self.solve(level, &[self.bad], None)
+    }
+
+    /// Find a predecessor of the cube in the previous frame, relative to the cube itself
+    fn predecessor(&mut self, level: usize, c: &Cube) -> Option<(Cube, Vec<bool>)> {
+        let next: Vec<Signal> = c.iter().map(|l| Self::lit(&self.next, *l)).collect();
+        let blocking = Self::blocking_clause(&self.state, c);
+        self.solve(level - 1, &next, Some(blocking))
+    }
+
+    /// Whether the cube is already blocked at this frame
+    fn is_blocked(&self, level: usize, c: &Cube) -> bool {
+        self.lemmas[level..]
+            .iter()
+            .flatten()
+            .any(|l| contains(c, l))
+    }
+
+    /// Remove literals from a blocked cube while it stays blocked
+    fn generalize(&mut self, level: usize, c: &Cube) -> Cube {
+        let mut ret = c.clone();
+        let mut i = 0;
+        while i < ret.len() {
+            let mut g = ret.clone();
+            g.remove(i);
+            if !intersects_init(&g) && self.predecessor(level, &g).is_none() {
+                ret = g;
+            } else {
+                i += 1;
+            }
+        }
+        ret
+    }
+
+    /// Add a lemma at a frame, removing the lemmas it subsumes
+    ///
+    /// Subsumed lemmas are kept in the solver, where they are redundant.
+    fn add_lemma(&mut self, level: usize, c: Cube) {
+        for lemmas in &mut self.lemmas[1..=level] {
+            lemmas.retain(|l| !contains(l, &c));
+        }
+        let clause = Self::blocking_clause(&self.state, &c);
+        self.solver
+            .add_guarded_clause(Some(self.acts[level]), &clause);
+        self.lemmas[level].push(c);
+    }
+
+    /// Block all bad states at the last frame
+    ///
+    /// Returns a counterexample if one is found.
+    fn block_bad_states(&mut self) -> Option<Vec<Vec<bool>>> {
+        let k = self.lemmas.len() - 1;
+        while let Some((cube, inputs)) = self.get_bad(k) {
+            let mut obligations = vec![Obligation {
+                cube,
+                inputs,
+                next: None,
+            }];
+            let mut queue = BinaryHeap::new();
+            queue.push(Reverse((k, 0)));
+            while let Some(Reverse((level, i))) = queue.pop() {
+                let c = obligations[i].cube.clone();
+                if self.is_blocked(level, &c) {
+                    continue;
+                }
+                match self.predecessor(level, &c) {
+                    Some((p, inputs)) => {
+                        if level == 1 {
+                            return Some(Self::trace(&obligations, inputs, i));
+                        }
+                        obligations.push(Obligation {
+                            cube: p,
+                            inputs,
+                            next: Some(i),
+                        });
+                        queue.push(Reverse((level - 1, obligations.len() - 1)));
+                        queue.push(Reverse((level, i)));
+                    }
+                    None => {
+                        let g = self.generalize(level, &c);
+                        self.add_lemma(level, g);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Build the counterexample trace, starting from the initial state
+    fn trace(obligations: &[Obligation], init_inputs: Vec<bool>, first: usize) -> Vec<Vec<bool>> {
+        let mut ret = vec![init_inputs];
+        let mut i = Some(first);
+        while let Some(j) = i {
+            ret.push(obligations[j].inputs.clone());
+            i = obligations[j].next;
+        }
+        ret
+    }
+
+    /// Push lemmas to the next frame when possible
+    ///
+    /// Returns the index of the first frame equal to the next one, if any.
+    fn propagate(&mut self) -> Option<usize> {
+        let k = self.lemmas.len() - 1;
+        for level in 1..k {
+            for c in self.lemmas[level].clone() {
+                let next: Vec<Signal> = c.iter().map(|l| Self::lit(&self.next, *l)).collect();
+                if self.solve(level, &next, None).is_none() {
+                    self.lemmas[level].retain(|l| *l != c);
+                    let clause = Self::blocking_clause(&self.state, &c);
+                    self.solver
+                        .add_guarded_clause(Some(self.acts[level + 1]), &clause);
+                    self.lemmas[level + 1].push(c);
+                }
+            }
+            if self.lemmas[level].is_empty() {
+                return Some(level);
+            }
+        }
+        None
+    }
+
+    /// Network representing the invariant given by the frames after the given one
+    fn invariant(&self, level: usize) -> Network {
+        let mut ret = Network::new();
+        ret.add_inputs(self.state.len());
+        for (i, name) in self.names.iter().enumerate() {
+            if let Some(name) = name {
+                ret.set_input_name(i, name);
+            }
+        }
+        let inputs: Vec<Signal> = (0..self.state.len()).map(|i| ret.input(i)).collect();
+        let mut clauses = Vec::new();
+        for c in self.lemmas[level..].iter().flatten() {
+            let clause = Self::blocking_clause(&inputs, c);
+            clauses.push(ret.add_canonical(Gate::Nary(clause.into(), NaryType::Or)));
+        }
+        let inv = ret.add_canonical(Gate::Nary(clauses.into(), NaryType::And));
+        ret.add_output(inv);
+        ret
+    }
+}
+
+/// Prove that the outputs of a sequential network are never 1, using property-directed reachability
+///
+/// Each output is considered as a bad state monitor, starting from the all-zero initial state.
+/// The algorithm maintains a sequence of frames over-approximating the states reachable in a
+/// given number of cycles, and refines them by blocking states that lead to a bad state.
+/// It stops when two consecutive frames are identical, which yields an inductive invariant,
+/// or when a counterexample is found.
+pub fn check_property(aig: &Network, max_frames: usize) -> PropertyResult {
+    let mut pdr = Pdr::new(aig);
+
+    // Bad state reachable without any transition
+    if let Some((_, inputs)) = pdr.get_bad(0) {
+        return PropertyResult::Counterexample(vec![inputs]);
+    }
+
+    for k in 1..=max_frames {
+        if let Some(trace) = pdr.block_bad_states() {
+            return PropertyResult::Counterexample(trace);
+        }
+        if let Some(level) = pdr.propagate() {
+            return PropertyResult::Proved(pdr.invariant(level + 1));
+        }
+        if k != max_frames {
+            pdr.add_frame();
+        }
+    }
+    PropertyResult::Unknown(max_frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_property, PropertyResult};
+    use crate::sim::simulate;
+    use crate::{Gate, Network, Signal};
+
+    /// Three-bit counter that wraps around at 5, with an output when it reaches the given value
+    fn counter(bad_value: usize) -> Network {
+        let mut aig = Network::new();
+        let en = aig.add_input();
+        let b: Vec<Signal> = (0..3)
+            .map(|_| aig.add(Gate::dff(Signal::zero(), Signal::one(), Signal::zero())))
+            .collect();
+        let c0 = en;
+        let c1 = aig.and(c0, b[0]);
+        let c2 = aig.and(c1, b[1]);
+        let n0 = aig.xor(b[0], c0);
+        let n1 = aig.xor(b[1], c1);
+        let n2 = aig.xor(b[2], c2);
+        // Reset when reaching 5 with the enable
+        let is5 = aig.add(Gate::and3(b[0], !b[1], b[2]));
+        let wrap = aig.and(is5, en);
+        for (i, n) in [n0, n1, n2].into_iter().enumerate() {
+            aig.replace(i, Gate::dff(n, Signal::one(), wrap));
+        }
+        let v = |i: usize| b[i] ^ ((bad_value >> i) & 1 == 0);
+        let o = aig.add(Gate::and3(v(0), v(1), v(2)));
+        aig.add_output(o);
+        aig
+    }
+
+    #[test]
+    fn test_counter_unreachable() {
+        for bad_value in [6, 7] {
+            let aig = counter(bad_value);
+            let PropertyResult::Proved(inv) = check_property(&aig, 20) else {
+                panic!("Property should be proved");
+            };
+            assert_eq!(inv.nb_inputs(), 3);
+            assert_eq!(inv.nb_outputs(), 1);
+            // The invariant holds for all reachable states
+            for value in 0..6 {
+                let state: Vec<bool> = (0..3).map(|i| (value >> i) & 1 != 0).collect();
+                assert_eq!(simulate(&inv, &vec![state]), vec![vec![true]]);
+            }
+            let state: Vec<bool> = (0..3).map(|i| (bad_value >> i) & 1 != 0).collect();
+            assert_eq!(simulate(&inv, &vec![state]), vec![vec![false]]);
+        }
+    }
+
+    #[test]
+    fn test_counter_reachable() {
+        for bad_value in 0..6 {
+            let aig = counter(bad_value);
+            let PropertyResult::Counterexample(trace) = check_property(&aig, 20) else {
+                panic!("Property should fail");
+            };
+            assert_eq!(trace.len(), bad_value + 1);
+            let outputs = simulate(&aig, &trace);
+            assert_eq!(outputs.last().unwrap(), &vec![true]);
+        }
+    }
+
+    #[test]
+    fn test_comb() {
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        aig.add_input();
+        let o = aig.and(a, !a);
+        aig.add_output(o);
+        assert!(matches!(check_property(&aig, 4), PropertyResult::Proved(_)));
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let o = aig.add(Gate::and(a, b));
+        aig.add_output(o);
+        let PropertyResult::Counterexample(trace) = check_property(&aig, 4) else {
+            panic!("Property should fail");
+        };
+        assert_eq!(trace, vec![vec![true, true]]);
+    }
+}
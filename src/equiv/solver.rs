@@ -0,0 +1,297 @@
+//! Incremental Sat solver on the signals of a network
+
+use std::collections::HashMap;
+
+use rustsat::solvers::{Solve, SolveIncremental, SolverResult};
+use rustsat::types::{Clause, Lit, TernaryVal};
+use rustsat_minisat::core::Minisat;
+
+use crate::{Gate, Network, Signal};
+
+use super::{to_cnf_gates, to_cnf_nodes};
+
+/// Incremental Sat solver, whose variables are the signals of a network
+///
+/// Clauses are kept from one query to the next, and each query is made under assumptions.
+/// Clauses that only hold for some queries are guarded by activation literals, that are
+/// assumed for the queries where they hold.
+pub(crate) struct IncrementalSolver {
+    solver: Minisat,
+    /// Solver literal for each signal, without inversion
+    lits: HashMap<Signal, Lit>,
+    /// Number of variables in the solver
+    nb_vars: u32,
+    /// Whether the clauses are unsatisfiable regardless of the assumptions
+    unsat: bool,
+}
+
+impl IncrementalSolver {
+    pub fn new() -> IncrementalSolver {
+        IncrementalSolver {
+            solver: Minisat::default(),
+            lits: HashMap::new(),
+            nb_vars: 0,
+            unsat: false,
+        }
+    }
+
+    /// Create a new literal, not associated with any signal
+    pub fn new_lit(&mut self) -> Lit {
+        let l = Lit::new(self.nb_vars, false);
+        self.nb_vars += 1;
+        l
+    }
+
+    /// Solver literal for a signal, or None for constants
+    pub fn lit(&mut self, s: Signal) -> Option<Lit> {
+        if s.is_constant() {
+            return None;
+        }
+        let v = s.without_inversion();
+        let l = match self.lits.get(&v) {
+            Some(l) => *l,
+            None => {
+                let l = self.new_lit();
+                self.lits.insert(v, l);
+                l
+            }
+        };
+        Some(if s.is_inverted() { !l } else { l })
+    }
+
+    /// Add a clause over solver literals
+    fn add_lits(&mut self, lits: Vec<Lit>) {
+        if lits.is_empty() {
+            self.unsat = true;
+        } else {
+            self.solver.add_clause(Clause::from_iter(lits)).unwrap();
+        }
+    }
+
+    /// Add a clause over signals, that only holds when the activation literal is assumed
+    pub fn add_guarded_clause(&mut self, act: Option<Lit>, clause: &[Signal]) {
+        if clause.contains(&Signal::one()) {
+            return;
+        }
+        let lits = clause
+            .iter()
+            .filter_map(|s| self.lit(*s))
+            .chain(act.map(|a| !a))
+            .collect();
+        self.add_lits(lits);
+    }
+
+    /// Add a clause over signals
+    pub fn add_clause(&mut self, clause: &[Signal]) {
+        self.add_guarded_clause(None, clause);
+    }
+
+    /// Add the clauses for a subset of the nodes of a network
+    ///
+    /// Flip-flops are not allowed in the subset. The additional variables used by the encoding
+    /// are local to this call.
+    pub fn add_nodes(&mut self, aig: &Network, nodes: impl IntoIterator<Item = usize>) {
+        let first_extra = aig.nb_nodes() as u32;
+        let mut var = first_extra;
+        let clauses = to_cnf_nodes(aig, nodes, &mut var);
+        self.add_cnf(None, clauses, first_extra, var);
+    }
+
+    /// Add the clauses for gates that are not in a network, that only hold when the activation
+    /// literal is assumed
+    ///
+    /// The gates are numbered as nodes from `first_node`, and may use each other. Once the
+    /// activation literal is released, their signals can be reused by other nodes.
+    pub fn add_guarded_gates(&mut self, act: Option<Lit>, first_node: usize, gates: &[Gate]) {
+        let first_extra = (first_node + gates.len()) as u32;
+        let mut var = first_extra;
+        let outputs = (first_node..).map(|i| Signal::from_var(i as u32));
+        let clauses = to_cnf_gates(outputs.zip(gates), &mut var);
+        self.add_cnf(act, clauses, first_extra, var);
+    }
+
+    /// Add clauses from the CNF encoding, with local variables from `first_extra` to `end_extra`
+    fn add_cnf(
+        &mut self,
+        act: Option<Lit>,
+        clauses: Vec<Vec<Signal>>,
+        first_extra: u32,
+        end_extra: u32,
+    ) {
+        let extra: Vec<Lit> = (first_extra..end_extra).map(|_| self.new_lit()).collect();
+        for c in clauses {
+            let lits = c
+                .iter()
+                .filter(|s| **s != Signal::zero())
+                .map(|s| {
+                    if s.is_var() && s.var() >= first_extra {
+                        let l = extra[(s.var() - first_extra) as usize];
+                        if s.is_inverted() {
+                            !l
+                        } else {
+                            l
+                        }
+                    } else {
+                        self.lit(*s).unwrap()
+                    }
+                })
+                .chain(act.map(|a| !a))
+                .collect();
+            self.add_lits(lits);
+        }
+    }
+
+    /// Disable the clauses guarded by an activation literal for all subsequent queries
+    pub fn release(&mut self, act: Lit) {
+        self.add_lits(vec![!act]);
+    }
+
+    /// Solve under the given assumptions on signals and activation literals
+    ///
+    /// Returns whether the clauses are satisfiable. The values of the signals can then be
+    /// obtained with [`IncrementalSolver::value`].
+    pub fn solve(&mut self, assumptions: &[Signal], acts: &[Lit]) -> bool {
+        if self.unsat || assumptions.contains(&Signal::zero()) {
+            return false;
+        }
+        let mut lits: Vec<Lit> = assumptions.iter().filter_map(|s| self.lit(*s)).collect();
+        lits.extend(acts);
+        match self.solver.solve_assumps(&lits).unwrap() {
+            SolverResult::Sat => true,
+            SolverResult::Unsat => false,
+            SolverResult::Interrupted => panic!("Sat solver couldn't run to completion"),
+        }
+    }
+
+    /// Value of a signal in the last solution
+    ///
+    /// Signals that do not appear in any clause are considered to be 0.
+    pub fn value(&self, s: Signal) -> bool {
+        if s.is_constant() {
+            return s == Signal::one();
+        }
+        match self.lits.get(&s.without_inversion()) {
+            Some(l) => {
+                let v = self.solver.lit_val(*l) == Ok(TernaryVal::True);
+                v ^ s.is_inverted()
+            }
+            None => s.is_inverted(),
+        }
+    }
+}
+
+/// Incremental equivalence prover for the combinatorial logic of a network
+///
+/// The fanin cones of the signals are encoded lazily, and flip-flops are free variables. The
+/// network may grow between queries, and its nodes may be replaced by equivalent logic.
+pub(crate) struct Prover {
+    /// Solver, shared by all queries
+    solver: IncrementalSolver,
+    /// Whether the clauses of each node have been added to the solver
+    loaded: Vec<bool>,
+}
+
+impl Prover {
+    pub fn new() -> Prover {
+        Prover {
+            solver: IncrementalSolver::new(),
+            loaded: Vec::new(),
+        }
+    }
+
+    /// Add the clauses of the fanin cone of a signal
+    ///
+    /// Signals beyond the nodes of the network are ignored.
+    fn load(&mut self, aig: &Network, s: Signal) {
+        self.loaded.resize(aig.nb_nodes(), false);
+        let mut to_visit: Vec<u32> = Vec::new();
+        if s.is_var() && (s.var() as usize) < aig.nb_nodes() {
+            to_visit.push(s.var());
+        }
+        let mut nodes = Vec::new();
+        while let Some(v) = to_visit.pop() {
+            let i = v as usize;
+            if self.loaded[i] {
+                continue;
+            }
+            self.loaded[i] = true;
+            if aig.gate(i).is_comb() {
+                to_visit.extend(aig.gate(i).vars());
+                nodes.push(i);
+            }
+        }
+        self.solver.add_nodes(aig, nodes);
+    }
+
+    /// Solve for two signals with different values, under an activation literal
+    fn solve_difference(
+        &mut self,
+        aig: &Network,
+        a: Signal,
+        b: Signal,
+        act: Lit,
+    ) -> Option<(Vec<bool>, Vec<bool>)> {
+        self.solver.add_guarded_clause(Some(act), &[a, b]);
+        self.solver.add_guarded_clause(Some(act), &[!a, !b]);
+        if self.solver.solve(&[], &[act]) {
+            let inputs = (0..aig.nb_inputs())
+                .map(|i| self.solver.value(aig.input(i)))
+                .collect();
+            let dffs = (0..aig.nb_nodes())
+                .filter(|i| !aig.gate(*i).is_comb())
+                .map(|i| self.solver.value(aig.node(i)))
+                .collect();
+            Some((inputs, dffs))
+        } else {
+            None
+        }
+    }
+
+    /// Find an assignment such that two signals have different values
+    ///
+    /// Returns the values of the inputs and of the flip-flops, or None if the signals are
+    /// equivalent. In this case, the equivalence is kept for subsequent queries.
+    pub fn find_difference(
+        &mut self,
+        aig: &Network,
+        a: Signal,
+        b: Signal,
+    ) -> Option<(Vec<bool>, Vec<bool>)> {
+        self.load(aig, a);
+        self.load(aig, b);
+        let act = self.solver.new_lit();
+        let ret = self.solve_difference(aig, a, b, act);
+        self.solver.release(act);
+        if ret.is_none() {
+            self.solver.add_clause(&[!a, b]);
+            self.solver.add_clause(&[a, !b]);
+        }
+        ret
+    }
+
+    /// Find an assignment such that a signal differs from new logic on top of the network
+    ///
+    /// The new gates are numbered as nodes after the network, and `b` may use them. They are
+    /// only encoded for this query, so they can be rejected without modifying the network.
+    pub fn find_difference_with(
+        &mut self,
+        aig: &Network,
+        a: Signal,
+        b: Signal,
+        gates: &[Gate],
+    ) -> Option<(Vec<bool>, Vec<bool>)> {
+        self.load(aig, a);
+        self.load(aig, b);
+        for g in gates {
+            for s in g.dependencies() {
+                self.load(aig, *s);
+            }
+        }
+        let act = self.solver.new_lit();
+        self.solver
+            .add_guarded_gates(Some(act), aig.nb_nodes(), gates);
+        let ret = self.solve_difference(aig, a, b, act);
+        self.solver.release(act);
+        ret
+    }
+}
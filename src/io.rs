@@ -1,71 +1,180 @@
 //! Read and write logic networks to files
 
+mod aiger;
 mod bench;
 mod blif;
+mod error;
+mod expression;
+mod genlib;
+mod liberty;
 mod patterns;
 mod utils;
+mod verilog;
 
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+pub use aiger::{read_aiger, write_aiger};
 pub use bench::{read_bench, write_bench};
-pub use blif::{read_blif, write_blif};
+pub use blif::{read_blif, write_blif, write_blif_mapped};
+pub use error::{Error, Location};
+pub use genlib::read_genlib;
+pub use liberty::read_liberty;
 pub use patterns::{read_patterns, write_patterns};
+pub use verilog::{read_verilog, write_verilog, write_verilog_mapped};
 
+use crate::network::library::{Library, MappedNetwork};
 use crate::Network;
 
+/// File extensions supported for logic networks
+const NETWORK_EXTENSIONS: [&str; 5] = ["bench", "blif", "aag", "aig", "v"];
+
+/// Error for a file whose extension is not supported
+fn unknown_format(path: &Path) -> Error {
+    let ext = path.extension().unwrap_or_default();
+    Error::UnknownFormat {
+        loc: Location::default(),
+        token: ext.to_string_lossy().to_string(),
+    }
+}
+
 /// Read a logic network from a file
 ///
-/// .bench and .blif formats are supported, with limitations to the .blif format support
-pub fn read_network_file(path: &PathBuf) -> Network {
-    let ext = path.extension();
-    let f = File::open(path).unwrap();
-    match ext {
-        None => panic!("No extension given"),
-        Some(s) => {
-            if s == "bench" {
-                read_bench(f).unwrap()
-            } else if s == "blif" {
-                read_blif(f).unwrap()
-            } else {
-                panic!("Unknown extension {}", s.to_string_lossy());
-            }
+/// .bench, .blif, .aag, .aig and .v formats are supported, with limitations to the .blif and .v format support
+pub fn read_network_file(path: &PathBuf) -> Result<Network, Error> {
+    let read = || {
+        let Some(s) = path.extension() else {
+            return Err(unknown_format(path));
+        };
+        if !NETWORK_EXTENSIONS.iter().any(|e| s == *e) {
+            return Err(unknown_format(path));
         }
-    }
+        let f = File::open(path)?;
+        if s == "bench" {
+            read_bench(f)
+        } else if s == "blif" {
+            read_blif(f)
+        } else if s == "aag" || s == "aig" {
+            read_aiger(f)
+        } else {
+            read_verilog(f)
+        }
+    };
+    read().map_err(|e| e.with_file(path))
 }
 
 /// Write a logic network to a file
 ///
-/// .bench and .blif formats are supported
-pub fn write_network_file(path: &PathBuf, aig: &Network) {
-    let ext = path.extension();
-    match ext {
-        None => panic!("No extension given"),
-        Some(s) => {
-            let mut f = File::create(path).unwrap();
-            if s == "bench" {
-                write_bench(&mut f, aig);
-            } else if s == "blif" {
-                write_blif(&mut f, aig);
-            } else {
-                panic!("Unknown extension {}", s.to_string_lossy());
-            }
+/// .bench, .blif, .aag, .aig and .v formats are supported
+pub fn write_network_file(path: &PathBuf, aig: &Network) -> Result<(), Error> {
+    let write = || {
+        let Some(s) = path.extension() else {
+            return Err(unknown_format(path));
+        };
+        if !NETWORK_EXTENSIONS.iter().any(|e| s == *e) {
+            return Err(unknown_format(path));
         }
-    }
+        let mut f = File::create(path)?;
+        if s == "bench" {
+            write_bench(&mut f, aig);
+        } else if s == "blif" {
+            write_blif(&mut f, aig);
+        } else if s == "aag" {
+            write_aiger(&mut f, aig, false);
+        } else if s == "aig" {
+            write_aiger(&mut f, aig, true);
+        } else {
+            write_verilog(&mut f, aig);
+        }
+        Ok(())
+    };
+    write().map_err(|e| e.with_file(path))
+}
+
+/// Read a cell library from a file
+///
+/// .genlib and .lib (Liberty) formats are supported, with only the combinatorial cells
+pub fn read_library_file(path: &PathBuf) -> Result<Library, Error> {
+    let read = || {
+        let Some(s) = path.extension() else {
+            return Err(unknown_format(path));
+        };
+        if s == "genlib" {
+            read_genlib(File::open(path)?)
+        } else if s == "lib" {
+            read_liberty(File::open(path)?)
+        } else {
+            Err(unknown_format(path))
+        }
+    };
+    read().map_err(|e| e.with_file(path))
+}
+
+/// Write a network mapped to library cells to a file
+///
+/// .blif and .v formats are supported
+pub fn write_mapped_file(
+    path: &PathBuf,
+    mapped: &MappedNetwork,
+    library: &Library,
+) -> Result<(), Error> {
+    let write = || {
+        let Some(s) = path.extension() else {
+            return Err(unknown_format(path));
+        };
+        if s != "blif" && s != "v" {
+            return Err(unknown_format(path));
+        }
+        let mut f = File::create(path)?;
+        if s == "blif" {
+            write_blif_mapped(&mut f, mapped, library);
+        } else {
+            write_verilog_mapped(&mut f, mapped, library);
+        }
+        Ok(())
+    };
+    write().map_err(|e| e.with_file(path))
 }
 
 /// Read patterns from a file
 ///
 /// Each pattern may contain multiple timesteps. For each timestep, the value of each circuit input is given.
-pub fn read_pattern_file(path: &PathBuf) -> Vec<Vec<Vec<bool>>> {
-    let f = File::open(path).unwrap();
-    read_patterns(f).unwrap()
+pub fn read_pattern_file(path: &PathBuf) -> Result<Vec<Vec<Vec<bool>>>, Error> {
+    let read = || read_patterns(File::open(path)?);
+    read().map_err(|e| e.with_file(path))
 }
 
 /// Write patterns to a file
 ///
 /// Each pattern may contain multiple timesteps. For each timestep, the value of each circuit input is given.
-pub fn write_pattern_file(path: &PathBuf, patterns: &Vec<Vec<Vec<bool>>>) {
-    let mut f = File::create(path).unwrap();
+pub fn write_pattern_file(path: &PathBuf, patterns: &Vec<Vec<Vec<bool>>>) -> Result<(), Error> {
+    let mut f = File::create(path).map_err(|e| Error::from(e).with_file(path))?;
     write_patterns(&mut f, patterns);
+    Ok(())
+}
+
+/// Small mapped network with its library, shared by the tests of the writers
+#[cfg(test)]
+fn mapped_example() -> (MappedNetwork, Library) {
+    use crate::network::library::Cell;
+    use crate::{Gate, Signal};
+    use volute::Lut;
+
+    let mut lib = Library::new();
+    lib.add_cell(Cell::new("INV", 1.0, 1.0, &["A"], "Y", !Lut::nth_var(1, 0)));
+    let nand = !(Lut::nth_var(2, 0) & Lut::nth_var(2, 1));
+    lib.add_cell(Cell::new("NAND2", 2.0, 1.0, &["A", "B"], "Y", nand.clone()));
+    let mut aig = Network::new();
+    let a = aig.add_input();
+    let b = aig.add_input();
+    aig.set_input_name(0, "a");
+    aig.set_input_name(1, "b");
+    let x = aig.add(Gate::lut(&[a, b], nand));
+    let y = aig.add(Gate::lut(&[x], !Lut::nth_var(1, 0)));
+    let q = aig.add(Gate::dff(y, Signal::one(), Signal::zero()));
+    aig.add_output(y);
+    aig.add_output(q);
+    aig.set_output_name(0, "y");
+    let mapped = MappedNetwork::new(aig, vec![Some(1), Some(0), None]);
+    (mapped, lib)
 }
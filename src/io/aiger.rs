@@ -0,0 +1,784 @@
+//! IO for .aag and .aig (Aiger) files
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use volute::Lut;
+
+use crate::network::{BinaryType, NaryType, TernaryType};
+use crate::{Gate, Network, Signal};
+
+use super::error::{Error, Location};
+use super::utils::SignalNames;
+
+/// Cursor over an Aiger file, that mixes text lines and binary data
+struct AigerParser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> AigerParser<'a> {
+    /// Returns whether the whole file has been consumed
+    fn at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    /// Location of the last line read
+    fn loc(&self) -> Location {
+        Location::new(self.line, 1)
+    }
+
+    /// Read the next text line
+    fn next_line(&mut self) -> Result<String, Error> {
+        if self.at_end() {
+            return Err(Error::syntax(
+                Location::new(self.line + 1, 1),
+                "",
+                "Unexpected end of file",
+            ));
+        }
+        let end = self.data[self.pos..]
+            .iter()
+            .position(|c| *c == b'\n')
+            .map(|p| self.pos + p)
+            .unwrap_or(self.data.len());
+        let line = String::from_utf8_lossy(&self.data[self.pos..end]).to_string();
+        self.pos = end + 1;
+        self.line += 1;
+        Ok(line)
+    }
+
+    /// Read the next line, expecting a fixed number of integers
+    fn next_numbers(&mut self, min_count: usize, max_count: usize) -> Result<Vec<u32>, Error> {
+        let line = self.next_line()?;
+        let mut numbers = Vec::new();
+        for t in line.split_whitespace() {
+            let n = t.parse::<u32>().map_err(|_| {
+                Error::syntax(
+                    Location::of_token(self.line, &line, t),
+                    t,
+                    "Invalid integer in Aiger file",
+                )
+            })?;
+            numbers.push(n);
+        }
+        if numbers.len() < min_count || numbers.len() > max_count {
+            return Err(Error::arity(self.loc(), &line, min_count, numbers.len()));
+        }
+        Ok(numbers)
+    }
+
+    /// Read a variable-length integer, as used by the binary format
+    fn next_varint(&mut self) -> Result<u32, Error> {
+        let mut ret: u32 = 0;
+        let mut shift = 0;
+        loop {
+            if self.at_end() {
+                return Err(Error::syntax(
+                    self.loc(),
+                    "",
+                    "Unexpected end of file in binary And section",
+                ));
+            }
+            if shift > 28 {
+                return Err(Error::syntax(
+                    self.loc(),
+                    "",
+                    "Invalid integer in binary And section",
+                ));
+            }
+            let c = self.data[self.pos];
+            self.pos += 1;
+            if c == b'\n' {
+                self.line += 1;
+            }
+            ret |= ((c & 0x7f) as u32) << shift;
+            shift += 7;
+            if c & 0x80 == 0 {
+                return Ok(ret);
+            }
+        }
+    }
+}
+
+/// Header of an Aiger file
+struct AigerHeader {
+    binary: bool,
+    max_var: u32,
+    nb_inputs: u32,
+    nb_latches: u32,
+    nb_outputs: u32,
+    nb_ands: u32,
+    nb_bad: u32,
+}
+
+fn read_header(p: &mut AigerParser) -> Result<AigerHeader, Error> {
+    let line = p.next_line()?;
+    let tokens: Vec<_> = line.split_whitespace().collect();
+    if tokens.is_empty() {
+        return Err(Error::syntax(p.loc(), "", "Empty Aiger header"));
+    }
+    let binary = match tokens[0] {
+        "aag" => false,
+        "aig" => true,
+        t => return Err(Error::syntax(p.loc(), t, "Invalid Aiger header")),
+    };
+    let mut numbers = Vec::new();
+    for t in &tokens[1..] {
+        let n = t.parse::<u32>().map_err(|_| {
+            Error::syntax(
+                Location::of_token(p.line, &line, t),
+                t,
+                "Invalid Aiger header",
+            )
+        })?;
+        numbers.push(n);
+    }
+    if numbers.len() < 5 || numbers.len() > 9 {
+        return Err(Error::arity(p.loc(), tokens[0], 5, numbers.len()));
+    }
+    let get = |i: usize| numbers.get(i).copied().unwrap_or(0);
+    if get(6) != 0 || get(7) != 0 || get(8) != 0 {
+        return Err(Error::syntax(
+            p.loc(),
+            &line,
+            "Invariant constraints, justice and fairness properties are not supported",
+        ));
+    }
+    let header = AigerHeader {
+        binary,
+        max_var: get(0),
+        nb_inputs: get(1),
+        nb_latches: get(2),
+        nb_outputs: get(3),
+        nb_ands: get(4),
+        nb_bad: get(5),
+    };
+    let nb_vars = header
+        .nb_inputs
+        .checked_add(header.nb_latches)
+        .and_then(|n| n.checked_add(header.nb_ands));
+    let bad_max_var = |message: &str| {
+        Error::syntax(
+            Location::of_token(p.line, &line, tokens[1]),
+            tokens[1],
+            message,
+        )
+    };
+    match nb_vars {
+        None => return Err(bad_max_var("Too many variables")),
+        Some(n) if n > header.max_var => {
+            return Err(bad_max_var("Maximum variable index is too small"))
+        }
+        Some(n) if binary && n != header.max_var => {
+            return Err(bad_max_var(
+                "Maximum variable index does not match the number of variables",
+            ))
+        }
+        _ => (),
+    }
+    if header.max_var > u32::MAX / 2 - 1 {
+        // Literals are twice the variable index, plus one for inversion
+        return Err(bad_max_var("Maximum variable index is too large"));
+    }
+    if header.nb_outputs.checked_add(header.nb_bad).is_none() {
+        return Err(Error::syntax(
+            Location::of_token(p.line, &line, tokens[4]),
+            tokens[4],
+            "Too many outputs",
+        ));
+    }
+    Ok(header)
+}
+
+/// Signals associated with the variables of an Aiger file
+///
+/// This is a map rather than a vector, so that memory does not depend on the maximum variable
+/// index given in the header.
+struct VarMap {
+    max_var: u32,
+    sigs: HashMap<u32, Signal>,
+}
+
+/// Record the signal associated with a variable
+fn define_var(var_to_sig: &mut VarMap, lit: u32, s: Signal, loc: Location) -> Result<(), Error> {
+    let var = lit >> 1;
+    if lit & 1 != 0 || var == 0 {
+        return Err(Error::syntax(
+            loc,
+            &lit.to_string(),
+            "Invalid literal in definition",
+        ));
+    }
+    if var > var_to_sig.max_var {
+        return Err(Error::syntax(
+            loc,
+            &lit.to_string(),
+            "Literal is out of bounds",
+        ));
+    }
+    if var_to_sig.sigs.insert(var, s).is_some() {
+        return Err(Error::redefinition(loc, &lit.to_string()));
+    }
+    Ok(())
+}
+
+/// Obtain the signal associated with a literal
+fn lit_to_sig(var_to_sig: &VarMap, lit: u32, loc: &Location) -> Result<Signal, Error> {
+    match var_to_sig.sigs.get(&(lit >> 1)) {
+        Some(s) => Ok(s ^ (lit & 1 != 0)),
+        None => Err(Error::undefined(loc.clone(), &lit.to_string())),
+    }
+}
+
+/// Read the symbol table and comments at the end of the file, as (kind, index, name)
+fn read_symbols(
+    p: &mut AigerParser,
+    header: &AigerHeader,
+) -> Result<Vec<(char, u32, String)>, Error> {
+    let mut ret = Vec::new();
+    while !p.at_end() {
+        let line = p.next_line()?;
+        if line == "c" {
+            // Comment section: ignore everything afterwards
+            break;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = || Error::syntax(p.loc(), &line, "Invalid symbol table entry");
+        let mut chars = line.chars();
+        let kind = chars.next().ok_or_else(invalid)?;
+        let (index, name) = chars.as_str().split_once(' ').ok_or_else(invalid)?;
+        let index = index.parse::<u32>().map_err(|_| invalid())?;
+        let bound = match kind {
+            'i' => header.nb_inputs,
+            'l' => header.nb_latches,
+            'o' => header.nb_outputs,
+            'b' => header.nb_bad,
+            _ => return Err(invalid()),
+        };
+        if index >= bound {
+            return Err(Error::syntax(
+                p.loc(),
+                &line,
+                "Symbol table entry out of bounds",
+            ));
+        }
+        ret.push((kind, index, name.to_owned()));
+    }
+    Ok(ret)
+}
+
+/// Read a network in .aag (ascii) or .aig (binary) format, as used by the Aiger tools
+///
+/// The format is detected from the header. The specification is available
+/// [here](https://fmv.jku.at/aiger/FORMAT.aiger).
+///
+/// Latches become flip-flops without enable or reset. Latches initialized to one are represented
+/// with inverted flip-flops, and uninitialized latches are considered initialized to zero.
+/// Bad state properties are added as outputs after the regular outputs. Invariant constraints,
+/// justice and fairness properties are not supported.
+pub fn read_aiger<R: Read>(mut r: R) -> Result<Network, Error> {
+    let mut data = Vec::new();
+    r.read_to_end(&mut data)?;
+    let mut p = AigerParser {
+        data: &data,
+        pos: 0,
+        line: 0,
+    };
+    let header = read_header(&mut p)?;
+
+    let mut var_to_sig = VarMap {
+        max_var: header.max_var,
+        sigs: HashMap::from([(0, Signal::zero())]),
+    };
+
+    let mut ret = Network::new();
+    ret.add_inputs(header.nb_inputs as usize);
+
+    // Inputs
+    for i in 0..header.nb_inputs {
+        let lit = if header.binary {
+            2 * (i + 1)
+        } else {
+            p.next_numbers(1, 1)?[0]
+        };
+        define_var(&mut var_to_sig, lit, ret.input(i as usize), p.loc())?;
+    }
+
+    // Latches, as (next state, reset value, location)
+    let mut latches = Vec::new();
+    for i in 0..header.nb_latches {
+        let (lit, next, reset) = if header.binary {
+            let v = p.next_numbers(1, 2)?;
+            (2 * (header.nb_inputs + i + 1), v[0], v.get(1).copied())
+        } else {
+            let v = p.next_numbers(2, 3)?;
+            (v[0], v[1], v.get(2).copied())
+        };
+        let init_one = match reset {
+            None | Some(0) => false,
+            Some(1) => true,
+            Some(r) if r == lit => false,
+            Some(r) => {
+                return Err(Error::syntax(
+                    p.loc(),
+                    &r.to_string(),
+                    "Invalid reset value for latch",
+                ))
+            }
+        };
+        define_var(
+            &mut var_to_sig,
+            lit,
+            Signal::from_var(i) ^ init_one,
+            p.loc(),
+        )?;
+        latches.push((next, init_one, p.loc()));
+    }
+
+    // Outputs and bad state properties
+    let mut outputs = Vec::new();
+    for _ in 0..header.nb_outputs + header.nb_bad {
+        outputs.push((p.next_numbers(1, 1)?[0], p.loc()));
+    }
+
+    // And gates
+    let mut ands = Vec::new();
+    for i in 0..header.nb_ands {
+        let (lhs, rhs0, rhs1) = if header.binary {
+            let lhs = 2 * (header.nb_inputs + header.nb_latches + i + 1);
+            let delta0 = p.next_varint()?;
+            let delta1 = p.next_varint()?;
+            if delta0 > lhs || delta1 > lhs - delta0 {
+                return Err(Error::syntax(
+                    p.loc(),
+                    &lhs.to_string(),
+                    "Invalid binary encoding for And gate",
+                ));
+            }
+            (lhs, lhs - delta0, lhs - delta0 - delta1)
+        } else {
+            let v = p.next_numbers(3, 3)?;
+            (v[0], v[1], v[2])
+        };
+        let s = Signal::from_var(header.nb_latches + i);
+        define_var(&mut var_to_sig, lhs, s, p.loc())?;
+        ands.push((lhs, rhs0, rhs1, p.loc()));
+    }
+
+    let symbols = read_symbols(&mut p, &header)?;
+
+    for (next, init_one, loc) in &latches {
+        let d = lit_to_sig(&var_to_sig, *next, loc)? ^ *init_one;
+        ret.add(Gate::dff(d, Signal::one(), Signal::zero()));
+    }
+    for (_, rhs0, rhs1, loc) in &ands {
+        let a = lit_to_sig(&var_to_sig, *rhs0, loc)?;
+        let b = lit_to_sig(&var_to_sig, *rhs1, loc)?;
+        ret.add(Gate::and(std::cmp::min(a, b), std::cmp::max(a, b)));
+    }
+    for (o, loc) in &outputs {
+        ret.add_output(lit_to_sig(&var_to_sig, *o, loc)?);
+    }
+    for (kind, index, name) in symbols {
+        let index = index as usize;
+        match kind {
+            'i' => ret.set_input_name(index, &name),
+            'o' => ret.set_output_name(index, &name),
+            'b' => ret.set_output_name(header.nb_outputs as usize + index, &name),
+            // Latches initialized to one are inverted flip-flops, and do not keep their name
+            _ => {
+                if !latches[index].1 {
+                    ret.set_node_name(index, &name);
+                }
+            }
+        }
+    }
+    if !ret.is_topo_sorted() {
+        if let Err(i) = ret.try_topo_sort() {
+            // Only And gates can be part of a combinatorial loop
+            let (lhs, _, _, loc) = &ands[i - latches.len()];
+            return Err(Error::comb_loop(loc.clone(), &lhs.to_string()));
+        }
+    }
+    ret.check();
+    Ok(ret)
+}
+
+/// Conversion of a network to the And-only representation used by Aiger
+struct AigerEncoder {
+    nb_inputs: u32,
+    nb_latches: u32,
+    node_lits: Vec<u32>,
+    ands: Vec<(u32, u32)>,
+}
+
+impl AigerEncoder {
+    /// Literal associated with a signal
+    fn lit(&self, s: &Signal) -> u32 {
+        let inv = s.is_inverted() as u32;
+        if s.is_constant() {
+            inv
+        } else if s.is_input() {
+            (2 * (s.input() + 1)) ^ inv
+        } else {
+            self.node_lits[s.var() as usize] ^ inv
+        }
+    }
+
+    /// Create a new And gate, without any simplification
+    fn add_and(&mut self, a: u32, b: u32) -> u32 {
+        let lhs = 2 * (self.nb_inputs + self.nb_latches + self.ands.len() as u32 + 1);
+        self.ands.push((a, b));
+        lhs
+    }
+
+    /// Create an And gate, with simplification of trivial cases
+    fn and(&mut self, a: u32, b: u32) -> u32 {
+        if a == 0 || b == 0 || a == b ^ 1 {
+            0
+        } else if a == 1 || a == b {
+            b
+        } else if b == 1 {
+            a
+        } else {
+            self.add_and(a, b)
+        }
+    }
+
+    fn or(&mut self, a: u32, b: u32) -> u32 {
+        self.and(a ^ 1, b ^ 1) ^ 1
+    }
+
+    fn xor(&mut self, a: u32, b: u32) -> u32 {
+        let x0 = self.and(a, b ^ 1);
+        let x1 = self.and(a ^ 1, b);
+        self.or(x0, x1)
+    }
+
+    fn mux(&mut self, s: u32, a: u32, b: u32) -> u32 {
+        let x0 = self.and(s, a);
+        let x1 = self.and(s ^ 1, b);
+        self.or(x0, x1)
+    }
+
+    fn maj(&mut self, a: u32, b: u32, c: u32) -> u32 {
+        let x0 = self.and(a, b);
+        let x1 = self.or(a, b);
+        let x2 = self.and(c, x1);
+        self.or(x0, x2)
+    }
+
+    /// Balanced tree of And gates
+    fn andn(&mut self, v: &[u32]) -> u32 {
+        match v.len() {
+            0 => 1,
+            1 => v[0],
+            _ => {
+                let (l, r) = v.split_at(v.len() / 2);
+                let a = self.andn(l);
+                let b = self.andn(r);
+                self.and(a, b)
+            }
+        }
+    }
+
+    /// Balanced tree of Xor gates
+    fn xorn(&mut self, v: &[u32]) -> u32 {
+        match v.len() {
+            0 => 0,
+            1 => v[0],
+            _ => {
+                let (l, r) = v.split_at(v.len() / 2);
+                let a = self.xorn(l);
+                let b = self.xorn(r);
+                self.xor(a, b)
+            }
+        }
+    }
+
+    /// Shannon decomposition of a Lut, starting from the last variable
+    fn lut(&mut self, lut: &Lut, v: &[u32]) -> u32 {
+        if *lut == Lut::zero(lut.num_vars()) {
+            return 0;
+        }
+        if *lut == Lut::one(lut.num_vars()) {
+            return 1;
+        }
+        let ind = v.len() - 1;
+        let (c0, c1) = lut.cofactors(ind);
+        let l0 = self.lut(&c0, &v[..ind]);
+        let l1 = self.lut(&c1, &v[..ind]);
+        self.mux(v[ind], l1, l0)
+    }
+
+    /// Convert a combinatorial gate to And gates
+    fn encode_gate(&mut self, g: &Gate) -> u32 {
+        use Gate::*;
+        let v: Vec<u32> = g.dependencies().iter().map(|s| self.lit(s)).collect();
+        match g {
+            Binary(_, BinaryType::And) => self.add_and(v[0], v[1]),
+            Binary(_, BinaryType::Xor) => self.xor(v[0], v[1]),
+            Ternary(_, TernaryType::And) => self.andn(&v),
+            Ternary(_, TernaryType::Xor) => self.xorn(&v),
+            Ternary(_, TernaryType::Mux) => self.mux(v[0], v[1], v[2]),
+            Ternary(_, TernaryType::Maj) => self.maj(v[0], v[1], v[2]),
+            Nary(_, tp) => {
+                let vi: Vec<u32> = v.iter().map(|l| l ^ 1).collect();
+                match tp {
+                    NaryType::And => self.andn(&v),
+                    NaryType::Nand => self.andn(&v) ^ 1,
+                    NaryType::Or => self.andn(&vi) ^ 1,
+                    NaryType::Nor => self.andn(&vi),
+                    NaryType::Xor => self.xorn(&v),
+                    NaryType::Xnor => self.xorn(&v) ^ 1,
+                }
+            }
+            Buf(_) => v[0],
+            Lut(lut) => self.lut(&lut.lut, &v),
+            Dff(_) => panic!("Flip-flops are handled separately"),
+        }
+    }
+
+    /// Convert the whole network. Returns the next state of the latches
+    fn encode(aig: &Network) -> (AigerEncoder, Vec<u32>) {
+        assert!(aig.is_topo_sorted());
+        let mut enc = AigerEncoder {
+            nb_inputs: aig.nb_inputs() as u32,
+            nb_latches: 0,
+            node_lits: vec![0; aig.nb_nodes()],
+            ands: Vec::new(),
+        };
+        for i in 0..aig.nb_nodes() {
+            if !aig.gate(i).is_comb() {
+                enc.node_lits[i] = 2 * (enc.nb_inputs + enc.nb_latches + 1);
+                enc.nb_latches += 1;
+            }
+        }
+        for i in 0..aig.nb_nodes() {
+            let g = aig.gate(i);
+            if g.is_comb() {
+                enc.node_lits[i] = enc.encode_gate(g);
+            }
+        }
+        let mut next_state = Vec::new();
+        for i in 0..aig.nb_nodes() {
+            if let Gate::Dff([d, en, res]) = aig.gate(i) {
+                // Enable and reset are not supported by Aiger: represent them with logic
+                let q = enc.node_lits[i];
+                let (d, en, res) = (enc.lit(d), enc.lit(en), enc.lit(res));
+                let mx = enc.mux(en, d, q);
+                next_state.push(enc.and(mx, res ^ 1));
+            }
+        }
+        (enc, next_state)
+    }
+}
+
+/// Write an integer in the variable-length encoding of the binary format
+fn write_varint<W: Write>(w: &mut W, mut x: u32) {
+    while x & !0x7f != 0 {
+        w.write_all(&[((x & 0x7f) | 0x80) as u8]).unwrap();
+        x >>= 7;
+    }
+    w.write_all(&[x as u8]).unwrap();
+}
+
+/// Write a network in .aag (ascii) or .aig (binary) format, as used by the Aiger tools
+///
+/// The specification is available [here](https://fmv.jku.at/aiger/FORMAT.aiger).
+/// All gates are converted to And gates, and flip-flop enables and resets are converted to logic.
+/// 2-input And gates are exported as is, so that an And-only network is read back exactly.
+pub fn write_aiger<W: Write>(w: &mut W, aig: &Network, binary: bool) {
+    let (enc, next_state) = AigerEncoder::encode(aig);
+    let nb_inputs = enc.nb_inputs;
+    let nb_latches = enc.nb_latches;
+    let nb_ands = enc.ands.len() as u32;
+    writeln!(
+        w,
+        "{} {} {} {} {} {}",
+        if binary { "aig" } else { "aag" },
+        nb_inputs + nb_latches + nb_ands,
+        nb_inputs,
+        nb_latches,
+        aig.nb_outputs(),
+        nb_ands
+    )
+    .unwrap();
+    if !binary {
+        for i in 0..nb_inputs {
+            writeln!(w, "{}", 2 * (i + 1)).unwrap();
+        }
+    }
+    for (i, next) in next_state.iter().enumerate() {
+        if binary {
+            writeln!(w, "{}", next).unwrap();
+        } else {
+            writeln!(w, "{} {}", 2 * (nb_inputs + i as u32 + 1), next).unwrap();
+        }
+    }
+    for i in 0..aig.nb_outputs() {
+        writeln!(w, "{}", enc.lit(&aig.output(i))).unwrap();
+    }
+    for (i, (a, b)) in enc.ands.iter().enumerate() {
+        let lhs = 2 * (nb_inputs + nb_latches + i as u32 + 1);
+        let (rhs0, rhs1) = (std::cmp::max(*a, *b), std::cmp::min(*a, *b));
+        if binary {
+            write_varint(w, lhs - rhs0);
+            write_varint(w, rhs0 - rhs1);
+        } else {
+            writeln!(w, "{} {} {}", lhs, rhs0, rhs1).unwrap();
+        }
+    }
+
+    // Symbol table, with the same names as other formats
+    let names = SignalNames::from_network(aig);
+    for i in 0..aig.nb_inputs() {
+        writeln!(w, "i{} {}", i, names.input(i)).unwrap();
+    }
+    let mut latch_index = 0;
+    for i in 0..aig.nb_nodes() {
+        if !aig.gate(i).is_comb() {
+            writeln!(w, "l{} {}", latch_index, names.node(i)).unwrap();
+            latch_index += 1;
+        }
+    }
+    for i in 0..aig.nb_outputs() {
+        writeln!(w, "o{} {}", i, names.output(i)).unwrap();
+    }
+    writeln!(w, "c").unwrap();
+    writeln!(w, "Generated by quaigh").unwrap();
+}
+
+mod test {
+    #[cfg(test)]
+    fn check_identical(a: &crate::Network, b: &crate::Network) {
+        assert_eq!(a.nb_inputs(), b.nb_inputs());
+        assert_eq!(a.nb_outputs(), b.nb_outputs());
+        assert_eq!(a.nb_nodes(), b.nb_nodes());
+        for i in 0..a.nb_nodes() {
+            assert_eq!(a.gate(i), b.gate(i));
+        }
+        for i in 0..a.nb_outputs() {
+            assert_eq!(a.output(i), b.output(i));
+        }
+    }
+
+    #[test]
+    fn test_read_ascii() {
+        use crate::Signal;
+
+        // Half adder and toggle flip-flop, with out-of-order gates
+        let example = "aag 8 2 1 3 5
+2
+4
+6 16 1
+14
+12
+6
+10 8 12
+8 2 4
+12 3 5
+14 13 9
+16 7 11
+i0 a
+i1 b
+l0 t
+o0 sum
+o1 carry_n
+o2 toggle
+c
+Some comment
+";
+        let aig = super::read_aiger(example.as_bytes()).unwrap();
+        assert_eq!(aig.nb_inputs(), 2);
+        assert_eq!(aig.nb_outputs(), 3);
+        assert_eq!(aig.nb_nodes(), 6);
+        assert!(!aig.is_comb());
+        // Latch initialized to one
+        assert_eq!(aig.output(2), !Signal::from_var(0));
+        assert_eq!(aig.input_name(1), Some("b"));
+        assert_eq!(aig.output_name(1), Some("carry_n"));
+        assert_eq!(aig.node_name(0), None);
+    }
+
+    #[test]
+    fn test_read_binary() {
+        // And gate from the Aiger specification
+        let mut example = b"aig 3 2 0 1 1\n6\n".to_vec();
+        example.extend([0x02, 0x02]);
+        example.extend(b"i0 x\ni1 y\no0 o\n");
+        let aig = super::read_aiger(example.as_slice()).unwrap();
+        assert_eq!(aig.nb_inputs(), 2);
+        assert_eq!(aig.nb_outputs(), 1);
+        assert_eq!(aig.nb_nodes(), 1);
+        assert_eq!(aig.gate(0), &crate::Gate::and(aig.input(1), aig.input(0)));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(super::read_aiger("aag 1 1 0 1 0\n2\n4\n".as_bytes()).is_err());
+        assert!(super::read_aiger("aag 3 1 0 1 1\n2\n6\n6 2 4\n".as_bytes()).is_err());
+        assert!(super::read_aiger("aag 2 2 0 0 0\n2\n2\n".as_bytes()).is_err());
+        assert!(super::read_aiger("aag 1 1 0 0 0 0 1\n2\n".as_bytes()).is_err());
+        assert!(super::read_aiger("abc 1 1 0 0 0\n2\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_malformed() {
+        // Symbol starting with a multibyte character
+        assert!(super::read_aiger("aag 1 1 0 0 0\n2\n\u{e9}0 x\n".as_bytes()).is_err());
+        // Overflow in the number of variables
+        assert!(super::read_aiger("aag 4294967295 4294967295 1 0 0\n".as_bytes()).is_err());
+        assert!(super::read_aiger("aag 5 1 0 4294967295 0 1\n".as_bytes()).is_err());
+        // Maximum variable index that does not fit in a literal
+        assert!(super::read_aiger("aag 4294967295 1 0 1 0\n2\n2\n".as_bytes()).is_err());
+        // Binary files must not have unused variables
+        assert!(super::read_aiger("aig 5 2 0 1 0\n2\n".as_bytes()).is_err());
+        // Large maximum variable index with few variables, without a large allocation
+        let aig = super::read_aiger("aag 2000000000 1 0 1 0\n3999999998\n3999999999\n".as_bytes())
+            .unwrap();
+        assert_eq!(aig.output(0), !aig.input(0));
+    }
+
+    #[test]
+    fn test_and_roundtrip() {
+        use crate::network::generators::carry_chain;
+        use std::io::BufWriter;
+
+        let mut aig = carry_chain::ripple_carry(8);
+        aig.add_output(crate::Signal::one());
+        aig.make_canonical();
+        for binary in [false, true] {
+            let mut buf = BufWriter::new(Vec::new());
+            super::write_aiger(&mut buf, &aig, binary);
+            let data = buf.into_inner().unwrap();
+            let read_back = super::read_aiger(data.as_slice()).unwrap();
+            check_identical(&aig, &read_back);
+        }
+    }
+
+    #[test]
+    fn test_seq_roundtrip() {
+        use crate::equiv::check_equivalence_bounded;
+        use crate::network::generators::{adder, testcases};
+        use std::io::BufWriter;
+
+        for aig in [
+            adder::ripple_carry(4),
+            testcases::toggle_chain(3, true, true),
+            testcases::ff_tree(2, true, false, 2),
+        ] {
+            for binary in [false, true] {
+                let mut buf = BufWriter::new(Vec::new());
+                super::write_aiger(&mut buf, &aig, binary);
+                let data = buf.into_inner().unwrap();
+                let read_back = super::read_aiger(data.as_slice()).unwrap();
+                check_equivalence_bounded(&aig, &read_back, 4, true).unwrap();
+            }
+        }
+    }
+}
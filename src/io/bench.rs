@@ -8,24 +8,45 @@ use volute::Lut;
 use crate::network::{BinaryType, NaryType, TernaryType};
 use crate::{Gate, Network, Signal};
 
-use super::utils::{get_inverted_signals, sig_to_string};
+use super::error::{Error, Location};
+use super::utils::{get_inverted_signals, SignalNames};
+
+/// A statement in the file, with its position
+struct Statement {
+    loc: Location,
+    text: String,
+    parts: Vec<String>,
+}
+
+impl Statement {
+    /// Location of a token within the statement
+    fn loc_of(&self, token: &str) -> Location {
+        Location::of_token(self.loc.line, &self.text, token)
+    }
+}
 
 fn build_name_to_sig(
-    statements: &Vec<Vec<String>>,
-    inputs: &Vec<String>,
-) -> HashMap<String, Signal> {
+    statements: &[Statement],
+    inputs: &[Statement],
+) -> Result<HashMap<String, Signal>, Error> {
     let mut ret = HashMap::new();
-    for (i, name) in inputs.iter().enumerate() {
+    for (i, s) in inputs.iter().enumerate() {
+        let name = &s.parts[1];
         let present = ret
             .insert(name.clone(), Signal::from_input(i as u32))
             .is_some();
-        assert!(!present, "{} is defined twice", name)
+        if present {
+            return Err(Error::redefinition(s.loc_of(name), name));
+        }
     }
     for (i, s) in statements.iter().enumerate() {
+        let name = &s.parts[0];
         let present = ret
-            .insert(s[0].to_string(), Signal::from_var(i as u32))
+            .insert(name.clone(), Signal::from_var(i as u32))
             .is_some();
-        assert!(!present, "{} is defined twice", s[0].to_string())
+        if present {
+            return Err(Error::redefinition(s.loc_of(name), name));
+        }
     }
 
     // ABC-style naming for constant signals
@@ -35,63 +56,81 @@ fn build_name_to_sig(
     if !ret.contains_key("gnd") {
         ret.insert("gnd".to_string(), Signal::zero());
     }
-    ret
+    Ok(ret)
 }
 
-fn check_statement(statement: &Vec<String>, name_to_sig: &HashMap<String, Signal>) {
-    let deps = &statement[2..];
+fn check_statement(
+    statement: &Statement,
+    name_to_sig: &HashMap<String, Signal>,
+) -> Result<(), Error> {
+    let deps = &statement.parts[2..];
     for dep in deps {
-        assert!(
-            name_to_sig.contains_key(dep),
-            "Gate input {dep} is not generated anywhere"
-        );
+        if !name_to_sig.contains_key(dep) {
+            return Err(Error::undefined(statement.loc_of(dep), dep));
+        }
     }
-    match statement[1].to_uppercase().as_str() {
-        "DFF" | "BUF" | "BUFF" | "NOT" => assert_eq!(deps.len(), 1),
-        "VDD" | "VSS" => assert_eq!(deps.len(), 0),
-        "MUX" | "MAJ" => assert_eq!(deps.len(), 3),
-        _ => (),
+    let tp = &statement.parts[1];
+    let expected = match tp.to_uppercase().as_str() {
+        "DFF" | "BUF" | "BUFF" | "NOT" => Some(1),
+        "VDD" | "VSS" | "GND" => Some(0),
+        "MUX" | "MAJ" => Some(3),
+        "DFFRSE" => Some(4),
+        _ => None,
     };
+    match expected {
+        Some(e) if e != deps.len() => Err(Error::arity(statement.loc_of(tp), tp, e, deps.len())),
+        _ => Ok(()),
+    }
 }
 
 fn gate_dependencies(
-    statement: &Vec<String>,
+    statement: &Statement,
     name_to_sig: &HashMap<String, Signal>,
 ) -> Box<[Signal]> {
-    statement[2..].iter().map(|n| name_to_sig[n]).collect()
+    statement.parts[2..]
+        .iter()
+        .map(|n| name_to_sig[n])
+        .collect()
 }
 
 fn network_from_statements(
-    statements: &Vec<Vec<String>>,
-    inputs: &Vec<String>,
-    outputs: &Vec<String>,
-) -> Result<Network, String> {
+    statements: &[Statement],
+    inputs: &[Statement],
+    outputs: &[Statement],
+) -> Result<Network, Error> {
     let mut ret = Network::new();
     ret.add_inputs(inputs.len());
 
     // Compute a mapping between the two
-    let name_to_sig = build_name_to_sig(statements, inputs);
+    let name_to_sig = build_name_to_sig(statements, inputs)?;
 
     // Check everything
     for statement in statements {
-        check_statement(statement, &name_to_sig);
+        check_statement(statement, &name_to_sig)?;
     }
     for output in outputs {
-        assert!(
-            name_to_sig.contains_key(output),
-            "Output {output} is not generated anywhere"
-        );
+        let name = &output.parts[1];
+        if !name_to_sig.contains_key(name) {
+            return Err(Error::undefined(output.loc_of(name), name));
+        }
     }
 
     // Setup the variables based on the mapping
     for s in statements {
         let sigs: Box<[Signal]> = gate_dependencies(s, &name_to_sig);
-        match s[1].to_uppercase().as_str() {
+        let tp = &s.parts[1];
+        match tp.to_uppercase().as_str() {
             "DFF" => {
                 ret.add(Gate::Dff([sigs[0], Signal::one(), Signal::zero()]));
             }
             "DFFRSE" => {
-                assert_eq!(sigs[1], Signal::zero());
+                if sigs[2] != Signal::zero() {
+                    return Err(Error::syntax(
+                        s.loc_of(&s.parts[4]),
+                        &s.parts[4],
+                        "Set signal of DFFRSE is not supported",
+                    ));
+                }
                 ret.add(Gate::Dff([sigs[0], sigs[3], sigs[1]]));
             }
             "BUF" | "BUFF" => {
@@ -131,21 +170,38 @@ fn network_from_statements(
                 ret.add(Gate::maj(sigs[0], sigs[1], sigs[2]));
             }
             _ => {
-                if s[1].starts_with("LUT 0x") {
-                    ret.add(Gate::lut(
-                        sigs.as_ref(),
-                        Lut::from_hex_string(sigs.len(), &s[1][6..]).unwrap(),
-                    ));
-                } else {
-                    return Err(format!("Unknown gate type {}", s[1]));
+                let lut = tp
+                    .strip_prefix("LUT 0x")
+                    .and_then(|h| Lut::from_hex_string(sigs.len(), h).ok());
+                match lut {
+                    Some(lut) => {
+                        ret.add(Gate::lut(sigs.as_ref(), lut));
+                    }
+                    None => {
+                        return Err(Error::syntax(s.loc_of(tp), tp, "Unknown gate type"));
+                    }
                 }
             }
         }
     }
     for o in outputs {
-        ret.add_output(name_to_sig[o]);
+        ret.add_output(name_to_sig[&o.parts[1]]);
+    }
+
+    // Keep the names from the file
+    for (i, s) in inputs.iter().enumerate() {
+        ret.set_input_name(i, &s.parts[1]);
+    }
+    for (i, s) in statements.iter().enumerate() {
+        ret.set_node_name(i, &s.parts[0]);
+    }
+    for (i, s) in outputs.iter().enumerate() {
+        ret.set_output_name(i, &s.parts[1]);
+    }
+    if let Err(i) = ret.try_topo_sort() {
+        let s = &statements[i];
+        return Err(Error::comb_loop(s.loc_of(&s.parts[0]), &s.parts[0]));
     }
-    ret.topo_sort();
     ret.check();
     Ok(ret)
 }
@@ -168,41 +224,56 @@ fn network_from_statements(
 ///     x8 = vdd
 ///     OUTPUT(x0)
 /// ```
-pub fn read_bench<R: Read>(r: R) -> Result<Network, String> {
+pub fn read_bench<R: Read>(r: R) -> Result<Network, Error> {
     let mut statements = Vec::new();
     let mut inputs = Vec::new();
     let mut outputs = Vec::new();
-    for l in BufReader::new(r).lines() {
-        if let Ok(s) = l {
-            let t = s.trim().to_owned();
-            if t.is_empty() || t.starts_with('#') {
-                continue;
+    for (i, l) in BufReader::new(r).lines().enumerate() {
+        let s = l?;
+        let t = s.trim();
+        if t.is_empty() || t.starts_with('#') {
+            continue;
+        }
+        let loc = Location::of_token(i + 1, &s, t);
+        if !t.contains('=') {
+            let parts: Vec<_> = t
+                .split(&['(', ')'])
+                .map(|s| s.trim().to_owned())
+                .filter(|s| !s.is_empty())
+                .collect();
+            let statement = Statement {
+                loc,
+                text: s.clone(),
+                parts,
+            };
+            if statement.parts.len() != 2 {
+                return Err(Error::syntax(statement.loc, t, "Invalid statement"));
             }
-            if !t.contains("=") {
-                let parts: Vec<_> = t
-                    .split(&['(', ')'])
-                    .map(|s| s.trim())
-                    .filter(|s| !s.is_empty())
-                    .collect();
-                assert_eq!(parts.len(), 2);
-                if ["INPUT", "PINPUT"].contains(&parts[0]) {
-                    inputs.push(parts[1].to_string());
-                } else if ["OUTPUT", "POUTPUT"].contains(&parts[0]) {
-                    outputs.push(parts[1].to_string());
-                } else {
-                    return Err(format!("Unknown keyword {}", parts[0]));
-                }
+            if ["INPUT", "PINPUT"].contains(&statement.parts[0].as_str()) {
+                inputs.push(statement);
+            } else if ["OUTPUT", "POUTPUT"].contains(&statement.parts[0].as_str()) {
+                outputs.push(statement);
             } else {
-                let parts: Vec<_> = t
-                    .split(&['=', '(', ',', ')'])
-                    .map(|s| s.trim().to_owned())
-                    .filter(|s| !s.is_empty())
-                    .collect();
-                assert!(parts.len() >= 2);
-                statements.push(parts);
+                return Err(Error::syntax(
+                    statement.loc,
+                    &statement.parts[0],
+                    "Unknown keyword",
+                ));
             }
         } else {
-            return Err("Error during file IO".to_string());
+            let parts: Vec<_> = t
+                .split(&['=', '(', ',', ')'])
+                .map(|s| s.trim().to_owned())
+                .filter(|s| !s.is_empty())
+                .collect();
+            if parts.len() < 2 {
+                return Err(Error::syntax(loc, t, "Invalid statement"));
+            }
+            statements.push(Statement {
+                loc,
+                text: s.clone(),
+                parts,
+            });
         }
     }
     network_from_statements(&statements, &inputs, &outputs)
@@ -229,12 +300,13 @@ pub fn read_bench<R: Read>(r: R) -> Result<Network, String> {
 pub fn write_bench<W: Write>(w: &mut W, aig: &Network) {
     writeln!(w, "# .bench (ISCAS) file").unwrap();
     writeln!(w, "# Generated by quaigh").unwrap();
+    let names = SignalNames::from_network(aig);
     for i in 0..aig.nb_inputs() {
-        writeln!(w, "INPUT({})", aig.input(i)).unwrap();
+        writeln!(w, "INPUT({})", names.input(i)).unwrap();
     }
     writeln!(w).unwrap();
     for i in 0..aig.nb_outputs() {
-        writeln!(w, "OUTPUT({})", sig_to_string(&aig.output(i))).unwrap();
+        writeln!(w, "OUTPUT({})", names.output(i)).unwrap();
     }
     writeln!(w).unwrap();
     for i in 0..aig.nb_nodes() {
@@ -243,10 +315,10 @@ pub fn write_bench<W: Write>(w: &mut W, aig: &Network) {
         let rep = g
             .dependencies()
             .iter()
-            .map(sig_to_string)
+            .map(|s| names.sig_to_string(s))
             .collect::<Vec<_>>()
             .join(", ");
-        write!(w, "x{} = ", i).unwrap();
+        write!(w, "{} = ", names.node(i)).unwrap();
         match g {
             Binary(_, BinaryType::And) | Ternary(_, TernaryType::And) => {
                 writeln!(w, "AND({})", rep).unwrap();
@@ -267,13 +339,13 @@ pub fn write_bench<W: Write>(w: &mut W, aig: &Network) {
                     writeln!(
                         w,
                         "DFFRSE({}, {}, gnd, {})",
-                        sig_to_string(d),
-                        sig_to_string(res),
-                        sig_to_string(en)
+                        names.sig_to_string(d),
+                        names.sig_to_string(res),
+                        names.sig_to_string(en)
                     )
                     .unwrap();
                 } else {
-                    writeln!(w, "DFF({})", sig_to_string(d)).unwrap();
+                    writeln!(w, "DFF({})", names.sig_to_string(d)).unwrap();
                 }
             }
             Ternary(_, TernaryType::Mux) => {
@@ -284,9 +356,9 @@ pub fn write_bench<W: Write>(w: &mut W, aig: &Network) {
             }
             Buf(s) => {
                 if s.is_constant() {
-                    writeln!(w, "{}", sig_to_string(s)).unwrap();
+                    writeln!(w, "{}", names.sig_to_string(s)).unwrap();
                 } else if s.is_inverted() {
-                    writeln!(w, "NOT({})", sig_to_string(&!s)).unwrap();
+                    writeln!(w, "NOT({})", names.sig_to_string(&!s)).unwrap();
                 } else {
                     writeln!(w, "BUF({})", rep).unwrap();
                 }
@@ -299,7 +371,16 @@ pub fn write_bench<W: Write>(w: &mut W, aig: &Network) {
 
     let signals_with_inv = get_inverted_signals(aig);
     for s in signals_with_inv {
-        writeln!(w, "{}_n = NOT({})", s, s).unwrap();
+        let name = names.sig_name(&s);
+        writeln!(w, "{}_n = NOT({})", name, name).unwrap();
+    }
+
+    // Outputs with their own names
+    for i in 0..aig.nb_outputs() {
+        if !names.output_is_signal(aig, i) {
+            let s = names.sig_to_string(&aig.output(i));
+            writeln!(w, "{} = BUF({})", names.output(i), s).unwrap();
+        }
     }
 }
 
@@ -343,4 +424,73 @@ x12 = LUT 0x45fc (x0, x1, x2, x3)
         super::write_bench(&mut buf, &aig);
         String::from_utf8(buf.into_inner().unwrap()).unwrap();
     }
+
+    #[test]
+    fn test_names() {
+        use std::io::BufWriter;
+
+        let example = "INPUT(a)
+INPUT(b)
+OUTPUT(sum)
+OUTPUT(carry)
+OUTPUT(a)
+carry = AND(a, b)
+sum = XOR(a, b)
+";
+        let aig = super::read_bench(example.as_bytes()).unwrap();
+        assert_eq!(aig.input_name(0), Some("a"));
+        assert_eq!(aig.output_name(0), Some("sum"));
+        let mut buf = BufWriter::new(Vec::new());
+        super::write_bench(&mut buf, &aig);
+        let s = String::from_utf8(buf.into_inner().unwrap()).unwrap();
+        assert!(s.contains("INPUT(b)"));
+        assert!(s.contains("OUTPUT(a)"));
+        assert!(s.contains("carry = AND(a, b)"));
+        assert!(s.contains("sum = XOR(a, b)"));
+
+        // Output names are kept with a buffer when the node loses its name
+        let mut aig = aig;
+        aig.set_output_name(1, "c");
+        let mut buf = BufWriter::new(Vec::new());
+        super::write_bench(&mut buf, &aig);
+        let s = String::from_utf8(buf.into_inner().unwrap()).unwrap();
+        assert!(s.contains("c = BUF(carry)"));
+        let read_back = super::read_bench(s.as_bytes()).unwrap();
+        assert_eq!(read_back.output_name(1), Some("c"));
+    }
+
+    #[test]
+    fn test_errors() {
+        use crate::io::Error;
+
+        let err = super::read_bench("INPUT(a)\nOUTPUT(y)\ny = AND(a, b)\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Undefined { .. }));
+        assert_eq!(err.token(), Some("b"));
+        assert_eq!((err.location().line, err.location().column), (3, 12));
+
+        let err = super::read_bench("INPUT(a)\nOUTPUT(y)\ny = NOT(a, a)\n".as_bytes()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Arity {
+                expected: 1,
+                found: 2,
+                ..
+            }
+        ));
+        assert_eq!(err.token(), Some("NOT"));
+
+        let err = super::read_bench("INPUT(a)\nOUTPUT(a)\na = NOT(a)\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Redefinition { .. }));
+        assert_eq!(err.location().line, 3);
+
+        let err =
+            super::read_bench("INPUT(a)\nOUTPUT(y)\n\nx = AND(a, y)\ny = NOT(x)\n".as_bytes())
+                .unwrap_err();
+        assert!(matches!(err, Error::CombinatorialLoop { .. }));
+        assert!(["x", "y"].contains(&err.token().unwrap()));
+
+        let err = super::read_bench("INPUT(a)\nOUTPUT(y)\ny = FOO(a)\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Syntax { .. }));
+        assert_eq!(err.to_string(), "3:5: Unknown gate type at \"FOO\"");
+    }
 }
@@ -3,10 +3,12 @@ use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Write};
 use std::iter::zip;
 
+use crate::network::library::{Library, MappedNetwork};
 use crate::network::{BinaryType, NaryType, TernaryType};
 use crate::{Gate, Network, Signal};
 
-use super::utils::{get_inverted_signals, sig_to_string};
+use super::error::{Error, Location};
+use super::utils::{get_inverted_signals, SignalNames};
 
 enum Statement {
     Model(String),
@@ -19,35 +21,57 @@ enum Statement {
     Cube(String),
 }
 
-fn build_name_to_sig(statements: &Vec<Statement>) -> Result<HashMap<String, Signal>, String> {
+/// A statement with its position in the file
+struct Line {
+    loc: Location,
+    text: String,
+    statement: Statement,
+}
+
+impl Line {
+    /// Location of a token within the statement
+    fn loc_of(&self, token: &str) -> Location {
+        Location::of_token(self.loc.line, &self.text, token)
+    }
+}
+
+fn build_name_to_sig(statements: &[Line]) -> Result<HashMap<String, Signal>, Error> {
     let mut found_model = false;
 
     let mut ret = HashMap::new();
     let mut var_index = 0;
     let mut input_index = 0;
-    for statement in statements {
-        match statement {
-            Statement::Model(_) => {
+    for line in statements {
+        match &line.statement {
+            Statement::Model(name) => {
                 if found_model {
-                    return Err("Multiple models in the same file are not supported".to_owned());
+                    return Err(Error::syntax(
+                        line.loc.clone(),
+                        name,
+                        "Multiple models in the same file are not supported",
+                    ));
                 }
                 found_model = true;
             }
             Statement::End => {
                 if !found_model {
-                    return Err("End statement before the end of the model".to_owned());
+                    return Err(Error::syntax(
+                        line.loc.clone(),
+                        ".end",
+                        "End statement before the beginning of the model",
+                    ));
                 }
             }
             Statement::Exdc => {
                 break;
             }
             Statement::Inputs(inputs) => {
-                for (_, name) in inputs.iter().enumerate() {
+                for name in inputs {
                     let s = Signal::from_input(input_index as u32);
                     input_index += 1;
                     let present = ret.insert(name.clone(), s).is_some();
                     if present {
-                        return Err(format!("{} is defined twice", name));
+                        return Err(Error::redefinition(line.loc_of(name), name));
                     }
                 }
             }
@@ -60,19 +84,23 @@ fn build_name_to_sig(statements: &Vec<Statement>) -> Result<HashMap<String, Sign
                 var_index += 1;
                 let present = ret.insert(name.clone(), s).is_some();
                 if present {
-                    return Err(format!("{} is defined twice", name));
+                    return Err(Error::redefinition(line.loc_of(name), name));
                 }
             }
             Statement::Name(names) => {
                 if names.is_empty() {
-                    return Err(".names statement with no output".to_owned());
+                    return Err(Error::syntax(
+                        line.loc.clone(),
+                        ".names",
+                        ".names statement with no output",
+                    ));
                 }
                 let s = Signal::from_var(var_index as u32);
                 let name = names.last().unwrap();
                 var_index += 1;
                 let present = ret.insert(name.clone(), s).is_some();
                 if present {
-                    return Err(format!("{} is defined twice", name));
+                    return Err(Error::redefinition(line.loc_of(name), name));
                 }
             }
             Statement::Cube(_) => (),
@@ -81,37 +109,46 @@ fn build_name_to_sig(statements: &Vec<Statement>) -> Result<HashMap<String, Sign
     Ok(ret)
 }
 
+fn lookup(line: &Line, name: &str, name_to_sig: &HashMap<String, Signal>) -> Result<Signal, Error> {
+    name_to_sig
+        .get(name)
+        .copied()
+        .ok_or_else(|| Error::undefined(line.loc_of(name), name))
+}
+
 fn build_network(
-    statements: &Vec<Statement>,
+    statements: &[Line],
     name_to_sig: &HashMap<String, Signal>,
-) -> Result<Network, String> {
+) -> Result<Network, Error> {
     let mut ret: Network = Network::new();
 
     let mut names_to_process = Vec::new();
 
-    for (i, statement) in statements.iter().enumerate() {
-        match statement {
+    // Statement that created each node, to report errors
+    let mut node_statement = Vec::new();
+
+    for (i, line) in statements.iter().enumerate() {
+        match &line.statement {
             Statement::Inputs(inputs) => ret.add_inputs(inputs.len()),
             Statement::Outputs(outputs) => {
                 for name in outputs {
-                    let s = name_to_sig
-                        .get(name)
-                        .ok_or_else(|| format!("{} is not defined", name))?;
-                    ret.add_output(*s);
+                    let s = lookup(line, name, name_to_sig)?;
+                    ret.add_output(s);
+                    ret.set_output_name(ret.nb_outputs() - 1, name);
                 }
             }
             Statement::Latch { input, output: _ } => {
-                ret.add(Gate::dff(name_to_sig[input], Signal::one(), Signal::zero()));
+                let d = lookup(line, input, name_to_sig)?;
+                node_statement.push(i);
+                ret.add(Gate::dff(d, Signal::one(), Signal::zero()));
             }
             Statement::Name(names) => {
                 let mut deps = Vec::new();
                 for name in names.iter().take(names.len() - 1) {
-                    let s = name_to_sig
-                        .get(name)
-                        .ok_or_else(|| format!("{} is not defined", name))?;
-                    deps.push(*s);
+                    deps.push(lookup(line, name, name_to_sig)?);
                 }
                 names_to_process.push((i, ret.nb_nodes()));
+                node_statement.push(i);
                 ret.add(Gate::andn(&deps));
             }
             Statement::Cube(_) => (),
@@ -125,16 +162,16 @@ fn build_network(
     for (i, gate) in names_to_process {
         let inputs = ret.gate(gate).dependencies();
         let mut cubes = Vec::new();
-        for j in (i + 1)..statements.len() {
-            if let Statement::Cube(s) = &statements[j] {
-                cubes.push(s);
+        for line in &statements[i + 1..] {
+            if let Statement::Cube(s) = &line.statement {
+                cubes.push((line, s));
             } else {
                 break;
             }
         }
         let mut cube_gates = Vec::new();
         let mut polarities = Vec::new();
-        for s in cubes {
+        for (line, s) in cubes {
             let mut deps = Vec::new();
             let t = s.split_whitespace().collect::<Vec<_>>();
 
@@ -143,47 +180,51 @@ fn build_network(
             } else if t.len() == 1 {
                 ("".as_bytes(), t[0])
             } else {
-                return Err(format!("Invalid cube: {}", s));
+                return Err(Error::syntax(line.loc.clone(), s, "Invalid cube"));
             };
             if cube_inputs.len() != inputs.len() {
-                return Err(format!(
-                    "Invalid cube: {} has {} inputs, expected {}",
+                return Err(Error::arity(
+                    line.loc.clone(),
                     s,
+                    inputs.len(),
                     cube_inputs.len(),
-                    inputs.len()
                 ));
             }
             for (c, s) in zip(cube_inputs, inputs) {
-                if *c == '0' as u8 {
+                if *c == b'0' {
                     deps.push(!s);
-                } else if *c == '1' as u8 {
+                } else if *c == b'1' {
                     deps.push(*s);
-                } else if *c != '-' as u8 {
-                    return Err(format!("Invalid cube: {}", s));
+                } else if *c != b'-' {
+                    return Err(Error::syntax(line.loc.clone(), t[0], "Invalid cube"));
                 }
             }
             let pol = match cube_pol {
                 "0" => false,
                 "1" => true,
-                _ => return Err(format!("Invalid cube: {}", s)),
+                _ => {
+                    return Err(Error::syntax(
+                        line.loc_of(cube_pol),
+                        cube_pol,
+                        "Invalid cube output",
+                    ))
+                }
             };
             polarities.push(pol);
             let g = if pol {
-                if deps.len() == 0 {
+                if deps.is_empty() {
                     Gate::Buf(Signal::one())
                 } else if deps.len() == 1 {
                     Gate::Buf(deps[0])
                 } else {
                     Gate::andn(&deps)
                 }
+            } else if deps.is_empty() {
+                Gate::Buf(Signal::zero())
+            } else if deps.len() == 1 {
+                Gate::Buf(!deps[0])
             } else {
-                if deps.len() == 0 {
-                    Gate::Buf(Signal::zero())
-                } else if deps.len() == 1 {
-                    Gate::Buf(!deps[0])
-                } else {
-                    Gate::Nary(deps.into(), NaryType::Nand)
-                }
+                Gate::Nary(deps.into(), NaryType::Nand)
             };
             cube_gates.push(g);
         }
@@ -194,45 +235,85 @@ fn build_network(
         } else {
             for p in &polarities {
                 if *p != polarities[0] {
-                    return Err("Inconsistent polarities in cubes".to_owned());
+                    return Err(Error::syntax(
+                        statements[i + 1].loc.clone(),
+                        "",
+                        "Inconsistent polarities in cubes",
+                    ));
                 }
             }
             let mut deps = Vec::new();
             for g in cube_gates {
+                node_statement.push(i);
                 deps.push(ret.add(g));
             }
             if polarities[0] {
                 ret.replace(gate, Gate::Nary(deps.into(), NaryType::Or));
             } else {
-                ret.replace(gate, Gate::Nary(deps.into(), NaryType::Nand));
+                // The cubes describe the off-set: the output is true when no cube is
+                ret.replace(gate, Gate::Nary(deps.into(), NaryType::And));
             }
         }
     }
-    ret.topo_sort();
+
+    // Keep the names from the file
+    for (name, s) in name_to_sig {
+        if s.is_input() {
+            ret.set_input_name(s.input() as usize, name);
+        } else {
+            ret.set_node_name(s.var() as usize, name);
+        }
+    }
+    if let Err(i) = ret.try_topo_sort() {
+        let line = &statements[node_statement[i]];
+        let name = match &line.statement {
+            Statement::Latch { output, .. } => output.as_str(),
+            Statement::Name(names) => names.last().unwrap().as_str(),
+            _ => "",
+        };
+        return Err(Error::comb_loop(line.loc_of(name), name));
+    }
     Ok(ret)
 }
 
-fn read_single_statement(tokens: Vec<&str>) -> Result<Statement, String> {
+fn read_single_statement(tokens: Vec<&str>, loc: &Location) -> Result<Statement, Error> {
+    let expect_args = |n: usize| {
+        if tokens.len() < n + 1 {
+            Err(Error::arity(loc.clone(), tokens[0], n, tokens.len() - 1))
+        } else {
+            Ok(())
+        }
+    };
     match tokens[0] {
-        ".model" => Ok(Statement::Model(tokens[1].to_owned())),
+        ".model" => {
+            expect_args(1)?;
+            Ok(Statement::Model(tokens[1].to_owned()))
+        }
         ".inputs" => Ok(Statement::Inputs(
             tokens[1..].iter().map(|s| (*s).to_owned()).collect(),
         )),
         ".outputs" => Ok(Statement::Outputs(
             tokens[1..].iter().map(|s| (*s).to_owned()).collect(),
         )),
-        ".latch" => Ok(Statement::Latch {
-            input: tokens[1].to_owned(),
-            output: tokens[2].to_owned(),
-        }),
+        ".latch" => {
+            expect_args(2)?;
+            Ok(Statement::Latch {
+                input: tokens[1].to_owned(),
+                output: tokens[2].to_owned(),
+            })
+        }
         ".names" => Ok(Statement::Name(
             tokens[1..].iter().map(|s| (*s).to_owned()).collect(),
         )),
         ".end" => Ok(Statement::End),
         ".exdc" => Ok(Statement::Exdc),
         _ => {
-            if tokens[0].starts_with(".") {
-                Err(format!("{} construct is not supported", tokens[0]))
+            if tokens[0].starts_with('.') {
+                Err(Error::syntax(
+                    loc.clone(),
+                    tokens[0],
+                    "Construct is not supported",
+                ))
             } else {
                 Ok(Statement::Cube(tokens.join(" ")))
             }
@@ -240,47 +321,58 @@ fn read_single_statement(tokens: Vec<&str>) -> Result<Statement, String> {
     }
 }
 
-fn read_statements<R: std::io::Read>(r: R) -> Result<Vec<Statement>, String> {
-    let mut ret: Vec<Statement> = Vec::new();
+fn read_line(ss: &str, line: usize) -> Result<Option<Line>, Error> {
+    let t = ss.trim();
+    let tokens: Vec<_> = t.split_whitespace().collect();
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    let loc = Location::of_token(line, ss, tokens[0]);
+    let statement = read_single_statement(tokens, &loc)?;
+    Ok(Some(Line {
+        loc,
+        text: ss.to_owned(),
+        statement,
+    }))
+}
+
+fn read_statements<R: std::io::Read>(r: R) -> Result<Vec<Line>, Error> {
+    let mut ret: Vec<Line> = Vec::new();
 
-    // Buffer for multi-line strings
+    // Buffer for multi-line strings, and the line where it starts
     let mut ss = String::new();
+    let mut start_line = 0;
 
-    for l in BufReader::new(r).lines() {
-        if let Ok(s) = l {
-            // TODO: parse comments properly, not just at the beginning of the line
-            let comment_pos = s.find('#');
+    for (i, l) in BufReader::new(r).lines().enumerate() {
+        let s = l?;
+        // TODO: parse comments properly, not just at the beginning of the line
+        let comment_pos = s.find('#');
 
-            // Extend multi-line buffers
+        // Extend multi-line buffers
+        if ss.is_empty() {
+            start_line = i + 1;
+        } else {
             ss += " ";
-            ss += &s[0..comment_pos.unwrap_or(s.len())];
+        }
+        ss += &s[0..comment_pos.unwrap_or(s.len())];
 
-            let is_continuation = comment_pos.is_none() && ss.ends_with("\\");
-            if is_continuation {
-                ss.pop().unwrap();
-            }
-            if is_continuation || ss.is_empty() {
-                continue;
-            }
+        let is_continuation = comment_pos.is_none() && ss.ends_with('\\');
+        if is_continuation {
+            ss.pop().unwrap();
+        }
+        if is_continuation || ss.is_empty() {
+            continue;
+        }
 
-            let t = ss.trim();
-            let tokens: Vec<_> = t.split_whitespace().collect();
-            if !tokens.is_empty() {
-                let statement = read_single_statement(tokens)?;
-                ret.push(statement);
-            }
-            ss.clear();
+        if let Some(line) = read_line(&ss, start_line)? {
+            ret.push(line);
         }
+        ss.clear();
     }
 
     // Handle a line continuation at the end of the file
-    if !ss.is_empty() {
-        let t = ss.trim();
-        let tokens: Vec<_> = t.split_whitespace().collect();
-        if !tokens.is_empty() {
-            let statement = read_single_statement(tokens)?;
-            ret.push(statement);
-        }
+    if let Some(line) = read_line(&ss, start_line)? {
+        ret.push(line);
     }
     Ok(ret)
 }
@@ -293,7 +385,7 @@ fn read_statements<R: std::io::Read>(r: R) -> Result<Vec<Statement>, String> {
 /// [VPR](https://docs.verilogtorouting.org/en/latest/vpr/file_formats/).
 ///
 /// Quaigh only support a small subset, with a single module and a single clock.
-pub fn read_blif<R: std::io::Read>(r: R) -> Result<Network, String> {
+pub fn read_blif<R: std::io::Read>(r: R) -> Result<Network, Error> {
     let statements = read_statements(r)?;
     let name_to_sig = build_name_to_sig(&statements)?;
     build_network(&statements, &name_to_sig)
@@ -316,6 +408,70 @@ pub fn write_blif_cube<W: Write>(w: &mut W, mask: usize, num_vars: usize, val: b
 ///
 /// Quaigh only support a small subset, with a single module and a single clock.
 pub fn write_blif<W: Write>(w: &mut W, aig: &Network) {
+    let names = SignalNames::from_network(aig);
+    write_blif_header(w, aig, &names);
+
+    // Write gates
+    for i in 0..aig.nb_nodes() {
+        let g = aig.gate(i);
+        if !g.is_comb() {
+            continue;
+        }
+        write!(w, ".names").unwrap();
+        if let Gate::Buf(s) = g {
+            // Buffers handle the inversions themselves
+            write!(w, " {}", names.sig_to_string(&s.without_inversion())).unwrap();
+        } else {
+            // Other signals use a buffered signal for inverted inputs
+            for s in g.dependencies() {
+                write!(w, " {}", names.sig_to_string(s)).unwrap();
+            }
+        }
+        writeln!(w, " {}", names.node(i)).unwrap();
+        write_blif_gate_cubes(w, g);
+    }
+
+    // Write inverters
+    let signals_with_inv = get_inverted_signals(aig);
+    for s in signals_with_inv {
+        let name = names.sig_name(&s);
+        writeln!(w, ".names {} {}_n", name, name).unwrap();
+        writeln!(w, "0 1").unwrap();
+    }
+
+    write_blif_footer(w, aig, &names);
+}
+
+/// Write a network mapped to library cells in .blif format
+///
+/// Cells are written as `.gate` statements, with the pin names of the library:
+/// ```text
+///     .gate NAND2 A=i0 B=x1 Y=x2
+/// ```
+pub fn write_blif_mapped<W: Write>(w: &mut W, mapped: &MappedNetwork, library: &Library) {
+    let aig = mapped.network();
+    let names = SignalNames::from_network(aig);
+    write_blif_header(w, aig, &names);
+
+    // Write cells
+    for i in 0..aig.nb_nodes() {
+        let Some(c) = mapped.cell(i) else {
+            continue;
+        };
+        let cell = library.cell(c);
+        write!(w, ".gate {}", cell.name).unwrap();
+        for (pin, s) in zip(&cell.inputs, aig.gate(i).dependencies()) {
+            write!(w, " {}={}", pin, names.sig_to_string(s)).unwrap();
+        }
+        writeln!(w, " {}={}", cell.output, names.node(i)).unwrap();
+    }
+    writeln!(w).unwrap();
+
+    write_blif_footer(w, aig, &names);
+}
+
+/// Write the model declaration, the inputs, the outputs and the latches of a .blif file
+fn write_blif_header<W: Write>(w: &mut W, aig: &Network, names: &SignalNames) {
     writeln!(w, "# .blif file").unwrap();
     writeln!(w, "# Generated by quaigh").unwrap();
     writeln!(w).unwrap();
@@ -325,7 +481,7 @@ pub fn write_blif<W: Write>(w: &mut W, aig: &Network) {
     // Write input specifiers
     write!(w, ".inputs").unwrap();
     for i in 0..aig.nb_inputs() {
-        write!(w, " {}", aig.input(i)).unwrap();
+        write!(w, " {}", names.input(i)).unwrap();
     }
     writeln!(w).unwrap();
     writeln!(w).unwrap();
@@ -333,7 +489,7 @@ pub fn write_blif<W: Write>(w: &mut W, aig: &Network) {
     // Write output specifiers
     write!(w, ".outputs").unwrap();
     for i in 0..aig.nb_outputs() {
-        write!(w, " {}", sig_to_string(&aig.output(i))).unwrap();
+        write!(w, " {}", names.output(i)).unwrap();
     }
     writeln!(w).unwrap();
     writeln!(w).unwrap();
@@ -343,122 +499,117 @@ pub fn write_blif<W: Write>(w: &mut W, aig: &Network) {
         if let Gate::Dff([d, en, res]) = aig.gate(i) {
             if *en != Signal::one() || *res != Signal::zero() {
                 // ABC extension to blif
-                write!(w, ".flop D={} Q=x{} init=0", sig_to_string(d), i).unwrap();
+                write!(
+                    w,
+                    ".flop D={} Q={} init=0",
+                    names.sig_to_string(d),
+                    names.node(i)
+                )
+                .unwrap();
                 if *en != Signal::one() {
-                    write!(w, " E={}", en).unwrap();
+                    write!(w, " E={}", names.sig_to_string(en)).unwrap();
                 }
                 if *res != Signal::zero() {
-                    write!(w, " R={}", en).unwrap();
+                    write!(w, " R={}", names.sig_to_string(res)).unwrap();
                 }
                 writeln!(w).unwrap();
             } else {
-                writeln!(w, ".latch {} x{} 0", sig_to_string(d), i).unwrap();
+                writeln!(w, ".latch {} {} 0", names.sig_to_string(d), names.node(i)).unwrap();
             }
         }
     }
     writeln!(w).unwrap();
+}
 
-    // Write gates
-    for i in 0..aig.nb_nodes() {
-        let g = aig.gate(i);
-        if !g.is_comb() {
-            continue;
+/// Write the cubes of a .names statement for a combinatorial gate
+fn write_blif_gate_cubes<W: Write>(w: &mut W, g: &Gate) {
+    match g {
+        Gate::Binary(_, BinaryType::And) => {
+            writeln!(w, "11 1").unwrap();
         }
-        write!(w, ".names").unwrap();
-        if let Gate::Buf(s) = g {
-            // Buffers handle the inversions themselves
-            write!(w, " {}", sig_to_string(&s.without_inversion())).unwrap();
-        } else {
-            // Other signals use a buffered signal for inverted inputs
-            for s in g.dependencies() {
-                write!(w, " {}", sig_to_string(s)).unwrap();
-            }
+        Gate::Binary(_, BinaryType::Xor) => {
+            writeln!(w, "10 1").unwrap();
+            writeln!(w, "01 1").unwrap();
         }
-        writeln!(w, " x{}", i).unwrap();
-
-        match g {
-            Gate::Binary(_, BinaryType::And) => {
-                writeln!(w, "11 1").unwrap();
-            }
-            Gate::Binary(_, BinaryType::Xor) => {
-                writeln!(w, "10 1").unwrap();
-                writeln!(w, "01 1").unwrap();
-            }
-            Gate::Ternary(_, TernaryType::And) => {
-                writeln!(w, "111 1").unwrap();
-            }
-            Gate::Ternary(_, TernaryType::Xor) => {
-                writeln!(w, "111 1").unwrap();
-                writeln!(w, "100 1").unwrap();
-                writeln!(w, "010 1").unwrap();
-                writeln!(w, "001 1").unwrap();
-            }
-            Gate::Ternary(_, TernaryType::Mux) => {
-                writeln!(w, "11- 1").unwrap();
-                writeln!(w, "0-1 1").unwrap();
-            }
-            Gate::Ternary(_, TernaryType::Maj) => {
-                writeln!(w, "11- 1").unwrap();
-                writeln!(w, "-11 1").unwrap();
-                writeln!(w, "1-1 1").unwrap();
-            }
-            Gate::Nary(v, tp) => {
-                if matches!(
-                    tp,
-                    NaryType::And | NaryType::Nand | NaryType::Nor | NaryType::Or
-                ) {
-                    let input_inv = matches!(tp, NaryType::Nor | NaryType::Or);
-                    let output_inv = matches!(tp, NaryType::Or | NaryType::Nand);
-                    for _ in 0..v.len() {
-                        if input_inv {
-                            write!(w, "0").unwrap();
-                        } else {
-                            write!(w, "1").unwrap();
-                        }
-                    }
-                    if output_inv {
-                        writeln!(w, " 0").unwrap();
+        Gate::Ternary(_, TernaryType::And) => {
+            writeln!(w, "111 1").unwrap();
+        }
+        Gate::Ternary(_, TernaryType::Xor) => {
+            writeln!(w, "111 1").unwrap();
+            writeln!(w, "100 1").unwrap();
+            writeln!(w, "010 1").unwrap();
+            writeln!(w, "001 1").unwrap();
+        }
+        Gate::Ternary(_, TernaryType::Mux) => {
+            writeln!(w, "11- 1").unwrap();
+            writeln!(w, "0-1 1").unwrap();
+        }
+        Gate::Ternary(_, TernaryType::Maj) => {
+            writeln!(w, "11- 1").unwrap();
+            writeln!(w, "-11 1").unwrap();
+            writeln!(w, "1-1 1").unwrap();
+        }
+        Gate::Nary(v, tp) => {
+            if matches!(
+                tp,
+                NaryType::And | NaryType::Nand | NaryType::Nor | NaryType::Or
+            ) {
+                let input_inv = matches!(tp, NaryType::Nor | NaryType::Or);
+                let output_inv = matches!(tp, NaryType::Or | NaryType::Nand);
+                for _ in 0..v.len() {
+                    if input_inv {
+                        write!(w, "0").unwrap();
                     } else {
-                        writeln!(w, " 1").unwrap();
-                    }
-                } else {
-                    for mask in 0usize..(1 << v.len()) {
-                        let xor_val = mask.count_ones() % 2 != 0;
-                        let val = match tp {
-                            NaryType::Xor => xor_val,
-                            NaryType::Xnor => !xor_val,
-                            _ => unreachable!(),
-                        };
-                        if val {
-                            write_blif_cube(w, mask, v.len(), val);
-                        }
+                        write!(w, "1").unwrap();
                     }
                 }
-            }
-            Gate::Buf(s) => {
-                if s.is_inverted() {
-                    writeln!(w, "0 1").unwrap();
+                if output_inv {
+                    writeln!(w, " 0").unwrap();
                 } else {
-                    writeln!(w, "1 1").unwrap();
+                    writeln!(w, " 1").unwrap();
                 }
-            }
-            Gate::Lut(lut) => {
-                for mask in 0..lut.lut.num_bits() {
-                    let val = lut.lut.value(mask);
+            } else {
+                for mask in 0usize..(1 << v.len()) {
+                    let xor_val = mask.count_ones() % 2 != 0;
+                    let val = match tp {
+                        NaryType::Xor => xor_val,
+                        NaryType::Xnor => !xor_val,
+                        _ => unreachable!(),
+                    };
                     if val {
-                        write_blif_cube(w, mask, lut.lut.num_vars(), val);
+                        write_blif_cube(w, mask, v.len(), val);
                     }
                 }
             }
-            _ => panic!("Gate type not supported"),
         }
+        Gate::Buf(s) => {
+            if s.is_inverted() {
+                writeln!(w, "0 1").unwrap();
+            } else {
+                writeln!(w, "1 1").unwrap();
+            }
+        }
+        Gate::Lut(lut) => {
+            for mask in 0..lut.lut.num_bits() {
+                let val = lut.lut.value(mask);
+                if val {
+                    write_blif_cube(w, mask, lut.lut.num_vars(), val);
+                }
+            }
+        }
+        _ => panic!("Gate type not supported"),
     }
+}
 
-    // Write inverters
-    let signals_with_inv = get_inverted_signals(aig);
-    for s in signals_with_inv {
-        writeln!(w, ".names {} {}_n", s, s).unwrap();
-        writeln!(w, "0 1").unwrap();
+/// Write the named outputs and the constants of a .blif file
+fn write_blif_footer<W: Write>(w: &mut W, aig: &Network, names: &SignalNames) {
+    // Write outputs with their own names
+    for i in 0..aig.nb_outputs() {
+        if !names.output_is_signal(aig, i) {
+            let s = names.sig_to_string(&aig.output(i));
+            writeln!(w, ".names {} {}", s, names.output(i)).unwrap();
+            writeln!(w, "1 1").unwrap();
+        }
     }
 
     // Write constants
@@ -495,4 +646,83 @@ mod test {
         super::write_blif(&mut buf, &aig);
         String::from_utf8(buf.into_inner().unwrap()).unwrap();
     }
+
+    #[test]
+    fn test_errors() {
+        use crate::io::Error;
+
+        let example = ".model m
+.inputs a
+.outputs y
+.names a b \\
+  y
+11 1
+.end
+";
+        let err = super::read_blif(example.as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Undefined { .. }));
+        assert_eq!(err.token(), Some("b"));
+        assert_eq!(err.location().line, 4);
+
+        let example = ".model m
+.inputs a
+.outputs y
+.names a y
+1 1
+.names a y
+0 1
+.end
+";
+        let err = super::read_blif(example.as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Redefinition { .. }));
+        assert_eq!((err.location().line, err.location().column), (6, 10));
+
+        let example = ".model m
+.inputs a
+.outputs y
+.names a x y
+11 1
+.names y x
+1 1
+.end
+";
+        let err = super::read_blif(example.as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::CombinatorialLoop { .. }));
+
+        let err = super::read_blif(".model m\n.subckt foo a=b\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Syntax { .. }));
+        assert_eq!(err.location().line, 2);
+    }
+
+    #[test]
+    fn test_offset_cubes() {
+        use crate::sim::simulate_comb;
+
+        let example = ".model m
+.inputs a b
+.outputs y
+.names a b y
+11 0
+00 0
+.end
+";
+        let aig = super::read_blif(example.as_bytes()).unwrap();
+        for (a, b) in [(false, false), (false, true), (true, false), (true, true)] {
+            assert_eq!(simulate_comb(&aig, &vec![a, b]), vec![a ^ b]);
+        }
+    }
+
+    #[test]
+    fn test_write_mapped() {
+        use std::io::BufWriter;
+
+        let (mapped, lib) = crate::io::mapped_example();
+        let mut buf = BufWriter::new(Vec::new());
+        super::write_blif_mapped(&mut buf, &mapped, &lib);
+        let s = String::from_utf8(buf.into_inner().unwrap()).unwrap();
+        assert!(s.contains(".gate NAND2 A=a B=b Y=x0"));
+        assert!(s.contains(".gate INV A=x0 Y=x1"));
+        assert!(s.contains(".latch x1 x2 0"));
+        assert!(s.contains(".names x1 y\n1 1"));
+    }
 }
@@ -0,0 +1,271 @@
+//! Error type for reading files
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Position in a file
+///
+/// Lines and columns start at 1. A value of 0 means that the position is unknown.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Location {
+    /// File being read, if known
+    pub file: Option<PathBuf>,
+    /// Line number
+    pub line: usize,
+    /// Column number
+    pub column: usize,
+}
+
+impl Location {
+    /// Create a new location, without the file name
+    pub fn new(line: usize, column: usize) -> Location {
+        Location {
+            file: None,
+            line,
+            column,
+        }
+    }
+
+    /// Location of a token on a line, or the beginning of the line if not found
+    ///
+    /// Occurrences of the token inside a longer identifier are skipped.
+    pub(crate) fn of_token(line: usize, text: &str, token: &str) -> Location {
+        let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+        let column = text
+            .match_indices(token)
+            .find(|(i, _)| {
+                let before = text[..*i].chars().next_back();
+                let after = text[i + token.len()..].chars().next();
+                !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+            })
+            .or_else(|| text.match_indices(token).next())
+            .map(|(i, _)| i + 1)
+            .unwrap_or(1);
+        Location::new(line, column)
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(file) = &self.file {
+            parts.push(file.display().to_string());
+        }
+        if self.line != 0 {
+            parts.push(self.line.to_string());
+            if self.column != 0 {
+                parts.push(self.column.to_string());
+            }
+        }
+        write!(f, "{}", parts.join(":"))
+    }
+}
+
+/// Error encountered when reading a file
+#[derive(Debug)]
+pub enum Error {
+    /// Invalid or unsupported syntax
+    Syntax {
+        /// Position of the error
+        loc: Location,
+        /// Offending token
+        token: String,
+        /// Description of the error
+        message: String,
+    },
+    /// Signal used but never defined
+    Undefined {
+        /// Position of the error
+        loc: Location,
+        /// Name of the signal
+        token: String,
+    },
+    /// Signal defined multiple times
+    Redefinition {
+        /// Position of the error
+        loc: Location,
+        /// Name of the signal
+        token: String,
+    },
+    /// Combinatorial loop in the design
+    CombinatorialLoop {
+        /// Position of the error
+        loc: Location,
+        /// Name of a signal in the loop
+        token: String,
+    },
+    /// Gate with an unexpected number of inputs
+    Arity {
+        /// Position of the error
+        loc: Location,
+        /// Gate type
+        token: String,
+        /// Expected number of inputs
+        expected: usize,
+        /// Actual number of inputs
+        found: usize,
+    },
+    /// File extension that does not correspond to a known format
+    UnknownFormat {
+        /// Position of the error
+        loc: Location,
+        /// Extension of the file
+        token: String,
+    },
+    /// Error from the operating system
+    Io {
+        /// Position of the error
+        loc: Location,
+        /// Underlying error
+        error: std::io::Error,
+    },
+}
+
+impl Error {
+    /// Create a syntax error
+    pub(crate) fn syntax(loc: Location, token: &str, message: &str) -> Error {
+        Error::Syntax {
+            loc,
+            token: token.to_owned(),
+            message: message.to_owned(),
+        }
+    }
+
+    /// Create an error for an undefined signal
+    pub(crate) fn undefined(loc: Location, token: &str) -> Error {
+        Error::Undefined {
+            loc,
+            token: token.to_owned(),
+        }
+    }
+
+    /// Create an error for a signal defined twice
+    pub(crate) fn redefinition(loc: Location, token: &str) -> Error {
+        Error::Redefinition {
+            loc,
+            token: token.to_owned(),
+        }
+    }
+
+    /// Create an error for a combinatorial loop
+    pub(crate) fn comb_loop(loc: Location, token: &str) -> Error {
+        Error::CombinatorialLoop {
+            loc,
+            token: token.to_owned(),
+        }
+    }
+
+    /// Create an error for a gate with the wrong number of inputs
+    pub(crate) fn arity(loc: Location, token: &str, expected: usize, found: usize) -> Error {
+        Error::Arity {
+            loc,
+            token: token.to_owned(),
+            expected,
+            found,
+        }
+    }
+
+    /// Create an IO error
+    pub(crate) fn io(error: std::io::Error) -> Error {
+        Error::Io {
+            loc: Location::default(),
+            error,
+        }
+    }
+
+    /// Position of the error
+    pub fn location(&self) -> &Location {
+        match self {
+            Error::Syntax { loc, .. }
+            | Error::Undefined { loc, .. }
+            | Error::Redefinition { loc, .. }
+            | Error::CombinatorialLoop { loc, .. }
+            | Error::Arity { loc, .. }
+            | Error::UnknownFormat { loc, .. }
+            | Error::Io { loc, .. } => loc,
+        }
+    }
+
+    /// Offending token, if any
+    pub fn token(&self) -> Option<&str> {
+        match self {
+            Error::Syntax { token, .. }
+            | Error::Undefined { token, .. }
+            | Error::Redefinition { token, .. }
+            | Error::CombinatorialLoop { token, .. }
+            | Error::Arity { token, .. }
+            | Error::UnknownFormat { token, .. } => Some(token),
+            Error::Io { .. } => None,
+        }
+    }
+
+    /// Set the file where the error happened
+    pub fn with_file(mut self, file: &Path) -> Error {
+        match &mut self {
+            Error::Syntax { loc, .. }
+            | Error::Undefined { loc, .. }
+            | Error::Redefinition { loc, .. }
+            | Error::CombinatorialLoop { loc, .. }
+            | Error::Arity { loc, .. }
+            | Error::UnknownFormat { loc, .. }
+            | Error::Io { loc, .. } => loc.file = Some(file.to_owned()),
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let loc = self.location().to_string();
+        if !loc.is_empty() {
+            write!(f, "{}: ", loc)?;
+        }
+        match self {
+            Error::Syntax { token, message, .. } => {
+                if token.is_empty() {
+                    write!(f, "{}", message)
+                } else {
+                    write!(f, "{} at \"{}\"", message, token)
+                }
+            }
+            Error::Undefined { token, .. } => write!(f, "{} is not defined", token),
+            Error::Redefinition { token, .. } => write!(f, "{} is defined twice", token),
+            Error::CombinatorialLoop { token, .. } => {
+                write!(f, "combinatorial loop through {}", token)
+            }
+            Error::Arity {
+                token,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "{} expects {} inputs, but {} were given",
+                token, expected, found
+            ),
+            Error::UnknownFormat { token, .. } => {
+                if token.is_empty() {
+                    write!(f, "no file extension given")
+                } else {
+                    write!(f, "unknown file extension {}", token)
+                }
+            }
+            Error::Io { error, .. } => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::io(error)
+    }
+}
@@ -0,0 +1,232 @@
+//! Boolean expressions used to describe cell functions in .genlib and Liberty files
+
+use volute::Lut;
+
+/// A parsed boolean expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Const(bool),
+    Var(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Parse an expression
+    ///
+    /// Both .genlib and Liberty operators are accepted: `!` and postfix `'` for inversion,
+    /// `*`, `&` or juxtaposition for And, `+` or `|` for Or, and `^` for Xor.
+    /// Constants are `0`, `1`, `CONST0` and `CONST1`.
+    pub fn parse(s: &str) -> Result<Expr, String> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let ret = parser.or_expr()?;
+        match parser.peek() {
+            None => Ok(ret),
+            Some(t) => Err(format!("Unexpected token {} in expression", t)),
+        }
+    }
+
+    /// Variables of the expression, in order of first appearance
+    pub fn vars(&self) -> Vec<String> {
+        let mut ret = Vec::new();
+        self.collect_vars(&mut ret);
+        ret
+    }
+
+    fn collect_vars(&self, ret: &mut Vec<String>) {
+        match self {
+            Expr::Const(_) => (),
+            Expr::Var(v) => {
+                if !ret.contains(v) {
+                    ret.push(v.clone());
+                }
+            }
+            Expr::Not(a) => a.collect_vars(ret),
+            Expr::And(a, b) | Expr::Or(a, b) | Expr::Xor(a, b) => {
+                a.collect_vars(ret);
+                b.collect_vars(ret);
+            }
+        }
+    }
+
+    /// Truth table of the expression, with the variables in the order given
+    ///
+    /// Returns the name of the first variable that is not in the list as error.
+    pub fn to_lut(&self, vars: &[String]) -> Result<Lut, String> {
+        let n = vars.len();
+        Ok(match self {
+            Expr::Const(false) => Lut::zero(n),
+            Expr::Const(true) => Lut::one(n),
+            Expr::Var(v) => match vars.iter().position(|x| x == v) {
+                Some(i) => Lut::nth_var(n, i),
+                None => return Err(v.clone()),
+            },
+            Expr::Not(a) => !a.to_lut(vars)?,
+            Expr::And(a, b) => a.to_lut(vars)? & b.to_lut(vars)?,
+            Expr::Or(a, b) => a.to_lut(vars)? | b.to_lut(vars)?,
+            Expr::Xor(a, b) => a.to_lut(vars)? ^ b.to_lut(vars)?,
+        })
+    }
+}
+
+/// Split an expression into identifiers and operators
+fn tokenize(s: &str) -> Result<Vec<String>, String> {
+    let mut ret = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if "!'*&+|^()".contains(c) {
+            ret.push(c.to_string());
+        } else if c.is_alphanumeric() || "_[].<>".contains(c) {
+            let mut t = c.to_string();
+            while let Some(&c) = chars.peek() {
+                if c.is_alphanumeric() || "_[].<>".contains(c) {
+                    t.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            ret.push(t);
+        } else {
+            return Err(format!("Unexpected character {} in expression", c));
+        }
+    }
+    Ok(ret)
+}
+
+/// Recursive descent parser, by decreasing precedence: inversion, Xor, And, Or
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|s| s.as_str())
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let ret = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        ret
+    }
+
+    fn or_expr(&mut self) -> Result<Expr, String> {
+        let mut ret = self.and_expr()?;
+        while matches!(self.peek(), Some("+") | Some("|")) {
+            self.next();
+            ret = Expr::Or(Box::new(ret), Box::new(self.and_expr()?));
+        }
+        Ok(ret)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, String> {
+        let mut ret = self.xor_expr()?;
+        loop {
+            match self.peek() {
+                Some("*") | Some("&") => {
+                    self.next();
+                }
+                // Juxtaposition is an And
+                Some("!") | Some("(") => (),
+                Some(t) if is_identifier(t) => (),
+                _ => break,
+            }
+            ret = Expr::And(Box::new(ret), Box::new(self.xor_expr()?));
+        }
+        Ok(ret)
+    }
+
+    fn xor_expr(&mut self) -> Result<Expr, String> {
+        let mut ret = self.unary()?;
+        while self.peek() == Some("^") {
+            self.next();
+            ret = Expr::Xor(Box::new(ret), Box::new(self.unary()?));
+        }
+        Ok(ret)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some("!") {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let mut ret = self.primary()?;
+        while self.peek() == Some("'") {
+            self.next();
+            ret = Expr::Not(Box::new(ret));
+        }
+        Ok(ret)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(t) if t == "(" => {
+                let ret = self.or_expr()?;
+                match self.next() {
+                    Some(t) if t == ")" => Ok(ret),
+                    _ => Err("Missing closing parenthesis in expression".to_owned()),
+                }
+            }
+            Some(t) if t == "0" || t == "CONST0" => Ok(Expr::Const(false)),
+            Some(t) if t == "1" || t == "CONST1" => Ok(Expr::Const(true)),
+            Some(t) if is_identifier(&t) => Ok(Expr::Var(t)),
+            Some(t) => Err(format!("Unexpected token {} in expression", t)),
+            None => Err("Unexpected end of expression".to_owned()),
+        }
+    }
+}
+
+fn is_identifier(t: &str) -> bool {
+    t.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+}
+
+mod test {
+    #[cfg(test)]
+    fn vars(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_genlib_syntax() {
+        use super::Expr;
+        use volute::Lut;
+
+        let e = Expr::parse("!(a*b+c)").unwrap();
+        assert_eq!(e.vars(), vars(&["a", "b", "c"]));
+        let a = Lut::nth_var(3, 0);
+        let b = Lut::nth_var(3, 1);
+        let c = Lut::nth_var(3, 2);
+        assert_eq!(
+            e.to_lut(&vars(&["a", "b", "c"])).unwrap(),
+            !((&a & &b) | &c)
+        );
+        assert_eq!(
+            Expr::parse("CONST1").unwrap().to_lut(&[]).unwrap(),
+            Lut::one(0)
+        );
+    }
+
+    #[test]
+    fn test_liberty_syntax() {
+        use super::Expr;
+        use volute::Lut;
+
+        let e = Expr::parse("(A1 B')|(A2 ^ C)").unwrap();
+        let names = vars(&["A1", "A2", "B", "C"]);
+        let a1 = Lut::nth_var(4, 0);
+        let a2 = Lut::nth_var(4, 1);
+        let b = Lut::nth_var(4, 2);
+        let c = Lut::nth_var(4, 3);
+        assert_eq!(e.to_lut(&names).unwrap(), (a1 & !b) | (a2 ^ c));
+        assert_eq!(e.to_lut(&vars(&["A1"])), Err("B".to_owned()));
+        assert!(Expr::parse("(A & B").is_err());
+        assert!(Expr::parse("A & ").is_err());
+    }
+}
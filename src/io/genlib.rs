@@ -0,0 +1,250 @@
+//! IO for .genlib cell libraries
+
+use std::io::{BufRead, BufReader, Read};
+
+use crate::network::library::{Cell, Library};
+
+use super::error::{Error, Location};
+use super::expression::Expr;
+
+/// A token with its position in the file
+struct Token {
+    loc: Location,
+    text: String,
+}
+
+/// Split a .genlib file into tokens, with `=` and `;` as separate tokens
+fn tokenize<R: Read>(r: R) -> Result<Vec<Token>, Error> {
+    let mut ret = Vec::new();
+    for (i, line) in BufReader::new(r).lines().enumerate() {
+        let line = line?;
+        let text = line.split('#').next().unwrap();
+        let mut start = None;
+        for (j, c) in text
+            .char_indices()
+            .chain(std::iter::once((text.len(), ' ')))
+        {
+            let is_sep = c.is_whitespace() || c == '=' || c == ';';
+            if is_sep {
+                if let Some(s) = start {
+                    ret.push(Token {
+                        loc: Location::new(i + 1, s + 1),
+                        text: text[s..j].to_owned(),
+                    });
+                    start = None;
+                }
+                if c == '=' || c == ';' {
+                    ret.push(Token {
+                        loc: Location::new(i + 1, j + 1),
+                        text: c.to_string(),
+                    });
+                }
+            } else if start.is_none() {
+                start = Some(j);
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Cursor over the tokens of a .genlib file
+struct Tokens {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Tokens {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.text.as_str())
+    }
+
+    fn loc(&self) -> Location {
+        match self.tokens.get(self.pos).or(self.tokens.last()) {
+            Some(t) => t.loc.clone(),
+            None => Location::default(),
+        }
+    }
+
+    fn next(&mut self, what: &str) -> Result<String, Error> {
+        match self.tokens.get(self.pos) {
+            Some(t) => {
+                self.pos += 1;
+                Ok(t.text.clone())
+            }
+            None => Err(Error::syntax(
+                self.loc(),
+                "",
+                &format!("Unexpected end of file, expected {}", what),
+            )),
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), Error> {
+        let loc = self.loc();
+        let t = self.next(token)?;
+        if t != token {
+            return Err(Error::syntax(loc, &t, &format!("Expected {}", token)));
+        }
+        Ok(())
+    }
+
+    fn number(&mut self, what: &str) -> Result<f64, Error> {
+        let loc = self.loc();
+        let t = self.next(what)?;
+        t.parse::<f64>()
+            .map_err(|_| Error::syntax(loc, &t, &format!("Expected {}", what)))
+    }
+
+    /// Read an expression up to the semicolon
+    fn expression(&mut self) -> Result<(Location, String), Error> {
+        let loc = self.loc();
+        let mut ret = Vec::new();
+        loop {
+            let t = self.next(";")?;
+            if t == ";" {
+                break;
+            }
+            ret.push(t);
+        }
+        Ok((loc, ret.join(" ")))
+    }
+}
+
+/// Read a cell library in .genlib format
+///
+/// This is the format used by SIS and ABC for standard cell libraries.
+/// Each gate is given with its area and its function, followed by the delay of its pins:
+/// ```text
+///     GATE nand2  2  O=!(a*b);  PIN * INV 1 999 1.0 0.2 1.0 0.2
+/// ```
+/// The delay of a cell is the largest block delay of its pins. Latches are ignored.
+pub fn read_genlib<R: Read>(r: R) -> Result<Library, Error> {
+    let mut tokens = Tokens {
+        tokens: tokenize(r)?,
+        pos: 0,
+    };
+    let mut ret = Library::new();
+    let mut in_latch = false;
+    while let Some(t) = tokens.peek().map(|t| t.to_owned()) {
+        match t.as_str() {
+            "GATE" => {
+                tokens.next("GATE")?;
+                in_latch = false;
+                let loc = tokens.loc();
+                let cell = read_gate(&mut tokens)?;
+                if ret.find_cell(&cell.name).is_some() {
+                    return Err(Error::redefinition(loc, &cell.name));
+                }
+                ret.add_cell(cell);
+            }
+            "LATCH" => {
+                tokens.next("LATCH")?;
+                in_latch = true;
+            }
+            _ => {
+                if !in_latch {
+                    let loc = tokens.loc();
+                    return Err(Error::syntax(loc, &t, "Expected GATE or LATCH statement"));
+                }
+                tokens.next("")?;
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Read a GATE statement and the following PIN statements
+fn read_gate(tokens: &mut Tokens) -> Result<Cell, Error> {
+    let name = tokens.next("gate name")?;
+    let area = tokens.number("gate area")?;
+    let output = tokens.next("output name")?;
+    tokens.expect("=")?;
+    let (loc, expr) = tokens.expression()?;
+    let expr = Expr::parse(&expr).map_err(|msg| Error::syntax(loc.clone(), &expr, &msg))?;
+
+    let mut pins = Vec::new();
+    let mut delay: Option<f64> = None;
+    while tokens.peek() == Some("PIN") {
+        tokens.next("PIN")?;
+        let pin = tokens.next("pin name")?;
+        let _phase = tokens.next("pin phase")?;
+        let _input_load = tokens.number("input load")?;
+        let _max_load = tokens.number("max load")?;
+        let rise_block = tokens.number("rise block delay")?;
+        let _rise_fanout = tokens.number("rise fanout delay")?;
+        let fall_block = tokens.number("fall block delay")?;
+        let _fall_fanout = tokens.number("fall fanout delay")?;
+        if pin != "*" {
+            pins.push(pin);
+        }
+        delay = Some(delay.unwrap_or(0.0).max(rise_block).max(fall_block));
+    }
+
+    // Use the order of the PIN statements if they name all inputs
+    let vars = expr.vars();
+    let inputs = if pins.len() == vars.len() && vars.iter().all(|v| pins.contains(v)) {
+        pins
+    } else {
+        vars
+    };
+    let function = expr
+        .to_lut(&inputs)
+        .map_err(|v| Error::undefined(loc.clone(), &v))?;
+    Ok(Cell {
+        name,
+        area,
+        delay: delay.unwrap_or(1.0),
+        inputs,
+        output,
+        function,
+    })
+}
+
+mod test {
+    #[test]
+    fn test_read_genlib() {
+        use super::read_genlib;
+        use volute::Lut;
+
+        let example = "# Small library
+GATE zero 0 O=CONST0;
+GATE inv 1 O=!a;  PIN * INV 1 999 1.0 0.2 1.0 0.2
+GATE nand2 2 Y=!(a*b);
+    PIN b INV 1 999 1.0 0.2 1.2 0.2
+    PIN a INV 1 999 1.1 0.2 1.0 0.2
+GATE aoi21 3 O=!(a*b+c);  # Comment
+LATCH dff 5 Q=D; PIN D NONINV 1 999 1 .2 1 .2
+SEQ Q ANY ACTIVE_HIGH
+GATE xor2 4 O=a*!b+!a*b; PIN * UNKNOWN 2 999 1.9 0.5 1.9 0.5
+";
+        let lib = read_genlib(example.as_bytes()).unwrap();
+        assert_eq!(lib.nb_cells(), 5);
+        assert_eq!(lib.cell(0).function, Lut::zero(0));
+        assert!(lib.cell(1).is_inverter());
+        assert_eq!(lib.cell(1).delay, 1.0);
+        let nand = lib.cell(2);
+        assert_eq!(nand.inputs, vec!["b", "a"]);
+        assert_eq!(nand.output, "Y");
+        assert_eq!(nand.delay, 1.2);
+        let aoi = lib.cell(3);
+        assert_eq!(aoi.area, 3.0);
+        assert_eq!(aoi.inputs, vec!["a", "b", "c"]);
+        assert_eq!(aoi.delay, 1.0);
+        assert_eq!(lib.cell(4).function, Lut::parity(2));
+        assert_eq!(lib.cell(4).delay, 1.9);
+    }
+
+    #[test]
+    fn test_genlib_errors() {
+        use super::read_genlib;
+
+        let err = read_genlib("GATE inv 1 O=!a;\nGATE inv 1 O=!b;\n".as_bytes()).unwrap_err();
+        assert_eq!(err.location().line, 2);
+        let err = read_genlib("GATE and 2 O=a*;\n".as_bytes()).unwrap_err();
+        assert_eq!(err.location().line, 1);
+        let err = read_genlib("GATE and x O=a*b;\n".as_bytes()).unwrap_err();
+        assert_eq!(err.token(), Some("x"));
+        assert!(read_genlib("GATE inv 1 O=!a\n".as_bytes()).is_err());
+        assert!(read_genlib("PIN * INV 1 999 1 0 1 0\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,465 @@
+//! IO for Liberty (.lib) cell libraries
+
+use std::io::Read;
+
+use crate::network::library::{Cell, Library};
+
+use super::error::{Error, Location};
+use super::expression::Expr;
+
+/// A token with its position in the file
+struct Token {
+    loc: Location,
+    text: String,
+    /// Whether the token was a quoted string
+    quoted: bool,
+}
+
+/// Split a Liberty file into tokens
+///
+/// Comments and line continuations are removed, and quotes are removed from strings.
+fn tokenize(text: &str) -> Result<Vec<Token>, Error> {
+    let mut ret = Vec::new();
+    let mut line = 1;
+    let mut line_start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let loc = Location::new(line, i - line_start + 1);
+        if c == '\n' {
+            line += 1;
+            line_start = i + 1;
+        } else if c.is_whitespace() || c == '\\' {
+            continue;
+        } else if c == '/' && chars.peek().is_some_and(|(_, c)| *c == '*') {
+            chars.next();
+            let mut prev = ' ';
+            loop {
+                let Some((j, c)) = chars.next() else {
+                    return Err(Error::syntax(loc, "/*", "Unterminated comment"));
+                };
+                if c == '\n' {
+                    line += 1;
+                    line_start = j + 1;
+                }
+                if prev == '*' && c == '/' {
+                    break;
+                }
+                prev = c;
+            }
+        } else if c == '/' && chars.peek().is_some_and(|(_, c)| *c == '/') {
+            while chars.peek().is_some_and(|(_, c)| *c != '\n') {
+                chars.next();
+            }
+        } else if c == '"' {
+            let mut s = String::new();
+            loop {
+                let Some((j, c)) = chars.next() else {
+                    return Err(Error::syntax(loc, "\"", "Unterminated string"));
+                };
+                match c {
+                    '"' => break,
+                    '\\' => {
+                        // Line continuation inside a string
+                        if chars.peek().is_some_and(|(_, c)| *c == '\n') {
+                            chars.next();
+                            line += 1;
+                            line_start = j + 2;
+                        }
+                    }
+                    '\n' => {
+                        line += 1;
+                        line_start = j + 1;
+                        s.push(' ');
+                    }
+                    _ => s.push(c),
+                }
+            }
+            ret.push(Token {
+                loc,
+                text: s,
+                quoted: true,
+            });
+        } else if "(){}:;,".contains(c) {
+            ret.push(Token {
+                loc,
+                text: c.to_string(),
+                quoted: false,
+            });
+        } else {
+            let mut s = c.to_string();
+            while let Some((_, c)) = chars.peek() {
+                if c.is_whitespace() || "(){}:;,\"\\".contains(*c) {
+                    break;
+                }
+                s.push(*c);
+                chars.next();
+            }
+            ret.push(Token {
+                loc,
+                text: s,
+                quoted: false,
+            });
+        }
+    }
+    Ok(ret)
+}
+
+/// A Liberty group, such as `cell (NAND2) { ... }`
+struct Group {
+    loc: Location,
+    kind: String,
+    args: Vec<String>,
+    /// Simple and complex attributes, with their values
+    attributes: Vec<(String, Vec<String>)>,
+    groups: Vec<Group>,
+}
+
+impl Group {
+    /// Value of a simple attribute
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, v)| v.first())
+            .map(|s| s.as_str())
+    }
+
+    /// Subgroups of a given kind
+    fn groups<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a Group> {
+        self.groups.iter().filter(move |g| g.kind == kind)
+    }
+}
+
+/// Cursor over the tokens of a Liberty file
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_is(&self, s: &str) -> bool {
+        self.peek().is_some_and(|t| !t.quoted && t.text == s)
+    }
+
+    fn loc(&self) -> Location {
+        match self.tokens.get(self.pos).or(self.tokens.last()) {
+            Some(t) => t.loc.clone(),
+            None => Location::default(),
+        }
+    }
+
+    fn next(&mut self) -> Result<&Token, Error> {
+        if self.pos >= self.tokens.len() {
+            return Err(Error::syntax(self.loc(), "", "Unexpected end of file"));
+        }
+        self.pos += 1;
+        Ok(&self.tokens[self.pos - 1])
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), Error> {
+        let t = self.next()?;
+        if t.quoted || t.text != s {
+            return Err(Error::syntax(
+                t.loc.clone(),
+                &t.text,
+                &format!("Expected {}", s),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parse the contents of a group, up to the closing brace
+    fn group_body(&mut self, group: &mut Group) -> Result<(), Error> {
+        loop {
+            if self.peek_is("}") {
+                self.next()?;
+                return Ok(());
+            }
+            let t = self.next()?;
+            let loc = t.loc.clone();
+            let name = t.text.clone();
+            if self.peek_is(":") {
+                // Simple attribute, up to the semicolon or the end of the line
+                self.next()?;
+                let mut values = Vec::new();
+                while let Some(t) = self.peek() {
+                    if t.loc.line != loc.line && !values.is_empty() {
+                        break;
+                    }
+                    if !t.quoted && (t.text == ";" || t.text == "}") {
+                        break;
+                    }
+                    values.push(t.text.clone());
+                    self.next()?;
+                }
+                if self.peek_is(";") {
+                    self.next()?;
+                }
+                group.attributes.push((name, vec![values.join(" ")]));
+            } else if self.peek_is("(") {
+                self.next()?;
+                let mut args = Vec::new();
+                while !self.peek_is(")") {
+                    let t = self.next()?;
+                    if t.quoted || t.text != "," {
+                        args.push(t.text.clone());
+                    }
+                }
+                self.next()?;
+                if self.peek_is("{") {
+                    self.next()?;
+                    let mut sub = Group {
+                        loc,
+                        kind: name,
+                        args,
+                        attributes: Vec::new(),
+                        groups: Vec::new(),
+                    };
+                    self.group_body(&mut sub)?;
+                    group.groups.push(sub);
+                } else {
+                    if self.peek_is(";") {
+                        self.next()?;
+                    }
+                    group.attributes.push((name, args));
+                }
+            } else {
+                return Err(Error::syntax(loc, &name, "Expected attribute or group"));
+            }
+        }
+    }
+}
+
+/// Parse a Liberty file into its top-level group
+fn parse_liberty(text: &str) -> Result<Group, Error> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        pos: 0,
+    };
+    let loc = parser.loc();
+    let kind = parser.next()?.text.clone();
+    if kind != "library" {
+        return Err(Error::syntax(loc, &kind, "Expected library group"));
+    }
+    parser.expect("(")?;
+    let mut args = Vec::new();
+    while !parser.peek_is(")") {
+        args.push(parser.next()?.text.clone());
+    }
+    parser.next()?;
+    parser.expect("{")?;
+    let mut ret = Group {
+        loc,
+        kind,
+        args,
+        attributes: Vec::new(),
+        groups: Vec::new(),
+    };
+    parser.group_body(&mut ret)?;
+    Ok(ret)
+}
+
+/// Parse a number in an attribute
+fn parse_number(loc: &Location, s: &str) -> Result<f64, Error> {
+    s.trim()
+        .parse::<f64>()
+        .map_err(|_| Error::syntax(loc.clone(), s, "Expected a number"))
+}
+
+/// Delay of a timing arc: intrinsic delays if present, or the first value of the delay tables
+fn timing_delay(timing: &Group) -> Result<Option<f64>, Error> {
+    let mut ret: Option<f64> = None;
+    for attr in ["intrinsic_rise", "intrinsic_fall"] {
+        if let Some(v) = timing.attribute(attr) {
+            let d = parse_number(&timing.loc, v)?;
+            ret = Some(ret.unwrap_or(0.0).max(d));
+        }
+    }
+    for table in ["cell_rise", "cell_fall"] {
+        for g in timing.groups(table) {
+            let Some((_, values)) = g.attributes.iter().find(|(n, _)| n == "values") else {
+                continue;
+            };
+            let Some(first) = values.first().and_then(|v| v.split(',').next()) else {
+                continue;
+            };
+            let d = parse_number(&g.loc, first)?;
+            ret = Some(ret.unwrap_or(0.0).max(d));
+        }
+    }
+    Ok(ret)
+}
+
+/// Read a cell from a Liberty cell group
+///
+/// Returns None if the cell is not a single-output combinatorial cell.
+fn read_cell(cell: &Group) -> Result<Option<Cell>, Error> {
+    let Some(name) = cell.args.first() else {
+        return Err(Error::syntax(cell.loc.clone(), "cell", "Missing cell name"));
+    };
+    let sequential = ["ff", "latch", "ff_bank", "latch_bank", "statetable", "bus"];
+    if cell
+        .groups
+        .iter()
+        .any(|g| sequential.contains(&g.kind.as_str()))
+    {
+        return Ok(None);
+    }
+    if cell.attribute("dont_use") == Some("true") {
+        return Ok(None);
+    }
+    let area = match cell.attribute("area") {
+        Some(v) => parse_number(&cell.loc, v)?,
+        None => 0.0,
+    };
+
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    for pin in cell.groups("pin") {
+        for pin_name in &pin.args {
+            match pin.attribute("direction") {
+                Some("input") => inputs.push(pin_name.clone()),
+                Some("output") => outputs.push((pin_name.clone(), pin)),
+                _ => return Ok(None),
+            }
+        }
+    }
+    let [(output, pin)] = outputs.as_slice() else {
+        return Ok(None);
+    };
+    let Some(function) = pin.attribute("function") else {
+        return Ok(None);
+    };
+    let expr =
+        Expr::parse(function).map_err(|msg| Error::syntax(pin.loc.clone(), function, &msg))?;
+    let Ok(function) = expr.to_lut(&inputs) else {
+        // Function that depends on internal signals
+        return Ok(None);
+    };
+
+    let mut delay: Option<f64> = None;
+    for timing in pin.groups("timing") {
+        if let Some(d) = timing_delay(timing)? {
+            delay = Some(delay.unwrap_or(0.0).max(d));
+        }
+    }
+    Ok(Some(Cell {
+        name: name.clone(),
+        area,
+        delay: delay.unwrap_or(1.0),
+        inputs,
+        output: output.clone(),
+        function,
+    }))
+}
+
+/// Read a cell library in Liberty format
+///
+/// Only the combinatorial subset is supported: cells with a single output pin with a function.
+/// Sequential cells and cells marked as `dont_use` are ignored.
+/// The delay of a cell is the largest intrinsic delay of its timing arcs, or the first value of
+/// its delay tables for non-linear delay models.
+pub fn read_liberty<R: Read>(mut r: R) -> Result<Library, Error> {
+    let mut text = String::new();
+    r.read_to_string(&mut text)?;
+    let lib = parse_liberty(&text)?;
+    let mut ret = Library::new();
+    for cell in lib.groups("cell") {
+        if let Some(c) = read_cell(cell)? {
+            if ret.find_cell(&c.name).is_some() {
+                return Err(Error::redefinition(cell.loc.clone(), &c.name));
+            }
+            ret.add_cell(c);
+        }
+    }
+    Ok(ret)
+}
+
+mod test {
+    #[test]
+    fn test_read_liberty() {
+        use super::read_liberty;
+        use volute::Lut;
+
+        let example = r#"/* Small library */
+library (test) {
+  delay_model : table_lookup;
+  time_unit : "1ns";
+  cell (INV) {
+    area : 1.5;
+    pin (A) { direction : input; capacitance : 0.01; }
+    pin (Y) {
+      direction : output;
+      function : "!A";
+      timing () {
+        related_pin : "A";
+        cell_rise (delay_template) {
+          index_1 ("0.1, 0.2");
+          values ("0.05, 0.07");
+        }
+        cell_fall (delay_template) {
+          values ("0.04, 0.06");
+        }
+      }
+    }
+  }
+  cell (AOI21) {
+    area : 3;
+    pin (A1, A2) { direction : input; }
+    pin (B) { direction : input; }
+    pin (ZN) {
+      direction : output;
+      function : "!((A1 A2) | \
+                    B)";
+      timing () { intrinsic_rise : 0.2; intrinsic_fall : 0.3; }
+    }
+  }
+  cell (DFF) {
+    area : 6;
+    ff (IQ, IQN) { next_state : "D"; clocked_on : "CK"; }
+    pin (D) { direction : input; }
+    pin (CK) { direction : input; clock : true; }
+    pin (Q) { direction : output; function : "IQ"; }
+  }
+  cell (TIEHI) {
+    area : 1
+    pin (Y) { direction : output; function : "1"; }
+  }
+}
+"#;
+        let lib = read_liberty(example.as_bytes()).unwrap();
+        assert_eq!(lib.nb_cells(), 3);
+        let inv = lib.cell(0);
+        assert!(inv.is_inverter());
+        assert_eq!(inv.area, 1.5);
+        assert_eq!(inv.delay, 0.05);
+        let aoi = lib.cell(1);
+        assert_eq!(aoi.inputs, vec!["A1", "A2", "B"]);
+        assert_eq!(aoi.output, "ZN");
+        assert_eq!(aoi.delay, 0.3);
+        let a1 = Lut::nth_var(3, 0);
+        let a2 = Lut::nth_var(3, 1);
+        let b = Lut::nth_var(3, 2);
+        assert_eq!(aoi.function, !((a1 & a2) | b));
+        assert_eq!(lib.cell(2).name, "TIEHI");
+        assert_eq!(lib.cell(2).function, Lut::one(0));
+        assert_eq!(lib.cell(2).delay, 1.0);
+    }
+
+    #[test]
+    fn test_liberty_errors() {
+        use super::read_liberty;
+
+        let err = read_liberty("cell (A) {}".as_bytes()).unwrap_err();
+        assert_eq!(err.token(), Some("cell"));
+        let err = read_liberty("library (a) {\n  cell (X) {\n".as_bytes()).unwrap_err();
+        assert_eq!(err.location().line, 2);
+        let example = "library (a) {\n cell (X) {\n  pin (Y) { direction : output; function : \"A &\"; }\n }\n}";
+        let err = read_liberty(example.as_bytes()).unwrap_err();
+        assert_eq!(err.location().line, 3);
+    }
+}
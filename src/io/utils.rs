@@ -2,15 +2,110 @@ use std::collections::HashSet;
 
 use crate::{Gate, Network, Signal};
 
-/// Ad-hoc to_string function to represent signals in bench files
-pub fn sig_to_string(s: &Signal) -> String {
-    if *s == Signal::one() {
-        return "vdd".to_string();
+/// Unique names for the signals of a network, as used by the writers
+///
+/// Names from the network are used when available, with default names (`i0`, `x3`, ...) otherwise.
+/// Names are made unique, taking into account the `_n` suffix used for inverted signals and the
+/// `vdd`/`gnd` constants.
+pub struct SignalNames {
+    inputs: Vec<String>,
+    nodes: Vec<String>,
+    outputs: Vec<String>,
+    used: HashSet<String>,
+}
+
+impl SignalNames {
+    /// Compute the names for a network
+    ///
+    /// Outputs are named after their signal, unless they have a name of their own.
+    pub fn from_network(aig: &Network) -> SignalNames {
+        let mut ret = SignalNames {
+            inputs: Vec::new(),
+            nodes: Vec::new(),
+            outputs: Vec::new(),
+            used: ["vdd", "gnd"].iter().map(|s| s.to_string()).collect(),
+        };
+        for i in 0..aig.nb_inputs() {
+            let default = aig.input(i).to_string();
+            let name = ret.add_unique(aig.input_name(i).unwrap_or(&default));
+            ret.inputs.push(name);
+        }
+        for i in 0..aig.nb_nodes() {
+            let default = aig.node(i).to_string();
+            let name = ret.add_unique(aig.node_name(i).unwrap_or(&default));
+            ret.nodes.push(name);
+        }
+        for i in 0..aig.nb_outputs() {
+            let s = aig.output(i);
+            let sig_name = ret.sig_to_string(&s);
+            let name = match aig.output_name(i) {
+                Some(name) if name != sig_name => ret.add_unique(name),
+                _ => sig_name,
+            };
+            ret.outputs.push(name);
+        }
+        ret
+    }
+
+    /// Reserve a new unique name, based on the one given
+    pub fn add_unique(&mut self, name: &str) -> String {
+        let is_free = |s: &String, used: &HashSet<String>| {
+            !used.contains(s) && !used.contains(&format!("{}_n", s))
+        };
+        let mut ret = name.to_string();
+        let mut i = 1;
+        while !is_free(&ret, &self.used) {
+            ret = format!("{}_{}", name, i);
+            i += 1;
+        }
+        self.used.insert(format!("{}_n", ret));
+        self.used.insert(ret.clone());
+        ret
+    }
+
+    /// Name of the input at index i
+    pub fn input(&self, i: usize) -> &str {
+        &self.inputs[i]
+    }
+
+    /// Name of the node at index i
+    pub fn node(&self, i: usize) -> &str {
+        &self.nodes[i]
+    }
+
+    /// Name of the output at index i
+    pub fn output(&self, i: usize) -> &str {
+        &self.outputs[i]
     }
-    if *s == Signal::zero() {
-        return "gnd".to_string();
+
+    /// Returns whether the output at index i is just the name of its signal, or requires a buffer
+    pub fn output_is_signal(&self, aig: &Network, i: usize) -> bool {
+        self.outputs[i] == self.sig_to_string(&aig.output(i))
+    }
+
+    /// Name of a signal, without its inversion
+    pub fn sig_name(&self, s: &Signal) -> &str {
+        if s.is_input() {
+            &self.inputs[s.input() as usize]
+        } else if s.is_var() {
+            &self.nodes[s.var() as usize]
+        } else if *s == Signal::one() {
+            "vdd"
+        } else {
+            "gnd"
+        }
+    }
+
+    /// Ad-hoc to_string function to represent signals in bench and blif files
+    pub fn sig_to_string(&self, s: &Signal) -> String {
+        if *s == Signal::one() {
+            return "vdd".to_string();
+        }
+        if *s == Signal::zero() {
+            return "gnd".to_string();
+        }
+        self.sig_name(s).to_string() + (if s.is_inverted() { "_n" } else { "" })
     }
-    s.without_inversion().to_string() + (if s.is_inverted() { "_n" } else { "" })
 }
 
 /// Find the set of signals that are used inverted
@@ -0,0 +1,1269 @@
+//! IO for .v (structural Verilog) files
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::iter::zip;
+
+use volute::Lut;
+
+use crate::network::library::{Library, MappedNetwork};
+use crate::network::{BinaryType, NaryType, TernaryType};
+use crate::{Gate, Network, Signal};
+
+use super::error::{Error, Location};
+use super::utils::SignalNames;
+
+/// Split a Verilog file into tokens, removing comments, attributes and compiler directives
+///
+/// Each token is returned with its location in the file.
+fn tokenize(s: &str) -> Result<Vec<(String, Location)>, Error> {
+    let c: Vec<char> = s.chars().collect();
+
+    // Line and column of each character
+    let mut locs = Vec::with_capacity(c.len() + 1);
+    let (mut line, mut column) = (1, 1);
+    for ch in &c {
+        locs.push(Location::new(line, column));
+        if *ch == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    locs.push(Location::new(line, column));
+
+    let mut ret = Vec::new();
+    let mut i = 0;
+    while i < c.len() {
+        let start = i;
+        let loc = locs[start].clone();
+        if c[i].is_whitespace() {
+            i += 1;
+        } else if c[i] == '/' && c.get(i + 1) == Some(&'/') || c[i] == '`' {
+            // Line comments and compiler directives
+            while i < c.len() && c[i] != '\n' {
+                i += 1;
+            }
+        } else if c[i] == '/' && c.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i + 1 < c.len() && !(c[i] == '*' && c[i + 1] == '/') {
+                i += 1;
+            }
+            if i + 1 >= c.len() {
+                return Err(Error::syntax(loc, "/*", "Unterminated comment"));
+            }
+            i += 2;
+        } else if c[i] == '(' && c.get(i + 1) == Some(&'*') && c.get(i + 2) != Some(&')') {
+            // Attributes are ignored
+            while i + 1 < c.len() && !(c[i] == '*' && c[i + 1] == ')') {
+                i += 1;
+            }
+            if i + 1 >= c.len() {
+                return Err(Error::syntax(loc, "(*", "Unterminated attribute"));
+            }
+            i += 2;
+        } else if c[i] == '\\' {
+            // Escaped identifier, up to the next whitespace
+            i += 1;
+            while i < c.len() && !c[i].is_whitespace() {
+                i += 1;
+            }
+            ret.push((c[start + 1..i].iter().collect(), loc));
+        } else if c[i].is_alphabetic() || c[i] == '_' {
+            while i < c.len() && (c[i].is_alphanumeric() || c[i] == '_' || c[i] == '$') {
+                i += 1;
+            }
+            ret.push((c[start..i].iter().collect(), loc));
+        } else if c[i].is_ascii_digit() || c[i] == '\'' {
+            while i < c.len() && (c[i].is_ascii_digit() || c[i] == '_') {
+                i += 1;
+            }
+            if i < c.len() && c[i] == '\'' {
+                i += 1;
+                if i < c.len() && (c[i] == 's' || c[i] == 'S') {
+                    i += 1;
+                }
+                i += 1;
+                while i < c.len() && (c[i].is_ascii_alphanumeric() || c[i] == '_' || c[i] == '?') {
+                    i += 1;
+                }
+            }
+            ret.push((c[start..i.min(c.len())].iter().collect(), loc));
+        } else {
+            let two: String = c[i..(i + 2).min(c.len())].iter().collect();
+            if ["<=", ">>", "~^", "^~", "~&", "~|", "&&", "||"].contains(&two.as_str()) {
+                i += 2;
+                ret.push((two, loc));
+            } else if "(),;=?:&|^~!{}@[]#.".contains(c[i]) {
+                i += 1;
+                ret.push((c[start].to_string(), loc));
+            } else {
+                return Err(Error::syntax(
+                    loc,
+                    &c[i].to_string(),
+                    "Unexpected character in Verilog file",
+                ));
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Value of a Verilog number, least significant bit first
+fn parse_number(s: &str) -> Result<Vec<bool>, String> {
+    let err = || format!("Unsupported number {}", s);
+    let Some(pos) = s.find('\'') else {
+        let v = s.replace('_', "").parse::<u64>().map_err(|_| err())?;
+        return Ok((0..64).map(|i| (v >> i) & 1 != 0).collect());
+    };
+    let mut spec = s[pos + 1..].chars();
+    let mut base = spec.next().ok_or_else(err)?;
+    if base == 's' || base == 'S' {
+        base = spec.next().ok_or_else(err)?;
+    }
+    let digits: String = spec.filter(|c| *c != '_').collect();
+    let bits_per_digit = match base.to_ascii_lowercase() {
+        'b' => 1,
+        'o' => 3,
+        'h' => 4,
+        'd' => {
+            let v = digits.parse::<u64>().map_err(|_| err())?;
+            return Ok((0..64).map(|i| (v >> i) & 1 != 0).collect());
+        }
+        _ => return Err(err()),
+    };
+    let mut ret = Vec::new();
+    for d in digits.chars().rev() {
+        let v = d.to_digit(1 << bits_per_digit).ok_or_else(err)?;
+        for i in 0..bits_per_digit {
+            ret.push((v >> i) & 1 != 0);
+        }
+    }
+    if pos > 0 {
+        let width = s[..pos].parse::<usize>().map_err(|_| err())?;
+        ret.resize(width, false);
+    }
+    Ok(ret)
+}
+
+/// Single-bit expression, as used in assignments
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Name(String),
+    Const(bool),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Xor(Vec<Expr>),
+    Mux(Box<Expr>, Box<Expr>, Box<Expr>),
+    Lut(Lut, Vec<Expr>),
+}
+
+/// Definition of a named signal
+enum Definition {
+    /// Combinatorial logic
+    Comb(Expr),
+    /// Register, with the expression of its next state
+    Reg(Expr),
+}
+
+/// Content of a Verilog module
+#[derive(Default)]
+struct Module {
+    inputs: Vec<(String, Location)>,
+    outputs: Vec<(String, Location)>,
+    definitions: Vec<(String, Definition, Location)>,
+    clocks: Vec<(String, Location)>,
+}
+
+struct VerilogParser {
+    tokens: Vec<String>,
+    locs: Vec<Location>,
+    end: Location,
+    pos: usize,
+}
+
+impl VerilogParser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|s| s.as_str())
+    }
+
+    /// Location of the next token
+    fn loc(&self) -> Location {
+        self.locs.get(self.pos).unwrap_or(&self.end).clone()
+    }
+
+    /// Location of the last token consumed
+    fn prev_loc(&self) -> Location {
+        self.locs[self.pos - 1].clone()
+    }
+
+    /// Error for an unsupported construct at the next token
+    fn unsupported(&self, message: &str) -> Error {
+        Error::syntax(self.loc(), self.peek().unwrap_or(""), message)
+    }
+
+    fn next(&mut self) -> Result<String, Error> {
+        let t = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| Error::syntax(self.end.clone(), "", "Unexpected end of file"))?;
+        self.pos += 1;
+        Ok(t)
+    }
+
+    /// Consume the next token if it matches
+    fn accept(&mut self, t: &str) -> bool {
+        if self.peek() == Some(t) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, t: &str) -> Result<(), Error> {
+        let n = self.next()?;
+        if n == t {
+            Ok(())
+        } else {
+            Err(Error::syntax(
+                self.prev_loc(),
+                &n,
+                &format!("Expected {}", t),
+            ))
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, Error> {
+        let n = self.next()?;
+        if n.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+            Ok(n)
+        } else {
+            Err(Error::syntax(self.prev_loc(), &n, "Expected an identifier"))
+        }
+    }
+
+    /// Comma-separated list of declarations, with optional assignments
+    fn declaration_list(&mut self) -> Result<Vec<(String, Location, Option<Expr>)>, Error> {
+        let mut ret = Vec::new();
+        loop {
+            if self.peek() == Some("[") {
+                return Err(self.unsupported("Buses are not supported"));
+            }
+            let name = self.identifier()?;
+            let loc = self.prev_loc();
+            let e = if self.accept("=") {
+                Some(self.parse_expr()?)
+            } else {
+                None
+            };
+            ret.push((name, loc, e));
+            if !self.accept(",") {
+                return Ok(ret);
+            }
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, Error> {
+        let c = self.parse_binary(0)?;
+        if self.accept("?") {
+            let a = self.parse_expr()?;
+            self.expect(":")?;
+            let b = self.parse_expr()?;
+            Ok(Expr::Mux(Box::new(c), Box::new(a), Box::new(b)))
+        } else {
+            Ok(c)
+        }
+    }
+
+    /// Parse binary operators, by increasing precedence level
+    fn parse_binary(&mut self, level: usize) -> Result<Expr, Error> {
+        const LEVELS: [&[&str]; 5] = [&["||"], &["&&"], &["|"], &["^", "~^", "^~"], &["&"]];
+        if level == LEVELS.len() {
+            return self.parse_unary();
+        }
+        let mut operands = vec![self.parse_binary(level + 1)?];
+        let mut inverted = false;
+        while let Some(op) = self.peek() {
+            if !LEVELS[level].contains(&op) {
+                break;
+            }
+            inverted ^= op != "^" && level == 3;
+            self.pos += 1;
+            operands.push(self.parse_binary(level + 1)?);
+        }
+        if operands.len() == 1 {
+            return Ok(operands.pop().unwrap());
+        }
+        let ret = match level {
+            0 | 2 => Expr::Or(operands),
+            3 => Expr::Xor(operands),
+            _ => Expr::And(operands),
+        };
+        if inverted {
+            Ok(Expr::Not(Box::new(ret)))
+        } else {
+            Ok(ret)
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, Error> {
+        if self.accept("~") || self.accept("!") {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.accept("(") {
+            let e = self.parse_expr()?;
+            self.expect(")")?;
+            return Ok(e);
+        }
+        let t = self.next()?;
+        if t.starts_with(|c: char| c.is_ascii_digit() || c == '\'') {
+            let bits = parse_number(&t).map_err(|m| Error::syntax(self.prev_loc(), &t, &m))?;
+            if !self.accept(">>") {
+                return Ok(Expr::Const(bits.first().copied().unwrap_or(false)));
+            }
+            // Lookup table, represented as a constant shifted by its inputs
+            let mut inputs = Vec::new();
+            if self.accept("{") {
+                loop {
+                    inputs.push(self.parse_expr()?);
+                    if !self.accept(",") {
+                        break;
+                    }
+                }
+                self.expect("}")?;
+            } else {
+                inputs.push(self.parse_unary()?);
+            }
+            inputs.reverse();
+            let mut lut = Lut::zero(inputs.len());
+            for mask in 0..lut.num_bits() {
+                lut.set_value(mask, bits.get(mask).copied().unwrap_or(false));
+            }
+            return Ok(Expr::Lut(lut, inputs));
+        }
+        if self.peek() == Some("[") {
+            return Err(self.unsupported("Buses are not supported"));
+        }
+        if t.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+            Ok(Expr::Name(t))
+        } else {
+            Err(Error::syntax(
+                self.prev_loc(),
+                &t,
+                "Unexpected token in expression",
+            ))
+        }
+    }
+
+    /// Parse a statement in an always block, updating the next state of the registers
+    fn parse_statement(
+        &mut self,
+        state: &mut HashMap<String, Expr>,
+        order: &mut Vec<(String, Location)>,
+    ) -> Result<(), Error> {
+        if self.accept("begin") {
+            while !self.accept("end") {
+                self.parse_statement(state, order)?;
+            }
+        } else if self.accept("if") {
+            self.expect("(")?;
+            let c = self.parse_expr()?;
+            self.expect(")")?;
+            let mut state_if = state.clone();
+            let mut state_else = state.clone();
+            self.parse_statement(&mut state_if, order)?;
+            if self.accept("else") {
+                self.parse_statement(&mut state_else, order)?;
+            }
+            for (name, _) in order.iter() {
+                let hold = state.get(name).cloned().unwrap_or(Expr::Name(name.clone()));
+                let a = state_if.remove(name).unwrap_or(hold.clone());
+                let b = state_else.remove(name).unwrap_or(hold.clone());
+                if a != hold || b != hold {
+                    let e = Expr::Mux(Box::new(c.clone()), Box::new(a), Box::new(b));
+                    state.insert(name.clone(), e);
+                }
+            }
+        } else {
+            let name = self.identifier()?;
+            let loc = self.prev_loc();
+            if self.peek() == Some("[") {
+                return Err(self.unsupported("Buses are not supported"));
+            }
+            if !self.accept("<=") {
+                self.expect("=")?;
+            }
+            let e = self.parse_expr()?;
+            self.expect(";")?;
+            if !order.iter().any(|(n, _)| *n == name) {
+                order.push((name.clone(), loc));
+            }
+            state.insert(name, e);
+        }
+        Ok(())
+    }
+
+    fn parse_always(&mut self, m: &mut Module) -> Result<(), Error> {
+        self.expect("@")?;
+        self.expect("(")?;
+        if !self.accept("posedge") {
+            return Err(
+                self.unsupported("Only always blocks on a positive clock edge are supported")
+            );
+        }
+        let clock = self.identifier()?;
+        let clock_loc = self.prev_loc();
+        if !self.accept(")") {
+            return Err(self.unsupported("Asynchronous resets are not supported"));
+        }
+        let mut state = HashMap::new();
+        let mut order = Vec::new();
+        self.parse_statement(&mut state, &mut order)?;
+        for (name, loc) in order {
+            let e = state.remove(&name).unwrap();
+            m.definitions.push((name, Definition::Reg(e), loc));
+        }
+        if !m.clocks.iter().any(|(c, _)| *c == clock) {
+            m.clocks.push((clock, clock_loc));
+        }
+        Ok(())
+    }
+
+    /// Parse a primitive gate instance, such as and(o, a, b)
+    fn parse_primitive(&mut self, kind: &str, m: &mut Module) -> Result<(), Error> {
+        loop {
+            if self.peek() != Some("(") {
+                self.identifier()?;
+            }
+            self.expect("(")?;
+            let loc = self.loc();
+            let mut args = Vec::new();
+            loop {
+                args.push(self.parse_expr()?);
+                if !self.accept(",") {
+                    break;
+                }
+            }
+            self.expect(")")?;
+            if args.len() < 2 {
+                return Err(Error::arity(self.prev_loc(), kind, 2, args.len()));
+            }
+            let output_names = |outputs: &[Expr]| -> Result<Vec<String>, Error> {
+                outputs
+                    .iter()
+                    .map(|e| match e {
+                        Expr::Name(n) => Ok(n.clone()),
+                        _ => Err(Error::syntax(
+                            loc.clone(),
+                            kind,
+                            "Invalid output connection for gate",
+                        )),
+                    })
+                    .collect()
+            };
+            if kind == "buf" || kind == "not" {
+                let input = args.pop().unwrap();
+                let e = if kind == "not" {
+                    Expr::Not(Box::new(input))
+                } else {
+                    input
+                };
+                for name in output_names(&args)? {
+                    m.definitions
+                        .push((name, Definition::Comb(e.clone()), loc.clone()));
+                }
+            } else {
+                let output = output_names(&args[0..1])?.pop().unwrap();
+                let inputs = args[1..].to_vec();
+                let e = match kind {
+                    "and" => Expr::And(inputs),
+                    "nand" => Expr::Not(Box::new(Expr::And(inputs))),
+                    "or" => Expr::Or(inputs),
+                    "nor" => Expr::Not(Box::new(Expr::Or(inputs))),
+                    "xor" => Expr::Xor(inputs),
+                    _ => Expr::Not(Box::new(Expr::Xor(inputs))),
+                };
+                m.definitions
+                    .push((output, Definition::Comb(e), loc.clone()));
+            }
+            if !self.accept(",") {
+                break;
+            }
+        }
+        self.expect(";")
+    }
+
+    fn parse_module(&mut self) -> Result<Module, Error> {
+        let mut m = Module::default();
+        self.expect("module")?;
+        self.identifier()?;
+        if self.peek() == Some("#") {
+            return Err(self.unsupported("Module parameters are not supported"));
+        }
+        if self.accept("(") {
+            // Port list, with optional ANSI-style declarations
+            let mut direction = None;
+            while !self.accept(")") {
+                let t = self.next()?;
+                let loc = self.prev_loc();
+                match t.as_str() {
+                    "input" | "output" => direction = Some(t),
+                    "inout" => return Err(Error::syntax(loc, &t, "Inout ports are not supported")),
+                    "wire" | "reg" | "," => (),
+                    "[" => return Err(Error::syntax(loc, &t, "Buses are not supported")),
+                    _ => match direction.as_deref() {
+                        Some("input") => m.inputs.push((t, loc)),
+                        Some("output") => m.outputs.push((t, loc)),
+                        _ => (),
+                    },
+                }
+            }
+        }
+        self.expect(";")?;
+        loop {
+            let t = self.next()?;
+            let loc = self.prev_loc();
+            match t.as_str() {
+                "endmodule" => break,
+                "input" | "output" | "wire" | "reg" => {
+                    let mut is_reg = t == "reg";
+                    while self.accept("wire") || self.accept("reg") {
+                        is_reg |= self.tokens[self.pos - 1] == "reg";
+                    }
+                    for (name, name_loc, e) in self.declaration_list()? {
+                        if t == "input" {
+                            m.inputs.push((name.clone(), name_loc.clone()));
+                        } else if t == "output" {
+                            m.outputs.push((name.clone(), name_loc.clone()));
+                        }
+                        match e {
+                            Some(Expr::Const(false)) if is_reg => (),
+                            Some(_) if is_reg => {
+                                return Err(Error::syntax(
+                                    name_loc,
+                                    &name,
+                                    "Only zero-initialized registers are supported",
+                                ));
+                            }
+                            Some(e) => m.definitions.push((name, Definition::Comb(e), name_loc)),
+                            None => (),
+                        }
+                    }
+                    self.expect(";")?;
+                }
+                "assign" => {
+                    loop {
+                        let name = self.identifier()?;
+                        let name_loc = self.prev_loc();
+                        if self.peek() == Some("[") {
+                            return Err(self.unsupported("Buses are not supported"));
+                        }
+                        self.expect("=")?;
+                        let e = self.parse_expr()?;
+                        m.definitions.push((name, Definition::Comb(e), name_loc));
+                        if !self.accept(",") {
+                            break;
+                        }
+                    }
+                    self.expect(";")?;
+                }
+                "always" => self.parse_always(&mut m)?,
+                "and" | "nand" | "or" | "nor" | "xor" | "xnor" | "buf" | "not" => {
+                    self.parse_primitive(&t, &mut m)?
+                }
+                "inout" => return Err(Error::syntax(loc, &t, "Inout ports are not supported")),
+                _ => {
+                    if self.peek() == Some("(") || self.peek() == Some("#") {
+                        return Err(Error::syntax(loc, &t, "Module instances are not supported"));
+                    }
+                    return Err(Error::syntax(loc, &t, "Construct is not supported"));
+                }
+            }
+        }
+        if self.peek().is_some() {
+            return Err(self.unsupported("Multiple modules in the same file are not supported"));
+        }
+        Ok(m)
+    }
+}
+
+/// Helper to build the network from the named definitions
+struct NetworkBuilder<'a> {
+    aig: Network,
+    name_to_sig: HashMap<String, Signal>,
+    aliases: HashMap<String, &'a Expr>,
+    resolving: HashSet<String>,
+    /// Location of the definition being processed, for error reporting
+    loc: Location,
+}
+
+impl<'a> NetworkBuilder<'a> {
+    /// Signal associated with a name, following assignments between names
+    fn lookup(&mut self, name: &str) -> Result<Signal, Error> {
+        if let Some(s) = self.name_to_sig.get(name) {
+            return Ok(*s);
+        }
+        let Some(e) = self.aliases.get(name).copied() else {
+            return Err(Error::undefined(self.loc.clone(), name));
+        };
+        if !self.resolving.insert(name.to_owned()) {
+            return Err(Error::comb_loop(self.loc.clone(), name));
+        }
+        let s = self.signal(e)?;
+        self.name_to_sig.insert(name.to_owned(), s);
+        Ok(s)
+    }
+
+    /// Signal associated with an expression, adding intermediate gates as required
+    fn signal(&mut self, e: &Expr) -> Result<Signal, Error> {
+        match e {
+            Expr::Name(n) => self.lookup(n),
+            Expr::Const(b) => Ok(if *b { Signal::one() } else { Signal::zero() }),
+            Expr::Not(e) => Ok(!self.signal(e)?),
+            _ => {
+                let g = self.gate(e)?;
+                Ok(self.aig.add(g))
+            }
+        }
+    }
+
+    fn signals(&mut self, v: &[Expr]) -> Result<Vec<Signal>, Error> {
+        v.iter().map(|e| self.signal(e)).collect()
+    }
+
+    /// Recognize the majority expression generated by the writer
+    fn as_maj(v: &[Expr]) -> Option<[&Expr; 3]> {
+        if v.len() != 3 {
+            return None;
+        }
+        let mut pairs = Vec::new();
+        for e in v {
+            if let Expr::And(p) = e {
+                if p.len() == 2 {
+                    pairs.push((&p[0], &p[1]));
+                }
+            }
+        }
+        if pairs.len() == 3
+            && pairs[0].0 == pairs[1].0
+            && pairs[0].1 == pairs[2].0
+            && pairs[1].1 == pairs[2].1
+        {
+            Some([pairs[0].0, pairs[0].1, pairs[1].1])
+        } else {
+            None
+        }
+    }
+
+    /// Gate implementing an expression
+    fn gate(&mut self, e: &Expr) -> Result<Gate, Error> {
+        Ok(match e {
+            Expr::Name(_) | Expr::Const(_) => Gate::Buf(self.signal(e)?),
+            Expr::Not(inner) => match inner.as_ref() {
+                Expr::And(v) if v.len() > 1 => Gate::Nary(self.signals(v)?.into(), NaryType::Nand),
+                Expr::Or(v) if v.len() > 1 => Gate::Nary(self.signals(v)?.into(), NaryType::Nor),
+                Expr::Xor(v) if v.len() > 1 => Gate::Nary(self.signals(v)?.into(), NaryType::Xnor),
+                _ => Gate::Buf(!self.signal(inner)?),
+            },
+            Expr::And(v) => {
+                let s = self.signals(v)?;
+                match s.len() {
+                    2 => Gate::and(s[0], s[1]),
+                    3 => Gate::and3(s[0], s[1], s[2]),
+                    _ => Gate::andn(&s),
+                }
+            }
+            Expr::Xor(v) => {
+                let s = self.signals(v)?;
+                match s.len() {
+                    2 => Gate::xor(s[0], s[1]),
+                    3 => Gate::xor3(s[0], s[1], s[2]),
+                    _ => Gate::xorn(&s),
+                }
+            }
+            Expr::Or(v) => {
+                if let Some([a, b, c]) = Self::as_maj(v) {
+                    Gate::maj(self.signal(a)?, self.signal(b)?, self.signal(c)?)
+                } else {
+                    Gate::Nary(self.signals(v)?.into(), NaryType::Or)
+                }
+            }
+            Expr::Mux(s, a, b) => Gate::mux(self.signal(s)?, self.signal(a)?, self.signal(b)?),
+            Expr::Lut(lut, v) => Gate::lut(&self.signals(v)?, lut.clone()),
+        })
+    }
+
+    /// Flip-flop implementing the next state of a register, extracting reset and enable
+    fn dff(&mut self, name: &str, e: &Expr) -> Result<Gate, Error> {
+        let hold = Expr::Name(name.to_owned());
+        let mut e = e;
+        let mut res = Signal::zero();
+        if let Expr::Mux(c, a, b) = e {
+            if **a == Expr::Const(false) {
+                res = self.signal(c)?;
+                e = b;
+            } else if **b == Expr::Const(false) {
+                res = !self.signal(c)?;
+                e = a;
+            }
+        }
+        let mut en = Signal::one();
+        if let Expr::Mux(c, a, b) = e {
+            if **b == hold {
+                en = self.signal(c)?;
+                e = a;
+            } else if **a == hold {
+                en = !self.signal(c)?;
+                e = b;
+            }
+        }
+        let d = self.signal(e)?;
+        Ok(Gate::dff(d, en, res))
+    }
+}
+
+/// Whether an expression is just another name for a signal, with no gate required
+fn is_alias(e: &Expr) -> bool {
+    match e {
+        Expr::Name(_) | Expr::Const(_) => true,
+        Expr::Not(e) => is_alias(e),
+        _ => false,
+    }
+}
+
+fn build_network(m: &Module) -> Result<Network, Error> {
+    if m.clocks.len() > 1 {
+        let (clock, loc) = &m.clocks[1];
+        return Err(Error::syntax(
+            loc.clone(),
+            clock,
+            "Multiple clocks are not supported",
+        ));
+    }
+    let is_clock = |n: &String| m.clocks.iter().any(|(c, _)| c == n);
+    let inputs: Vec<&(String, Location)> = m.inputs.iter().filter(|(n, _)| !is_clock(n)).collect();
+
+    let mut b = NetworkBuilder {
+        aig: Network::new(),
+        name_to_sig: HashMap::new(),
+        aliases: HashMap::new(),
+        resolving: HashSet::new(),
+        loc: Location::default(),
+    };
+    b.aig.add_inputs(inputs.len());
+    let mut defined = HashSet::new();
+    for (i, (name, loc)) in inputs.iter().enumerate() {
+        if !defined.insert(name.as_str()) {
+            return Err(Error::redefinition(loc.clone(), name));
+        }
+        b.name_to_sig
+            .insert(name.to_string(), Signal::from_input(i as u32));
+        b.aig.set_input_name(i, name);
+    }
+    let mut nodes = Vec::new();
+    for (name, def, loc) in &m.definitions {
+        if !defined.insert(name.as_str()) {
+            return Err(Error::redefinition(loc.clone(), name));
+        }
+        match def {
+            Definition::Comb(e) if is_alias(e) => {
+                b.aliases.insert(name.clone(), e);
+            }
+            _ => {
+                let s = b.aig.add(Gate::Buf(Signal::zero()));
+                b.name_to_sig.insert(name.clone(), s);
+                b.aig.set_node_name(s.var() as usize, name);
+                nodes.push((s.var() as usize, name, def, loc));
+            }
+        }
+    }
+
+    // Definition that created each node, to report combinatorial loops
+    let mut node_def: Vec<Option<(&String, &Location)>> = vec![None; b.aig.nb_nodes()];
+    for (i, name, def, loc) in nodes {
+        b.loc = loc.clone();
+        let g = match def {
+            Definition::Comb(e) => b.gate(e)?,
+            Definition::Reg(e) => b.dff(name, e)?,
+        };
+        b.aig.replace(i, g);
+        node_def[i] = Some((name, loc));
+        node_def.resize(b.aig.nb_nodes(), Some((name, loc)));
+    }
+    for (name, loc) in &m.outputs {
+        b.loc = loc.clone();
+        let s = b.lookup(name)?;
+        b.aig.add_output(s);
+        b.aig.set_output_name(b.aig.nb_outputs() - 1, name);
+    }
+    let mut ret = b.aig;
+    if !ret.is_topo_sorted() {
+        if let Err(i) = ret.try_topo_sort() {
+            let (name, loc) = node_def[i].unwrap();
+            return Err(Error::comb_loop(loc.clone(), name));
+        }
+    }
+    ret.check();
+    Ok(ret)
+}
+
+/// Read a network in structural Verilog format
+///
+/// Only a small structural subset is supported, with a single module and a single clock:
+/// ```text
+///     module top(clk, a, b, c, y, q);
+///       input clk, a, b, c;
+///       output y, q;
+///       wire n;
+///       reg q = 1'b0;
+///       assign n = (a & ~b) | (c ^ a);
+///       nand g0(y, n, c);
+///       always @(posedge clk)
+///         if (a) q <= 1'b0;
+///         else if (b) q <= n;
+///     endmodule
+/// ```
+///
+/// Continuous assignments may use bitwise operators, multiplexers and lookup tables
+/// (`16'h8ff0 >> {d, c, b, a}`). Primitive gates `and/nand/or/nor/xor/xnor/not/buf` are supported.
+/// Flip-flops are described by always blocks on a positive clock edge, and are
+/// zero-initialized. Blocking and non-blocking assignments are treated the same way.
+pub fn read_verilog<R: Read>(mut r: R) -> Result<Network, Error> {
+    let mut s = String::new();
+    r.read_to_string(&mut s)?;
+    let (tokens, locs) = tokenize(&s)?.into_iter().unzip();
+    let end = Location::new(s.lines().count().max(1), 1);
+    let mut parser = VerilogParser {
+        tokens,
+        locs,
+        end,
+        pos: 0,
+    };
+    let m = parser.parse_module()?;
+    build_network(&m)
+}
+
+/// Verilog keywords, that cannot be used as identifiers without escaping
+const KEYWORDS: [&str; 24] = [
+    "always",
+    "and",
+    "assign",
+    "begin",
+    "buf",
+    "else",
+    "end",
+    "endmodule",
+    "if",
+    "initial",
+    "inout",
+    "input",
+    "module",
+    "nand",
+    "negedge",
+    "nor",
+    "not",
+    "or",
+    "output",
+    "posedge",
+    "reg",
+    "wire",
+    "xnor",
+    "xor",
+];
+
+/// Representation of a name as a Verilog identifier, escaped if necessary
+fn escape_name(name: &str) -> String {
+    let is_simple = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        && !KEYWORDS.contains(&name);
+    if is_simple {
+        name.to_owned()
+    } else {
+        format!("\\{} ", name)
+    }
+}
+
+/// Representation of a signal in a Verilog expression
+fn sig_to_verilog(names: &SignalNames, s: &Signal) -> String {
+    if *s == Signal::zero() {
+        "1'b0".to_owned()
+    } else if *s == Signal::one() {
+        "1'b1".to_owned()
+    } else if s.is_inverted() {
+        format!("~{}", escape_name(names.sig_name(s)))
+    } else {
+        escape_name(names.sig_name(s))
+    }
+}
+
+/// Verilog expression for a combinatorial gate
+fn gate_to_verilog(names: &SignalNames, g: &Gate) -> String {
+    use Gate::*;
+    let deps: Vec<String> = g
+        .dependencies()
+        .iter()
+        .map(|s| sig_to_verilog(names, s))
+        .collect();
+    match g {
+        Binary(_, BinaryType::And) | Ternary(_, TernaryType::And) | Nary(_, NaryType::And) => {
+            deps.join(" & ")
+        }
+        Binary(_, BinaryType::Xor) | Ternary(_, TernaryType::Xor) | Nary(_, NaryType::Xor) => {
+            deps.join(" ^ ")
+        }
+        Nary(_, NaryType::Or) => deps.join(" | "),
+        Nary(_, NaryType::Nand) => format!("~({})", deps.join(" & ")),
+        Nary(_, NaryType::Nor) => format!("~({})", deps.join(" | ")),
+        Nary(_, NaryType::Xnor) => format!("~({})", deps.join(" ^ ")),
+        Ternary(_, TernaryType::Mux) => format!("{} ? {} : {}", deps[0], deps[1], deps[2]),
+        Ternary(_, TernaryType::Maj) => format!(
+            "({} & {}) | ({} & {}) | ({} & {})",
+            deps[0], deps[1], deps[0], deps[2], deps[1], deps[2]
+        ),
+        Buf(_) => deps[0].clone(),
+        Lut(lut) => {
+            let inputs: Vec<String> = deps.into_iter().rev().collect();
+            format!(
+                "{}'h{} >> {{{}}}",
+                lut.lut.num_bits(),
+                lut.lut.to_hex_string(),
+                inputs.join(", ")
+            )
+        }
+        Dff(_) => panic!("Flip-flops are not combinatorial"),
+    }
+}
+
+/// Write a network in structural Verilog format
+///
+/// The network is written as a single module, with a continuous assignment for each
+/// combinatorial gate and an always block for each flip-flop:
+/// ```text
+///     module quaigh(clk, i0, i1, x1, o1);
+///       input clk;
+///       input i0;
+///       input i1;
+///       output x1;
+///       output o1;
+///       wire x0;
+///       reg x1 = 1'b0;
+///       assign x0 = i0 & ~x1;
+///       always @(posedge clk)
+///         if (i1) x1 <= 1'b0;
+///         else x1 <= x0;
+///       assign o1 = ~x0;
+///     endmodule
+/// ```
+///
+/// Outputs that are not directly driven by a node get their own port and assignment.
+pub fn write_verilog<W: Write>(w: &mut W, aig: &Network) {
+    write_verilog_module(w, aig, |w, names, i| {
+        let name = escape_name(names.node(i));
+        let expr = gate_to_verilog(names, aig.gate(i));
+        writeln!(w, "  assign {} = {};", name, expr).unwrap();
+    });
+}
+
+/// Write a network mapped to library cells in structural Verilog format
+///
+/// Each cell is written as an instance of a module with the name of the cell, with the ports
+/// connected by name:
+/// ```text
+///     NAND2 g0 (.A(i0), .B(x1), .Y(x2));
+/// ```
+/// Flip-flops are written as always blocks, as in [`write_verilog`].
+pub fn write_verilog_mapped<W: Write>(w: &mut W, mapped: &MappedNetwork, library: &Library) {
+    let aig = mapped.network();
+    write_verilog_module(w, aig, |w, names, i| {
+        let cell = library.cell(mapped.cell(i).unwrap());
+        let instance = names.add_unique(&format!("g{}", i));
+        let mut ports: Vec<String> = zip(&cell.inputs, aig.gate(i).dependencies())
+            .map(|(pin, s)| format!(".{}({})", escape_name(pin), sig_to_verilog(names, s)))
+            .collect();
+        ports.push(format!(
+            ".{}({})",
+            escape_name(&cell.output),
+            escape_name(names.node(i))
+        ));
+        writeln!(
+            w,
+            "  {} {} ({});",
+            escape_name(&cell.name),
+            escape_name(&instance),
+            ports.join(", ")
+        )
+        .unwrap();
+    });
+}
+
+/// Write the module, with a callback to write each combinatorial gate
+fn write_verilog_module<W: Write, F: FnMut(&mut W, &mut SignalNames, usize)>(
+    w: &mut W,
+    aig: &Network,
+    mut write_gate: F,
+) {
+    writeln!(w, "// Structural Verilog file").unwrap();
+    writeln!(w, "// Generated by quaigh").unwrap();
+    writeln!(w).unwrap();
+
+    let mut names = SignalNames::from_network(aig);
+    let has_clock = (0..aig.nb_nodes()).any(|i| !aig.gate(i).is_comb());
+    let clock = names.add_unique("clk");
+
+    // Outputs are either a node or a separate port with an assignment
+    let mut node_is_port = vec![false; aig.nb_nodes()];
+    let mut outputs = Vec::new();
+    let mut output_assigns = Vec::new();
+    for i in 0..aig.nb_outputs() {
+        let s = aig.output(i);
+        if names.output_is_signal(aig, i) {
+            if s.is_var() && !s.is_inverted() && !node_is_port[s.var() as usize] {
+                node_is_port[s.var() as usize] = true;
+                outputs.push(names.node(s.var() as usize).to_owned());
+                continue;
+            }
+            let name = names.add_unique(aig.output_name(i).unwrap_or(&format!("o{}", i)));
+            outputs.push(name);
+        } else {
+            outputs.push(names.output(i).to_owned());
+        }
+        output_assigns.push((outputs.len() - 1, s));
+    }
+
+    let mut ports = Vec::new();
+    if has_clock {
+        ports.push(clock.clone());
+    }
+    ports.extend((0..aig.nb_inputs()).map(|i| names.input(i).to_owned()));
+    ports.extend(outputs.iter().cloned());
+    let ports: Vec<String> = ports.iter().map(|n| escape_name(n)).collect();
+    writeln!(w, "module quaigh({});", ports.join(", ")).unwrap();
+
+    // Write declarations
+    if has_clock {
+        writeln!(w, "  input {};", escape_name(&clock)).unwrap();
+    }
+    for i in 0..aig.nb_inputs() {
+        writeln!(w, "  input {};", escape_name(names.input(i))).unwrap();
+    }
+    for name in &outputs {
+        writeln!(w, "  output {};", escape_name(name)).unwrap();
+    }
+    for (i, is_port) in node_is_port.iter().enumerate() {
+        let name = escape_name(names.node(i));
+        if !aig.gate(i).is_comb() {
+            writeln!(w, "  reg {} = 1'b0;", name).unwrap();
+        } else if !is_port {
+            writeln!(w, "  wire {};", name).unwrap();
+        }
+    }
+    writeln!(w).unwrap();
+
+    // Write gates
+    for i in 0..aig.nb_nodes() {
+        let g = aig.gate(i);
+        let name = escape_name(names.node(i));
+        if let Gate::Dff([d, en, res]) = g {
+            writeln!(w, "  always @(posedge {})", escape_name(&clock)).unwrap();
+            write!(w, "    ").unwrap();
+            if *res != Signal::zero() {
+                let res = sig_to_verilog(&names, res);
+                writeln!(w, "if ({}) {} <= 1'b0;", res, name).unwrap();
+                write!(w, "    else ").unwrap();
+            }
+            if *en != Signal::one() {
+                write!(w, "if ({}) ", sig_to_verilog(&names, en)).unwrap();
+            }
+            writeln!(w, "{} <= {};", name, sig_to_verilog(&names, d)).unwrap();
+        } else {
+            write_gate(w, &mut names, i);
+        }
+    }
+    writeln!(w).unwrap();
+
+    // Write outputs
+    for (i, s) in output_assigns {
+        let name = escape_name(&outputs[i]);
+        writeln!(w, "  assign {} = {};", name, sig_to_verilog(&names, &s)).unwrap();
+    }
+    writeln!(w, "endmodule").unwrap();
+}
+
+mod test {
+    #[cfg(test)]
+    fn check_identical(a: &crate::Network, b: &crate::Network) {
+        assert_eq!(a.nb_inputs(), b.nb_inputs());
+        assert_eq!(a.nb_outputs(), b.nb_outputs());
+        assert_eq!(a.nb_nodes(), b.nb_nodes());
+        for i in 0..a.nb_nodes() {
+            assert_eq!(a.gate(i), b.gate(i));
+        }
+        for i in 0..a.nb_outputs() {
+            assert_eq!(a.output(i), b.output(i));
+        }
+    }
+
+    #[test]
+    fn test_basic_read() {
+        use crate::Gate;
+
+        let example = "// Comment
+`timescale 1ns/1ps
+module test (input clk, input a, b, input c, output y, output reg q, output z, output w);
+  wire n, m; /* Multi-line
+  comment */
+  wire k = a ^ m;
+  (* keep *)
+  wire \\esc[0] ;
+  assign n = (a & ~b) | (c ^ a), m = 1'b1;
+  assign \\esc[0]  = ~n;
+  nand g0(y, n, c);
+  xor (z, a, b, \\esc[0] );
+  buf (w, k);
+  always @(posedge clk) begin
+    if (!a) q <= 1'b0;
+    else if (b) q <= n;
+  end
+endmodule
+";
+        let aig = super::read_verilog(example.as_bytes()).unwrap();
+        assert_eq!(aig.nb_inputs(), 3);
+        assert_eq!(aig.nb_outputs(), 4);
+        assert_eq!(aig.nb_nodes(), 7);
+        assert!(!aig.is_comb());
+        let q = aig.output(1);
+        let Gate::Dff([_, en, res]) = aig.gate(q.var() as usize) else {
+            panic!("Expected a flip-flop");
+        };
+        assert_eq!(*en, aig.input(1));
+        assert_eq!(*res, !aig.input(0));
+    }
+
+    #[test]
+    fn test_errors() {
+        for example in [
+            "module m(a, y); input a; output y; assign y = b; endmodule",
+            "module m(a, y); input a; output y; assign y = a; assign y = ~a; endmodule",
+            "module m(a, y); input [1:0] a; output y; assign y = a; endmodule",
+            "module m(a, y); input a; output y; sub s(a, y); endmodule",
+            "module m(a, y); input a; output y; assign y = x; assign x = y; endmodule",
+            "module m(c, a, y); input c, a; output reg y; always @(negedge c) y <= a; endmodule",
+            "module m(c, a, y); input c, a; output reg y = 1'b1; always @(posedge c) y <= a; endmodule",
+            "module m(a, y); input a; output y; assign y = a;",
+        ] {
+            assert!(super::read_verilog(example.as_bytes()).is_err());
+        }
+
+        use crate::io::Error;
+        let example = "module m(a, y);\n  input a;\n  output y;\n  wire x;\n  assign x = a & y;\n  assign y = a ^ x;\nendmodule\n";
+        let err = super::read_verilog(example.as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::CombinatorialLoop { .. }));
+        assert!([5, 6].contains(&err.location().line));
+
+        let example = "module m(a, y);\n  input a;\n  output y;\n  assign y = a $ b;\nendmodule\n";
+        let err = super::read_verilog(example.as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "4:16: Unexpected character in Verilog file at \"$\""
+        );
+    }
+
+    #[test]
+    fn test_comb_roundtrip() {
+        use crate::network::generators::carry_chain;
+        use crate::{Gate, Signal};
+        use std::io::BufWriter;
+        use volute::Lut;
+
+        let mut aig = carry_chain::ripple_carry(8);
+        aig.add_output(Signal::one());
+        let i = aig.input(0);
+        let j = aig.input(1);
+        let k = aig.input(2);
+        let s = aig.add(Gate::maj(i, !j, k));
+        aig.add_output(s);
+        let s = aig.add(Gate::lut(
+            &[i, j, k],
+            Lut::from_hex_string(3, "b4").unwrap(),
+        ));
+        aig.add_output(!s);
+        aig.make_canonical();
+        let mut buf = BufWriter::new(Vec::new());
+        super::write_verilog(&mut buf, &aig);
+        let data = buf.into_inner().unwrap();
+        let read_back = super::read_verilog(data.as_slice()).unwrap();
+        check_identical(&aig, &read_back);
+    }
+
+    #[test]
+    fn test_seq_roundtrip() {
+        use crate::equiv::check_equivalence_bounded;
+        use crate::network::generators::{adder, testcases};
+        use std::io::BufWriter;
+
+        for aig in [
+            adder::ripple_carry(4),
+            testcases::toggle_chain(3, true, true),
+            testcases::ff_tree(2, true, false, 2),
+        ] {
+            let mut buf = BufWriter::new(Vec::new());
+            super::write_verilog(&mut buf, &aig);
+            let data = buf.into_inner().unwrap();
+            let read_back = super::read_verilog(data.as_slice()).unwrap();
+            check_equivalence_bounded(&aig, &read_back, 4, true).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        use std::io::BufWriter;
+
+        let example = "module m(clk, a, b, y, q, z);
+  input clk, a, b;
+  output y, q, z;
+  reg q;
+  assign \\and[0]  = a & b;
+  assign y = ~\\and[0] ;
+  assign z = a;
+  always @(posedge clk) q <= \\and[0] ;
+endmodule
+";
+        let aig = super::read_verilog(example.as_bytes()).unwrap();
+        let mut buf = BufWriter::new(Vec::new());
+        super::write_verilog(&mut buf, &aig);
+        let s = String::from_utf8(buf.into_inner().unwrap()).unwrap();
+        assert!(s.contains("module quaigh(clk, a, b, y, q, z);"));
+        assert!(s.contains("assign \\and[0]  = a & b;"));
+        assert!(s.contains("q <= \\and[0] ;"));
+        let read_back = super::read_verilog(s.as_bytes()).unwrap();
+        for i in 0..aig.nb_inputs() {
+            assert_eq!(aig.input_name(i), read_back.input_name(i));
+        }
+        for i in 0..aig.nb_outputs() {
+            assert_eq!(aig.output_name(i), read_back.output_name(i));
+        }
+        for i in 0..aig.nb_nodes() {
+            assert_eq!(aig.node_name(i), read_back.node_name(i));
+        }
+    }
+
+    #[test]
+    fn test_write_mapped() {
+        use std::io::BufWriter;
+
+        let (mapped, lib) = crate::io::mapped_example();
+        let mut buf = BufWriter::new(Vec::new());
+        super::write_verilog_mapped(&mut buf, &mapped, &lib);
+        let s = String::from_utf8(buf.into_inner().unwrap()).unwrap();
+        assert!(s.contains("NAND2 g0 (.A(a), .B(b), .Y(x0));"));
+        assert!(s.contains("INV g1 (.A(x0), .Y(x1));"));
+        assert!(s.contains("x2 <= x1;"));
+        assert!(s.contains("assign y = x1;"));
+    }
+}
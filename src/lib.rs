@@ -25,6 +25,18 @@
 //! ```bash
 //! quaigh equiv mydesign.bench optimized.bench
 //! ```
+//! Sequential designs are compared for a fixed number of cycles by default. With `--unbounded`,
+//! the equivalence is proved for any number of cycles using k-induction.
+//!
+//! The `check-property` command proves that the outputs of a sequential design can never be 1,
+//! starting from the all-zero state, using property-directed reachability (IC3/PDR).
+//! ```bash
+//! quaigh check-property mydesign.bench --invariant invariant.bench -o counterexample.test
+//! ```
+//! The `bmc` command searches for such a counterexample up to a given number of cycles.
+//! ```bash
+//! quaigh bmc mydesign.bench -c 50 -o counterexample.test
+//! ```
 //!
 //! The `optimize` command performs [logic optimization](https://en.wikipedia.org/wiki/Logic_optimization).
 //! At the moment, logic optimization is far from state of the art: for production designs, you should
@@ -32,11 +44,35 @@
 //! ```bash
 //! quaigh opt mydesign.bench -o optimized.bench
 //! ```
+//! By default it minimizes the number of gates. Use `--objective depth` to balance logic trees for timing-critical designs.
+//! ```bash
+//! quaigh opt mydesign.bench --objective depth -o optimized.bench
+//! ```
+//! The sequence of optimization passes can be customized with a script, given inline or in a file, to experiment with optimization recipes.
+//! The area, depth and runtime are reported after each pass.
+//! ```bash
+//! quaigh opt mydesign.bench -o optimized.bench --script "canon; share 64; infer-xor; infer-dffe; share"
+//! ```
+//!
+//! The `map` command performs technology mapping, for example to 6-input LUTs for FPGAs.
+//! ```bash
+//! quaigh map mydesign.bench --lut 6 -o mapped.blif
+//! ```
+//! It can also map to the cells of a standard cell library, in .genlib or Liberty format, with an optional delay target.
+//! ```bash
+//! quaigh map mydesign.bench --library cells.lib --delay 2.5 -o mapped.v
+//! ```
+//!
+//! The `retime` command moves flip-flops forward across the logic, to minimize the logic depth or, with `--objective area`, the number of flip-flops.
+//! ```bash
+//! quaigh retime mydesign.bench -o retimed.bench
+//! ```
 //!
 //! Quaigh supports a subset of the [Blif](https://course.ece.cmu.edu/~ee760/760docs/blif.pdf) file format, as well
-//! as the simple Bench file format used by ISCAS benchmarks. Benchmarks can be downloaded
+//! as the simple Bench file format used by ISCAS benchmarks, [Aiger](https://fmv.jku.at/aiger/) files and
+//! structural Verilog. Benchmarks can be downloaded
 //! [here](https://github.com/Coloquinte/moosic-yosys-plugin/releases/download/iscas_benchmarks/benchmarks.tar.xz).
-//! More features will be added over time, such as technology mapping, operator optimization, ...
+//! More features will be added over time, such as operator optimization, ...
 //! The complete documentation is available on [docs.rs](https://docs.rs/crate/quaigh/latest).
 //!
 //! # Development
@@ -59,7 +95,7 @@
 //! but there are some limitations to make it easy to optimize:
 //! *   all gates have a single output, representing a single binary value,
 //! *   the gates are kept in topological order (a gate has an index higher than its inputs),
-//! *   names and design hierarchy are not represented.
+//! *   design hierarchy is not represented, and names are optional annotations on inputs, outputs and gates.
 //!
 //! For example, here is a full adder circuit:
 //! ```
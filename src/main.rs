@@ -20,7 +20,11 @@ fn main() {
 
     match cli.command {
         cmd::Commands::CheckEquivalence(a) => a.run(),
+        cmd::Commands::CheckProperty(a) => a.run(),
+        cmd::Commands::Bmc(a) => a.run(),
         cmd::Commands::Optimize(a) => a.run(),
+        cmd::Commands::Map(a) => a.run(),
+        cmd::Commands::Retime(a) => a.run(),
         cmd::Commands::Show(a) => a.run(),
         cmd::Commands::Simulate(a) => a.run(),
         cmd::Commands::Atpg(a) => a.run(),
@@ -1,13 +1,19 @@
 //! Representation and handling of logic networks
 
 pub mod area;
+pub mod cuts;
 mod gates;
 pub mod generators;
+pub mod library;
 pub mod matcher;
+mod mffc;
 mod network;
 mod signal;
 pub mod stats;
+pub mod timing;
 
+pub(crate) use gates::Normalization;
 pub use gates::{BinaryType, Gate, NaryType, TernaryType};
+pub(crate) use mffc::{cone_area, RefCounts};
 pub use network::Network;
 pub use signal::Signal;
@@ -47,6 +47,7 @@ impl AreaParameters {
     ///
     /// In VLSI, And gates are cheap and easy to merge together, while Xor is more expensive.
     /// We use roughly the area the cells would use in a standard cell library.
+    /// For the area of an actual cell library, map the network with [`crate::optim::map_cells`].
     pub fn vlsi() -> AreaParameters {
         AreaParameters {
             and: 4,
@@ -0,0 +1,581 @@
+//! Enumeration of k-feasible cuts
+//!
+//! A cut of a node is a set of signals, its leaves, such that every path from the inputs
+//! to the node goes through a leaf. Cuts are the basis of rewriting and technology mapping.
+//! Only a limited number of priority cuts are kept for each node.
+//!
+//! ```
+//! # use quaigh::Network;
+//! # let mut aig = Network::new();
+//! # let a = aig.add_input();
+//! # let b = aig.add_input();
+//! # let x = aig.and(a, b);
+//! # aig.add_output(x);
+//! use quaigh::network::cuts::CutParameters;
+//!
+//! // Enumerate cuts with up to 6 leaves
+//! let cuts = CutParameters::default().enumerate(&aig);
+//! for cut in cuts.node_cuts(0) {
+//!     println!("{:?}: {}", cut.leaves(), cut.function());
+//! }
+//! ```
+
+use std::cmp::Ordering;
+
+use fxhash::FxHashSet;
+use volute::Lut;
+
+use crate::network::gates::{BinaryType, NaryType, TernaryType};
+use crate::{Gate, Network, Signal};
+
+/// Parameters for cut enumeration
+#[derive(Clone, Copy, Debug)]
+pub struct CutParameters {
+    /// Maximum number of leaves in a cut, up to 8
+    pub max_size: usize,
+    /// Maximum number of cuts kept for each node, in addition to the trivial cut
+    pub max_cuts: usize,
+    /// Allow cuts to go through flip-flops without enable or reset
+    ///
+    /// Cuts cross at most one level of flip-flops.
+    pub through_dff: bool,
+}
+
+impl Default for CutParameters {
+    fn default() -> Self {
+        CutParameters {
+            max_size: 6,
+            max_cuts: 8,
+            through_dff: false,
+        }
+    }
+}
+
+/// A cut of a node, with its function
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cut {
+    leaves: Box<[Signal]>,
+    function: Lut,
+    latency: usize,
+    area_flow: f64,
+    depth: usize,
+}
+
+impl Cut {
+    /// Leaves of the cut, inputs first then nodes, by increasing index; they are never inverted
+    pub fn leaves(&self) -> &[Signal] {
+        &self.leaves
+    }
+
+    /// Number of leaves of the cut
+    pub fn size(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Function of the node, with the i-th leaf as the i-th variable
+    pub fn function(&self) -> &Lut {
+        &self.function
+    }
+
+    /// Number of flip-flops between the leaves and the node
+    ///
+    /// The function uses the values of the leaves this number of cycles before.
+    pub fn latency(&self) -> usize {
+        self.latency
+    }
+
+    /// Area flow of the cut, counting one for each cut and sharing the area of the leaves
+    /// between their fanouts
+    pub fn area_flow(&self) -> f64 {
+        self.area_flow
+    }
+
+    /// Depth of the cut, in number of cuts on the longest path from the inputs
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Whether this is the trivial cut, whose only leaf is the node itself
+    pub fn is_trivial(&self, node: Signal) -> bool {
+        self.latency == 0 && self.leaves.len() == 1 && self.leaves[0] == node
+    }
+
+    /// Trivial cut of a signal, with a given depth and area flow
+    fn trivial(s: Signal, depth: usize, area_flow: f64) -> Cut {
+        Cut {
+            leaves: Box::new([s]),
+            function: Lut::nth_var(1, 0),
+            latency: 0,
+            area_flow,
+            depth,
+        }
+    }
+
+    /// Whether the leaves of this cut are a subset of the leaves of another cut
+    fn dominates(&self, other: &Cut) -> bool {
+        self.latency == other.latency && self.leaves.iter().all(|l| other.leaves.contains(l))
+    }
+
+    /// Order of priority between cuts: smaller depth, then smaller area flow, then fewer leaves
+    fn priority(&self, other: &Cut) -> Ordering {
+        self.depth
+            .cmp(&other.depth)
+            .then(self.area_flow.total_cmp(&other.area_flow))
+            .then(self.leaves.len().cmp(&other.leaves.len()))
+    }
+}
+
+/// Cuts of all nodes of a network
+#[derive(Clone, Debug)]
+pub struct Cuts {
+    cuts: Vec<Vec<Cut>>,
+}
+
+impl Cuts {
+    /// Cuts of a node, by order of priority; the trivial cut is last
+    pub fn node_cuts(&self, i: usize) -> &[Cut] {
+        &self.cuts[i]
+    }
+
+    /// Best non-trivial cut of a node, if any
+    pub fn best_cut(&self, i: usize) -> Option<&Cut> {
+        let c = self.cuts[i].first()?;
+        if c.is_trivial(Signal::from_var(i as u32)) {
+            None
+        } else {
+            Some(c)
+        }
+    }
+}
+
+/// Ordering of the leaves in a cut: inputs first then nodes, by increasing index
+fn leaf_order(s: &Signal) -> (bool, u32) {
+    if s.is_input() {
+        (false, s.input())
+    } else {
+        (true, s.var())
+    }
+}
+
+/// Expand a function to a larger set of leaves
+fn expand(function: &Lut, from: &[Signal], to: &[Signal]) -> Lut {
+    let pos: Vec<usize> = from
+        .iter()
+        .map(|s| to.iter().position(|t| t == s).unwrap())
+        .collect();
+    let mut ret = Lut::zero(to.len());
+    for mask in 0..(1 << to.len()) {
+        let mut from_mask = 0;
+        for (i, p) in pos.iter().enumerate() {
+            if (mask >> p) & 1 != 0 {
+                from_mask |= 1 << i;
+            }
+        }
+        ret.set_value(mask, function.value(from_mask));
+    }
+    ret
+}
+
+/// Evaluate a combinatorial gate, given the values of its dependencies
+pub(crate) fn eval_gate(g: &Gate, v: &[bool]) -> bool {
+    use Gate::*;
+    match g {
+        Binary(_, BinaryType::And) | Ternary(_, TernaryType::And) => v.iter().all(|b| *b),
+        Binary(_, BinaryType::Xor) | Ternary(_, TernaryType::Xor) => {
+            v.iter().filter(|b| **b).count() % 2 == 1
+        }
+        Ternary(_, TernaryType::Maj) => v.iter().filter(|b| **b).count() >= 2,
+        Ternary(_, TernaryType::Mux) => {
+            if v[0] {
+                v[1]
+            } else {
+                v[2]
+            }
+        }
+        Nary(_, tp) => match tp {
+            NaryType::And => v.iter().all(|b| *b),
+            NaryType::Or => v.iter().any(|b| *b),
+            NaryType::Nand => !v.iter().all(|b| *b),
+            NaryType::Nor => !v.iter().any(|b| *b),
+            NaryType::Xor => v.iter().filter(|b| **b).count() % 2 == 1,
+            NaryType::Xnor => v.iter().filter(|b| **b).count() % 2 == 0,
+        },
+        Buf(_) => v[0],
+        Lut(lut) => {
+            let mask = v
+                .iter()
+                .enumerate()
+                .fold(0, |m, (i, b)| m | ((*b as usize) << i));
+            lut.lut.value(mask)
+        }
+        Dff(_) => panic!("Combinatorial gate expected"),
+    }
+}
+
+/// Candidate cut during enumeration: merged leaves and the chosen cut of each dependency
+struct Candidate<'a> {
+    leaves: Vec<Signal>,
+    latency: Option<usize>,
+    choices: Vec<(Signal, Option<&'a Cut>)>,
+}
+
+/// Cut enumeration algorithm
+struct Enumerator<'a> {
+    aig: &'a Network,
+    params: CutParameters,
+    cuts: Vec<Vec<Cut>>,
+    nb_refs: Vec<usize>,
+}
+
+impl<'a> Enumerator<'a> {
+    fn new(aig: &'a Network, params: CutParameters) -> Enumerator<'a> {
+        assert!(params.max_size <= 8, "Cuts are limited to 8 leaves");
+        let mut nb_refs = vec![0; aig.nb_nodes()];
+        for i in 0..aig.nb_nodes() {
+            for v in aig.gate(i).vars() {
+                nb_refs[v as usize] += 1;
+            }
+        }
+        for o in 0..aig.nb_outputs() {
+            let s = aig.output(o);
+            if s.is_var() {
+                nb_refs[s.var() as usize] += 1;
+            }
+        }
+        Enumerator {
+            aig,
+            params,
+            cuts: vec![Vec::new(); aig.nb_nodes()],
+            nb_refs,
+        }
+    }
+
+    /// Depth and area flow of a leaf, based on its best cut
+    ///
+    /// Flip-flops start a new combinatorial path, so their depth is always 0.
+    fn leaf_cost(&self, s: Signal) -> (usize, f64) {
+        if !s.is_var() {
+            return (0, 0.0);
+        }
+        let i = s.var() as usize;
+        let is_comb = self.aig.gate(i).is_comb();
+        match self.cuts[i].first() {
+            Some(c) if is_comb => (c.depth, c.area_flow / self.nb_refs[i].max(1) as f64),
+            Some(c) => (0, c.area_flow / self.nb_refs[i].max(1) as f64),
+            None => (0, 0.0),
+        }
+    }
+
+    /// Cuts available for a dependency, ignoring the inversion
+    ///
+    /// Inputs and constants only have a trivial cut, represented by None.
+    fn dependency_cuts(&self, s: Signal) -> Vec<Option<&Cut>> {
+        if s.is_var() {
+            self.cuts[s.var() as usize].iter().map(Some).collect()
+        } else {
+            vec![None]
+        }
+    }
+
+    /// Build a cut from a candidate
+    fn make_cut(&self, g: &Gate, cand: &Candidate) -> Cut {
+        let leaves = cand.leaves.as_slice();
+        let functions: Vec<Option<Lut>> = cand
+            .choices
+            .iter()
+            .map(|(s, c)| match c {
+                Some(c) => Some(expand(&c.function, &c.leaves, leaves)),
+                None if s.is_input() => Some(expand(
+                    &Lut::nth_var(1, 0),
+                    &[s.without_inversion()],
+                    leaves,
+                )),
+                None => None,
+            })
+            .collect();
+        let mut function = Lut::zero(leaves.len());
+        for mask in 0..(1 << leaves.len()) {
+            let values: Vec<bool> = cand
+                .choices
+                .iter()
+                .zip(functions.iter())
+                .map(|((s, _), f)| {
+                    let v = match f {
+                        Some(f) => f.value(mask),
+                        None => false,
+                    };
+                    v ^ s.is_inverted()
+                })
+                .collect();
+            function.set_value(mask, eval_gate(g, &values));
+        }
+        let mut depth = 0;
+        let mut area_flow = 1.0;
+        for l in leaves {
+            let (d, af) = self.leaf_cost(*l);
+            depth = depth.max(d + 1);
+            area_flow += af;
+        }
+        Cut {
+            leaves: leaves.into(),
+            function,
+            latency: cand.latency.unwrap_or(0),
+            area_flow,
+            depth: depth.max(1),
+        }
+    }
+
+    /// Merge the cuts of the dependencies of a gate
+    fn merge_candidates(&self, g: &Gate) -> Vec<Candidate<'_>> {
+        let max_candidates = 4 * self.params.max_cuts * self.params.max_cuts.max(1);
+        let mut candidates = vec![Candidate {
+            leaves: Vec::new(),
+            latency: None,
+            choices: Vec::new(),
+        }];
+        for s in g.dependencies() {
+            let mut next = Vec::new();
+            let mut seen = FxHashSet::default();
+            for cand in &candidates {
+                for c in &self.dependency_cuts(*s) {
+                    let (leaves, latency): (Vec<Signal>, Option<usize>) = match c {
+                        Some(c) => (c.leaves.to_vec(), Some(c.latency)),
+                        None if s.is_input() => (vec![s.without_inversion()], Some(0)),
+                        None => (Vec::new(), None),
+                    };
+                    let latency = match (cand.latency, latency) {
+                        (Some(a), Some(b)) if a != b => continue,
+                        (a, b) => a.or(b),
+                    };
+                    let mut merged = cand.leaves.clone();
+                    merged.extend(leaves);
+                    merged.sort_by_key(leaf_order);
+                    merged.dedup();
+                    if merged.len() > self.params.max_size {
+                        continue;
+                    }
+                    if !seen.insert((merged.clone(), latency)) {
+                        continue;
+                    }
+                    let mut choices = cand.choices.clone();
+                    choices.push((*s, *c));
+                    next.push(Candidate {
+                        leaves: merged,
+                        latency,
+                        choices,
+                    });
+                }
+            }
+            next.sort_by_key(|c| c.leaves.len());
+            next.truncate(max_candidates);
+            candidates = next;
+        }
+        candidates
+    }
+
+    /// Select the priority cuts of a node and add the trivial cut
+    fn select_cuts(&self, i: usize, mut cuts: Vec<Cut>) -> Vec<Cut> {
+        cuts.sort_by(|a, b| a.priority(b));
+        let mut ret: Vec<Cut> = Vec::new();
+        for c in cuts {
+            if ret.len() >= self.params.max_cuts {
+                break;
+            }
+            if ret.iter().any(|r| r.dominates(&c)) {
+                continue;
+            }
+            ret.push(c);
+        }
+        let (depth, area_flow) = match ret.first() {
+            Some(c) => (c.depth, c.area_flow),
+            None => (0, 0.0),
+        };
+        ret.push(Cut::trivial(self.aig.node(i), depth, area_flow));
+        ret
+    }
+
+    /// Compute the cuts of all combinatorial nodes
+    fn run_comb(&mut self) {
+        for i in 0..self.aig.nb_nodes() {
+            let g = self.aig.gate(i);
+            if !g.is_comb() {
+                continue;
+            }
+            let cuts: Vec<Cut> = self
+                .merge_candidates(g)
+                .iter()
+                .map(|c| self.make_cut(g, c))
+                .collect();
+            self.cuts[i] = self.select_cuts(i, cuts);
+        }
+    }
+
+    /// Compute the cuts of flip-flops, either trivial or going through them
+    fn run_dff(&mut self, through_dff: bool) {
+        for i in 0..self.aig.nb_nodes() {
+            let Gate::Dff([d, en, res]) = self.aig.gate(i) else {
+                continue;
+            };
+            let mut cuts = Vec::new();
+            let simple = *en == Signal::one() && *res == Signal::zero();
+            if through_dff && simple && d.is_var() {
+                for c in &self.cuts[d.var() as usize] {
+                    if c.latency != 0 {
+                        continue;
+                    }
+                    let mut c = c.clone();
+                    c.latency = 1;
+                    if d.is_inverted() {
+                        c.function = !&c.function;
+                    }
+                    cuts.push(c);
+                }
+            } else if through_dff && simple && d.is_input() {
+                let mut c = Cut::trivial(d.without_inversion(), 0, 0.0);
+                c.latency = 1;
+                if d.is_inverted() {
+                    c.function = !&c.function;
+                }
+                cuts.push(c);
+            }
+            self.cuts[i] = self.select_cuts(i, cuts);
+        }
+    }
+}
+
+impl CutParameters {
+    /// Enumerate the cuts of all nodes of a network
+    ///
+    /// The network must be topologically sorted.
+    pub fn enumerate(&self, aig: &Network) -> Cuts {
+        assert!(aig.is_topo_sorted());
+        let mut e = Enumerator::new(aig, *self);
+        e.run_dff(false);
+        e.run_comb();
+        if self.through_dff {
+            e.run_dff(true);
+            e.run_comb();
+        }
+        Cuts { cuts: e.cuts }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use volute::Lut;
+
+    use super::CutParameters;
+    use crate::{Gate, Network, Signal};
+
+    #[test]
+    fn test_and_chain() {
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let x = aig.and(a, !b);
+        let y = aig.and(x, c);
+        aig.add_output(y);
+        let cuts = CutParameters::default().enumerate(&aig);
+        let best = cuts.best_cut(1).unwrap();
+        assert_eq!(best.leaves(), &[a, b, c]);
+        assert_eq!(best.depth(), 1);
+        let expected = Lut::nth_var(3, 0) & !Lut::nth_var(3, 1) & Lut::nth_var(3, 2);
+        assert_eq!(best.function(), &expected);
+        // Cut on x and c, then the trivial cut
+        assert_eq!(cuts.node_cuts(1).len(), 3);
+        assert!(cuts.node_cuts(1)[2].is_trivial(y));
+    }
+
+    #[test]
+    fn test_complex_gates() {
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let d = aig.add_input();
+        let x = aig.add(Gate::maj(a, b, !c));
+        let y = aig.add(Gate::mux(d, x, !a));
+        let z = aig.add(Gate::Nary(
+            [y, b, Signal::one()].into(),
+            crate::network::NaryType::Xor,
+        ));
+        let l = aig.add(Gate::lut(&[z, c], Lut::nth_var(2, 0) | Lut::nth_var(2, 1)));
+        aig.add_output(l);
+        let cuts = CutParameters::default().enumerate(&aig);
+        let best = cuts.best_cut(3).unwrap();
+        assert_eq!(best.leaves(), &[a, b, c, d]);
+        for mask in 0..16 {
+            let v = |i: usize| (mask >> i) & 1 != 0;
+            let x = (v(0) as u8 + v(1) as u8 + !v(2) as u8) >= 2;
+            let y = if v(3) { x } else { !v(0) };
+            let z = y ^ v(1) ^ true;
+            assert_eq!(best.function().value(mask), z | v(2));
+        }
+    }
+
+    #[test]
+    fn test_max_size() {
+        let mut aig = Network::new();
+        let inputs: Vec<Signal> = (0..8).map(|_| aig.add_input()).collect();
+        let o = aig.add(Gate::andn(&inputs));
+        aig.add_output(o);
+        let params = CutParameters {
+            max_size: 4,
+            ..Default::default()
+        };
+        let cuts = params.enumerate(&aig);
+        assert!(cuts.best_cut(0).is_none());
+        let params = CutParameters {
+            max_size: 8,
+            ..Default::default()
+        };
+        let cuts = params.enumerate(&aig);
+        assert_eq!(cuts.best_cut(0).unwrap().size(), 8);
+    }
+
+    #[test]
+    fn test_through_dff() {
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let d = aig.add(Gate::dff(Signal::zero(), Signal::one(), Signal::zero()));
+        let x = aig.xor(a, b);
+        aig.replace(0, Gate::dff(!x, Signal::one(), Signal::zero()));
+        let y = aig.and(d, x);
+        aig.add_output(y);
+        let cuts = CutParameters::default().enumerate(&aig);
+        assert!(cuts.node_cuts(2).iter().all(|c| c.latency() == 0));
+        let params = CutParameters {
+            through_dff: true,
+            ..Default::default()
+        };
+        let cuts = params.enumerate(&aig);
+        let c = cuts.node_cuts(0).iter().find(|c| c.size() == 2).unwrap();
+        assert_eq!(c.latency(), 1);
+        assert_eq!(c.leaves(), &[a, b]);
+        assert_eq!(c.function(), &!(Lut::nth_var(2, 0) ^ Lut::nth_var(2, 1)));
+        // The And mixes signals at different cycles, so it cannot use the cut through the Dff
+        assert!(cuts.node_cuts(2).iter().all(|c| c.latency() == 0));
+    }
+
+    #[test]
+    fn test_depth_through_dff() {
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let x = aig.xor(a, b);
+        let d = aig.add(Gate::dff(x, Signal::one(), Signal::zero()));
+        let y = aig.and(d, c);
+        aig.add_output(y);
+        let params = CutParameters {
+            through_dff: true,
+            ..Default::default()
+        };
+        let cuts = params.enumerate(&aig);
+        assert_eq!(cuts.best_cut(1).unwrap().depth(), 1);
+        // The depth does not accumulate across the flip-flop
+        assert!(cuts.node_cuts(2).iter().all(|c| c.depth() == 1));
+    }
+}
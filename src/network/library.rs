@@ -0,0 +1,247 @@
+//! Standard cell libraries and networks mapped to library cells
+//!
+//! ```
+//! # use quaigh::Network;
+//! # let aig = Network::new();
+//! use quaigh::network::library::{Cell, Library};
+//! use quaigh::optim::map_cells;
+//! use volute::Lut;
+//!
+//! // A minimal library with an inverter and a Nand2 cell
+//! let mut library = Library::new();
+//! library.add_cell(Cell::new("INV", 1.0, 1.0, &["A"], "Y", !Lut::nth_var(1, 0)));
+//! let nand = !(Lut::nth_var(2, 0) & Lut::nth_var(2, 1));
+//! library.add_cell(Cell::new("NAND2", 2.0, 1.0, &["A", "B"], "Y", nand));
+//!
+//! // Map a network with this library, without delay target
+//! let mapped = map_cells(&aig, &library, None);
+//! println!("Area: {}", mapped.area(&library));
+//! ```
+
+use std::fmt;
+
+use volute::Lut;
+
+use crate::{Gate, Network};
+
+/// A combinatorial cell with a single output
+#[derive(Clone, Debug)]
+pub struct Cell {
+    /// Name of the cell
+    pub name: String,
+    /// Area of the cell
+    pub area: f64,
+    /// Delay of the cell, from any input to the output
+    pub delay: f64,
+    /// Names of the input pins, in the order of the variables of the function
+    pub inputs: Vec<String>,
+    /// Name of the output pin
+    pub output: String,
+    /// Function of the cell
+    pub function: Lut,
+}
+
+impl Cell {
+    /// Create a new cell
+    pub fn new(
+        name: &str,
+        area: f64,
+        delay: f64,
+        inputs: &[&str],
+        output: &str,
+        function: Lut,
+    ) -> Cell {
+        assert_eq!(inputs.len(), function.num_vars());
+        Cell {
+            name: name.to_owned(),
+            area,
+            delay,
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            output: output.to_owned(),
+            function,
+        }
+    }
+
+    /// Number of inputs of the cell
+    pub fn nb_inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Returns whether the cell is an inverter
+    pub fn is_inverter(&self) -> bool {
+        self.nb_inputs() == 1 && self.function == !Lut::nth_var(1, 0)
+    }
+}
+
+/// A library of combinatorial cells
+#[derive(Clone, Debug, Default)]
+pub struct Library {
+    cells: Vec<Cell>,
+}
+
+impl Library {
+    /// Create an empty library
+    pub fn new() -> Library {
+        Library::default()
+    }
+
+    /// Number of cells in the library
+    pub fn nb_cells(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Get the cell at index i
+    pub fn cell(&self, i: usize) -> &Cell {
+        &self.cells[i]
+    }
+
+    /// Add a new cell and return its index
+    pub fn add_cell(&mut self, cell: Cell) -> usize {
+        self.cells.push(cell);
+        self.cells.len() - 1
+    }
+
+    /// Find a cell by name
+    pub fn find_cell(&self, name: &str) -> Option<usize> {
+        self.cells.iter().position(|c| c.name == name)
+    }
+
+    /// Smallest inverter of the library, if any
+    pub fn inverter(&self) -> Option<usize> {
+        (0..self.nb_cells())
+            .filter(|i| self.cells[*i].is_inverter())
+            .min_by(|a, b| self.cells[*a].area.total_cmp(&self.cells[*b].area))
+    }
+
+    /// Returns whether any network can be mapped to this library
+    ///
+    /// This requires an inverter and a two-input And, Or, Nand or Nor cell.
+    pub fn is_complete(&self) -> bool {
+        let and = Lut::nth_var(2, 0) & Lut::nth_var(2, 1);
+        let or = Lut::nth_var(2, 0) | Lut::nth_var(2, 1);
+        let has_and_like = self.cells.iter().any(|c| {
+            c.nb_inputs() == 2
+                && (c.function == and
+                    || c.function == or
+                    || c.function == !&and
+                    || c.function == !&or)
+        });
+        self.inverter().is_some() && has_and_like
+    }
+}
+
+/// A network mapped to the cells of a library
+///
+/// Each combinatorial node is a Lut gate that represents a cell, with the inputs given in the
+/// order of the cell pins. Flip-flops are kept as is.
+#[derive(Clone, Debug)]
+pub struct MappedNetwork {
+    network: Network,
+    cells: Vec<Option<usize>>,
+}
+
+impl MappedNetwork {
+    /// Create a mapped network from the network and the cell used for each node
+    pub fn new(network: Network, cells: Vec<Option<usize>>) -> MappedNetwork {
+        assert_eq!(network.nb_nodes(), cells.len());
+        for (i, c) in cells.iter().enumerate() {
+            assert_eq!(c.is_none(), !network.gate(i).is_comb());
+        }
+        MappedNetwork { network, cells }
+    }
+
+    /// Logic network, with a Lut gate for each cell
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+
+    /// Library cell for the node at index i, or None for flip-flops
+    pub fn cell(&self, i: usize) -> Option<usize> {
+        self.cells[i]
+    }
+
+    /// Total area of the cells
+    pub fn area(&self, library: &Library) -> f64 {
+        self.cells
+            .iter()
+            .flatten()
+            .map(|c| library.cell(*c).area)
+            .sum()
+    }
+
+    /// Delay of the longest combinatorial path
+    pub fn delay(&self, library: &Library) -> f64 {
+        let aig = &self.network;
+        let mut arrival = vec![0.0; aig.nb_nodes()];
+        for i in 0..aig.nb_nodes() {
+            let Some(c) = self.cells[i] else {
+                continue;
+            };
+            let input_arrival = aig
+                .gate(i)
+                .vars()
+                .map(|v| arrival[v as usize])
+                .fold(0.0, f64::max);
+            arrival[i] = input_arrival + library.cell(c).delay;
+        }
+        let mut delay: f64 = 0.0;
+        for o in 0..aig.nb_outputs() {
+            if aig.output(o).is_var() {
+                delay = delay.max(arrival[aig.output(o).var() as usize]);
+            }
+        }
+        for i in 0..aig.nb_nodes() {
+            if let Gate::Dff(_) = aig.gate(i) {
+                for v in aig.gate(i).vars() {
+                    delay = delay.max(arrival[v as usize]);
+                }
+            }
+        }
+        delay
+    }
+
+    /// Number of instances of each cell
+    pub fn cell_counts(&self, library: &Library) -> Vec<usize> {
+        let mut ret = vec![0; library.nb_cells()];
+        for c in self.cells.iter().flatten() {
+            ret[*c] += 1;
+        }
+        ret
+    }
+
+    /// Summary of the mapping, with the number of instances of each cell
+    pub fn report<'a>(&'a self, library: &'a Library) -> MappingReport<'a> {
+        MappingReport {
+            mapped: self,
+            library,
+        }
+    }
+}
+
+/// Summary of a mapped network, for display
+pub struct MappingReport<'a> {
+    mapped: &'a MappedNetwork,
+    library: &'a Library,
+}
+
+impl fmt::Display for MappingReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts = self.mapped.cell_counts(self.library);
+        let nb_dff = (0..self.mapped.network.nb_nodes())
+            .filter(|i| self.mapped.cells[*i].is_none())
+            .count();
+        writeln!(f, "Mapping:")?;
+        writeln!(f, "  Cells: {}", counts.iter().sum::<usize>())?;
+        writeln!(f, "  Area: {:.2}", self.mapped.area(self.library))?;
+        writeln!(f, "  Delay: {:.2}", self.mapped.delay(self.library))?;
+        if nb_dff != 0 {
+            writeln!(f, "  Dff: {}", nb_dff)?;
+        }
+        for (c, nb) in counts.iter().enumerate() {
+            if *nb != 0 {
+                writeln!(f, "  {}: {}", self.library.cell(c).name, nb)?;
+            }
+        }
+        Ok(())
+    }
+}
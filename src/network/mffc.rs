@@ -0,0 +1,130 @@
+//! Reference counting and maximum fanout-free cones (MFFC)
+
+use crate::network::area::AreaParameters;
+use crate::{Gate, Network, Signal};
+
+/// Number of references to each node of a network, including the outputs
+///
+/// The maximum fanout-free cone of a node is the logic that is only used through it, and that
+/// would be removed with it. It is obtained by dereferencing the node, which removes the
+/// references from its cone, and restored by referencing the node again.
+#[derive(Clone, Debug)]
+pub(crate) struct RefCounts {
+    refs: Vec<u32>,
+}
+
+impl RefCounts {
+    /// Count the references to each node of a network
+    pub fn new(aig: &Network) -> RefCounts {
+        let mut refs = vec![0; aig.nb_nodes()];
+        for i in 0..aig.nb_nodes() {
+            for v in aig.gate(i).vars() {
+                refs[v as usize] += 1;
+            }
+        }
+        for i in 0..aig.nb_outputs() {
+            let s = aig.output(i);
+            if s.is_var() {
+                refs[s.var() as usize] += 1;
+            }
+        }
+        RefCounts { refs }
+    }
+
+    /// Number of references to a node
+    pub fn get(&self, i: usize) -> u32 {
+        self.refs[i]
+    }
+
+    /// Take into account nodes added to the network, that are not referenced yet
+    pub fn resize(&mut self, aig: &Network) {
+        self.refs.resize(aig.nb_nodes(), 0);
+    }
+
+    /// Remove a reference to the inputs of a node, stopping at the leaves
+    ///
+    /// Returns the nodes of the cone that are not referenced anymore, starting with the node
+    /// itself.
+    pub fn deref_node(&mut self, aig: &Network, i: usize, leaves: &[Signal]) -> Vec<usize> {
+        let mut freed = vec![i];
+        let mut stack = vec![i];
+        while let Some(n) = stack.pop() {
+            for v in aig.gate(n).vars() {
+                let v = v as usize;
+                self.refs[v] -= 1;
+                if self.refs[v] == 0 && aig.gate(v).is_comb() && !leaves.contains(&aig.node(v)) {
+                    freed.push(v);
+                    stack.push(v);
+                }
+            }
+        }
+        freed
+    }
+
+    /// Add a reference to the inputs of a node, stopping at the leaves; opposite of deref_node
+    pub fn ref_node(&mut self, aig: &Network, i: usize, leaves: &[Signal]) {
+        let mut stack = vec![i];
+        while let Some(n) = stack.pop() {
+            for v in aig.gate(n).vars() {
+                let v = v as usize;
+                self.refs[v] += 1;
+                if self.refs[v] == 1 && aig.gate(v).is_comb() && !leaves.contains(&aig.node(v)) {
+                    stack.push(v);
+                }
+            }
+        }
+    }
+}
+
+/// Area of a set of nodes; Lut and Buf are not counted
+pub(crate) fn cone_area(aig: &Network, nodes: &[usize], params: &AreaParameters) -> usize {
+    nodes
+        .iter()
+        .map(|i| match aig.gate(*i) {
+            Gate::Lut(_) | Gate::Buf(_) => 0,
+            g => params.gate_area(g),
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cone_area, RefCounts};
+    use crate::network::area::AreaParameters;
+    use crate::Network;
+
+    #[test]
+    fn test_mffc() {
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let ab = aig.and(a, b);
+        let x = aig.and(ab, c);
+        let y = aig.xor(ab, x);
+        let z = aig.and(x, c);
+        aig.add_output(y);
+        aig.add_output(z);
+        let mut refs = RefCounts::new(&aig);
+        assert_eq!(refs.get(0), 2);
+        assert_eq!(refs.get(1), 2);
+        // ab and x are used elsewhere, so only y is freed
+        let freed = refs.deref_node(&aig, 2, &[]);
+        assert_eq!(freed, vec![2]);
+        refs.ref_node(&aig, 2, &[]);
+        // Without z, x and ab are only used by y
+        refs.deref_node(&aig, 3, &[]);
+        let mut freed = refs.deref_node(&aig, 2, &[]);
+        freed.sort();
+        assert_eq!(freed, vec![0, 1, 2]);
+        let params = AreaParameters::vlsi();
+        assert_eq!(
+            cone_area(&aig, &freed, &params),
+            2 * params.and + params.xor
+        );
+        // The leaves stop the traversal
+        refs.ref_node(&aig, 2, &[]);
+        let freed = refs.deref_node(&aig, 2, &[x]);
+        assert_eq!(freed, vec![2]);
+    }
+}
@@ -14,6 +14,30 @@ pub struct Network {
     nb_inputs: usize,
     nodes: Vec<Gate>,
     outputs: Vec<Signal>,
+    names: NameTable,
+}
+
+/// Optional names for the inputs, outputs and nodes of a network
+///
+/// Names are allocated lazily, so that unnamed networks do not pay for them.
+#[derive(Debug, Clone, Default)]
+struct NameTable {
+    inputs: Vec<Option<String>>,
+    outputs: Vec<Option<String>>,
+    nodes: Vec<Option<String>>,
+}
+
+impl NameTable {
+    fn get(names: &[Option<String>], i: usize) -> Option<&str> {
+        names.get(i).and_then(|n| n.as_deref())
+    }
+
+    fn set(names: &mut Vec<Option<String>>, i: usize, name: &str) {
+        if names.len() <= i {
+            names.resize(i + 1, None);
+        }
+        names[i] = Some(name.to_owned());
+    }
 }
 
 impl Network {
@@ -59,6 +83,44 @@ impl Network {
         &self.nodes[i]
     }
 
+    /// Get the name of the input at index i, if any
+    pub fn input_name(&self, i: usize) -> Option<&str> {
+        assert!(i < self.nb_inputs());
+        NameTable::get(&self.names.inputs, i)
+    }
+
+    /// Get the name of the output at index i, if any
+    pub fn output_name(&self, i: usize) -> Option<&str> {
+        assert!(i < self.nb_outputs());
+        NameTable::get(&self.names.outputs, i)
+    }
+
+    /// Get the name of the node at index i, if any
+    pub fn node_name(&self, i: usize) -> Option<&str> {
+        assert!(i < self.nb_nodes());
+        NameTable::get(&self.names.nodes, i)
+    }
+
+    /// Set the name of the input at index i
+    pub fn set_input_name(&mut self, i: usize, name: &str) {
+        assert!(i < self.nb_inputs());
+        NameTable::set(&mut self.names.inputs, i, name);
+    }
+
+    /// Set the name of the output at index i
+    pub fn set_output_name(&mut self, i: usize, name: &str) {
+        assert!(i < self.nb_outputs());
+        NameTable::set(&mut self.names.outputs, i, name);
+    }
+
+    /// Set the name of the node at index i
+    ///
+    /// Names are kept when the network is modified, as long as the node is not removed.
+    pub fn set_node_name(&mut self, i: usize, name: &str) {
+        assert!(i < self.nb_nodes());
+        NameTable::set(&mut self.names.nodes, i, name);
+    }
+
     /// Add a new primary input
     pub fn add_input(&mut self) -> Signal {
         self.nb_inputs += 1;
@@ -75,6 +137,12 @@ impl Network {
         self.outputs.push(l)
     }
 
+    /// Replace an existing primary output
+    pub fn replace_output(&mut self, i: usize, l: Signal) {
+        assert!(i < self.nb_outputs());
+        self.outputs[i] = l;
+    }
+
     /// Create an And2 gate
     pub fn and(&mut self, a: Signal, b: Signal) -> Signal {
         self.add_canonical(Gate::and(a, b))
@@ -155,6 +223,7 @@ impl Network {
 
         // Remap the outputs
         self.remap_outputs(&translation);
+        self.remap_names(&translation);
         translation.into()
     }
 
@@ -179,6 +248,23 @@ impl Network {
         self.outputs = new_outputs;
     }
 
+    /// Remap node names
+    ///
+    /// A name is kept if its node is translated to a non-inverted node.
+    /// If several nodes are merged, the first name is kept.
+    fn remap_names(&mut self, translation: &[Signal]) {
+        if self.names.nodes.is_empty() {
+            return;
+        }
+        let mut new_names = vec![None; self.nb_nodes()];
+        for (name, s) in self.names.nodes.iter_mut().zip(translation) {
+            if s.is_var() && !s.is_inverted() && new_names[s.var() as usize].is_none() {
+                new_names[s.var() as usize] = name.take();
+            }
+        }
+        self.names.nodes = new_names;
+    }
+
     /// Remove unused logic; this will invalidate all signals
     ///
     /// Returns the mapping of old variable indices to signals, if needed.
@@ -294,6 +380,7 @@ impl Network {
 
         self.nodes = new_nodes;
         self.remap_outputs(&translation);
+        self.remap_names(&translation);
         self.check();
         translation.into()
     }
@@ -303,6 +390,18 @@ impl Network {
     /// Ordering may be changed even if already sorted. Flip-flop ordering is kept as is.
     /// Returns the mapping of old variable indices to signals, if needed.
     pub(crate) fn topo_sort(&mut self) -> Box<[Signal]> {
+        match self.try_topo_sort() {
+            Ok(translation) => translation,
+            Err(_) => {
+                panic!(
+                    "Unable to find a valid topological sort: there must be a combinatorial loop"
+                )
+            }
+        }
+    }
+
+    /// Topologically sort the network, or return a node that is part of a combinatorial loop
+    pub(crate) fn try_topo_sort(&mut self) -> Result<Box<[Signal]>, usize> {
         // Count the output dependencies of each gate
         let mut count_deps = vec![0u32; self.nb_nodes()];
         for g in self.nodes.iter() {
@@ -356,12 +455,37 @@ impl Network {
         }
 
         if rev_order.len() != self.nb_nodes() {
-            panic!("Unable to find a valid topological sort: there must be a combinatorial loop");
+            return Err(self.find_loop_node(&visited));
         }
         rev_order.reverse();
         let order = rev_order;
 
-        self.remap(order.as_slice())
+        Ok(self.remap(order.as_slice()))
+    }
+
+    /// Find a node in a combinatorial loop, among the nodes that could not be sorted
+    fn find_loop_node(&self, visited: &[bool]) -> usize {
+        // Remove the nodes that do not depend on other unsorted nodes, leaving only loops and their fanout
+        let mut in_loop: Vec<bool> = visited.iter().map(|v| !v).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 0..self.nb_nodes() {
+                if in_loop[i] && !self.gate(i).vars().any(|v| in_loop[v as usize]) {
+                    in_loop[i] = false;
+                    changed = true;
+                }
+            }
+        }
+
+        // Follow the dependencies until a node is repeated
+        let mut i = in_loop.iter().position(|v| *v).unwrap();
+        let mut seen = vec![false; self.nb_nodes()];
+        while !seen[i] {
+            seen[i] = true;
+            i = self.gate(i).vars().find(|v| in_loop[*v as usize]).unwrap() as usize;
+        }
+        i
     }
 
     /// Check consistency of the datastructure
@@ -494,6 +618,37 @@ mod tests {
         assert_eq!(aig.nb_nodes(), 2);
     }
 
+    #[test]
+    fn test_names() {
+        let mut aig = Network::default();
+        let i0 = aig.add_input();
+        let i1 = aig.add_input();
+        aig.add(Gate::and(i0, i1));
+        let x1 = aig.add(Gate::and(i1, i0));
+        let x2 = aig.add(Gate::Buf(!x1));
+        let x3 = aig.add(Gate::and(x2, i0));
+        aig.add_output(x3);
+        aig.set_input_name(1, "b");
+        aig.set_output_name(0, "out");
+        for (i, name) in ["x", "y", "z", "t"].iter().enumerate() {
+            aig.set_node_name(i, name);
+        }
+        assert_eq!(aig.input_name(0), None);
+        assert_eq!(aig.input_name(1), Some("b"));
+        assert_eq!(aig.output_name(0), Some("out"));
+
+        // Merged nodes keep the first name, inverted copies lose theirs
+        aig.make_canonical();
+        assert_eq!(aig.nb_nodes(), 2);
+        assert_eq!(aig.node_name(0), Some("x"));
+        assert_eq!(aig.node_name(1), Some("t"));
+        aig.shuffle(1);
+        assert_eq!(aig.node_name(0), Some("x"));
+        assert_eq!(aig.node_name(1), Some("t"));
+        assert_eq!(aig.input_name(1), Some("b"));
+        assert_eq!(aig.output_name(0), Some("out"));
+    }
+
     #[test]
     fn test_topo_sort() {
         let mut aig = Network::default();
@@ -0,0 +1,432 @@
+//! Compute logic levels, arrival and required times, and critical paths of a network
+//!
+//! ```
+//! # use quaigh::Network;
+//! # let aig = Network::new();
+//! use quaigh::network::timing::{DelayParameters, TimingAnalysis};
+//!
+//! // Logic depth, with each gate counting as one level
+//! println!("Depth: {}", DelayParameters::unit().max_delay(&aig));
+//!
+//! // Full analysis with a delay model closer to VLSI designs
+//! let timing = TimingAnalysis::new(&aig, &DelayParameters::vlsi());
+//! for path in timing.critical_paths(&aig, 3) {
+//!     println!("Critical path: {:?}", path);
+//! }
+//! println!("{}", timing);
+//! ```
+//!
+//! Timing paths start at the inputs and flip-flops, and end at the outputs and at the inputs of
+//! flip-flops.
+
+use std::fmt;
+
+use crate::network::gates::{BinaryType, NaryType, TernaryType};
+use crate::{Gate, Network};
+
+/// Delay estimation parameters for timing analysis
+///
+/// Most gates have a delay. N-ary gates are extrapolated as trees of 3-input gates, buffers and
+/// flip-flops have no delay. Like [`crate::network::area::AreaParameters`], this is very
+/// inaccurate, and is meant to compare networks during logic optimization.
+#[derive(Clone, Copy, Debug)]
+pub struct DelayParameters {
+    /// Delay of And2
+    pub and: usize,
+    /// Delay of And3
+    pub and3: usize,
+    /// Delay of Xor2
+    pub xor: usize,
+    /// Delay of Xor3
+    pub xor3: usize,
+    /// Delay of Mux
+    pub mux: usize,
+    /// Delay of Maj
+    pub maj: usize,
+    /// Delay of Lut
+    pub lut: usize,
+}
+
+impl DelayParameters {
+    /// Unit delay model, where each gate counts as one logic level
+    pub fn unit() -> DelayParameters {
+        DelayParameters {
+            and: 1,
+            and3: 1,
+            xor: 1,
+            xor3: 1,
+            mux: 1,
+            maj: 1,
+            lut: 1,
+        }
+    }
+
+    /// Good default parameters for VLSI design
+    ///
+    /// Xor gates and gates with more inputs are slower. For the delay with an actual cell
+    /// library, map the network with [`crate::optim::map_cells`].
+    pub fn vlsi() -> DelayParameters {
+        DelayParameters {
+            and: 2,
+            and3: 3,
+            xor: 4,
+            xor3: 6,
+            mux: 4,
+            maj: 3,
+            lut: 4,
+        }
+    }
+
+    /// Extrapolate the delay of a n-ary gate from its 2- and 3-input versions
+    fn nary(n: usize, d2: usize, d3: usize) -> usize {
+        match n {
+            0 | 1 => 0,
+            2 => d2,
+            3 => d3,
+            _ => d3 + DelayParameters::nary(n.div_ceil(3), d2, d3),
+        }
+    }
+
+    /// Compute the delay of a gate
+    pub fn gate_delay(&self, g: &Gate) -> usize {
+        use Gate::*;
+        match g {
+            Binary(_, BinaryType::And) => self.and,
+            Ternary(_, TernaryType::And) => self.and3,
+            Binary(_, BinaryType::Xor) => self.xor,
+            Ternary(_, TernaryType::Xor) => self.xor3,
+            Nary(v, tp) => match tp {
+                NaryType::And | NaryType::Or | NaryType::Nand | NaryType::Nor => {
+                    DelayParameters::nary(v.len(), self.and, self.and3)
+                }
+                NaryType::Xor | NaryType::Xnor => {
+                    DelayParameters::nary(v.len(), self.xor, self.xor3)
+                }
+            },
+            Ternary(_, TernaryType::Mux) => self.mux,
+            Ternary(_, TernaryType::Maj) => self.maj,
+            Lut(_) => self.lut,
+            Buf(_) | Dff(_) => 0,
+        }
+    }
+
+    /// Compute the largest arrival time at the outputs and flip-flops of a network
+    pub fn max_delay(&self, aig: &Network) -> usize {
+        TimingAnalysis::new(aig, self).max_delay()
+    }
+}
+
+impl fmt::Display for DelayParameters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Delays:")?;
+        writeln!(f, "  And2: {}", self.and)?;
+        writeln!(f, "  And3: {}", self.and3)?;
+        writeln!(f, "  Xor2: {}", self.xor)?;
+        writeln!(f, "  Xor3: {}", self.xor3)?;
+        writeln!(f, "  Mux: {}", self.mux)?;
+        writeln!(f, "  Maj: {}", self.maj)?;
+        writeln!(f, "  Lut: {}", self.lut)?;
+        fmt::Result::Ok(())
+    }
+}
+
+/// Arrival and required times of each node of a network
+///
+/// The required time of the endpoints is the largest arrival time, so that the critical nodes
+/// have zero slack. Nodes that reach no endpoint have no required time.
+#[derive(Clone, Debug)]
+pub struct TimingAnalysis {
+    /// Delay of each node
+    delay: Vec<usize>,
+    /// Arrival time at the output of each node
+    arrival: Vec<usize>,
+    /// Required time at the output of each node, if it reaches an endpoint
+    required: Vec<Option<usize>>,
+    /// Largest arrival time at an endpoint
+    max_delay: usize,
+}
+
+/// Combinatorial nodes of the network in topological order
+fn topo_order(aig: &Network) -> Vec<usize> {
+    let order: Vec<usize> = if aig.is_topo_sorted() {
+        (0..aig.nb_nodes()).collect()
+    } else {
+        let mut sorted = aig.clone();
+        let translation = sorted.topo_sort();
+        let mut order: Vec<usize> = (0..aig.nb_nodes()).collect();
+        order.sort_by_key(|i| translation[*i].var());
+        order
+    };
+    order
+        .into_iter()
+        .filter(|i| aig.gate(*i).is_comb())
+        .collect()
+}
+
+impl TimingAnalysis {
+    /// Run the timing analysis of a network with the given delay model
+    pub fn new(aig: &Network, params: &DelayParameters) -> TimingAnalysis {
+        let order = topo_order(aig);
+        let delay: Vec<usize> = (0..aig.nb_nodes())
+            .map(|i| params.gate_delay(aig.gate(i)))
+            .collect();
+
+        let mut arrival = vec![0; aig.nb_nodes()];
+        for &i in &order {
+            let latest = aig.gate(i).vars().map(|v| arrival[v as usize]).max();
+            arrival[i] = latest.unwrap_or(0) + delay[i];
+        }
+
+        let endpoints = TimingAnalysis::endpoints(aig);
+        let max_delay = endpoints.iter().map(|v| arrival[*v]).max().unwrap_or(0);
+
+        let mut required = vec![None; aig.nb_nodes()];
+        for &v in &endpoints {
+            required[v] = Some(max_delay);
+        }
+        for &i in order.iter().rev() {
+            let Some(req) = required[i] else {
+                continue;
+            };
+            let req = req - delay[i];
+            for v in aig.gate(i).vars() {
+                let r = &mut required[v as usize];
+                *r = Some(r.map_or(req, |r| r.min(req)));
+            }
+        }
+
+        TimingAnalysis {
+            delay,
+            arrival,
+            required,
+            max_delay,
+        }
+    }
+
+    /// Nodes whose value is used by an output or a flip-flop
+    fn endpoints(aig: &Network) -> Vec<usize> {
+        let mut ret = Vec::new();
+        for i in 0..aig.nb_outputs() {
+            let s = aig.output(i);
+            if s.is_var() {
+                ret.push(s.var() as usize);
+            }
+        }
+        for i in 0..aig.nb_nodes() {
+            if !aig.gate(i).is_comb() {
+                ret.extend(aig.gate(i).vars().map(|v| v as usize));
+            }
+        }
+        ret.sort();
+        ret.dedup();
+        ret
+    }
+
+    /// Largest arrival time at an endpoint; this is the logic depth with the unit delay model
+    pub fn max_delay(&self) -> usize {
+        self.max_delay
+    }
+
+    /// Arrival time at the output of a node; this is its level with the unit delay model
+    pub fn arrival(&self, i: usize) -> usize {
+        self.arrival[i]
+    }
+
+    /// Required time at the output of a node, or None if it reaches no endpoint
+    pub fn required(&self, i: usize) -> Option<usize> {
+        self.required[i]
+    }
+
+    /// Slack of a node: how much its arrival time can increase without increasing the delay
+    ///
+    /// Returns None if the node reaches no endpoint.
+    pub fn slack(&self, i: usize) -> Option<usize> {
+        self.required[i].map(|r| r - self.arrival[i])
+    }
+
+    /// Return whether a node is on a critical path
+    pub fn is_critical(&self, i: usize) -> bool {
+        self.delay[i] != 0 && self.slack(i) == Some(0)
+    }
+
+    /// Find up to `max_paths` critical paths, as lists of node indices from start to endpoint
+    ///
+    /// Each path ends at a different endpoint with the largest arrival time, and follows the
+    /// latest input of each node. Buffers are skipped.
+    pub fn critical_paths(&self, aig: &Network, max_paths: usize) -> Vec<Vec<usize>> {
+        let mut ret = Vec::new();
+        if self.max_delay == 0 {
+            return ret;
+        }
+        for end in TimingAnalysis::endpoints(aig) {
+            if ret.len() >= max_paths {
+                break;
+            }
+            if self.arrival[end] != self.max_delay {
+                continue;
+            }
+            let mut path = Vec::new();
+            let mut cur = Some(end);
+            while let Some(i) = cur {
+                if !aig.gate(i).is_comb() {
+                    break;
+                }
+                if self.delay[i] != 0 {
+                    path.push(i);
+                }
+                let target = self.arrival[i] - self.delay[i];
+                cur = aig
+                    .gate(i)
+                    .vars()
+                    .map(|v| v as usize)
+                    .find(|v| self.arrival[*v] == target && aig.gate(*v).is_comb());
+            }
+            path.reverse();
+            ret.push(path);
+        }
+        ret
+    }
+
+    /// Number of gates for each slack value, from zero to the largest slack
+    ///
+    /// Buffers, flip-flops and nodes that reach no endpoint are not counted.
+    pub fn slack_histogram(&self) -> Vec<usize> {
+        let mut ret = Vec::new();
+        for i in 0..self.delay.len() {
+            if self.delay[i] == 0 {
+                continue;
+            }
+            let Some(s) = self.slack(i) else {
+                continue;
+            };
+            if ret.len() <= s {
+                ret.resize(s + 1, 0);
+            }
+            ret[s] += 1;
+        }
+        ret
+    }
+}
+
+impl fmt::Display for TimingAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Timing:")?;
+        writeln!(f, "  Delay: {}", self.max_delay)?;
+        let histogram = self.slack_histogram();
+        if !histogram.is_empty() {
+            writeln!(f, "  Slack:")?;
+            for (i, nb) in histogram.iter().enumerate() {
+                if *nb != 0 {
+                    writeln!(f, "      {}: {}", i, nb)?;
+                }
+            }
+        }
+        fmt::Result::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DelayParameters, TimingAnalysis};
+    use crate::network::generators::adder;
+    use crate::{Gate, Network, Signal};
+
+    #[test]
+    fn test_chain() {
+        let mut aig = Network::new();
+        let inputs: Vec<Signal> = (0..4).map(|_| aig.add_input()).collect();
+        let x0 = aig.add(Gate::and(inputs[0], inputs[1]));
+        let x1 = aig.add(Gate::xor(x0, inputs[2]));
+        let x2 = aig.add(Gate::and(x1, inputs[3]));
+        let x3 = aig.add(Gate::and(inputs[2], inputs[3]));
+        aig.add_output(x2);
+        aig.add_output(x3);
+
+        let t = TimingAnalysis::new(&aig, &DelayParameters::unit());
+        assert_eq!(t.max_delay(), 3);
+        assert_eq!(t.arrival(2), 3);
+        assert_eq!(t.slack(3), Some(2));
+        assert!(t.is_critical(0));
+        assert!(!t.is_critical(3));
+        assert_eq!(t.critical_paths(&aig, 10), vec![vec![0, 1, 2]]);
+        assert_eq!(t.slack_histogram(), vec![3, 0, 1]);
+
+        let t = TimingAnalysis::new(&aig, &DelayParameters::vlsi());
+        assert_eq!(t.max_delay(), 8);
+        assert_eq!(t.slack(3), Some(6));
+    }
+
+    #[test]
+    fn test_dangling() {
+        // Logic that reaches no endpoint has no required time, even if it is deeper
+        let mut aig = Network::new();
+        let i0 = aig.add_input();
+        let i1 = aig.add_input();
+        let x0 = aig.add(Gate::and(i0, i1));
+        let x1 = aig.add(Gate::xor(x0, i1));
+        aig.add(Gate::and(x1, i0));
+        aig.add_output(x0);
+
+        let t = TimingAnalysis::new(&aig, &DelayParameters::vlsi());
+        assert_eq!(t.max_delay(), 2);
+        assert_eq!(t.arrival(2), 8);
+        assert_eq!(t.required(0), Some(2));
+        assert_eq!(t.required(1), None);
+        assert_eq!(t.slack(2), None);
+        assert!(t.is_critical(0));
+        assert!(!t.is_critical(2));
+        assert_eq!(t.slack_histogram(), vec![1]);
+    }
+
+    #[test]
+    fn test_nary() {
+        let p = DelayParameters::vlsi();
+        let v: Vec<Signal> = (0..9).map(Signal::from_input).collect();
+        assert_eq!(p.gate_delay(&Gate::andn(&v[..2])), 2);
+        assert_eq!(p.gate_delay(&Gate::andn(&v[..4])), 5);
+        assert_eq!(p.gate_delay(&Gate::andn(&v)), 6);
+        assert_eq!(p.gate_delay(&Gate::xorn(&v)), 12);
+    }
+
+    #[test]
+    fn test_sequential() {
+        // Paths start and end at flip-flops
+        let mut aig = Network::new();
+        let i0 = aig.add_input();
+        let d = Signal::from_var(0);
+        aig.add(Gate::dff(
+            Signal::from_var(2),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        let x = aig.add(Gate::and(d, i0));
+        aig.add(Gate::xor(x, d));
+        aig.add_output(d);
+        let t = TimingAnalysis::new(&aig, &DelayParameters::unit());
+        assert_eq!(t.max_delay(), 2);
+        assert_eq!(t.critical_paths(&aig, 10), vec![vec![1, 2]]);
+    }
+
+    #[test]
+    fn test_unsorted() {
+        let mut aig = Network::new();
+        let i0 = aig.add_input();
+        let i1 = aig.add_input();
+        aig.add(Gate::and(Signal::from_var(1), i0));
+        aig.add(Gate::and(i0, i1));
+        aig.add_output(Signal::from_var(0));
+        let t = TimingAnalysis::new(&aig, &DelayParameters::unit());
+        assert_eq!(t.max_delay(), 2);
+        assert_eq!(t.critical_paths(&aig, 10), vec![vec![1, 0]]);
+    }
+
+    #[test]
+    fn test_adder() {
+        let aig = adder::ripple_carry(8);
+        let t = TimingAnalysis::new(&aig, &DelayParameters::unit());
+        assert!(t.max_delay() >= 8);
+        let paths = t.critical_paths(&aig, 1);
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].len(), t.max_delay());
+    }
+}
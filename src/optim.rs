@@ -1,7 +1,39 @@
 //! Optimization of logic networks
 
+mod balance;
+mod convert;
+mod decompose_luts;
+mod dff_controls;
+mod dont_cares;
+mod esop;
+mod fraig;
 mod infer_gates;
+mod map_cells;
+mod map_luts;
+mod remove_redundancies;
+mod resub;
+mod retime;
+mod rewrite;
+mod script;
+mod sequential_sweep;
 mod share_logic;
+mod sop;
 
-pub use infer_gates::{infer_dffe, infer_xor_mux};
+pub use balance::balance;
+pub use convert::{is_aig, is_mig, is_xag, to_aig, to_mig, to_xag};
+pub use decompose_luts::decompose_luts;
+pub use dff_controls::{infer_dffe, lower_dffe};
+pub use dont_cares::optimize_dont_cares;
+pub use esop::{minimize_esop, Esop};
+pub use fraig::fraig;
+pub use infer_gates::infer_xor_mux;
+pub use map_cells::map_cells;
+pub use map_luts::map_luts;
+pub use remove_redundancies::remove_redundancies;
+pub use resub::resub;
+pub use retime::{retime_min_area, retime_min_period};
+pub use rewrite::rewrite;
+pub use script::{Pass, Script};
+pub use sequential_sweep::sequential_sweep;
 pub use share_logic::share_logic;
+pub use sop::{minimize_sop, Cover, Cube};
@@ -0,0 +1,223 @@
+//! Balancing of And and Xor trees, to reduce the logic depth
+//!
+//! And and Xor gates are flattened, then rebuilt as trees of 2-input gates where the inputs
+//! that arrive first are combined first.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use fxhash::FxHashMap;
+
+use super::share_logic::flatten_nary;
+use crate::{Gate, Network, Signal};
+
+/// Rebuild N-input gates as trees of 2-input gates with minimal depth
+struct Balancer {
+    /// Network being rebuilt
+    aig: Network,
+    /// Logic depth of each node
+    arrival: Vec<u32>,
+    /// Existing 2-input gates, to share them between trees
+    built: FxHashMap<(Signal, Signal, bool), Signal>,
+    /// Whether to reuse existing gates when it does not increase the depth
+    sharing: bool,
+}
+
+impl Balancer {
+    /// Logic depth of a signal
+    fn arrival(&self, s: Signal) -> u32 {
+        if s.is_var() {
+            self.arrival[s.var() as usize]
+        } else {
+            0
+        }
+    }
+
+    /// Add a 2-input gate, or reuse an existing one
+    fn add_binary(&mut self, a: Signal, b: Signal, is_xor: bool) -> Signal {
+        let key = (a.min(b), a.max(b), is_xor);
+        if let Some(s) = self.built.get(&key) {
+            return *s;
+        }
+        let g = if is_xor {
+            Gate::xor(key.0, key.1)
+        } else {
+            Gate::and(key.0, key.1)
+        };
+        let s = self.aig.add(g);
+        self.arrival.push(1 + self.arrival(a).max(self.arrival(b)));
+        self.built.insert(key, s);
+        s
+    }
+
+    /// Pick two signals to combine among those that are ready
+    ///
+    /// The two earliest signals are combined, unless sharing is enabled and a pair that already
+    /// exists can be used without increasing the depth.
+    fn pick_pair(
+        &self,
+        heap: &mut BinaryHeap<Reverse<(u32, Signal)>>,
+        is_xor: bool,
+    ) -> (Signal, Signal) {
+        let Reverse((_, a)) = heap.pop().unwrap();
+        let Reverse((arr_b, b)) = heap.pop().unwrap();
+        if !self.sharing {
+            return (a, b);
+        }
+        let mut ready = vec![a, b];
+        while heap.peek().is_some_and(|Reverse((arr, _))| *arr <= arr_b) {
+            ready.push(heap.pop().unwrap().0 .1);
+        }
+        let mut ret = (0, 1);
+        'search: for i in 0..ready.len() {
+            for j in i + 1..ready.len() {
+                let (x, y) = (ready[i], ready[j]);
+                if self.built.contains_key(&(x.min(y), x.max(y), is_xor)) {
+                    ret = (i, j);
+                    break 'search;
+                }
+            }
+        }
+        for (k, s) in ready.iter().enumerate() {
+            if k != ret.0 && k != ret.1 {
+                heap.push(Reverse((self.arrival(*s), *s)));
+            }
+        }
+        (ready[ret.0], ready[ret.1])
+    }
+
+    /// Build a tree of 2-input gates, combining the earliest signals first
+    fn build_tree(&mut self, v: &[Signal], is_xor: bool) -> Signal {
+        let mut heap: BinaryHeap<Reverse<(u32, Signal)>> =
+            v.iter().map(|s| Reverse((self.arrival(*s), *s))).collect();
+        while heap.len() > 1 {
+            let (a, b) = self.pick_pair(&mut heap, is_xor);
+            let s = self.add_binary(a, b, is_xor);
+            heap.push(Reverse((self.arrival(s), s)));
+        }
+        heap.pop().unwrap().0 .1
+    }
+}
+
+/// Balance And and Xor gates to minimize the logic depth
+///
+/// And and Xor gates are flattened, then rebuilt as trees of 2-input gates, combining the inputs
+/// that arrive first. With sharing enabled, existing 2-input gates are reused whenever this does
+/// not increase the depth, trading off some depth optimization for area.
+pub fn balance(aig: &mut Network, flattening_limit: usize, sharing: bool) {
+    let mut net = flatten_nary(aig, flattening_limit);
+    if !net.is_topo_sorted() {
+        net.topo_sort();
+    }
+    let nb_nodes = net.nb_nodes();
+    let mut b = Balancer {
+        aig: net,
+        arrival: vec![0; nb_nodes],
+        built: FxHashMap::default(),
+        sharing,
+    };
+    for i in 0..nb_nodes {
+        let g = b.aig.gate(i).clone();
+        if !g.is_comb() {
+            continue;
+        }
+        if (g.is_and() || g.is_xor()) && g.dependencies().len() > 1 {
+            let root = b.build_tree(g.dependencies(), g.is_xor());
+            b.aig.replace(i, Gate::Buf(root));
+            b.arrival[i] = b.arrival(root);
+        } else {
+            let deps = g.dependencies().iter().map(|s| b.arrival(*s));
+            let delay = if matches!(g, Gate::Buf(_)) { 0 } else { 1 };
+            b.arrival[i] = deps.max().unwrap_or(0) + delay;
+        }
+    }
+    let mut net = b.aig;
+    net.topo_sort();
+    net.make_canonical();
+    net.cleanup();
+    *aig = net;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::balance;
+    use crate::equiv::check_equivalence_bounded;
+    use crate::network::generators::adder;
+    use crate::{Gate, Network, Signal};
+
+    /// Logic depth of a network, counting each gate as one level
+    fn depth(aig: &Network) -> usize {
+        let mut d = vec![0; aig.nb_nodes()];
+        for i in 0..aig.nb_nodes() {
+            if aig.gate(i).is_comb() {
+                d[i] = 1 + aig.gate(i).vars().map(|v| d[v as usize]).max().unwrap_or(0);
+            }
+        }
+        d.into_iter().max().unwrap_or(0)
+    }
+
+    #[test]
+    fn test_and_chain() {
+        let mut aig = Network::new();
+        let inputs: Vec<Signal> = (0..16).map(|_| aig.add_input()).collect();
+        let mut x = inputs[0];
+        for i in &inputs[1..] {
+            x = aig.and(x, *i);
+        }
+        aig.add_output(x);
+        let mut balanced = aig.clone();
+        balance(&mut balanced, 64, false);
+        assert_eq!(balanced.nb_nodes(), 15);
+        assert_eq!(depth(&balanced), 4);
+        check_equivalence_bounded(&aig, &balanced, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_arrival_times() {
+        // A late input should be combined last
+        let mut aig = Network::new();
+        let inputs: Vec<Signal> = (0..8).map(|_| aig.add_input()).collect();
+        let mut late = inputs[0];
+        for i in &inputs[1..4] {
+            late = aig.xor(late, *i);
+        }
+        let mut x = late;
+        for i in &inputs[4..] {
+            x = aig.and(x, !*i);
+        }
+        aig.add_output(x);
+        let mut balanced = aig.clone();
+        balance(&mut balanced, 64, false);
+        assert_eq!(depth(&balanced), 3);
+        check_equivalence_bounded(&aig, &balanced, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_sharing() {
+        let mut aig = Network::new();
+        let inputs: Vec<Signal> = (0..4).map(|_| aig.add_input()).collect();
+        let x1 = aig.add(Gate::andn(&inputs));
+        let x2 = aig.add(Gate::andn(&inputs[0..3]));
+        aig.add_output(x1);
+        aig.add_output(x2);
+        let mut shared = aig.clone();
+        balance(&mut shared, 64, true);
+        assert_eq!(shared.nb_nodes(), 4);
+        assert_eq!(depth(&shared), 2);
+        check_equivalence_bounded(&aig, &shared, 1, false).unwrap();
+        let mut unshared = aig.clone();
+        balance(&mut unshared, 64, false);
+        assert_eq!(unshared.nb_nodes(), 5);
+        check_equivalence_bounded(&aig, &unshared, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_adder() {
+        let aig = adder::ripple_carry(8);
+        for sharing in [false, true] {
+            let mut balanced = aig.clone();
+            balance(&mut balanced, 64, sharing);
+            check_equivalence_bounded(&aig, &balanced, 1, false).unwrap();
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! Conversion to restricted representations: And-Inverter graphs, Majority-Inverter graphs and
+//! Xor-And graphs
+//!
+//! Every gate is lowered to the 2-input And, 3-input Maj or 2-input And and Xor gates of the
+//! representation. Flip-flops are lowered to flip-flops without enable or reset.
+
+use fxhash::FxHashMap;
+use volute::Lut;
+
+use crate::network::{BinaryType, NaryType, TernaryType};
+use crate::{Gate, Network, Signal};
+
+/// Restricted set of gates to lower to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Basis {
+    /// 2-input And
+    Aig,
+    /// 3-input Maj
+    Mig,
+    /// 2-input And and Xor
+    Xag,
+}
+
+/// Lower the gates of a network to a restricted representation
+struct Lowering {
+    aig: Network,
+    basis: Basis,
+}
+
+impl Lowering {
+    fn and(&mut self, a: Signal, b: Signal) -> Signal {
+        self.aig.add_canonical(Gate::and(a, b))
+    }
+
+    fn or(&mut self, a: Signal, b: Signal) -> Signal {
+        !self.and(!a, !b)
+    }
+
+    fn xor(&mut self, a: Signal, b: Signal) -> Signal {
+        if self.basis == Basis::Xag {
+            self.aig.add_canonical(Gate::xor(a, b))
+        } else {
+            let x = self.and(a, !b);
+            let y = self.and(!a, b);
+            self.or(x, y)
+        }
+    }
+
+    fn mux(&mut self, s: Signal, a: Signal, b: Signal) -> Signal {
+        if self.basis == Basis::Xag {
+            // Only one And gate
+            let d = self.xor(a, b);
+            let x = self.and(s, d);
+            self.xor(b, x)
+        } else {
+            let x = self.and(s, a);
+            let y = self.and(!s, b);
+            self.or(x, y)
+        }
+    }
+
+    fn maj(&mut self, a: Signal, b: Signal, c: Signal) -> Signal {
+        match self.basis {
+            Basis::Mig => self.aig.add_canonical(Gate::maj(a, b, c)),
+            Basis::Aig => {
+                let x = self.and(a, b);
+                let y = self.or(a, b);
+                let z = self.and(c, y);
+                self.or(x, z)
+            }
+            Basis::Xag => {
+                // Only one And gate
+                let x = self.xor(a, b);
+                let y = self.xor(a, c);
+                let z = self.and(x, y);
+                self.xor(a, z)
+            }
+        }
+    }
+
+    /// Balanced tree of And gates
+    fn andn(&mut self, v: &[Signal]) -> Signal {
+        match v.len() {
+            0 => Signal::one(),
+            1 => v[0],
+            _ => {
+                let a = self.andn(&v[..v.len() / 2]);
+                let b = self.andn(&v[v.len() / 2..]);
+                self.and(a, b)
+            }
+        }
+    }
+
+    /// Balanced tree of Xor gates
+    fn xorn(&mut self, v: &[Signal]) -> Signal {
+        match v.len() {
+            0 => Signal::zero(),
+            1 => v[0],
+            _ => {
+                let a = self.xorn(&v[..v.len() / 2]);
+                let b = self.xorn(&v[v.len() / 2..]);
+                self.xor(a, b)
+            }
+        }
+    }
+
+    /// Shannon decomposition of a Lut, with Xor when the cofactors are complementary
+    fn lut(&mut self, lut: &Lut, v: &[Signal], cache: &mut FxHashMap<Lut, Signal>) -> Signal {
+        if let Some(s) = cache.get(lut) {
+            return *s;
+        }
+        let var = (0..lut.num_vars()).rev().find(|i| {
+            let (c0, c1) = lut.cofactors(*i);
+            c0 != c1
+        });
+        let ret = match var {
+            None => Signal::from(lut.value(0)),
+            Some(i) => {
+                let (c0, c1) = lut.cofactors(i);
+                if c0 == !&c1 {
+                    let s = self.lut(&c0, v, cache);
+                    self.xor(v[i], s)
+                } else {
+                    let s0 = self.lut(&c0, v, cache);
+                    let s1 = self.lut(&c1, v, cache);
+                    self.mux(v[i], s1, s0)
+                }
+            }
+        };
+        cache.insert(lut.clone(), ret);
+        ret
+    }
+
+    /// Lower a combinatorial gate
+    fn gate(&mut self, g: &Gate) -> Signal {
+        use Gate::*;
+        match g {
+            Binary([a, b], BinaryType::And) => self.and(*a, *b),
+            Binary([a, b], BinaryType::Xor) => self.xor(*a, *b),
+            Ternary(v, TernaryType::And) => self.andn(v),
+            Ternary(v, TernaryType::Xor) => self.xorn(v),
+            Ternary([s, a, b], TernaryType::Mux) => self.mux(*s, *a, *b),
+            Ternary([a, b, c], TernaryType::Maj) => self.maj(*a, *b, *c),
+            Nary(v, tp) => {
+                let inv: Vec<Signal> = v.iter().map(|s| !s).collect();
+                match tp {
+                    NaryType::And => self.andn(v),
+                    NaryType::Or => !self.andn(&inv),
+                    NaryType::Nand => !self.andn(v),
+                    NaryType::Nor => self.andn(&inv),
+                    NaryType::Xor => self.xorn(v),
+                    NaryType::Xnor => !self.xorn(v),
+                }
+            }
+            Buf(s) => *s,
+            Lut(lut) => self.lut(&lut.lut, &lut.inputs, &mut FxHashMap::default()),
+            Dff(_) => panic!("Flip-flops are not combinatorial"),
+        }
+    }
+
+    fn run(&mut self) {
+        for i in 0..self.aig.nb_nodes() {
+            let g = self.aig.gate(i).clone();
+            if let Gate::Dff([d, en, res]) = g {
+                let q = self.aig.node(i);
+                let x = self.mux(en, d, q);
+                let d = self.and(!res, x);
+                self.aig
+                    .replace(i, Gate::dff(d, Signal::one(), Signal::zero()));
+            } else {
+                let s = self.gate(&g);
+                self.aig.replace(i, Gate::Buf(s));
+            }
+        }
+    }
+}
+
+/// Lower a network to a restricted representation
+fn lower(aig: &mut Network, basis: Basis) {
+    let mut l = Lowering {
+        aig: aig.clone(),
+        basis,
+    };
+    l.run();
+    let mut net = l.aig;
+    net.topo_sort();
+    net.make_canonical();
+    net.cleanup();
+    if basis == Basis::Mig {
+        // Canonical form uses And gates instead of Maj gates with a constant input
+        for i in 0..net.nb_nodes() {
+            if let Gate::Binary([a, b], BinaryType::And) = *net.gate(i) {
+                net.replace(i, Gate::maj(Signal::zero(), a, b));
+            }
+        }
+    }
+    *aig = net;
+}
+
+/// Returns whether all gates of a network belong to a restricted representation
+fn is_in_basis(aig: &Network, basis: Basis) -> bool {
+    (0..aig.nb_nodes()).all(|i| match aig.gate(i) {
+        Gate::Dff([_, en, res]) => *en == Signal::one() && *res == Signal::zero(),
+        Gate::Binary(_, BinaryType::And) => basis != Basis::Mig,
+        Gate::Binary(_, BinaryType::Xor) => basis == Basis::Xag,
+        Gate::Ternary(_, TernaryType::Maj) => basis == Basis::Mig,
+        _ => false,
+    })
+}
+
+/// Convert a network to an And-Inverter graph, with only 2-input And gates and flip-flops
+/// without enable or reset
+pub fn to_aig(aig: &mut Network) {
+    lower(aig, Basis::Aig);
+}
+
+/// Convert a network to a Majority-Inverter graph, with only Maj gates and flip-flops
+/// without enable or reset
+///
+/// And gates are represented as Maj gates with a constant input.
+pub fn to_mig(aig: &mut Network) {
+    lower(aig, Basis::Mig);
+}
+
+/// Convert a network to a Xor-And graph, with only 2-input And and Xor gates and flip-flops
+/// without enable or reset
+pub fn to_xag(aig: &mut Network) {
+    lower(aig, Basis::Xag);
+}
+
+/// Returns whether the network is an And-Inverter graph, as obtained with [`to_aig`]
+pub fn is_aig(aig: &Network) -> bool {
+    is_in_basis(aig, Basis::Aig)
+}
+
+/// Returns whether the network is a Majority-Inverter graph, as obtained with [`to_mig`]
+pub fn is_mig(aig: &Network) -> bool {
+    is_in_basis(aig, Basis::Mig)
+}
+
+/// Returns whether the network is a Xor-And graph, as obtained with [`to_xag`]
+pub fn is_xag(aig: &Network) -> bool {
+    is_in_basis(aig, Basis::Xag)
+}
+
+#[cfg(test)]
+mod tests {
+    use volute::Lut;
+
+    use super::{is_aig, is_mig, is_xag, to_aig, to_mig, to_xag};
+    use crate::equiv::check_equivalence_bounded;
+    use crate::network::NaryType;
+    use crate::{Gate, Network, Signal};
+
+    /// Network with all types of gates
+    fn example() -> Network {
+        let mut aig = Network::new();
+        let i: Vec<Signal> = (0..5).map(|_| aig.add_input()).collect();
+        let mut outputs = vec![
+            aig.add(Gate::and(i[0], !i[1])),
+            aig.add(Gate::xor(i[1], i[2])),
+            aig.add(Gate::and3(i[0], i[1], !i[2])),
+            aig.add(Gate::xor3(i[0], i[1], i[2])),
+            aig.add(Gate::mux(i[0], i[1], !i[2])),
+            aig.add(Gate::maj(i[0], !i[1], i[2])),
+            aig.add(Gate::Buf(!i[3])),
+            aig.add(Gate::lut(&i, Lut::threshold(5, 2))),
+            aig.add(Gate::lut(&i[..3], Lut::parity(3))),
+        ];
+        for tp in [
+            NaryType::And,
+            NaryType::Or,
+            NaryType::Nand,
+            NaryType::Nor,
+            NaryType::Xor,
+            NaryType::Xnor,
+        ] {
+            outputs.push(aig.add(Gate::Nary(i[..4].into(), tp)));
+        }
+        outputs.push(aig.add(Gate::dff(i[0], i[1], i[2])));
+        outputs.push(aig.add(Gate::dff(outputs[1], Signal::one(), i[4])));
+        for o in outputs {
+            aig.add_output(o);
+        }
+        aig
+    }
+
+    #[test]
+    fn test_aig() {
+        let aig = example();
+        assert!(!is_aig(&aig));
+        let mut conv = aig.clone();
+        to_aig(&mut conv);
+        assert!(is_aig(&conv));
+        assert!(!is_mig(&conv));
+        check_equivalence_bounded(&aig, &conv, 3, false).unwrap();
+    }
+
+    #[test]
+    fn test_mig() {
+        let aig = example();
+        assert!(!is_mig(&aig));
+        let mut conv = aig.clone();
+        to_mig(&mut conv);
+        assert!(is_mig(&conv));
+        assert!(!is_aig(&conv));
+        check_equivalence_bounded(&aig, &conv, 3, false).unwrap();
+    }
+
+    #[test]
+    fn test_xag() {
+        let aig = example();
+        assert!(!is_xag(&aig));
+        let mut conv = aig.clone();
+        to_xag(&mut conv);
+        assert!(is_xag(&conv));
+        check_equivalence_bounded(&aig, &conv, 3, false).unwrap();
+
+        // Maj and Mux use a single And gate
+        let mut aig = Network::new();
+        let i: Vec<Signal> = (0..3).map(|_| aig.add_input()).collect();
+        let x = aig.add(Gate::maj(i[0], i[1], i[2]));
+        aig.add_output(x);
+        to_xag(&mut aig);
+        let nb_ands = (0..aig.nb_nodes())
+            .filter(|j| aig.gate(*j).is_and())
+            .count();
+        assert_eq!(nb_ands, 1);
+    }
+}
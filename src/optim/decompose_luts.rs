@@ -0,0 +1,326 @@
+//! Decomposition of Lut gates into And, Xor, Mux and Maj gates
+//!
+//! Simple functions are recognized directly, up to input and output negations and input
+//! permutations. Other functions are decomposed recursively with Shannon or Davio expansions, or
+//! implemented as a sum of products, whichever has the smallest area.
+
+use fxhash::FxHashMap;
+use itertools::Itertools;
+use volute::Lut;
+
+use super::sop::Cover;
+use crate::network::area::AreaParameters;
+use crate::{Gate, Network, Signal};
+
+/// Copy a single-output network into another, connecting its inputs to the given signals
+fn instantiate(aig: &mut Network, sub: &Network, inputs: &[Signal]) -> Signal {
+    let mut built: Vec<Signal> = Vec::new();
+    let translate = |s: &Signal, built: &[Signal]| -> Signal {
+        if s.is_input() {
+            inputs[s.input() as usize] ^ s.is_inverted()
+        } else if s.is_var() {
+            built[s.var() as usize] ^ s.is_inverted()
+        } else {
+            *s
+        }
+    };
+    for i in 0..sub.nb_nodes() {
+        let g = sub.gate(i).remap(|s| translate(s, &built));
+        built.push(aig.add_canonical(g));
+    }
+    translate(&sub.output(0), &built)
+}
+
+/// Variables that a function depends on
+fn support(lut: &Lut) -> Vec<usize> {
+    (0..lut.num_vars())
+        .filter(|v| {
+            let (c0, c1) = lut.cofactors(*v);
+            c0 != c1
+        })
+        .collect()
+}
+
+/// Restrict a function to a subset of its variables, that must contain its support
+fn restrict(lut: &Lut, vars: &[usize]) -> Lut {
+    let mut ret = Lut::zero(vars.len());
+    for m in 0..ret.num_bits() {
+        let mut mask = 0;
+        for (i, v) in vars.iter().enumerate() {
+            if (m >> i) & 1 != 0 {
+                mask |= 1 << v;
+            }
+        }
+        ret.set_value(m, lut.value(mask));
+    }
+    ret
+}
+
+/// Decompose the function of a Lut into a network with one input per Lut input
+struct Decomposer<'a> {
+    nb_vars: usize,
+    params: &'a AreaParameters,
+    /// Implementations already found
+    cache: FxHashMap<Lut, Network>,
+}
+
+impl<'a> Decomposer<'a> {
+    fn new(nb_vars: usize, params: &'a AreaParameters) -> Decomposer<'a> {
+        Decomposer {
+            nb_vars,
+            params,
+            cache: FxHashMap::default(),
+        }
+    }
+
+    /// Create a network with the inputs of the Lut and a single output
+    fn network(&self, f: impl FnOnce(&mut Network, &[Signal]) -> Signal) -> Network {
+        let mut ret = Network::new();
+        ret.add_inputs(self.nb_vars);
+        let inputs: Vec<Signal> = (0..self.nb_vars).map(|i| ret.input(i)).collect();
+        let s = f(&mut ret, &inputs);
+        ret.add_output(s);
+        ret
+    }
+
+    /// Recognize a function that is a single gate, up to negations and permutations
+    fn match_gate(&self, lut: &Lut) -> Option<Network> {
+        let vars = support(lut);
+        let f = restrict(lut, &vars);
+        let k = vars.len();
+        let nb_ones = (0..f.num_bits()).filter(|m| f.value(*m)).count();
+
+        // Literals of a minterm
+        let minterm = |inputs: &[Signal], m: usize| -> Vec<Signal> {
+            vars.iter()
+                .enumerate()
+                .map(|(i, v)| inputs[*v] ^ ((m >> i) & 1 == 0))
+                .collect()
+        };
+        if k == 0 {
+            let val = f.value(0);
+            return Some(self.network(|_, _| Signal::from(val)));
+        }
+        if nb_ones == 1 {
+            let m = (0..f.num_bits()).find(|m| f.value(*m)).unwrap();
+            return Some(
+                self.network(|aig, inputs| aig.add_canonical(Gate::andn(&minterm(inputs, m)))),
+            );
+        }
+        if nb_ones == f.num_bits() - 1 {
+            let m = (0..f.num_bits()).find(|m| !f.value(*m)).unwrap();
+            return Some(
+                self.network(|aig, inputs| !aig.add_canonical(Gate::andn(&minterm(inputs, m)))),
+            );
+        }
+        let parity = Lut::parity(k);
+        if f == parity || f == !&parity {
+            let inv = f != parity;
+            return Some(self.network(|aig, inputs| {
+                let v: Vec<Signal> = vars.iter().map(|v| inputs[*v]).collect();
+                aig.add_canonical(Gate::xorn(&v)) ^ inv
+            }));
+        }
+        if k == 3 {
+            for flips in 0..8 {
+                let lit = |i: usize| {
+                    let l = Lut::nth_var(3, i);
+                    if (flips >> i) & 1 != 0 {
+                        !l
+                    } else {
+                        l
+                    }
+                };
+                let maj = (lit(0) & lit(1)) | (lit(0) & lit(2)) | (lit(1) & lit(2));
+                if f == maj || f == !&maj {
+                    let inv = f != maj;
+                    return Some(self.network(|aig, inputs| {
+                        let l: Vec<Signal> = (0..3)
+                            .map(|i| inputs[vars[i]] ^ ((flips >> i) & 1 != 0))
+                            .collect();
+                        aig.add_canonical(Gate::maj(l[0], l[1], l[2])) ^ inv
+                    }));
+                }
+                for p in (0..3).permutations(3) {
+                    let mux = (lit(p[0]) & lit(p[1])) | (!lit(p[0]) & lit(p[2]));
+                    if f == mux {
+                        return Some(self.network(|aig, inputs| {
+                            let l: Vec<Signal> = p
+                                .iter()
+                                .map(|i| inputs[vars[*i]] ^ ((flips >> i) & 1 != 0))
+                                .collect();
+                            aig.add_canonical(Gate::mux(l[0], l[1], l[2]))
+                        }));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Implement the function as a sum of products or product of sums
+    fn sop(&self, lut: &Lut) -> Network {
+        let mut on = Cover::from_lut(lut);
+        let mut off = Cover::from_lut(&!lut);
+        on.minimize();
+        off.minimize();
+        self.network(|aig, inputs| {
+            if (off.nb_cubes(), off.nb_literals()) < (on.nb_cubes(), on.nb_literals()) {
+                !off.build(aig, inputs)
+            } else {
+                on.build(aig, inputs)
+            }
+        })
+    }
+
+    /// Decompose the function with the expansion that gives the smallest subfunctions
+    fn expand(&mut self, lut: &Lut) -> Network {
+        let mut best: Option<(usize, usize, usize)> = None;
+        for v in support(lut) {
+            let (f0, f1) = lut.cofactors(v);
+            let f2 = &f0 ^ &f1;
+            let (s0, s1, s2) = (support(&f0).len(), support(&f1).len(), support(&f2).len());
+            // Shannon, positive Davio and negative Davio; a Davio expansion gives a Xor when
+            // the cofactors are complementary
+            for (cost, tp) in [(s0 + s1, 0), (s0 + s2, 1), (s1 + s2, 2)] {
+                if best.is_none_or(|(c, _, _)| cost < c) {
+                    best = Some((cost, v, tp));
+                }
+            }
+        }
+        let (_, v, tp) = best.unwrap();
+        let (f0, f1) = lut.cofactors(v);
+        let f2 = &f0 ^ &f1;
+        let (a, b) = match tp {
+            0 => (self.decompose(&f0), self.decompose(&f1)),
+            1 => (self.decompose(&f0), self.decompose(&f2)),
+            _ => (self.decompose(&f1), self.decompose(&f2)),
+        };
+        self.network(|aig, inputs| {
+            let x = inputs[v];
+            let sa = instantiate(aig, &a, inputs);
+            let sb = instantiate(aig, &b, inputs);
+            match tp {
+                0 => aig.add_canonical(Gate::mux(x, sb, sa)),
+                1 => {
+                    let t = aig.add_canonical(Gate::and(x, sb));
+                    aig.add_canonical(Gate::xor(sa, t))
+                }
+                _ => {
+                    let t = aig.add_canonical(Gate::and(!x, sb));
+                    aig.add_canonical(Gate::xor(sa, t))
+                }
+            }
+        })
+    }
+
+    /// Decompose a function, with the smallest area among the methods
+    fn decompose(&mut self, lut: &Lut) -> Network {
+        if let Some(n) = self.cache.get(lut) {
+            return n.clone();
+        }
+        let ret = if let Some(n) = self.match_gate(lut) {
+            n
+        } else {
+            let mut candidates = vec![self.expand(lut), self.sop(lut)];
+            for c in &mut candidates {
+                c.make_canonical();
+                c.cleanup();
+            }
+            candidates
+                .into_iter()
+                .min_by_key(|c| self.params.area(c))
+                .unwrap()
+        };
+        self.cache.insert(lut.clone(), ret.clone());
+        ret
+    }
+}
+
+/// Decompose Lut gates into And, Xor, Mux and Maj gates
+///
+/// Functions that are a single gate up to negations and permutations are recognized directly.
+/// Other functions are decomposed with Shannon or Davio expansions or implemented as a sum of
+/// products, to minimize the area.
+pub fn decompose_luts(aig: &mut Network, params: &AreaParameters) {
+    let mut net = aig.clone();
+    for i in 0..net.nb_nodes() {
+        if let Gate::Lut(lut) = net.gate(i) {
+            let inputs = lut.inputs.to_vec();
+            let mut d = Decomposer::new(inputs.len(), params);
+            let sub = d.decompose(&lut.lut);
+            let s = instantiate(&mut net, &sub, &inputs);
+            net.replace(i, Gate::Buf(s));
+        }
+    }
+    net.topo_sort();
+    net.make_canonical();
+    net.cleanup();
+    *aig = net;
+}
+
+#[cfg(test)]
+mod tests {
+    use volute::Lut;
+
+    use super::decompose_luts;
+    use crate::equiv::check_equivalence_bounded;
+    use crate::network::area::AreaParameters;
+    use crate::network::{NaryType, TernaryType};
+    use crate::{Gate, Network, Signal};
+
+    /// Decompose a single Lut, and return the resulting network
+    fn decompose_one(lut: Lut) -> Network {
+        let mut aig = Network::new();
+        let inputs: Vec<Signal> = (0..lut.num_vars()).map(|_| aig.add_input()).collect();
+        let x = aig.add(Gate::lut(&inputs, lut));
+        aig.add_output(x);
+        let mut dec = aig.clone();
+        decompose_luts(&mut dec, &AreaParameters::vlsi());
+        check_equivalence_bounded(&aig, &dec, 1, false).unwrap();
+        assert!((0..dec.nb_nodes()).all(|i| !matches!(dec.gate(i), Gate::Lut(_))));
+        dec
+    }
+
+    #[test]
+    fn test_simple_gates() {
+        let v = |i| Lut::nth_var(4, i);
+
+        // Or with negated inputs
+        let dec = decompose_one(!v(0) | v(2) | !v(3));
+        assert_eq!(dec.nb_nodes(), 1);
+        assert!(dec.gate(0).is_and());
+
+        // Xnor, with an unused input
+        let dec = decompose_one(!(v(0) ^ v(1) ^ v(3)));
+        assert_eq!(dec.nb_nodes(), 1);
+        assert!(dec.gate(0).is_xor());
+
+        // Mux and Maj with negated inputs
+        let dec = decompose_one((v(3) & !v(0)) | (!v(3) & v(1)));
+        assert_eq!(dec.nb_nodes(), 1);
+        assert!(matches!(dec.gate(0), Gate::Ternary(_, TernaryType::Mux)));
+        let dec = decompose_one((v(0) & !v(1)) | (v(0) & v(2)) | (!v(1) & v(2)));
+        assert_eq!(dec.nb_nodes(), 1);
+        assert!(matches!(dec.gate(0), Gate::Ternary(_, TernaryType::Maj)));
+    }
+
+    #[test]
+    fn test_expansion() {
+        // a & (b ^ c ^ d)
+        let v = |i| Lut::nth_var(4, i);
+        let dec = decompose_one(v(0) & (v(1) ^ v(2) ^ v(3)));
+        assert_eq!(dec.nb_nodes(), 2);
+        assert!((0..2)
+            .any(|i| matches!(dec.gate(i), Gate::Nary(_, NaryType::Xor)) || dec.gate(i).is_xor()));
+    }
+
+    #[test]
+    fn test_random() {
+        for n in 1..=6 {
+            for _ in 0..5 {
+                decompose_one(Lut::random(n));
+            }
+        }
+    }
+}
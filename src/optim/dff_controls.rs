@@ -0,0 +1,603 @@
+//! Inference and lowering of the enables and resets of flip-flops
+//!
+//! A flip-flop with an enable and a reset computes `!res & (en ? d : q)` on each cycle. Inference
+//! recovers this form from the logic in front of a plain flip-flop:
+//!   * a synchronous reset is an inverted input of the And gates at the top of the data input;
+//!   * an enable is a path from the data input back to the flip-flop, through Mux, And and Or gates,
+//!     that holds the value of the flip-flop when all side conditions are met.
+//!
+//! Enables that are equivalent to an existing signal, such as the enable of another group of
+//! flip-flops, are replaced by it after checking by simulation and SAT.
+
+use fxhash::FxHashMap;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use crate::equiv::find_difference;
+use crate::network::{NaryType, TernaryType};
+use crate::sim::SimpleSimulator;
+use crate::{Gate, Network, Signal};
+
+/// Number of 64-bit words simulated to find equivalent enables
+const NB_WORDS: usize = 4;
+
+/// Returns whether a signal depends combinatorially on the flip-flop at index q
+fn depends_on(aig: &Network, q: usize, s: Signal, memo: &mut FxHashMap<usize, bool>) -> bool {
+    if !s.is_var() {
+        return false;
+    }
+    let root = s.var() as usize;
+    let mut stack = vec![root];
+    while let Some(&i) = stack.last() {
+        if memo.contains_key(&i) {
+            stack.pop();
+            continue;
+        }
+        let g = aig.gate(i);
+        if i == q || !g.is_comb() {
+            memo.insert(i, i == q);
+            stack.pop();
+            continue;
+        }
+        let mut done = true;
+        let mut dep = false;
+        for v in g.vars() {
+            match memo.get(&(v as usize)) {
+                Some(b) => dep |= b,
+                None => {
+                    done = false;
+                    stack.push(v as usize);
+                }
+            }
+        }
+        if done {
+            memo.insert(i, dep);
+            stack.pop();
+        }
+    }
+    memo[&root]
+}
+
+/// Inputs of the tree of And gates at the top of a signal
+fn and_leaves(aig: &Network, s: Signal) -> Vec<Signal> {
+    let mut leaves = Vec::new();
+    let mut to_visit = vec![s];
+    while let Some(s) = to_visit.pop() {
+        if s.is_var() && !s.is_inverted() && aig.gate(s.var() as usize).is_and() {
+            to_visit.extend(aig.gate(s.var() as usize).dependencies().iter().rev());
+        } else {
+            leaves.push(s);
+        }
+    }
+    leaves
+}
+
+/// Path from the data input of a flip-flop to its output, that holds its value
+struct HoldPath {
+    /// Gates on the path from the top, with the position of the input on the path
+    gates: Vec<(usize, usize)>,
+    /// Signals that must all be one for the value to be held
+    conditions: Vec<Signal>,
+}
+
+/// Find the path that holds the value of the flip-flop at index q, if there is one
+fn hold_path(
+    aig: &Network,
+    q: usize,
+    d: Signal,
+    memo: &mut FxHashMap<usize, bool>,
+) -> Option<HoldPath> {
+    let mut path = HoldPath {
+        gates: Vec::new(),
+        conditions: Vec::new(),
+    };
+    let mut s = d;
+    while s != aig.node(q) {
+        if !s.is_var() || s.var() as usize == q {
+            return None;
+        }
+        let i = s.var() as usize;
+        let g = aig.gate(i);
+        let deps = g.dependencies();
+        let on_path: Vec<usize> = (0..deps.len())
+            .filter(|j| depends_on(aig, q, deps[*j], memo))
+            .collect();
+        if on_path.len() != 1 {
+            return None;
+        }
+        let j = on_path[0];
+        // Nand and Nor invert the value on the path
+        let out_inv = matches!(g, Gate::Nary(_, NaryType::Nand | NaryType::Nor));
+        match g {
+            Gate::Buf(_) => (),
+            Gate::Ternary([sel, _, _], TernaryType::Mux) => match j {
+                1 => path.conditions.push(*sel),
+                2 => path.conditions.push(!*sel),
+                _ => return None,
+            },
+            _ if g.is_and_like() => {
+                let inv = matches!(g, Gate::Nary(_, NaryType::Or | NaryType::Nor));
+                for (k, x) in deps.iter().enumerate() {
+                    if k != j {
+                        path.conditions.push(*x ^ inv);
+                    }
+                }
+            }
+            _ => return None,
+        }
+        path.gates.push((i, j));
+        s = deps[j] ^ s.is_inverted() ^ out_inv;
+    }
+    Some(path)
+}
+
+/// Flip-flop inference engine
+struct DffInference<'a> {
+    /// Original network
+    aig: &'a Network,
+    /// Network being built, starting with the same nodes as the original
+    ret: Network,
+    /// Simulated values of the inputs and of the nodes of the original network
+    input_values: Vec<Vec<u64>>,
+    node_values: Vec<Vec<u64>>,
+    /// Signals by simulated value, normalized so that the first bit is zero
+    signatures: FxHashMap<Vec<u64>, Signal>,
+}
+
+impl<'a> DffInference<'a> {
+    fn new(aig: &'a Network) -> DffInference<'a> {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut input_values = Vec::new();
+        let mut node_values = Vec::new();
+        for _ in 0..NB_WORDS {
+            // Flip-flops are free variables, like the inputs
+            let inputs: Vec<u64> = (0..aig.nb_inputs()).map(|_| rng.gen()).collect();
+            let nodes: Vec<u64> = (0..aig.nb_nodes()).map(|_| rng.gen()).collect();
+            let mut sim = SimpleSimulator::from_values(aig, inputs, nodes);
+            sim.run_comb();
+            input_values.push(sim.input_values);
+            node_values.push(sim.node_values);
+        }
+        let mut ret = DffInference {
+            aig,
+            ret: aig.clone(),
+            input_values,
+            node_values,
+            signatures: FxHashMap::default(),
+        };
+        let signals = (0..aig.nb_inputs())
+            .map(|i| aig.input(i))
+            .chain((0..aig.nb_nodes()).map(|i| aig.node(i)));
+        for s in std::iter::once(Signal::zero()).chain(signals) {
+            let (sig, inv) = DffInference::normalize(ret.value(s));
+            ret.signatures.entry(sig).or_insert(s ^ inv);
+        }
+        ret
+    }
+
+    /// Simulated value of a signal of the original network
+    fn value(&self, s: Signal) -> Vec<u64> {
+        let inv = if s.is_inverted() { !0 } else { 0 };
+        (0..NB_WORDS)
+            .map(|w| {
+                let v = if s.is_constant() {
+                    0
+                } else if s.is_input() {
+                    self.input_values[w][s.input() as usize]
+                } else {
+                    self.node_values[w][s.var() as usize]
+                };
+                v ^ inv
+            })
+            .collect()
+    }
+
+    /// Normalize a simulated value so that its first bit is zero
+    fn normalize(mut v: Vec<u64>) -> (Vec<u64>, bool) {
+        let inv = v[0] & 1 != 0;
+        if inv {
+            for w in &mut v {
+                *w = !*w;
+            }
+        }
+        (v, inv)
+    }
+
+    /// Choose a synchronous reset for each flip-flop, preferring resets shared by many of them
+    ///
+    /// Returns the reset and the remaining inputs of the And gates.
+    fn find_resets(&self, dffs: &[usize]) -> Vec<Option<(Signal, Vec<Signal>)>> {
+        let mut candidates = Vec::new();
+        let mut counts: FxHashMap<Signal, usize> = FxHashMap::default();
+        for &i in dffs {
+            let Gate::Dff([d, en, res]) = self.aig.gate(i) else {
+                unreachable!();
+            };
+            let mut cands = Vec::new();
+            let mut leaves = Vec::new();
+            // Resets are only inferred from an And gate with a negated input; an inverted data
+            // input on its own is not a reset
+            let is_and = d.is_var() && !d.is_inverted() && self.aig.gate(d.var() as usize).is_and();
+            if *en == Signal::one() && *res == Signal::zero() && is_and {
+                let mut memo = FxHashMap::default();
+                leaves = and_leaves(self.aig, *d);
+                if leaves.len() < 2 {
+                    leaves.clear();
+                }
+                cands = leaves
+                    .iter()
+                    .cloned()
+                    .filter(|s| s.is_inverted() && !depends_on(self.aig, i, *s, &mut memo))
+                    .collect();
+                for s in &cands {
+                    *counts.entry(*s).or_default() += 1;
+                }
+            }
+            candidates.push((cands, leaves));
+        }
+        candidates
+            .into_iter()
+            .map(|(cands, leaves)| {
+                let best = cands.iter().max_by_key(|s| counts[*s])?;
+                let rest = leaves.iter().cloned().filter(|s| s != best).collect();
+                Some((!*best, rest))
+            })
+            .collect()
+    }
+
+    /// Find an existing signal equivalent to the enable, or build it
+    fn build_enable(&mut self, conditions: &[Signal]) -> Signal {
+        // The enable is the complement of the hold condition
+        let en = match conditions.len() {
+            0 => return Signal::zero(),
+            1 => !conditions[0],
+            _ => !self.ret.add(Gate::Nary(conditions.into(), NaryType::And)),
+        };
+        if conditions
+            .iter()
+            .any(|s| s.is_var() && s.var() as usize >= self.aig.nb_nodes())
+        {
+            return en;
+        }
+        let mut hold = vec![!0u64; NB_WORDS];
+        for c in conditions {
+            for (h, v) in hold.iter_mut().zip(self.value(*c)) {
+                *h &= v;
+            }
+        }
+        let (sig, inv) = DffInference::normalize(hold);
+        // The enable has the complement of the normalized value of the hold condition
+        let inv = !inv;
+        match self.signatures.get(&sig) {
+            Some(s) => {
+                let s = *s ^ inv;
+                if s == en || find_difference(&self.ret, s, en).is_none() {
+                    s
+                } else {
+                    en
+                }
+            }
+            None => {
+                if en.is_var() && en.var() as usize >= self.aig.nb_nodes() {
+                    self.signatures.insert(sig, en ^ inv);
+                }
+                en
+            }
+        }
+    }
+
+    /// Extract the enable of a flip-flop whose data input is d
+    ///
+    /// Returns the new data input and the enable.
+    fn extract_enable(&mut self, q: usize, d: Signal) -> Option<(Signal, Signal)> {
+        let mut memo = FxHashMap::default();
+        let path = hold_path(&self.ret, q, d, &mut memo)?;
+        // Rebuild the path from the bottom, replacing the flip-flop by the blocking value of
+        // the last gate
+        let mut new_d: Option<Signal> = None;
+        for &(i, j) in path.gates.iter().rev() {
+            let g = self.ret.gate(i).clone();
+            let deps = g.dependencies();
+            let repl = match new_d {
+                Some(s) => s ^ deps[j].is_inverted(),
+                None => match &g {
+                    Gate::Ternary([_, a, b], TernaryType::Mux) => {
+                        if j == 1 {
+                            *b
+                        } else {
+                            *a
+                        }
+                    }
+                    Gate::Nary(_, NaryType::Or | NaryType::Nor) => Signal::one(),
+                    _ => Signal::zero(),
+                },
+            };
+            let new_gate = g.remap_with_ind(|s, k| if k == j { repl } else { *s });
+            new_d = Some(self.ret.add(new_gate));
+        }
+        let new_d = new_d.map(|s| s ^ d.is_inverted()).unwrap_or(Signal::zero());
+        let en = self.build_enable(&path.conditions);
+        Some((new_d, en))
+    }
+
+    fn run(&mut self) {
+        let dffs: Vec<usize> = (0..self.aig.nb_nodes())
+            .filter(|i| !self.aig.gate(*i).is_comb())
+            .collect();
+        let resets = self.find_resets(&dffs);
+        for (&i, reset) in dffs.iter().zip(resets) {
+            let Gate::Dff([mut d, en, mut res]) = self.aig.gate(i).clone() else {
+                unreachable!();
+            };
+            if let Some((r, rest)) = reset {
+                res = r;
+                d = match rest.len() {
+                    0 => Signal::one(),
+                    1 => rest[0],
+                    _ => self.ret.add(Gate::Nary(rest.into(), NaryType::And)),
+                };
+            }
+            if en == Signal::one() {
+                if let Some((new_d, new_en)) = self.extract_enable(i, d) {
+                    self.ret.replace(i, Gate::dff(new_d, new_en, res));
+                    continue;
+                }
+            }
+            self.ret.replace(i, Gate::dff(d, en, res));
+        }
+    }
+}
+
+/// Infer the enables and synchronous resets of flip-flops from the logic in front of them
+///
+/// Resets are inverted inputs of And gates on the data input, and enables are paths of Mux, And
+/// and Or gates that hold the value of the flip-flop. Enables are shared with existing
+/// equivalent signals, which are found by simulation and proved with a SAT solver.
+pub fn infer_dffe(aig: &mut Network) {
+    if !aig.is_topo_sorted() {
+        aig.topo_sort();
+    }
+    let mut inference = DffInference::new(aig);
+    inference.run();
+    let mut ret = inference.ret;
+    ret.cleanup();
+    ret.make_canonical();
+    *aig = ret;
+}
+
+/// Lower the enables and resets of flip-flops into logic
+///
+/// This is the reverse of [`infer_dffe`], for targets that do not have flip-flops with enable or
+/// reset. An enable becomes a Mux and a reset becomes an And gate on the data input.
+pub fn lower_dffe(aig: &mut Network, enable: bool, reset: bool) {
+    for i in 0..aig.nb_nodes() {
+        let Gate::Dff([mut d, mut en, mut res]) = aig.gate(i).clone() else {
+            continue;
+        };
+        let q = aig.node(i);
+        if enable && en != Signal::one() {
+            d = aig.add(Gate::mux(en, d, q));
+            en = Signal::one();
+        }
+        if reset && res != Signal::zero() {
+            d = aig.and(d, !res);
+            // The reset takes precedence over the enable
+            if en != Signal::one() {
+                en = !aig.and(!en, !res);
+            }
+            res = Signal::zero();
+        }
+        aig.replace(i, Gate::dff(d, en, res));
+    }
+    aig.topo_sort();
+    aig.make_canonical();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{infer_dffe, lower_dffe};
+    use crate::equiv::check_equivalence_bounded;
+    use crate::network::NaryType;
+    use crate::{Gate, Network, Signal};
+
+    /// Enables and resets of the flip-flops of a network
+    fn controls(aig: &Network) -> Vec<(Signal, Signal)> {
+        (0..aig.nb_nodes())
+            .filter_map(|i| match aig.gate(i) {
+                Gate::Dff([_, en, res]) => Some((*en, *res)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_mux() {
+        let mut aig = Network::new();
+        let d = aig.add_input();
+        let en = aig.add_input();
+        let q = Signal::from_var(0);
+        aig.add(Gate::dff(
+            Signal::from_var(1),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        aig.add(Gate::mux(en, d, q));
+        aig.add_output(q);
+        let mut opt = aig.clone();
+        infer_dffe(&mut opt);
+        assert_eq!(opt.nb_nodes(), 1);
+        assert_eq!(controls(&opt), vec![(en, Signal::zero())]);
+        check_equivalence_bounded(&aig, &opt, 4, false).unwrap();
+    }
+
+    #[test]
+    fn test_mux_chain() {
+        // Two enables with priority: the value is held when neither is set
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let en_a = aig.add_input();
+        let en_b = aig.add_input();
+        let q = Signal::from_var(0);
+        aig.add(Gate::dff(
+            Signal::from_var(2),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        aig.add(Gate::mux(en_b, b, q));
+        aig.add(Gate::mux(en_a, a, Signal::from_var(1)));
+        aig.add_output(q);
+        let mut opt = aig.clone();
+        infer_dffe(&mut opt);
+        let (en, res) = controls(&opt)[0];
+        assert_ne!(en, Signal::one());
+        assert_eq!(res, Signal::zero());
+        check_equivalence_bounded(&aig, &opt, 4, false).unwrap();
+    }
+
+    #[test]
+    fn test_and_or() {
+        // Enable written as (en & d) | (!en & q)
+        let mut aig = Network::new();
+        let d = aig.add_input();
+        let en = aig.add_input();
+        let q = Signal::from_var(0);
+        aig.add(Gate::dff(
+            !Signal::from_var(3),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        let x = aig.and(en, d);
+        let y = aig.and(!en, q);
+        aig.and(!x, !y);
+        aig.add_output(q);
+        let mut opt = aig.clone();
+        infer_dffe(&mut opt);
+        assert_eq!(controls(&opt), vec![(en, Signal::zero())]);
+        check_equivalence_bounded(&aig, &opt, 4, false).unwrap();
+    }
+
+    #[test]
+    fn test_nand_nor() {
+        // Nand(x, q) and Nor(x, q) toggle the flip-flop, while Nand(x, !q) holds it
+        let mut aig = Network::new();
+        let x = aig.add_input();
+        for i in 0..3 {
+            let q = Signal::from_var(2 * i);
+            let n = Signal::from_var(2 * i + 1);
+            aig.add(Gate::dff(n, Signal::one(), Signal::zero()));
+            let g = match i {
+                0 => Gate::Nary([x, q].into(), NaryType::Nand),
+                1 => Gate::Nary([x, q].into(), NaryType::Nor),
+                _ => Gate::Nary([x, !q].into(), NaryType::Nand),
+            };
+            aig.add(g);
+            aig.add_output(q);
+        }
+        let mut opt = aig.clone();
+        infer_dffe(&mut opt);
+        let c = controls(&opt);
+        assert_eq!(c[0].0, Signal::one());
+        assert_eq!(c[1].0, Signal::one());
+        assert_eq!(c[2].0, !x);
+        check_equivalence_bounded(&aig, &opt, 6, false).unwrap();
+    }
+
+    #[test]
+    fn test_inverted_data() {
+        // Inverted data inputs without an And gate are not resets
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let x = aig.and(a, b);
+        let q1 = aig.dff(!x, Signal::one(), Signal::zero());
+        let n = aig.add(Gate::Nary([a, b].into(), NaryType::Nand));
+        let q2 = aig.dff(n, Signal::one(), Signal::zero());
+        aig.add_output(q1);
+        aig.add_output(q2);
+        let mut opt = aig.clone();
+        infer_dffe(&mut opt);
+        assert_eq!(controls(&opt), vec![(Signal::one(), Signal::zero()); 2]);
+        check_equivalence_bounded(&aig, &opt, 3, false).unwrap();
+    }
+
+    #[test]
+    fn test_reset() {
+        // Reset with priority over the enable
+        let mut aig = Network::new();
+        let d = aig.add_input();
+        let en = aig.add_input();
+        let rst = aig.add_input();
+        let q = Signal::from_var(0);
+        aig.add(Gate::dff(
+            Signal::from_var(2),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        let m = aig.add(Gate::mux(en, d, q));
+        aig.and(m, !rst);
+        aig.add_output(q);
+        let mut opt = aig.clone();
+        infer_dffe(&mut opt);
+        assert_eq!(opt.nb_nodes(), 1);
+        assert_eq!(controls(&opt), vec![(en, rst)]);
+        check_equivalence_bounded(&aig, &opt, 4, false).unwrap();
+    }
+
+    #[test]
+    fn test_shared() {
+        // Two flip-flops with the same enable, written differently, and a shared reset
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let rst = aig.add_input();
+        let q1 = Signal::from_var(0);
+        let q2 = Signal::from_var(1);
+        aig.add(Gate::dff(
+            Signal::from_var(4),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        aig.add(Gate::dff(
+            Signal::from_var(6),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        // First flip-flop: enabled when b | c
+        let en = !aig.and(!b, !c);
+        let m1 = aig.add(Gate::mux(en, a, q1));
+        aig.and(m1, !rst);
+        // Second flip-flop: enabled when b or c, through a chain of Muxes
+        let m2 = aig.add(Gate::mux(c, !a, q2));
+        aig.add(Gate::mux(b, !a, m2));
+        aig.add_output(q1);
+        aig.add_output(q2);
+        let mut opt = aig.clone();
+        infer_dffe(&mut opt);
+        let ctrl = controls(&opt);
+        assert_eq!(ctrl[0].0, ctrl[1].0);
+        assert!(ctrl.contains(&(ctrl[0].0, rst)));
+        check_equivalence_bounded(&aig, &opt, 6, false).unwrap();
+    }
+
+    #[test]
+    fn test_lower() {
+        let mut aig = Network::new();
+        let d = aig.add_input();
+        let en = aig.add_input();
+        let rst = aig.add_input();
+        let q = aig.add(Gate::dff(d, en, rst));
+        aig.add_output(q);
+        for (enable, reset) in [(true, false), (false, true), (true, true)] {
+            let mut opt = aig.clone();
+            lower_dffe(&mut opt, enable, reset);
+            let (e, r) = controls(&opt)[0];
+            assert_eq!(e == Signal::one(), enable);
+            assert_eq!(r == Signal::zero(), reset);
+            check_equivalence_bounded(&aig, &opt, 6, false).unwrap();
+            infer_dffe(&mut opt);
+            check_equivalence_bounded(&aig, &opt, 6, false).unwrap();
+        }
+    }
+}
@@ -0,0 +1,545 @@
+//! Optimization using the don't cares of each node, computed in a window around it
+//!
+//! Satisfiability don't cares (input combinations of a gate that never occur) and observability
+//! don't cares (values of a gate that do not change the outputs of its window) are found by
+//! simulation. They are used to simplify Lut functions and to replace gates by cheaper ones, and
+//! each change is proved correct with a SAT solver.
+
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+use volute::Lut;
+
+use crate::equiv::find_difference;
+use crate::network::area::AreaParameters;
+use crate::network::{NaryType, TernaryType};
+use crate::sim::{SimpleSimulator, NB_RANDOM_WORDS};
+use crate::{Gate, Network, Signal};
+
+/// Number of levels of the transitive fanout in the window of a node
+const MAX_LEVELS: usize = 3;
+
+/// Maximum number of nodes in the window of a node
+const MAX_WINDOW: usize = 32;
+
+/// Maximum number of candidates refuted by SAT for a node
+const MAX_TRIES: usize = 4;
+
+/// Result of an attempt to replace a gate
+enum Replacement {
+    /// The new gate is used
+    Accepted,
+    /// The new gate differs from the old one in simulation
+    Rejected,
+    /// The new gate was refuted by the SAT solver, and the counterexample added to the simulation
+    Refuted,
+}
+
+/// Don't care engine, with the simulation values of all nodes
+struct DontCares<'a> {
+    /// Network being modified; it is kept topologically sorted
+    aig: Network,
+    params: &'a AreaParameters,
+    /// Gates using each node
+    fanouts: Vec<Vec<usize>>,
+    /// Whether each node is used by an output
+    is_output: Vec<bool>,
+    /// Simulation values of the inputs, one vector per word
+    input_values: Vec<Vec<u64>>,
+    /// Simulation values of the nodes, one vector per word
+    node_values: Vec<Vec<u64>>,
+}
+
+/// And gate with the smallest representation
+fn and_gate(v: &[Signal]) -> Gate {
+    match v.len() {
+        2 => Gate::and(v[0], v[1]),
+        3 => Gate::and3(v[0], v[1], v[2]),
+        _ => Gate::andn(v),
+    }
+}
+
+/// Or gate, to be made canonical later
+fn or_gate(a: Signal, b: Signal) -> Gate {
+    Gate::Nary([a, b].into(), NaryType::Or)
+}
+
+/// Remove the Lut variables that are not needed to obtain the function on the care set
+///
+/// Returns the variables that are kept, and the function on these variables.
+fn reduce_lut(lut: &Lut, care: &[bool]) -> (Vec<usize>, Lut) {
+    let mut f: Vec<bool> = (0..lut.num_bits()).map(|m| lut.value(m)).collect();
+    let mut c = care.to_vec();
+    let mut vars: Vec<usize> = (0..lut.num_vars()).collect();
+    for pos in (0..vars.len()).rev() {
+        let bit = 1 << pos;
+        let removable = (0..f.len())
+            .filter(|m| m & bit == 0)
+            .all(|m| !c[m] || !c[m | bit] || f[m] == f[m | bit]);
+        if !removable {
+            continue;
+        }
+        let mut nf = Vec::new();
+        let mut nc = Vec::new();
+        for m in 0..f.len() / 2 {
+            let m0 = ((m >> pos) << (pos + 1)) | (m & (bit - 1));
+            let m1 = m0 | bit;
+            nf.push(if c[m0] { f[m0] } else { f[m1] });
+            nc.push(c[m0] || c[m1]);
+        }
+        f = nf;
+        c = nc;
+        vars.remove(pos);
+    }
+    let mut ret = Lut::zero(vars.len());
+    for (m, v) in f.into_iter().enumerate() {
+        ret.set_value(m, v);
+    }
+    (vars, ret)
+}
+
+impl<'a> DontCares<'a> {
+    fn new(aig: Network, params: &'a AreaParameters) -> DontCares<'a> {
+        let mut fanouts = vec![Vec::new(); aig.nb_nodes()];
+        for i in 0..aig.nb_nodes() {
+            for v in aig.gate(i).vars() {
+                fanouts[v as usize].push(i);
+            }
+        }
+        let mut is_output = vec![false; aig.nb_nodes()];
+        for i in 0..aig.nb_outputs() {
+            let s = aig.output(i);
+            if s.is_var() {
+                is_output[s.var() as usize] = true;
+            }
+        }
+        DontCares {
+            aig,
+            params,
+            fanouts,
+            is_output,
+            input_values: Vec::new(),
+            node_values: Vec::new(),
+        }
+    }
+
+    /// Simulate one word, with the given values for the inputs and the flip-flops
+    fn add_word(&mut self, inputs: Vec<u64>, dffs: Vec<u64>) {
+        let node_values = SimpleSimulator::simulate_word(&self.aig, &inputs, &dffs);
+        self.input_values.push(inputs);
+        self.node_values.push(node_values);
+    }
+
+    /// Simulate random words
+    fn add_random_words(&mut self, nb_words: usize) {
+        let mut rng = SmallRng::seed_from_u64(1);
+        for (inputs, dffs) in SimpleSimulator::random_words(&self.aig, nb_words, &mut rng) {
+            self.add_word(inputs, dffs);
+        }
+    }
+
+    /// Simulate a counterexample returned by the SAT solver
+    fn add_counterexample(&mut self, inputs: Vec<bool>, dffs: Vec<bool>) {
+        let word = |b: bool| if b { !0u64 } else { 0u64 };
+        self.add_word(
+            inputs.into_iter().map(word).collect(),
+            dffs.into_iter().map(word).collect(),
+        );
+    }
+
+    /// Resimulate some nodes for a given word
+    fn simulate(&mut self, w: usize, nodes: &[usize]) {
+        let mut sim = SimpleSimulator::from_values(
+            &self.aig,
+            std::mem::take(&mut self.input_values[w]),
+            std::mem::take(&mut self.node_values[w]),
+        );
+        for &i in nodes {
+            sim.node_values[i] = sim.run_gate(i);
+        }
+        self.input_values[w] = sim.input_values;
+        self.node_values[w] = sim.node_values;
+    }
+
+    /// Simulation value of a signal for a given word
+    fn value(&self, w: usize, s: Signal) -> u64 {
+        let inv = if s.is_inverted() { !0 } else { 0 };
+        let v = if s.is_constant() {
+            0
+        } else if s.is_input() {
+            self.input_values[w][s.input() as usize]
+        } else {
+            self.node_values[w][s.var() as usize]
+        };
+        v ^ inv
+    }
+
+    /// Returns whether a node is not used anymore
+    fn is_dead(&self, i: usize) -> bool {
+        self.fanouts[i].is_empty() && !self.is_output[i]
+    }
+
+    /// Compute the window of a node in its transitive fanout
+    ///
+    /// Returns the nodes of the window in topological order, and the roots of the window: the
+    /// nodes whose value is observed outside of it.
+    fn window(&self, i: usize) -> (Vec<usize>, Vec<usize>) {
+        let mut window: Vec<usize> = Vec::new();
+        let mut level = vec![i];
+        for _ in 0..MAX_LEVELS {
+            let mut next = Vec::new();
+            for n in level {
+                for &f in &self.fanouts[n] {
+                    if self.aig.gate(f).is_comb()
+                        && !window.contains(&f)
+                        && window.len() < MAX_WINDOW
+                    {
+                        window.push(f);
+                        next.push(f);
+                    }
+                }
+            }
+            level = next;
+        }
+        window.sort();
+        let roots = std::iter::once(i)
+            .chain(window.iter().copied())
+            .filter(|j| self.is_output[*j] || self.fanouts[*j].iter().any(|f| !window.contains(f)))
+            .collect();
+        (window, roots)
+    }
+
+    /// Compute the care set of a node for each word: the bits where its value is observable at
+    /// the roots of the window
+    fn care(&mut self, i: usize, window: &[usize], roots: &[usize]) -> Vec<u64> {
+        let mut ret = Vec::new();
+        for w in 0..self.node_values.len() {
+            if roots.contains(&i) {
+                ret.push(!0);
+                continue;
+            }
+            let saved: Vec<u64> = window.iter().map(|j| self.node_values[w][*j]).collect();
+            let before: Vec<u64> = roots.iter().map(|r| self.node_values[w][*r]).collect();
+            self.node_values[w][i] = !self.node_values[w][i];
+            self.simulate(w, window);
+            let care = roots
+                .iter()
+                .zip(before)
+                .fold(0, |acc, (r, v)| acc | (v ^ self.node_values[w][*r]));
+            ret.push(care);
+            self.node_values[w][i] = !self.node_values[w][i];
+            for (j, v) in window.iter().zip(saved) {
+                self.node_values[w][*j] = v;
+            }
+        }
+        ret
+    }
+
+    /// Input combinations of a gate that are observable
+    fn care_minterms(&self, i: usize, care: &[u64]) -> Vec<bool> {
+        let deps = self.aig.gate(i).dependencies();
+        let mut ret = vec![false; 1 << deps.len()];
+        for (w, c) in care.iter().enumerate() {
+            let values: Vec<u64> = deps.iter().map(|s| self.value(w, *s)).collect();
+            for b in 0..64 {
+                if (c >> b) & 1 != 0 {
+                    let m = values
+                        .iter()
+                        .enumerate()
+                        .fold(0, |m, (j, v)| m | (((v >> b) & 1) as usize) << j);
+                    ret[m] = true;
+                }
+            }
+        }
+        ret
+    }
+
+    /// Candidate replacements for a gate, cheapest first
+    fn candidates(&self, i: usize, care: &[u64]) -> Vec<Gate> {
+        let g = self.aig.gate(i);
+        let deps = g.dependencies();
+        if let Gate::Lut(lut) = g {
+            let (vars, f) = reduce_lut(&lut.lut, &self.care_minterms(i, care));
+            if vars.len() == lut.inputs.len() {
+                return Vec::new();
+            }
+            let inputs: Vec<Signal> = vars.iter().map(|v| lut.inputs[*v]).collect();
+            let ret = match vars.len() {
+                0 => Gate::Buf(Signal::from(f.value(0))),
+                1 if f.value(0) == f.value(1) => Gate::Buf(Signal::from(f.value(0))),
+                1 => Gate::Buf(inputs[0] ^ f.value(0)),
+                _ => Gate::lut(&inputs, f),
+            };
+            return vec![ret];
+        }
+        if matches!(g, Gate::Buf(_)) || !g.is_comb() {
+            return Vec::new();
+        }
+
+        let mut ret = vec![Gate::Buf(Signal::zero()), Gate::Buf(Signal::one())];
+        for s in deps {
+            ret.push(Gate::Buf(*s));
+            ret.push(Gate::Buf(!s));
+        }
+        match g {
+            Gate::Ternary([s, a, b], TernaryType::Mux) => {
+                ret.push(Gate::and(*s, *a));
+                ret.push(Gate::and(!s, *b));
+                ret.push(or_gate(!s, *a));
+                ret.push(or_gate(*s, *b));
+                ret.push(Gate::and(*a, *b));
+                ret.push(or_gate(*a, *b));
+            }
+            Gate::Ternary([a, b, c], TernaryType::Maj) => {
+                for (x, y) in [(a, b), (a, c), (b, c)] {
+                    ret.push(Gate::and(*x, *y));
+                    ret.push(or_gate(*x, *y));
+                }
+            }
+            _ if g.is_and() && deps.len() > 2 => {
+                for j in 0..deps.len() {
+                    let mut v = deps.to_vec();
+                    v.remove(j);
+                    ret.push(and_gate(&v));
+                }
+            }
+            _ => (),
+        }
+        let cost = self.params.gate_area(g);
+        ret.retain(|c| self.params.gate_area(c) < cost);
+        ret.sort_by_key(|c| self.params.gate_area(c));
+        ret
+    }
+
+    /// Prove that the roots of the window are unchanged when the gate of a node is replaced
+    ///
+    /// The new gate is already in the network. Returns a counterexample if the replacement is
+    /// incorrect.
+    fn verify(
+        &self,
+        i: usize,
+        old: &Gate,
+        window: &[usize],
+        roots: &[usize],
+    ) -> Option<(Vec<bool>, Vec<bool>)> {
+        // Copy of the window with the old gate
+        let mut net = self.aig.clone();
+        let mut copies: Vec<(usize, Signal)> = Vec::new();
+        copies.push((i, net.add(old.clone())));
+        for &j in window {
+            let g = self.aig.gate(j).remap(|s| {
+                let c = copies
+                    .iter()
+                    .find(|(n, _)| s.is_var() && s.var() as usize == *n);
+                match c {
+                    Some((_, t)) => *t ^ s.is_inverted(),
+                    None => *s,
+                }
+            });
+            let s = net.add(g);
+            copies.push((j, s));
+        }
+        let diffs: Vec<Signal> = copies
+            .iter()
+            .filter(|(n, _)| roots.contains(n))
+            .map(|(n, s)| net.add(Gate::xor(net.node(*n), *s)))
+            .collect();
+        if diffs.is_empty() {
+            // Nothing in the window is observed
+            return None;
+        }
+        let miter = net.add(Gate::Nary(diffs.into(), NaryType::Or));
+        find_difference(&net, miter, Signal::zero())
+    }
+
+    /// Try to replace the gate of a node by a cheaper one
+    fn try_replace(
+        &mut self,
+        i: usize,
+        g: Gate,
+        care: &[u64],
+        window: &[usize],
+        roots: &[usize],
+    ) -> Replacement {
+        let old = self.aig.gate(i).clone();
+        self.aig.replace(i, g);
+
+        // Check on the simulation first
+        for (w, c) in care.iter().enumerate() {
+            let before = self.node_values[w][i];
+            self.simulate(w, &[i]);
+            let after = self.node_values[w][i];
+            self.node_values[w][i] = before;
+            if (before ^ after) & c != 0 {
+                self.aig.replace(i, old);
+                return Replacement::Rejected;
+            }
+        }
+
+        if let Some((inputs, dffs)) = self.verify(i, &old, window, roots) {
+            self.aig.replace(i, old);
+            self.add_counterexample(inputs, dffs);
+            return Replacement::Refuted;
+        }
+
+        // Update the fanouts and the simulation
+        for v in old.vars() {
+            self.fanouts[v as usize].retain(|f| *f != i);
+        }
+        let vars: Vec<u32> = self.aig.gate(i).vars().collect();
+        for v in vars {
+            self.fanouts[v as usize].push(i);
+        }
+        let nodes: Vec<usize> = std::iter::once(i).chain(window.iter().copied()).collect();
+        for w in 0..self.node_values.len() {
+            self.simulate(w, &nodes);
+        }
+        Replacement::Accepted
+    }
+
+    /// Simplify a node as much as possible with its don't cares
+    fn optimize_node(&mut self, i: usize) {
+        let mut nb_tries = 0;
+        'restart: while nb_tries < MAX_TRIES {
+            if self.is_dead(i) {
+                return;
+            }
+            let (window, roots) = self.window(i);
+            let care = self.care(i, &window, &roots);
+            for g in self.candidates(i, &care) {
+                match self.try_replace(i, g, &care, &window, &roots) {
+                    Replacement::Accepted => continue 'restart,
+                    Replacement::Rejected => (),
+                    Replacement::Refuted => {
+                        nb_tries += 1;
+                        continue 'restart;
+                    }
+                }
+            }
+            return;
+        }
+    }
+
+    fn run(&mut self) {
+        self.add_random_words(NB_RANDOM_WORDS);
+        for i in 0..self.aig.nb_nodes() {
+            self.optimize_node(i);
+        }
+    }
+}
+
+/// Simplify the network using satisfiability and observability don't cares
+///
+/// For each node, the input combinations that never occur and the values that are not observable
+/// in a window of its transitive fanout are found by simulation. Lut functions are simplified to
+/// use fewer inputs, and other gates are replaced by cheaper ones when the difference is not
+/// observable. Each change is proved correct with a SAT solver.
+/// Flip-flops are handled as free variables, so that only combinatorial don't cares are used.
+pub fn optimize_dont_cares(aig: &mut Network, params: &AreaParameters) {
+    let mut net = aig.clone();
+    if !net.is_topo_sorted() {
+        net.topo_sort();
+    }
+    let mut d = DontCares::new(net, params);
+    d.run();
+    let mut net = d.aig;
+    net.make_canonical();
+    net.cleanup();
+    *aig = net;
+}
+
+#[cfg(test)]
+mod tests {
+    use volute::Lut;
+
+    use super::optimize_dont_cares;
+    use crate::equiv::check_equivalence_bounded;
+    use crate::network::area::AreaParameters;
+    use crate::network::generators::adder;
+    use crate::{Gate, Network, Signal};
+
+    #[test]
+    fn test_satisfiability() {
+        // The Lut never sees a = b = 1, so it can ignore its third input
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let x = aig.and(a, !b);
+        let y = aig.and(!a, b);
+        let v = |i| Lut::nth_var(3, i);
+        let lut = (v(0) | v(1)) & !(v(0) & v(1) & v(2));
+        let l = aig.add(Gate::lut(&[x, y, c], lut));
+        aig.add_output(l);
+        let mut opt = aig.clone();
+        optimize_dont_cares(&mut opt, &AreaParameters::vlsi());
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+        let luts: Vec<&Gate> = (0..opt.nb_nodes())
+            .map(|i| opt.gate(i))
+            .filter(|g| matches!(g, Gate::Lut(_)))
+            .collect();
+        assert!(luts.iter().all(|g| g.dependencies().len() <= 2));
+    }
+
+    #[test]
+    fn test_observability() {
+        // The Mux is only observed when a is true, so it can be replaced by b
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let m = aig.add(Gate::mux(a, b, c));
+        let x = aig.and(a, m);
+        aig.add_output(x);
+        let mut opt = aig.clone();
+        optimize_dont_cares(&mut opt, &AreaParameters::vlsi());
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+        assert_eq!(opt.nb_nodes(), 1);
+        assert!(opt.gate(0).is_and());
+    }
+
+    #[test]
+    fn test_maj() {
+        // The Maj is only observed when c is false, where it is an And
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let m = aig.add(Gate::maj(a, b, c));
+        let x = aig.and(!c, m);
+        aig.add_output(x);
+        let mut opt = aig.clone();
+        optimize_dont_cares(&mut opt, &AreaParameters::vlsi());
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+        assert!(opt.nb_nodes() <= 2);
+        assert!((0..opt.nb_nodes()).all(|i| opt.gate(i).is_and()));
+    }
+
+    #[test]
+    fn test_sequential() {
+        // The flip-flop is only observed when a is true
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let q = Signal::from_var(0);
+        aig.add(Gate::dff(
+            Signal::from_var(2),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        let m = aig.add(Gate::mux(a, q, b));
+        let x = aig.add(Gate::and(a, m));
+        aig.add_output(x);
+        let mut opt = aig.clone();
+        optimize_dont_cares(&mut opt, &AreaParameters::vlsi());
+        check_equivalence_bounded(&aig, &opt, 4, false).unwrap();
+        assert!(!(0..opt.nb_nodes()).any(|i| matches!(opt.gate(i), Gate::Ternary(..))));
+    }
+
+    #[test]
+    fn test_adder() {
+        let aig = adder::ripple_carry(8);
+        let mut opt = aig.clone();
+        optimize_dont_cares(&mut opt, &AreaParameters::vlsi());
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+    }
+}
@@ -0,0 +1,486 @@
+//! Minimization of exclusive sums of products
+//!
+//! Small cones of logic are expressed as exclusive sums of products (ESOP), obtained from the best
+//! of the Shannon and Davio expansions for each variable. They are then improved with
+//! EXORCISM-style rewriting of cube pairs, and rebuilt as Xor of And gates when this reduces the
+//! area.
+
+use fxhash::FxHashMap;
+use volute::Lut;
+
+use super::share_logic::share_logic;
+use super::sop::Cube;
+use crate::network::area::AreaParameters;
+use crate::network::cuts::{Cut, CutParameters};
+use crate::network::{cone_area, RefCounts};
+use crate::{Gate, Network, Signal};
+
+/// State of a variable in a cube: `Some(polarity)` for a literal, `None` if absent
+type VarState = Option<bool>;
+
+/// The third state, different from the two given states
+///
+/// This is the Xor of the two literals: `x ^ !x = 1`, `x ^ 1 = !x` and `!x ^ 1 = x`.
+fn third_state(a: VarState, b: VarState) -> VarState {
+    match (a, b) {
+        (Some(x), Some(y)) => {
+            assert_ne!(x, y);
+            None
+        }
+        (Some(x), None) | (None, Some(x)) => Some(!x),
+        (None, None) => panic!("States should be different"),
+    }
+}
+
+/// Set the state of a variable in a cube
+fn set_state(c: &Cube, var: usize, state: VarState) -> Cube {
+    let mut ret = *c;
+    ret.pos &= !(1 << var);
+    ret.neg &= !(1 << var);
+    match state {
+        Some(true) => ret.pos |= 1 << var,
+        Some(false) => ret.neg |= 1 << var,
+        None => (),
+    }
+    ret
+}
+
+/// Variables whose state differs between two cubes, as a bitmask
+fn differing_vars(a: &Cube, b: &Cube) -> u64 {
+    (a.pos ^ b.pos) | (a.neg ^ b.neg)
+}
+
+/// An exclusive sum of products, on up to 64 variables
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Esop {
+    nb_vars: usize,
+    cubes: Vec<Cube>,
+}
+
+impl Esop {
+    /// Create an empty ESOP, that is always false
+    pub fn new(nb_vars: usize) -> Esop {
+        assert!(nb_vars <= 64);
+        Esop {
+            nb_vars,
+            cubes: Vec::new(),
+        }
+    }
+
+    /// Create an ESOP from its cubes
+    pub fn from_cubes(nb_vars: usize, cubes: Vec<Cube>) -> Esop {
+        let mut ret = Esop::new(nb_vars);
+        for c in cubes {
+            ret.add_cube(c);
+        }
+        ret
+    }
+
+    /// Create an ESOP for a truth table, using the best expansion for each variable
+    pub fn from_lut(lut: &Lut) -> Esop {
+        let mut cache = FxHashMap::default();
+        let cubes = expand_lut(lut, &mut cache);
+        Esop {
+            nb_vars: lut.num_vars(),
+            cubes,
+        }
+    }
+
+    /// Compute the truth table of the ESOP
+    pub fn to_lut(&self) -> Lut {
+        let mut ret = Lut::zero(self.nb_vars);
+        for m in 0..ret.num_bits() {
+            ret.set_value(m, self.value(m as u64));
+        }
+        ret
+    }
+
+    /// Number of variables
+    pub fn nb_vars(&self) -> usize {
+        self.nb_vars
+    }
+
+    /// Cubes of the ESOP
+    pub fn cubes(&self) -> &[Cube] {
+        &self.cubes
+    }
+
+    /// Number of cubes
+    pub fn nb_cubes(&self) -> usize {
+        self.cubes.len()
+    }
+
+    /// Total number of literals in the cubes
+    pub fn nb_literals(&self) -> usize {
+        self.cubes.iter().map(|c| c.nb_literals()).sum()
+    }
+
+    /// Add a cube to the ESOP; empty cubes are ignored
+    pub fn add_cube(&mut self, cube: Cube) {
+        assert!(self.nb_vars == 64 || cube.vars() >> self.nb_vars == 0);
+        if cube.pos & cube.neg == 0 {
+            self.cubes.push(cube);
+        }
+    }
+
+    /// Value of the ESOP for an assignment of the variables, given as a bitmask
+    pub fn value(&self, mask: u64) -> bool {
+        self.cubes.iter().filter(|c| c.value(mask)).count() % 2 == 1
+    }
+
+    /// Cost of the ESOP: number of cubes, then number of literals
+    fn cost(&self) -> (usize, usize) {
+        (self.nb_cubes(), self.nb_literals())
+    }
+
+    /// Merge pairs of cubes at distance 0 or 1 until there are none left
+    fn merge_pairs(&mut self) {
+        'restart: loop {
+            for i in 0..self.cubes.len() {
+                for j in i + 1..self.cubes.len() {
+                    let diff = differing_vars(&self.cubes[i], &self.cubes[j]);
+                    if diff == 0 {
+                        // Identical cubes cancel out
+                        self.cubes.remove(j);
+                        self.cubes.remove(i);
+                        continue 'restart;
+                    }
+                    if diff.count_ones() == 1 {
+                        let v = diff.trailing_zeros() as usize;
+                        let (a, b) = (self.cubes[i], self.cubes[j]);
+                        self.cubes[i] = set_state(&a, v, third_state(a.literal(v), b.literal(v)));
+                        self.cubes.remove(j);
+                        continue 'restart;
+                    }
+                }
+            }
+            return;
+        }
+    }
+
+    /// Equivalent pairs of cubes for two cubes at distance 2
+    ///
+    /// This uses the identity `A.B ^ C.D = (A ^ C).B ^ C.(B ^ D)` on the two differing variables.
+    fn exorlink(a: &Cube, b: &Cube) -> [(Cube, Cube); 2] {
+        let diff = differing_vars(a, b);
+        let i = diff.trailing_zeros() as usize;
+        let j = 63 - diff.leading_zeros() as usize;
+        let ti = third_state(a.literal(i), b.literal(i));
+        let tj = third_state(a.literal(j), b.literal(j));
+        [
+            (set_state(a, i, ti), set_state(b, j, tj)),
+            (set_state(a, j, tj), set_state(b, i, ti)),
+        ]
+    }
+
+    /// Minimize the ESOP, by merging cubes and rewriting pairs of cubes at distance 2 when it
+    /// allows further merging
+    pub fn minimize(&mut self) {
+        self.merge_pairs();
+        'improve: loop {
+            for i in 0..self.cubes.len() {
+                for j in i + 1..self.cubes.len() {
+                    if differing_vars(&self.cubes[i], &self.cubes[j]).count_ones() != 2 {
+                        continue;
+                    }
+                    for (a, b) in Esop::exorlink(&self.cubes[i], &self.cubes[j]) {
+                        let mut cand = self.clone();
+                        cand.cubes[i] = a;
+                        cand.cubes[j] = b;
+                        cand.merge_pairs();
+                        if cand.cost() < self.cost() {
+                            *self = cand;
+                            continue 'improve;
+                        }
+                    }
+                }
+            }
+            return;
+        }
+    }
+
+    /// Create the logic for the ESOP in a network, as a Xor of Ands of the input signals
+    pub fn build(&self, aig: &mut Network, inputs: &[Signal]) -> Signal {
+        assert_eq!(inputs.len(), self.nb_vars);
+        let mut inv = false;
+        let mut terms = Vec::new();
+        for c in &self.cubes {
+            let lits: Vec<Signal> = c
+                .literals()
+                .iter()
+                .map(|(v, pol)| inputs[*v] ^ !pol)
+                .collect();
+            match lits.len() {
+                0 => inv = !inv,
+                1 => terms.push(lits[0]),
+                _ => terms.push(aig.add_canonical(Gate::andn(&lits))),
+            }
+        }
+        let s = match terms.len() {
+            0 => Signal::zero(),
+            1 => terms[0],
+            _ => aig.add_canonical(Gate::xorn(&terms)),
+        };
+        s ^ inv
+    }
+}
+
+/// Returns whether a function depends on a variable
+fn depends_on(lut: &Lut, var: usize) -> bool {
+    let (c0, c1) = lut.cofactors(var);
+    c0 != c1
+}
+
+/// Multiply the cubes by a literal
+fn with_literal(cubes: &[Cube], var: usize, pol: bool) -> Vec<Cube> {
+    cubes.iter().map(|c| set_state(c, var, Some(pol))).collect()
+}
+
+/// Compute an ESOP for a function, choosing the smallest of the Shannon, positive Davio and
+/// negative Davio expansions on its highest variable
+fn expand_lut(lut: &Lut, cache: &mut FxHashMap<Lut, Vec<Cube>>) -> Vec<Cube> {
+    if let Some(cubes) = cache.get(lut) {
+        return cubes.clone();
+    }
+    let ret = match (0..lut.num_vars()).rev().find(|v| depends_on(lut, *v)) {
+        None => {
+            if lut.value(0) {
+                vec![Cube::one()]
+            } else {
+                Vec::new()
+            }
+        }
+        Some(v) => {
+            let (f0, f1) = lut.cofactors(v);
+            let f2 = &f0 ^ &f1;
+            let e0 = expand_lut(&f0, cache);
+            let e1 = expand_lut(&f1, cache);
+            let e2 = expand_lut(&f2, cache);
+            let shannon = [with_literal(&e0, v, false), with_literal(&e1, v, true)].concat();
+            let pos_davio = [e0.clone(), with_literal(&e2, v, true)].concat();
+            let neg_davio = [e1.clone(), with_literal(&e2, v, false)].concat();
+            [shannon, pos_davio, neg_davio]
+                .into_iter()
+                .min_by_key(|e| (e.len(), e.iter().map(|c| c.nb_literals()).sum::<usize>()))
+                .unwrap()
+        }
+    };
+    cache.insert(lut.clone(), ret.clone());
+    ret
+}
+
+/// Rewrite cones of logic as ESOPs
+struct EsopRewriter<'a> {
+    aig: Network,
+    params: &'a AreaParameters,
+    /// Number of references to each node
+    refs: RefCounts,
+}
+
+impl<'a> EsopRewriter<'a> {
+    fn new(aig: Network, params: &'a AreaParameters) -> EsopRewriter<'a> {
+        EsopRewriter {
+            refs: RefCounts::new(&aig),
+            aig,
+            params,
+        }
+    }
+
+    /// Remove a reference to the inputs of a node, and return the area of the logic freed,
+    /// stopping at the leaves
+    fn deref_node(&mut self, i: usize, leaves: &[Signal]) -> usize {
+        let freed = self.refs.deref_node(&self.aig, i, leaves);
+        cone_area(&self.aig, &freed, self.params)
+    }
+
+    /// Add a reference to the inputs of a node, stopping at the leaves; opposite of deref_node
+    fn ref_node(&mut self, i: usize, leaves: &[Signal]) {
+        self.refs.ref_node(&self.aig, i, leaves);
+    }
+
+    /// Replace a node by the logic for an ESOP
+    fn replace(&mut self, root: usize, esop: &Esop, inputs: &[Signal]) {
+        let s = esop.build(&mut self.aig, inputs);
+        self.refs.resize(&self.aig);
+        self.deref_node(root, &[]);
+        self.aig.replace(root, Gate::Buf(s));
+        self.ref_node(root, &[]);
+    }
+
+    /// Minimized ESOP of a cut, with the area gained by using it
+    fn evaluate(&mut self, root: usize, cut: &Cut) -> Option<(isize, Esop)> {
+        let leaves = cut.leaves();
+        if leaves
+            .iter()
+            .any(|s| s.is_var() && self.refs.get(s.var() as usize) == 0)
+        {
+            // Do not revive logic that was already removed
+            return None;
+        }
+        let mut esop = Esop::from_lut(cut.function());
+        esop.minimize();
+        let mut scratch = Network::new();
+        scratch.add_inputs(leaves.len());
+        let inputs: Vec<Signal> = (0..leaves.len()).map(|i| scratch.input(i)).collect();
+        esop.build(&mut scratch, &inputs);
+        let added = self.params.area(&scratch);
+        let removed = self.deref_node(root, leaves);
+        self.ref_node(root, leaves);
+        Some((removed as isize - added as isize, esop))
+    }
+
+    fn run(&mut self, max_inputs: usize) {
+        // Luts are always converted
+        for i in 0..self.aig.nb_nodes() {
+            if let Gate::Lut(lut) = self.aig.gate(i) {
+                let inputs = lut.inputs.to_vec();
+                let mut esop = Esop::from_lut(&lut.lut);
+                esop.minimize();
+                self.replace(i, &esop, &inputs);
+            }
+        }
+        self.aig.topo_sort();
+        self.refs = RefCounts::new(&self.aig);
+
+        let cuts = CutParameters {
+            max_size: max_inputs,
+            max_cuts: 16,
+            through_dff: false,
+        }
+        .enumerate(&self.aig);
+        for i in 0..self.aig.nb_nodes() {
+            let g = self.aig.gate(i);
+            if !g.is_comb() || matches!(g, Gate::Buf(_)) || self.refs.get(i) == 0 {
+                continue;
+            }
+            let mut best: Option<(isize, &Cut, Esop)> = None;
+            for cut in cuts.node_cuts(i) {
+                if cut.is_trivial(self.aig.node(i)) || cut.latency() != 0 {
+                    continue;
+                }
+                if let Some((gain, esop)) = self.evaluate(i, cut) {
+                    if gain > best.as_ref().map(|b| b.0).unwrap_or(0) {
+                        best = Some((gain, cut, esop));
+                    }
+                }
+            }
+            if let Some((_, cut, esop)) = best {
+                self.replace(i, &esop, cut.leaves());
+            }
+        }
+    }
+}
+
+/// Rewrite the network using exclusive sums of products
+///
+/// Lut gates are always converted to ESOPs. For other nodes, cuts with up to `max_inputs`
+/// inputs (at most 8) are enumerated. Their function is expressed as an ESOP, minimized, and used if this reduces the area.
+/// The And terms are finally shared between ESOPs with [`share_logic`].
+pub fn minimize_esop(aig: &mut Network, max_inputs: usize, params: &AreaParameters) {
+    let mut net = aig.clone();
+    if !net.is_topo_sorted() {
+        net.topo_sort();
+    }
+    let mut rewriter = EsopRewriter::new(net, params);
+    rewriter.run(max_inputs);
+    let mut net = rewriter.aig;
+    net.topo_sort();
+    net.make_canonical();
+    net.cleanup();
+    share_logic(&mut net, 64);
+    *aig = net;
+}
+
+#[cfg(test)]
+mod tests {
+    use volute::Lut;
+
+    use super::{minimize_esop, Esop};
+    use crate::equiv::check_equivalence_bounded;
+    use crate::network::area::AreaParameters;
+    use crate::network::generators::adder;
+    use crate::optim::Cube;
+    use crate::{Gate, Network, Signal};
+
+    #[test]
+    fn test_from_lut() {
+        for _ in 0..20 {
+            let lut = Lut::random(6);
+            let esop = Esop::from_lut(&lut);
+            assert_eq!(esop.to_lut(), lut);
+        }
+        // Parity is a single Xor of literals
+        let esop = Esop::from_lut(&Lut::parity(5));
+        assert_eq!(esop.nb_cubes(), 5);
+        assert_eq!(esop.nb_literals(), 5);
+    }
+
+    #[test]
+    fn test_minimize() {
+        // a.b ^ a.!b ^ !a.b.c ^ !a.!b.!c = a ^ !a.!(b ^ c)
+        let mut esop = Esop::from_cubes(
+            3,
+            vec![
+                Cube::from_literals(&[(0, true), (1, true)]),
+                Cube::from_literals(&[(0, true), (1, false)]),
+                Cube::from_literals(&[(0, false), (1, true), (2, true)]),
+                Cube::from_literals(&[(0, false), (1, false), (2, false)]),
+            ],
+        );
+        let lut = esop.to_lut();
+        esop.minimize();
+        assert_eq!(esop.to_lut(), lut);
+        assert!(esop.nb_cubes() <= 3);
+
+        for _ in 0..20 {
+            let lut = Lut::random(5);
+            let mut esop = Esop::from_lut(&lut);
+            let cost = esop.cost();
+            esop.minimize();
+            assert_eq!(esop.to_lut(), lut);
+            assert!(esop.cost() <= cost);
+        }
+    }
+
+    #[test]
+    fn test_lut() {
+        let mut aig = Network::new();
+        let inputs: Vec<Signal> = (0..4).map(|_| aig.add_input()).collect();
+        let x = aig.add(Gate::lut(&inputs, Lut::parity(4)));
+        let y = aig.add(Gate::lut(&inputs[..3], Lut::majority(3)));
+        aig.add_output(x);
+        aig.add_output(y);
+        let mut opt = aig.clone();
+        minimize_esop(&mut opt, 6, &AreaParameters::vlsi());
+        assert!((0..opt.nb_nodes()).all(|i| !matches!(opt.gate(i), Gate::Lut(_))));
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_xor_from_and() {
+        // Xor3 written with And gates
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let t1 = aig.and(a, !b);
+        let t2 = aig.and(!a, b);
+        let ab = !aig.and(!t1, !t2);
+        let t3 = aig.and(ab, !c);
+        let t4 = aig.and(!ab, c);
+        let x = !aig.and(!t3, !t4);
+        aig.add_output(x);
+        let mut opt = aig.clone();
+        minimize_esop(&mut opt, 6, &AreaParameters::vlsi());
+        assert!(opt.nb_nodes() <= 2);
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_adder() {
+        let aig = adder::ripple_carry(8);
+        let mut opt = aig.clone();
+        minimize_esop(&mut opt, 6, &AreaParameters::vlsi());
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+    }
+}
@@ -0,0 +1,260 @@
+//! SAT sweeping (fraiging), merging functionally equivalent nodes
+//!
+//! Candidate equivalences are found by random simulation, then proved or refuted with a SAT solver.
+//! Counterexamples are simulated to refine the candidates.
+
+use fxhash::FxHashMap;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use crate::equiv::Prover;
+use crate::sim::{SimpleSimulator, NB_RANDOM_WORDS};
+use crate::{Gate, Network, Signal};
+
+/// Simulation-based candidate equivalences
+///
+/// Candidates are kept as a partition of the signals, that is refined each time a new word is
+/// simulated. Signals are normalized so that their value is zero for the first pattern, and
+/// the first signal of each class is its representative.
+struct Sweeper<'a> {
+    /// Network being simulated, before any merging
+    aig: &'a Network,
+    /// Signals in each equivalence class, normalized
+    classes: Vec<Vec<Signal>>,
+    /// Class of each signal, indexed by the signal without inversion
+    class_of: FxHashMap<Signal, usize>,
+    /// Normalization of each signal, indexed by the signal without inversion
+    normalized: FxHashMap<Signal, Signal>,
+    /// Random number generator
+    rng: SmallRng,
+}
+
+impl<'a> Sweeper<'a> {
+    fn new(aig: &'a Network, seed: u64) -> Sweeper<'a> {
+        Sweeper {
+            aig,
+            classes: Vec::new(),
+            class_of: FxHashMap::default(),
+            normalized: FxHashMap::default(),
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+
+    /// All signals that can be merged: the constant, the inputs and the nodes
+    fn signals(&self) -> impl Iterator<Item = Signal> + 'a {
+        let aig = self.aig;
+        std::iter::once(Signal::zero())
+            .chain((0..aig.nb_inputs()).map(move |i| aig.input(i)))
+            .chain((0..aig.nb_nodes()).map(move |i| aig.node(i)))
+    }
+
+    /// Simulate one word, with the given values for the inputs and the flip-flops
+    ///
+    /// The classes are split according to the values of their signals.
+    fn add_word(&mut self, inputs: Vec<u64>, dffs: Vec<u64>) {
+        let node_values = SimpleSimulator::simulate_word(self.aig, &inputs, &dffs);
+        let value = |s: Signal| {
+            let v = if s.is_constant() {
+                0
+            } else if s.is_input() {
+                inputs[s.input() as usize]
+            } else {
+                node_values[s.var() as usize]
+            };
+            if s.is_inverted() {
+                !v
+            } else {
+                v
+            }
+        };
+
+        if self.classes.is_empty() {
+            // First word: normalize the signals and start with a single class
+            let mut class = Vec::new();
+            for s in self.signals() {
+                let n = if value(s) & 1 != 0 { !s } else { s };
+                self.normalized.insert(s, n);
+                self.class_of.insert(s, 0);
+                class.push(n);
+            }
+            self.classes.push(class);
+        }
+
+        for c in 0..self.classes.len() {
+            if self.classes[c].len() <= 1 {
+                continue;
+            }
+            let mut split: Vec<(u64, Vec<Signal>)> = Vec::new();
+            for s in &self.classes[c] {
+                let v = value(*s);
+                match split.iter_mut().find(|(w, _)| *w == v) {
+                    Some((_, members)) => members.push(*s),
+                    None => split.push((v, vec![*s])),
+                }
+            }
+            let mut split = split.into_iter().map(|(_, members)| members);
+            self.classes[c] = split.next().unwrap();
+            for members in split {
+                let new_class = self.classes.len();
+                for s in &members {
+                    self.class_of.insert(s.without_inversion(), new_class);
+                }
+                self.classes.push(members);
+            }
+        }
+    }
+
+    /// Simulate random words
+    fn add_random_words(&mut self, nb_words: usize) {
+        for (inputs, dffs) in SimpleSimulator::random_words(self.aig, nb_words, &mut self.rng) {
+            self.add_word(inputs, dffs);
+        }
+    }
+
+    /// Simulate a counterexample, and random variations around it
+    fn add_counterexample(&mut self, inputs: Vec<bool>, dffs: Vec<bool>) {
+        let mut vary = |b: bool| {
+            // Mostly the original value, with 1/16 values being flipped
+            let mut change = !0u64;
+            for _ in 0..4 {
+                change &= self.rng.gen::<u64>();
+            }
+            let val = if b { !0 } else { 0 };
+            (val ^ change) & !1 | (b as u64)
+        };
+        let inputs = inputs.into_iter().map(&mut vary).collect();
+        let dffs = dffs.into_iter().map(&mut vary).collect();
+        self.add_word(inputs, dffs);
+    }
+
+    /// Candidate equivalent signal for a node, if any
+    fn candidate(&self, i: usize) -> Option<Signal> {
+        let n = self.aig.node(i);
+        let s = self.normalized[&n];
+        let rep = self.classes[self.class_of[&n]][0];
+        if rep.without_inversion() == n {
+            None
+        } else {
+            Some(rep ^ s.is_inverted())
+        }
+    }
+}
+
+/// Merge functionally equivalent nodes, using SAT sweeping
+///
+/// Nodes with identical values in random simulation are candidates for merging.
+/// Each candidate pair is proved equivalent with an incremental SAT solver, or the
+/// counterexample is used to refine the candidates.
+/// Flip-flops are handled as free variables, so that only combinatorial equivalences are found.
+pub fn fraig(aig: &mut Network) {
+    if !aig.is_topo_sorted() {
+        aig.topo_sort();
+    }
+    let orig = aig.clone();
+    let mut sweeper = Sweeper::new(&orig, 1);
+    sweeper.add_random_words(NB_RANDOM_WORDS);
+    let mut prover = Prover::new();
+    for i in 0..aig.nb_nodes() {
+        if !aig.gate(i).is_comb() {
+            continue;
+        }
+        while let Some(rep) = sweeper.candidate(i) {
+            match prover.find_difference(&orig, orig.node(i), rep) {
+                None => {
+                    aig.replace(i, Gate::Buf(rep));
+                    break;
+                }
+                Some((inputs, dffs)) => sweeper.add_counterexample(inputs, dffs),
+            }
+        }
+    }
+    aig.make_canonical();
+    aig.cleanup();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fraig;
+    use crate::equiv::check_equivalence_bounded;
+    use crate::{Gate, Network, Signal};
+
+    #[test]
+    fn test_merge_and() {
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let ab = aig.and(a, b);
+        let x1 = aig.and(ab, c);
+        let bc = aig.and(b, c);
+        let x2 = aig.and(a, bc);
+        aig.add_output(x1);
+        aig.add_output(!x2);
+        let mut opt = aig.clone();
+        fraig(&mut opt);
+        assert_eq!(opt.nb_nodes(), 2);
+        assert_eq!(opt.output(0), !opt.output(1));
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_constant() {
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let x = aig.xor(a, b);
+        let o = aig.add(Gate::and3(a, b, x));
+        aig.add_output(o);
+        let ab = aig.and(a, b);
+        let o2 = aig.add(Gate::xor3(ab, x, a));
+        aig.add_output(o2);
+        fraig(&mut aig);
+        assert_eq!(aig.output(0), Signal::zero());
+    }
+
+    #[test]
+    fn test_sequential() {
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let d = aig.add(Gate::dff(a, Signal::one(), Signal::zero()));
+        let x1 = aig.add(Gate::mux(d, a, !a));
+        let x2 = aig.xor(d, a);
+        aig.add_output(x1);
+        aig.add_output(x2);
+        let mut opt = aig.clone();
+        fraig(&mut opt);
+        assert_eq!(opt.output(0), !opt.output(1));
+        check_equivalence_bounded(&aig, &opt, 3, false).unwrap();
+    }
+
+    #[test]
+    fn test_adders() {
+        // Two implementations of the same adder, sharing the inputs
+        let mut aig = Network::new();
+        let len = 8;
+        let inputs: Vec<(Signal, Signal)> = (0..len)
+            .map(|_| (aig.add_input(), aig.add_input()))
+            .collect();
+        let mut c = Signal::zero();
+        for (a, b) in &inputs {
+            let o = aig.add(Gate::xor3(*a, *b, c));
+            aig.add_output(o);
+            c = aig.add(Gate::maj(*a, *b, c));
+        }
+        aig.add_output(c);
+        let mut c = Signal::zero();
+        for (a, b) in &inputs {
+            let x = aig.xor(*a, *b);
+            let o = aig.xor(x, c);
+            aig.add_output(o);
+            let g = aig.and(*a, *b);
+            let p = aig.and(x, c);
+            c = !aig.and(!g, !p);
+        }
+        aig.add_output(c);
+        fraig(&mut aig);
+        for o in 0..=len {
+            assert_eq!(aig.output(o), aig.output(o + len + 1));
+        }
+    }
+}
@@ -0,0 +1,821 @@
+//! Technology mapping to the cells of a standard cell library
+//!
+//! The network is first decomposed into And2 gates. Each node is then matched against the
+//! library cells using its cuts, with Boolean matching on the truth tables: all permutations of
+//! the cell inputs are precomputed, and the cut functions are tried with all input inversions.
+//! Both polarities of each node are mapped, and inverter cells are inserted where required.
+//!
+//! A delay-optimal mapping is computed first, then the area is recovered using area flow and
+//! then exact area, without violating the delay target.
+
+use fxhash::FxHashMap;
+use itertools::Itertools;
+use volute::Lut;
+
+use crate::network::cuts::CutParameters;
+use crate::network::library::{Library, MappedNetwork};
+use crate::network::{BinaryType, NaryType, TernaryType};
+use crate::{Gate, Network, Signal};
+
+/// Tolerance for the comparison of arrival and required times
+const EPSILON: f64 = 1e-9;
+
+/// Build a balanced tree of And2 gates
+fn and2_tree(net: &mut Network, v: &[Signal]) -> Signal {
+    match v.len() {
+        0 => Signal::one(),
+        1 => v[0],
+        _ => {
+            let (a, b) = v.split_at(v.len() / 2);
+            let a = and2_tree(net, a);
+            let b = and2_tree(net, b);
+            net.and(a, b)
+        }
+    }
+}
+
+/// Build a Xor2 from And2 gates
+fn and2_xor(net: &mut Network, a: Signal, b: Signal) -> Signal {
+    let x = net.and(a, !b);
+    let y = net.and(!a, b);
+    !net.and(!x, !y)
+}
+
+/// Build a Mux from And2 gates
+fn and2_mux(net: &mut Network, s: Signal, a: Signal, b: Signal) -> Signal {
+    let x = net.and(s, a);
+    let y = net.and(!s, b);
+    !net.and(!x, !y)
+}
+
+/// Build a Lut from And2 gates, using Shannon expansion on the variables before var
+fn and2_lut(net: &mut Network, lut: &Lut, inputs: &[Signal], var: usize) -> Signal {
+    if var == 0 {
+        return Signal::from(lut.value(0));
+    }
+    let (c0, c1) = lut.cofactors(var - 1);
+    if c0 == c1 {
+        return and2_lut(net, &c0, inputs, var - 1);
+    }
+    let s0 = and2_lut(net, &c0, inputs, var - 1);
+    let s1 = and2_lut(net, &c1, inputs, var - 1);
+    and2_mux(net, inputs[var - 1], s1, s0)
+}
+
+/// Decompose all combinatorial gates into And2 gates
+fn decompose_and2(aig: &Network) -> Network {
+    let mut net = Network::new();
+    net.add_inputs(aig.nb_inputs());
+    for i in 0..aig.nb_inputs() {
+        if let Some(name) = aig.input_name(i) {
+            net.set_input_name(i, name);
+        }
+    }
+
+    // Flip-flops are created first, and connected at the end
+    let mut t = Vec::with_capacity(aig.nb_nodes());
+    for i in 0..aig.nb_nodes() {
+        if aig.gate(i).is_comb() {
+            t.push(Signal::zero());
+            continue;
+        }
+        let s = net.add(Gate::Buf(Signal::zero()));
+        if let Some(name) = aig.node_name(i) {
+            net.set_node_name(s.var() as usize, name);
+        }
+        t.push(s);
+    }
+    let tr = |t: &[Signal], s: &Signal| {
+        if s.is_var() {
+            t[s.var() as usize] ^ s.is_inverted()
+        } else {
+            *s
+        }
+    };
+    for i in 0..aig.nb_nodes() {
+        let g = aig.gate(i);
+        if !g.is_comb() {
+            continue;
+        }
+        let v: Vec<Signal> = g.dependencies().iter().map(|s| tr(&t, s)).collect();
+        t[i] = match g {
+            Gate::Binary(_, BinaryType::And)
+            | Gate::Ternary(_, TernaryType::And)
+            | Gate::Nary(_, NaryType::And) => and2_tree(&mut net, &v),
+            Gate::Nary(_, NaryType::Nand) => !and2_tree(&mut net, &v),
+            Gate::Nary(_, NaryType::Or) => {
+                let inv: Vec<Signal> = v.iter().map(|s| !*s).collect();
+                !and2_tree(&mut net, &inv)
+            }
+            Gate::Nary(_, NaryType::Nor) => {
+                let inv: Vec<Signal> = v.iter().map(|s| !*s).collect();
+                and2_tree(&mut net, &inv)
+            }
+            Gate::Binary(_, BinaryType::Xor)
+            | Gate::Ternary(_, TernaryType::Xor)
+            | Gate::Nary(_, NaryType::Xor)
+            | Gate::Nary(_, NaryType::Xnor) => {
+                let mut x = Signal::zero();
+                for s in &v {
+                    x = and2_xor(&mut net, x, *s);
+                }
+                x ^ matches!(g, Gate::Nary(_, NaryType::Xnor))
+            }
+            Gate::Ternary(_, TernaryType::Mux) => and2_mux(&mut net, v[0], v[1], v[2]),
+            Gate::Ternary(_, TernaryType::Maj) => {
+                let ab = net.and(v[0], v[1]);
+                let a_or_b = !net.and(!v[0], !v[1]);
+                let c = net.and(v[2], a_or_b);
+                !net.and(!ab, !c)
+            }
+            Gate::Buf(_) => v[0],
+            Gate::Lut(lut) => and2_lut(&mut net, &lut.lut, &v, v.len()),
+            Gate::Dff(_) => unreachable!(),
+        };
+    }
+    for i in 0..aig.nb_nodes() {
+        if !aig.gate(i).is_comb() {
+            let g = aig.gate(i).remap(|s| tr(&t, s));
+            net.replace(t[i].var() as usize, g);
+        }
+    }
+    for o in 0..aig.nb_outputs() {
+        net.add_output(tr(&t, &aig.output(o)));
+        if let Some(name) = aig.output_name(o) {
+            net.set_output_name(o, name);
+        }
+    }
+    net.make_canonical();
+    net.cleanup();
+    if !net.is_topo_sorted() {
+        net.topo_sort();
+    }
+    net
+}
+
+/// Cells that implement each function, for all permutations of their inputs
+struct MatchTable {
+    /// For each function, the cells and the cut leaf connected to each of their inputs
+    table: FxHashMap<Lut, Vec<(usize, Vec<usize>)>>,
+}
+
+impl MatchTable {
+    fn new(library: &Library, max_size: usize) -> MatchTable {
+        let mut table: FxHashMap<Lut, Vec<(usize, Vec<usize>)>> = FxHashMap::default();
+        for c in 0..library.nb_cells() {
+            let cell = library.cell(c);
+            let k = cell.nb_inputs();
+            if k == 0 || k > max_size {
+                continue;
+            }
+            for perm in (0..k).permutations(k) {
+                // Cell input j is connected to leaf perm[j]
+                let mut function = Lut::zero(k);
+                for mask in 0..(1 << k) {
+                    let cell_mask = (0..k)
+                        .filter(|j| mask >> perm[*j] & 1 != 0)
+                        .map(|j| 1 << j)
+                        .sum();
+                    function.set_value(mask, cell.function.value(cell_mask));
+                }
+                let matches = table.entry(function).or_default();
+                if !matches.iter().any(|(other, _)| *other == c) {
+                    matches.push((c, perm));
+                }
+            }
+        }
+        MatchTable { table }
+    }
+}
+
+/// Implementation of a signal by a cell
+struct Match {
+    /// Library cell
+    cell: usize,
+    /// Signal connected to each input of the cell
+    pins: Vec<Signal>,
+}
+
+/// Mapping state: selected match for both polarities of each node, and the cover it induces
+///
+/// Each polarity of a node or input is a vertex of the mapping. Inputs and flip-flops are free in
+/// positive polarity.
+struct CellMapper<'a> {
+    aig: &'a Network,
+    library: &'a Library,
+    /// Candidate matches for each vertex
+    matches: Vec<Vec<Match>>,
+    /// Index of the selected match for each vertex, or None for free vertices
+    choice: Vec<Option<usize>>,
+    /// Arrival time of each vertex with the selected matches
+    arrival: Vec<f64>,
+    /// Area flow of each vertex with the selected matches
+    area_flow: Vec<f64>,
+    /// Required time of each vertex in the current cover
+    required: Vec<f64>,
+    /// Number of references of each vertex in the current cover
+    refs: Vec<usize>,
+    /// Structural fanout of each node or input
+    fanout: Vec<usize>,
+    /// Required time at the outputs
+    target: f64,
+}
+
+impl<'a> CellMapper<'a> {
+    fn new(aig: &'a Network, library: &'a Library) -> CellMapper<'a> {
+        let inverter = library.inverter().expect("The library has no inverter");
+        let max_size = (0..library.nb_cells())
+            .map(|c| library.cell(c).nb_inputs())
+            .max()
+            .unwrap_or(0)
+            .clamp(2, 6);
+        let params = CutParameters {
+            max_size,
+            max_cuts: 10,
+            ..Default::default()
+        };
+        let cuts = params.enumerate(aig);
+        let table = MatchTable::new(library, max_size);
+
+        let nb_vertices = 2 * (aig.nb_nodes() + aig.nb_inputs());
+        let mut ret = CellMapper {
+            aig,
+            library,
+            matches: (0..nb_vertices).map(|_| Vec::new()).collect(),
+            choice: vec![None; nb_vertices],
+            arrival: vec![0.0; nb_vertices],
+            area_flow: vec![0.0; nb_vertices],
+            required: vec![f64::INFINITY; nb_vertices],
+            refs: vec![0; nb_vertices],
+            fanout: vec![0; nb_vertices / 2],
+            target: f64::INFINITY,
+        };
+
+        // Matches of the cuts of each node
+        for i in 0..aig.nb_nodes() {
+            if !aig.gate(i).is_comb() {
+                continue;
+            }
+            let node = aig.node(i);
+            for cut in cuts.node_cuts(i) {
+                if cut.is_trivial(node) || cut.size() == 0 {
+                    continue;
+                }
+                let k = cut.size();
+                for phase in [false, true] {
+                    let target = if phase {
+                        !cut.function()
+                    } else {
+                        cut.function().clone()
+                    };
+                    for flips in 0..(1usize << k) {
+                        let mut function = target.clone();
+                        for j in 0..k {
+                            if flips >> j & 1 != 0 {
+                                function.flip_inplace(j);
+                            }
+                        }
+                        let Some(cells) = table.table.get(&function) else {
+                            continue;
+                        };
+                        for (c, perm) in cells {
+                            let pins = perm
+                                .iter()
+                                .map(|l| cut.leaves()[*l] ^ (flips >> l & 1 != 0))
+                                .collect();
+                            let v = ret.vertex(node ^ phase);
+                            ret.matches[v].push(Match { cell: *c, pins });
+                        }
+                    }
+                }
+            }
+        }
+
+        // Inverters for all signals that are not free
+        for s in (0..aig.nb_inputs())
+            .map(|i| aig.input(i))
+            .chain((0..aig.nb_nodes()).map(|i| aig.node(i)))
+        {
+            let is_free = !s.is_var() || !aig.gate(s.var() as usize).is_comb();
+            if !is_free {
+                let v = ret.vertex(s);
+                ret.matches[v].push(Match {
+                    cell: inverter,
+                    pins: vec![!s],
+                });
+            }
+            let v = ret.vertex(!s);
+            ret.matches[v].push(Match {
+                cell: inverter,
+                pins: vec![s],
+            });
+            if is_free {
+                ret.set_choice(v, 0);
+            }
+        }
+
+        // Structural fanout
+        let mut users = ret.roots();
+        for i in 0..aig.nb_nodes() {
+            if aig.gate(i).is_comb() {
+                users.extend(aig.gate(i).dependencies());
+            }
+        }
+        for s in users {
+            let v = ret.vertex(s);
+            ret.fanout[v / 2] += 1;
+        }
+        ret
+    }
+
+    /// Index of the vertex for a signal
+    fn vertex(&self, s: Signal) -> usize {
+        let base = if s.is_input() {
+            self.aig.nb_nodes() + s.input() as usize
+        } else {
+            s.var() as usize
+        };
+        2 * base + s.is_inverted() as usize
+    }
+
+    /// Signals that must be available: outputs and flip-flop inputs
+    fn roots(&self) -> Vec<Signal> {
+        let mut ret: Vec<Signal> = (0..self.aig.nb_outputs())
+            .map(|o| self.aig.output(o))
+            .collect();
+        for i in 0..self.aig.nb_nodes() {
+            if !self.aig.gate(i).is_comb() {
+                ret.extend(self.aig.gate(i).dependencies());
+            }
+        }
+        ret.retain(|s| !s.is_constant());
+        ret
+    }
+
+    /// Whether a match uses the other polarity of the same signal, like an inverter
+    fn uses_other_phase(&self, v: usize, j: usize) -> bool {
+        let pins = &self.matches[v][j].pins;
+        pins.len() == 1 && self.vertex(pins[0]) == v ^ 1
+    }
+
+    /// Whether a match can be selected without creating a loop between the two polarities
+    fn is_valid(&self, v: usize, j: usize) -> bool {
+        !self.uses_other_phase(v, j)
+            || !self.choice[v ^ 1].is_some_and(|k| self.uses_other_phase(v ^ 1, k))
+    }
+
+    /// Arrival time of a match with the current selection
+    fn match_arrival(&self, v: usize, j: usize) -> f64 {
+        let m = &self.matches[v][j];
+        let input_arrival = m
+            .pins
+            .iter()
+            .map(|s| self.arrival[self.vertex(*s)])
+            .fold(0.0, f64::max);
+        input_arrival + self.library.cell(m.cell).delay
+    }
+
+    /// Area flow of a match with the current selection
+    ///
+    /// The area of the inputs is shared between their fanouts, using the references of the
+    /// current cover if available.
+    fn match_area_flow(&self, v: usize, j: usize) -> f64 {
+        let m = &self.matches[v][j];
+        let input_area: f64 = m
+            .pins
+            .iter()
+            .map(|s| {
+                let u = self.vertex(*s);
+                let nb_refs = if self.refs[u] != 0 {
+                    self.refs[u]
+                } else {
+                    self.fanout[u / 2]
+                };
+                self.area_flow[u] / nb_refs.max(1) as f64
+            })
+            .sum();
+        input_area + self.library.cell(m.cell).area
+    }
+
+    /// Select a match for a vertex and update its arrival time and area flow
+    fn set_choice(&mut self, v: usize, j: usize) {
+        self.arrival[v] = self.match_arrival(v, j);
+        self.area_flow[v] = self.match_area_flow(v, j);
+        self.choice[v] = Some(j);
+    }
+
+    /// Vertices of the combinatorial nodes, in topological order
+    fn comb_vertices(&self) -> Vec<usize> {
+        (0..self.aig.nb_nodes())
+            .filter(|i| self.aig.gate(*i).is_comb())
+            .flat_map(|i| [2 * i, 2 * i + 1])
+            .collect()
+    }
+
+    /// Select the best match for each polarity of each node, according to a cost function
+    ///
+    /// Matches from the other polarity are considered last, once both polarities have been
+    /// mapped. The current selection is kept if no match is acceptable.
+    fn select<F>(&mut self, cost: F)
+    where
+        F: Fn(&Self, usize, usize) -> Option<(f64, f64)>,
+    {
+        let vertices = self.comb_vertices();
+        for pair in vertices.chunks(2) {
+            for other_phase in [false, true] {
+                for &v in pair {
+                    let mut best: Option<((f64, f64), usize)> = self.choice[v]
+                        .filter(|j| self.is_valid(v, *j))
+                        .and_then(|j| cost(self, v, j).map(|c| (c, j)));
+                    for j in 0..self.matches[v].len() {
+                        if self.uses_other_phase(v, j) != other_phase || !self.is_valid(v, j) {
+                            continue;
+                        }
+                        let Some(c) = cost(self, v, j) else {
+                            continue;
+                        };
+                        let better = match best {
+                            None => true,
+                            Some((b, _)) => {
+                                c.0 < b.0 - EPSILON || (c.0 <= b.0 + EPSILON && c.1 < b.1 - EPSILON)
+                            }
+                        };
+                        if better {
+                            best = Some((c, j));
+                        }
+                    }
+                    if let Some((_, j)) = best {
+                        self.set_choice(v, j);
+                    }
+                }
+            }
+            for &v in pair {
+                assert!(self.choice[v].is_some(), "No library cell matches the node");
+            }
+        }
+    }
+
+    /// Select the match of each vertex, minimizing delay and then area flow
+    fn select_delay(&mut self) {
+        self.select(|m, v, j| Some((m.match_arrival(v, j), m.match_area_flow(v, j))));
+    }
+
+    /// Select the match of each vertex, minimizing area flow without violating the required times
+    fn select_area_flow(&mut self) {
+        self.select(|m, v, j| {
+            let arrival = m.match_arrival(v, j);
+            if arrival > m.required[v] + EPSILON {
+                None
+            } else {
+                Some((m.match_area_flow(v, j), arrival))
+            }
+        });
+    }
+
+    /// Select the match of each vertex in the cover, minimizing exact area without violating the
+    /// required times
+    fn select_exact_area(&mut self) {
+        for v in self.comb_vertices() {
+            if self.refs[v] == 0 {
+                continue;
+            }
+            let current = self.choice[v].unwrap();
+            self.deref_match(v, current);
+            let mut best = (f64::INFINITY, current);
+            for j in 0..self.matches[v].len() {
+                if !self.is_valid(v, j) || self.match_arrival(v, j) > self.required[v] + EPSILON {
+                    continue;
+                }
+                let area = self.ref_match(v, j);
+                self.deref_match(v, j);
+                if area < best.0 - EPSILON {
+                    best = (area, j);
+                }
+            }
+            self.ref_match(v, best.1);
+            self.set_choice(v, best.1);
+        }
+    }
+
+    /// Reference the inputs of a match, recursively selecting unreferenced vertices
+    ///
+    /// Returns the area added to the cover.
+    fn ref_match(&mut self, v: usize, j: usize) -> f64 {
+        let m = &self.matches[v][j];
+        let mut area = self.library.cell(m.cell).area;
+        let inputs: Vec<usize> = m.pins.iter().map(|s| self.vertex(*s)).collect();
+        for u in inputs {
+            if self.refs[u] == 0 {
+                if let Some(k) = self.choice[u] {
+                    area += self.ref_match(u, k);
+                }
+            }
+            self.refs[u] += 1;
+        }
+        area
+    }
+
+    /// Dereference the inputs of a match, recursively removing unreferenced vertices
+    ///
+    /// Returns the area removed from the cover.
+    fn deref_match(&mut self, v: usize, j: usize) -> f64 {
+        let m = &self.matches[v][j];
+        let mut area = self.library.cell(m.cell).area;
+        let inputs: Vec<usize> = m.pins.iter().map(|s| self.vertex(*s)).collect();
+        for u in inputs {
+            self.refs[u] -= 1;
+            if self.refs[u] == 0 {
+                if let Some(k) = self.choice[u] {
+                    area += self.deref_match(u, k);
+                }
+            }
+        }
+        area
+    }
+
+    /// Largest arrival time of the roots
+    fn delay(&self) -> f64 {
+        self.roots()
+            .iter()
+            .map(|s| self.arrival[self.vertex(*s)])
+            .fold(0.0, f64::max)
+    }
+
+    /// Compute the cover from the current selection, with its references and required times
+    fn compute_cover(&mut self) {
+        self.refs.fill(0);
+        self.required.fill(f64::INFINITY);
+        for s in self.roots() {
+            let v = self.vertex(s);
+            if self.refs[v] == 0 {
+                if let Some(j) = self.choice[v] {
+                    self.ref_match(v, j);
+                }
+            }
+            self.refs[v] += 1;
+            self.required[v] = self.target;
+        }
+        let mut vertices = self.comb_vertices();
+        vertices.reverse();
+        for pair in vertices.chunks(2) {
+            // The polarity implemented from the other one is processed first
+            let order = if self.choice[pair[1]].is_some_and(|j| self.uses_other_phase(pair[1], j)) {
+                [pair[1], pair[0]]
+            } else {
+                [pair[0], pair[1]]
+            };
+            for v in order {
+                if self.refs[v] == 0 {
+                    continue;
+                }
+                let m = &self.matches[v][self.choice[v].unwrap()];
+                let req = self.required[v] - self.library.cell(m.cell).delay;
+                let inputs: Vec<usize> = m.pins.iter().map(|s| self.vertex(*s)).collect();
+                for u in inputs {
+                    self.required[u] = self.required[u].min(req);
+                }
+            }
+        }
+    }
+
+    /// Build the mapped network
+    fn build(&self) -> MappedNetwork {
+        let aig = self.aig;
+        let mut net = Network::new();
+        let mut cells = Vec::new();
+        let mut t: Vec<Option<Signal>> = vec![None; self.matches.len()];
+        net.add_inputs(aig.nb_inputs());
+        for i in 0..aig.nb_inputs() {
+            t[self.vertex(aig.input(i))] = Some(net.input(i));
+            if let Some(name) = aig.input_name(i) {
+                net.set_input_name(i, name);
+            }
+        }
+        for i in 0..aig.nb_nodes() {
+            if !aig.gate(i).is_comb() {
+                let s = net.add(Gate::Buf(Signal::zero()));
+                cells.push(None);
+                t[self.vertex(aig.node(i))] = Some(s);
+                if let Some(name) = aig.node_name(i) {
+                    net.set_node_name(s.var() as usize, name);
+                }
+            }
+        }
+
+        // Inverters for inputs and flip-flops come first, then the combinatorial nodes
+        let mut vertices: Vec<usize> = (0..aig.nb_inputs())
+            .map(|i| self.vertex(!aig.input(i)))
+            .chain(
+                (0..aig.nb_nodes())
+                    .filter(|i| !aig.gate(*i).is_comb())
+                    .map(|i| self.vertex(!aig.node(i))),
+            )
+            .collect();
+        for pair in self.comb_vertices().chunks(2) {
+            if self.choice[pair[0]].is_some_and(|j| self.uses_other_phase(pair[0], j)) {
+                vertices.extend([pair[1], pair[0]]);
+            } else {
+                vertices.extend([pair[0], pair[1]]);
+            }
+        }
+        for v in vertices {
+            if self.refs[v] == 0 {
+                continue;
+            }
+            let m = &self.matches[v][self.choice[v].unwrap()];
+            let pins: Vec<Signal> = m.pins.iter().map(|s| t[self.vertex(*s)].unwrap()).collect();
+            let function = self.library.cell(m.cell).function.clone();
+            t[v] = Some(net.add(Gate::lut(&pins, function)));
+            cells.push(Some(m.cell));
+        }
+
+        let tr = |s: &Signal| {
+            if s.is_constant() {
+                *s
+            } else {
+                t[self.vertex(*s)].unwrap()
+            }
+        };
+        for i in 0..aig.nb_nodes() {
+            if !aig.gate(i).is_comb() {
+                let s = t[self.vertex(aig.node(i))].unwrap();
+                net.replace(s.var() as usize, aig.gate(i).remap(tr));
+            }
+        }
+        for o in 0..aig.nb_outputs() {
+            let s = tr(&aig.output(o));
+            net.add_output(s);
+            if let Some(name) = aig.output_name(o) {
+                net.set_output_name(o, name);
+                // Name the cell after the output, so that no buffer is required
+                if s.is_var() && net.node_name(s.var() as usize).is_none() {
+                    net.set_node_name(s.var() as usize, name);
+                }
+            }
+        }
+        MappedNetwork::new(net, cells)
+    }
+}
+
+/// Map a network to the cells of a library
+///
+/// The mapping minimizes the area, without exceeding the delay target if one is given. If the
+/// target cannot be met, the smallest achievable delay is used instead.
+/// The library must contain an inverter and a two-input And, Or, Nand or Nor cell.
+/// Flip-flops are kept as is.
+pub fn map_cells(aig: &Network, library: &Library, delay_target: Option<f64>) -> MappedNetwork {
+    assert!(
+        library.is_complete(),
+        "The library must contain an inverter and a two-input And, Or, Nand or Nor cell"
+    );
+    let net = decompose_and2(aig);
+    let mut mapper = CellMapper::new(&net, library);
+    mapper.select_delay();
+    if let Some(target) = delay_target {
+        mapper.target = target.max(mapper.delay());
+    }
+    mapper.compute_cover();
+    for _ in 0..2 {
+        mapper.select_area_flow();
+        mapper.compute_cover();
+    }
+    mapper.select_exact_area();
+    mapper.compute_cover();
+    mapper.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use volute::Lut;
+
+    use super::{decompose_and2, map_cells};
+    use crate::equiv::check_equivalence_bounded;
+    use crate::network::generators::{adder, testcases};
+    use crate::network::library::{Cell, Library, MappedNetwork};
+    use crate::{Gate, Network, Signal};
+
+    /// A small library with the usual cells
+    fn library() -> Library {
+        let a = || Lut::nth_var(2, 0);
+        let b = || Lut::nth_var(2, 1);
+        let a3 = || Lut::nth_var(3, 0);
+        let b3 = || Lut::nth_var(3, 1);
+        let c3 = || Lut::nth_var(3, 2);
+        let mut lib = Library::new();
+        lib.add_cell(Cell::new("INV", 1.0, 1.0, &["A"], "Y", !Lut::nth_var(1, 0)));
+        lib.add_cell(Cell::new("NAND2", 2.0, 1.0, &["A", "B"], "Y", !(a() & b())));
+        lib.add_cell(Cell::new("NOR2", 2.0, 1.4, &["A", "B"], "Y", !(a() | b())));
+        lib.add_cell(Cell::new("AND2", 3.0, 1.8, &["A", "B"], "Y", a() & b()));
+        lib.add_cell(Cell::new("XOR2", 5.0, 2.0, &["A", "B"], "Y", a() ^ b()));
+        let aoi = !((a3() & b3()) | c3());
+        lib.add_cell(Cell::new("AOI21", 3.0, 1.6, &["A", "B", "C"], "Y", aoi));
+        let mux = (a3() & b3()) | (!a3() & c3());
+        lib.add_cell(Cell::new("MUX2", 6.0, 2.0, &["S", "A", "B"], "Y", mux));
+        lib
+    }
+
+    /// Check that the mapped network is consistent and equivalent to the original one
+    fn check_mapped(aig: &Network, mapped: &MappedNetwork, lib: &Library, nb_steps: usize) {
+        let net = mapped.network();
+        for i in 0..net.nb_nodes() {
+            match (net.gate(i), mapped.cell(i)) {
+                (Gate::Lut(lut), Some(c)) => {
+                    assert_eq!(lut.lut, lib.cell(c).function);
+                    assert!(lut.inputs.iter().all(|s| !s.is_inverted()));
+                }
+                (Gate::Dff(_), None) => (),
+                (g, c) => panic!("Unexpected gate {g:?} for cell {c:?}"),
+            }
+        }
+        check_equivalence_bounded(aig, net, nb_steps, false).unwrap();
+    }
+
+    #[test]
+    fn test_decompose() {
+        let aig = testcases::toggle_chain(4, true, true);
+        let net = decompose_and2(&aig);
+        for i in 0..net.nb_nodes() {
+            assert!(net.gate(i).is_and() || !net.gate(i).is_comb());
+        }
+        check_equivalence_bounded(&aig, &net, 4, false).unwrap();
+
+        let mut aig = Network::new();
+        let v: Vec<Signal> = (0..4).map(|_| aig.add_input()).collect();
+        let lut = Lut::random(4);
+        let o = aig.add(Gate::lut(&v, lut));
+        aig.add_output(o);
+        let o = aig.add(Gate::maj(v[0], !v[1], v[2]));
+        aig.add_output(!o);
+        let o = aig.add(Gate::Nary(v.clone().into(), crate::network::NaryType::Nor));
+        aig.add_output(o);
+        check_equivalence_bounded(&aig, &decompose_and2(&aig), 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_single_gates() {
+        let lib = library();
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let x = aig.xor(a, b);
+        aig.add_output(x);
+        let mapped = map_cells(&aig, &lib, None);
+        check_mapped(&aig, &mapped, &lib, 1);
+        assert_eq!(mapped.cell_counts(&lib)[4], 1);
+        assert_eq!(mapped.area(&lib), 5.0);
+
+        let mut aig = Network::new();
+        aig.add_inputs(3);
+        let ab = aig.and(a, b);
+        let o = aig.and(!ab, !c);
+        aig.add_output(o);
+        let mapped = map_cells(&aig, &lib, None);
+        check_mapped(&aig, &mapped, &lib, 1);
+        assert_eq!(mapped.network().nb_nodes(), 1);
+        assert_eq!(mapped.area(&lib), 3.0);
+    }
+
+    #[test]
+    fn test_inverted_outputs() {
+        let lib = library();
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let x = aig.and(a, b);
+        aig.add_output(x);
+        aig.add_output(!x);
+        aig.add_output(!a);
+        aig.add_output(Signal::one());
+        let mapped = map_cells(&aig, &lib, None);
+        check_mapped(&aig, &mapped, &lib, 1);
+        assert_eq!(mapped.network().output(3), Signal::one());
+    }
+
+    #[test]
+    fn test_adder_delay() {
+        let lib = library();
+        let aig = adder::ripple_carry(8);
+        let area_mapped = map_cells(&aig, &lib, None);
+        check_mapped(&aig, &area_mapped, &lib, 1);
+        let delay_mapped = map_cells(&aig, &lib, Some(0.0));
+        check_mapped(&aig, &delay_mapped, &lib, 1);
+        assert!(area_mapped.area(&lib) <= delay_mapped.area(&lib) + 1e-9);
+        assert!(delay_mapped.delay(&lib) <= area_mapped.delay(&lib) + 1e-9);
+
+        // Relaxing the target allows a smaller area, within the target
+        let target = delay_mapped.delay(&lib) * 1.5;
+        let relaxed = map_cells(&aig, &lib, Some(target));
+        check_mapped(&aig, &relaxed, &lib, 1);
+        assert!(relaxed.delay(&lib) <= target + 1e-9);
+        assert!(relaxed.area(&lib) <= delay_mapped.area(&lib) + 1e-9);
+    }
+
+    #[test]
+    fn test_sequential() {
+        let lib = library();
+        let aig = testcases::toggle_chain(5, true, true);
+        let mapped = map_cells(&aig, &lib, None);
+        check_mapped(&aig, &mapped, &lib, 4);
+    }
+}
@@ -0,0 +1,482 @@
+//! Technology mapping to K-input LUTs for FPGAs
+//!
+//! Mapping is based on priority cuts. A depth-optimal mapping is computed first, then the area
+//! is recovered without increasing the depth, using area flow and then exact area.
+
+use crate::network::area::AreaParameters;
+use crate::network::cuts::{Cut, CutParameters, Cuts};
+use crate::network::NaryType;
+use crate::optim::decompose_luts;
+use crate::{Gate, Network, Signal};
+
+/// Split And and Xor gates with more than k inputs into trees of smaller gates
+fn split_wide_gates(aig: &mut Network, k: usize) {
+    let mut changed = false;
+    for i in 0..aig.nb_nodes() {
+        let Gate::Nary(v, tp) = aig.gate(i) else {
+            continue;
+        };
+        if v.len() <= k {
+            continue;
+        }
+        let tp = *tp;
+        let (leaf_tp, out_inv) = match tp {
+            NaryType::And | NaryType::Nand => (NaryType::And, tp == NaryType::Nand),
+            NaryType::Or | NaryType::Nor => (NaryType::Or, tp == NaryType::Nor),
+            NaryType::Xor | NaryType::Xnor => (NaryType::Xor, tp == NaryType::Xnor),
+        };
+        let mut v: Vec<Signal> = v.to_vec();
+        while v.len() > k {
+            v = v
+                .chunks(k)
+                .map(|c| {
+                    if c.len() == 1 {
+                        c[0]
+                    } else {
+                        aig.add(Gate::Nary(c.into(), leaf_tp))
+                    }
+                })
+                .collect();
+        }
+        let out_tp = match (leaf_tp, out_inv) {
+            (NaryType::And, true) => NaryType::Nand,
+            (NaryType::Or, true) => NaryType::Nor,
+            (NaryType::Xor, true) => NaryType::Xnor,
+            _ => leaf_tp,
+        };
+        aig.replace(i, Gate::Nary(v.into(), out_tp));
+        changed = true;
+    }
+    if changed {
+        aig.topo_sort();
+    }
+}
+
+/// Mapping state: selected cut for each node, and the cover it induces
+struct LutMapper<'a> {
+    aig: &'a Network,
+    cuts: Cuts,
+    /// Index of the selected cut for each node
+    choice: Vec<usize>,
+    /// Arrival time of each node with the selected cuts
+    arrival: Vec<usize>,
+    /// Area flow of each node with the selected cuts
+    area_flow: Vec<f64>,
+    /// Required time of each node in the current cover
+    required: Vec<usize>,
+    /// Number of references of each node in the current cover
+    refs: Vec<usize>,
+    /// Structural fanout of each node
+    fanout: Vec<usize>,
+}
+
+impl<'a> LutMapper<'a> {
+    fn new(aig: &'a Network, k: usize) -> LutMapper<'a> {
+        let params = CutParameters {
+            max_size: k,
+            ..Default::default()
+        };
+        let mut fanout = vec![0; aig.nb_nodes()];
+        for i in 0..aig.nb_nodes() {
+            for v in aig.gate(i).vars() {
+                fanout[v as usize] += 1;
+            }
+        }
+        for o in 0..aig.nb_outputs() {
+            if aig.output(o).is_var() {
+                fanout[aig.output(o).var() as usize] += 1;
+            }
+        }
+        LutMapper {
+            aig,
+            cuts: params.enumerate(aig),
+            choice: vec![0; aig.nb_nodes()],
+            arrival: vec![0; aig.nb_nodes()],
+            area_flow: vec![0.0; aig.nb_nodes()],
+            required: vec![usize::MAX; aig.nb_nodes()],
+            refs: vec![0; aig.nb_nodes()],
+            fanout,
+        }
+    }
+
+    /// Whether a node is mapped to a LUT
+    fn is_lut(&self, i: usize) -> bool {
+        self.aig.gate(i).is_comb()
+    }
+
+    /// Nodes of the leaves of a cut that are mapped to LUTs
+    fn lut_leaves<'b>(&'b self, cut: &'b Cut) -> impl Iterator<Item = usize> + 'b {
+        cut.leaves()
+            .iter()
+            .filter(|s| s.is_var())
+            .map(|s| s.var() as usize)
+            .filter(|i| self.is_lut(*i))
+    }
+
+    /// Candidate cuts of a node, excluding the trivial cut
+    fn candidates(&self, i: usize) -> impl Iterator<Item = (usize, &Cut)> {
+        let node = self.aig.node(i);
+        self.cuts
+            .node_cuts(i)
+            .iter()
+            .enumerate()
+            .filter(move |(_, c)| !c.is_trivial(node))
+    }
+
+    /// Arrival time of a cut with the current selection
+    fn cut_arrival(&self, cut: &Cut) -> usize {
+        1 + self
+            .lut_leaves(cut)
+            .map(|l| self.arrival[l])
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Area flow of a cut with the current selection
+    ///
+    /// The area of the leaves is shared between their fanouts, using the references of the
+    /// current cover if available.
+    fn cut_area_flow(&self, cut: &Cut) -> f64 {
+        1.0 + self
+            .lut_leaves(cut)
+            .map(|l| {
+                let nb_refs = if self.refs[l] != 0 {
+                    self.refs[l]
+                } else {
+                    self.fanout[l]
+                };
+                self.area_flow[l] / nb_refs.max(1) as f64
+            })
+            .sum::<f64>()
+    }
+
+    /// Select the cut of each node, minimizing depth and then area flow
+    fn select_depth(&mut self) {
+        for i in 0..self.aig.nb_nodes() {
+            if !self.is_lut(i) {
+                continue;
+            }
+            let best = self
+                .candidates(i)
+                .map(|(j, c)| (self.cut_arrival(c), self.cut_area_flow(c), j))
+                .min_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)))
+                .expect("No cut available for the node");
+            self.set_choice(i, best.2);
+        }
+    }
+
+    /// Select the cut of each node, minimizing area flow without violating the required times
+    fn select_area_flow(&mut self) {
+        for i in 0..self.aig.nb_nodes() {
+            if !self.is_lut(i) {
+                continue;
+            }
+            let best = self
+                .candidates(i)
+                .map(|(j, c)| (self.cut_arrival(c), self.cut_area_flow(c), j))
+                .filter(|(a, _, _)| *a <= self.required[i])
+                .min_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+            if let Some(best) = best {
+                self.set_choice(i, best.2);
+            }
+        }
+    }
+
+    /// Select the cut of each node in the cover, minimizing exact area without violating the
+    /// required times
+    fn select_exact_area(&mut self) {
+        for i in 0..self.aig.nb_nodes() {
+            if !self.is_lut(i) || self.refs[i] == 0 {
+                continue;
+            }
+            self.deref_cut(i, self.choice[i]);
+            let mut best = (usize::MAX, self.choice[i]);
+            let candidates: Vec<usize> = self
+                .candidates(i)
+                .filter(|(_, c)| self.cut_arrival(c) <= self.required[i])
+                .map(|(j, _)| j)
+                .collect();
+            for j in candidates {
+                let area = self.ref_cut(i, j);
+                self.deref_cut(i, j);
+                if area < best.0 {
+                    best = (area, j);
+                }
+            }
+            self.ref_cut(i, best.1);
+            self.set_choice(i, best.1);
+        }
+    }
+
+    /// Select a cut for a node and update its arrival time and area flow
+    fn set_choice(&mut self, i: usize, j: usize) {
+        let cut = &self.cuts.node_cuts(i)[j];
+        self.arrival[i] = self.cut_arrival(cut);
+        self.area_flow[i] = self.cut_area_flow(cut);
+        self.choice[i] = j;
+    }
+
+    /// Reference the leaves of a cut, recursively selecting unreferenced leaves
+    ///
+    /// Returns the number of LUTs added to the cover.
+    fn ref_cut(&mut self, i: usize, j: usize) -> usize {
+        let leaves: Vec<usize> = self.lut_leaves(&self.cuts.node_cuts(i)[j]).collect();
+        let mut area = 1;
+        for l in leaves {
+            if self.refs[l] == 0 {
+                area += self.ref_cut(l, self.choice[l]);
+            }
+            self.refs[l] += 1;
+        }
+        area
+    }
+
+    /// Dereference the leaves of a cut, recursively removing unreferenced leaves
+    ///
+    /// Returns the number of LUTs removed from the cover.
+    fn deref_cut(&mut self, i: usize, j: usize) -> usize {
+        let leaves: Vec<usize> = self.lut_leaves(&self.cuts.node_cuts(i)[j]).collect();
+        let mut area = 1;
+        for l in leaves {
+            self.refs[l] -= 1;
+            if self.refs[l] == 0 {
+                area += self.deref_cut(l, self.choice[l]);
+            }
+        }
+        area
+    }
+
+    /// Signals that must be available: outputs and flip-flop inputs
+    fn roots(&self) -> Vec<Signal> {
+        let mut ret: Vec<Signal> = (0..self.aig.nb_outputs())
+            .map(|o| self.aig.output(o))
+            .collect();
+        for i in 0..self.aig.nb_nodes() {
+            if !self.aig.gate(i).is_comb() {
+                ret.extend(self.aig.gate(i).dependencies());
+            }
+        }
+        ret
+    }
+
+    /// Compute the cover from the current selection, with its references and required times
+    fn compute_cover(&mut self) {
+        self.refs = vec![0; self.aig.nb_nodes()];
+        let roots: Vec<usize> = self
+            .roots()
+            .iter()
+            .filter(|s| s.is_var() && self.is_lut(s.var() as usize))
+            .map(|s| s.var() as usize)
+            .collect();
+        let depth = roots.iter().map(|i| self.arrival[*i]).max().unwrap_or(0);
+        self.required = vec![usize::MAX; self.aig.nb_nodes()];
+        for i in roots {
+            if self.refs[i] == 0 {
+                self.ref_cut(i, self.choice[i]);
+            }
+            self.refs[i] += 1;
+            self.required[i] = depth;
+        }
+        for i in (0..self.aig.nb_nodes()).rev() {
+            if !self.is_lut(i) || self.refs[i] == 0 {
+                continue;
+            }
+            let req = self.required[i] - 1;
+            let leaves: Vec<usize> = self
+                .lut_leaves(&self.cuts.node_cuts(i)[self.choice[i]])
+                .collect();
+            for l in leaves {
+                self.required[l] = self.required[l].min(req);
+            }
+        }
+    }
+
+    /// Build the mapped network
+    ///
+    /// LUTs that are only used inverted by outputs and flip-flops are inverted, so that no
+    /// additional inverter is required.
+    fn build(&self) -> Network {
+        let mut used_pos = vec![false; self.aig.nb_nodes()];
+        let mut used_neg = vec![false; self.aig.nb_nodes()];
+        for s in self.roots() {
+            if s.is_var() {
+                if s.is_inverted() {
+                    used_neg[s.var() as usize] = true;
+                } else {
+                    used_pos[s.var() as usize] = true;
+                }
+            }
+        }
+        for i in 0..self.aig.nb_nodes() {
+            if self.is_lut(i) && self.refs[i] != 0 {
+                for l in self.lut_leaves(&self.cuts.node_cuts(i)[self.choice[i]]) {
+                    used_pos[l] = true;
+                }
+            }
+        }
+        let inverted: Vec<bool> = (0..self.aig.nb_nodes())
+            .map(|i| used_neg[i] && !used_pos[i])
+            .collect();
+        let t = |s: &Signal| {
+            if s.is_var() && inverted[s.var() as usize] {
+                !s
+            } else {
+                *s
+            }
+        };
+
+        let mut ret = self.aig.clone();
+        for i in 0..self.aig.nb_nodes() {
+            if !self.is_lut(i) {
+                ret.replace(i, self.aig.gate(i).remap(t));
+                continue;
+            }
+            if self.refs[i] == 0 {
+                continue;
+            }
+            let cut = &self.cuts.node_cuts(i)[self.choice[i]];
+            let function = if t(&self.aig.node(i)).is_inverted() {
+                !cut.function()
+            } else {
+                cut.function().clone()
+            };
+            let g = if cut.size() == 0 {
+                Gate::Buf(Signal::from(function.value(0)))
+            } else {
+                Gate::lut(cut.leaves(), function)
+            };
+            ret.replace(i, g);
+        }
+        for o in 0..ret.nb_outputs() {
+            ret.replace_output(o, t(&ret.output(o)));
+        }
+        ret.cleanup();
+        ret
+    }
+}
+
+/// Map a network to K-input LUTs
+///
+/// After mapping, the network only contains Lut gates and flip-flops. The mapping minimizes the
+/// number of LUTs on the longest path, then the number of LUTs.
+/// Lut gates with more than k inputs are decomposed into smaller gates before mapping.
+pub fn map_luts(aig: &mut Network, k: usize) {
+    assert!((3..=8).contains(&k), "LUT size must be between 3 and 8");
+    let mut net = aig.clone();
+    net.make_canonical();
+    let has_wide_luts =
+        (0..net.nb_nodes()).any(|i| matches!(net.gate(i), Gate::Lut(lut) if lut.inputs.len() > k));
+    if has_wide_luts {
+        decompose_luts(&mut net, &AreaParameters::fpga());
+    }
+    split_wide_gates(&mut net, k);
+    for i in 0..net.nb_nodes() {
+        assert!(
+            net.gate(i).dependencies().len() <= k || !net.gate(i).is_comb(),
+            "Gate with more than {k} inputs cannot be mapped"
+        );
+    }
+
+    let mut mapper = LutMapper::new(&net, k);
+    mapper.select_depth();
+    mapper.compute_cover();
+    mapper.select_area_flow();
+    mapper.compute_cover();
+    mapper.select_exact_area();
+    mapper.compute_cover();
+    *aig = mapper.build();
+}
+
+#[cfg(test)]
+mod tests {
+    use volute::Lut;
+
+    use super::map_luts;
+    use crate::equiv::check_equivalence_bounded;
+    use crate::network::generators::{adder, testcases};
+    use crate::{Gate, Network, Signal};
+
+    /// Check that a network only contains LUTs of the given size and flip-flops
+    fn check_mapped(aig: &Network, k: usize) {
+        for i in 0..aig.nb_nodes() {
+            match aig.gate(i) {
+                Gate::Lut(lut) => assert!(lut.inputs.len() <= k),
+                Gate::Dff(_) => (),
+                g => panic!("Unexpected gate {g:?}"),
+            }
+        }
+    }
+
+    /// Number of LUTs on the longest path
+    fn depth(aig: &Network) -> usize {
+        let mut d = vec![0; aig.nb_nodes()];
+        for i in 0..aig.nb_nodes() {
+            if aig.gate(i).is_comb() {
+                d[i] = 1 + aig.gate(i).vars().map(|v| d[v as usize]).max().unwrap_or(0);
+            }
+        }
+        d.into_iter().max().unwrap_or(0)
+    }
+
+    #[test]
+    fn test_and_tree() {
+        let mut aig = Network::new();
+        let inputs: Vec<Signal> = (0..16).map(|_| aig.add_input()).collect();
+        let mut v = inputs.clone();
+        while v.len() > 1 {
+            v = v.chunks(2).map(|c| aig.and(c[0], !c[1])).collect();
+        }
+        aig.add_output(v[0]);
+        let mut mapped = aig.clone();
+        map_luts(&mut mapped, 4);
+        check_mapped(&mapped, 4);
+        assert_eq!(mapped.nb_nodes(), 5);
+        assert_eq!(depth(&mapped), 2);
+        check_equivalence_bounded(&aig, &mapped, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_wide_gate() {
+        let mut aig = Network::new();
+        let inputs: Vec<Signal> = (0..20).map(|_| aig.add_input()).collect();
+        let o = aig.add(Gate::xorn(&inputs));
+        aig.add_output(!o);
+        let mut mapped = aig.clone();
+        map_luts(&mut mapped, 6);
+        check_mapped(&mapped, 6);
+        assert_eq!(depth(&mapped), 2);
+        check_equivalence_bounded(&aig, &mapped, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_wide_lut() {
+        let mut aig = Network::new();
+        let inputs: Vec<Signal> = (0..6).map(|_| aig.add_input()).collect();
+        let o = aig.add(Gate::lut(&inputs, Lut::threshold(6, 4)));
+        aig.add_output(o);
+        let mut mapped = aig.clone();
+        map_luts(&mut mapped, 4);
+        check_mapped(&mapped, 4);
+        check_equivalence_bounded(&aig, &mapped, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_adder() {
+        let aig = adder::ripple_carry(8);
+        for k in 3..=6 {
+            let mut mapped = aig.clone();
+            map_luts(&mut mapped, k);
+            check_mapped(&mapped, k);
+            check_equivalence_bounded(&aig, &mapped, 1, false).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_sequential() {
+        let aig = testcases::toggle_chain(5, true, true);
+        let mut mapped = aig.clone();
+        map_luts(&mut mapped, 4);
+        check_mapped(&mapped, 4);
+        check_equivalence_bounded(&aig, &mapped, 4, false).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+//! Redundancy removal, using the untestable faults found by ATPG
+//!
+//! A stuck-at fault that no input pattern can detect means that the connection can be replaced
+//! by the constant without changing the function of the network.
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use crate::atpg::{apply_fault, expose_dff, find_pattern_detecting_fault};
+use crate::sim::{detects_faults, detects_faults_multi, Fault};
+use crate::{Gate, Network, Signal};
+
+/// Number of random 64-bit words simulated at once to filter out testable faults
+const NB_RANDOM_WORDS: usize = 4;
+
+/// Returns whether applying the fault would leave the network unchanged
+fn is_trivial(aig: &Network, fault: &Fault) -> bool {
+    match *fault {
+        Fault::OutputStuckAtFault { gate, value } => {
+            *aig.gate(gate) == Gate::Buf(Signal::from(value))
+        }
+        Fault::InputStuckAtFault { gate, input, value } => {
+            aig.gate(gate).dependencies()[input] == Signal::from(value)
+        }
+    }
+}
+
+/// Remove faults detected by random patterns
+fn filter_random(aig: &Network, faults: &mut Vec<Fault>, rng: &mut SmallRng) {
+    loop {
+        let nb_before = faults.len();
+        for _ in 0..NB_RANDOM_WORDS {
+            let pattern = (0..aig.nb_inputs()).map(|_| rng.gen::<u64>()).collect();
+            let detected = detects_faults_multi(aig, &pattern, faults);
+            let mut it = detected.iter();
+            faults.retain(|_| *it.next().unwrap() == 0);
+        }
+        // Stop when random simulation becomes ineffective
+        if faults.is_empty() || 100 * (nb_before - faults.len()) < nb_before {
+            return;
+        }
+    }
+}
+
+/// Find untestable faults with a SAT solver, and apply them to the network
+///
+/// Returns whether the network was modified.
+fn remove_untestable(aig: &mut Network, rng: &mut SmallRng) -> bool {
+    let mut exposed = expose_dff(aig);
+    // Faults on flip-flops do not have the same meaning in the exposed network
+    let mut faults: Vec<Fault> = Fault::all_unique(&exposed)
+        .into_iter()
+        .filter(|f| {
+            let gate = match f {
+                Fault::OutputStuckAtFault { gate, .. } => *gate,
+                Fault::InputStuckAtFault { gate, .. } => *gate,
+            };
+            aig.gate(gate).is_comb() && !is_trivial(aig, f)
+        })
+        .collect();
+    filter_random(&exposed, &mut faults, rng);
+
+    let mut changed = false;
+    while let Some(fault) = faults.pop() {
+        if is_trivial(&exposed, &fault) {
+            continue;
+        }
+        match find_pattern_detecting_fault(&exposed, fault) {
+            Some(pattern) => {
+                let detected = detects_faults(&exposed, &pattern, &faults);
+                let mut it = detected.iter();
+                faults.retain(|_| !*it.next().unwrap());
+            }
+            None => {
+                // Node indices are the same in both networks
+                apply_fault(&mut exposed, fault);
+                apply_fault(aig, fault);
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// Remove redundant logic, by replacing connections with untestable stuck-at faults by constants
+///
+/// Faults are first filtered by random simulation, and the remaining ones are proved untestable
+/// with a SAT solver. Since removing a redundancy may make other faults testable or untestable,
+/// this is repeated until nothing changes.
+/// Flip-flops are handled as free inputs and outputs, so that only combinatorial redundancies are
+/// found.
+pub fn remove_redundancies(aig: &mut Network) {
+    let mut rng = SmallRng::seed_from_u64(1);
+    if !aig.is_topo_sorted() {
+        aig.topo_sort();
+    }
+    aig.make_canonical();
+    while remove_untestable(aig, &mut rng) {
+        aig.make_canonical();
+        aig.cleanup();
+    }
+    aig.cleanup();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::remove_redundancies;
+    use crate::equiv::check_equivalence_bounded;
+    use crate::network::generators::adder;
+    use crate::{Gate, Network, Signal};
+
+    #[test]
+    fn test_consensus() {
+        // a.b + !a.c + b.c: the consensus term b.c is redundant
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let x = aig.and(a, b);
+        let y = aig.and(!a, c);
+        let z = aig.and(b, c);
+        let o = aig.add(Gate::andn(&[!x, !y, !z]));
+        aig.add_output(!o);
+        let mut opt = aig.clone();
+        remove_redundancies(&mut opt);
+        assert_eq!(opt.nb_nodes(), 3);
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_redundant_input() {
+        // a & (a | b) = a
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let x = !aig.and(!a, !b);
+        let y = aig.and(a, x);
+        aig.add_output(y);
+        let mut opt = aig.clone();
+        remove_redundancies(&mut opt);
+        assert_eq!(opt.nb_nodes(), 0);
+        assert_eq!(opt.output(0), a);
+    }
+
+    #[test]
+    fn test_sequential() {
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let q = Signal::from_var(0);
+        aig.add(Gate::dff(
+            Signal::from_var(2),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        let x = aig.and(q, a);
+        let y = aig.and(x, !q);
+        let z = aig.xor(y, b);
+        aig.add_output(z);
+        aig.add_output(q);
+        let mut opt = aig.clone();
+        remove_redundancies(&mut opt);
+        // Only the flip-flop remains, with a constant input
+        assert_eq!(opt.nb_nodes(), 1);
+        assert_eq!(opt.gate(0).dependencies()[0], Signal::zero());
+        check_equivalence_bounded(&aig, &opt, 3, false).unwrap();
+    }
+
+    #[test]
+    fn test_adder() {
+        let aig = adder::ripple_carry(4);
+        let mut opt = aig.clone();
+        remove_redundancies(&mut opt);
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+    }
+}
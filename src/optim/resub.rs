@@ -0,0 +1,579 @@
+//! Resubstitution, re-expressing nodes with existing signals
+//!
+//! For each node, divisors are collected in a window of its transitive fanin. Simulation is used to
+//! find a function of at most two gates of the divisors that matches the node, and the candidate is
+//! proved equivalent with a SAT solver before replacing the node.
+
+use fxhash::FxHashMap;
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+
+use crate::equiv::Prover;
+use crate::network::area::AreaParameters;
+use crate::network::{cone_area, RefCounts};
+use crate::sim::{SimpleSimulator, NB_RANDOM_WORDS};
+use crate::{Gate, Network, Signal};
+
+/// Number of levels of the transitive fanin explored to find divisors
+const MAX_LEVELS: usize = 4;
+
+/// Maximum number of divisors in the transitive fanin of a node
+const MAX_DIVISORS: usize = 24;
+
+/// Maximum number of additional divisors outside of the transitive fanin of a node
+const MAX_SIDE_DIVISORS: usize = 16;
+
+/// Maximum number of candidates refuted by SAT for a node
+const MAX_TRIES: usize = 4;
+
+/// A replacement for a node, as a function of existing signals
+#[derive(Clone, Debug)]
+enum Resub {
+    /// An existing signal
+    Copy(Signal),
+    /// A single gate with an optional inverted output
+    One(Gate, bool),
+    /// A gate on an existing signal and on the output of an inner gate, with optional inverted
+    /// outputs
+    Two {
+        is_xor: bool,
+        signal: Signal,
+        inner: Gate,
+        inner_inv: bool,
+        inv: bool,
+    },
+}
+
+impl Resub {
+    /// Create the logic for the replacement, adding each new gate with the given function
+    fn build(&self, mut add_gate: impl FnMut(Gate) -> Signal) -> Signal {
+        match self {
+            Resub::Copy(s) => *s,
+            Resub::One(g, inv) => add_gate(g.clone()) ^ *inv,
+            Resub::Two {
+                is_xor,
+                signal,
+                inner,
+                inner_inv,
+                inv,
+            } => {
+                let x = add_gate(inner.clone()) ^ *inner_inv;
+                let g = if *is_xor {
+                    Gate::xor(*signal, x)
+                } else {
+                    Gate::and(*signal, x)
+                };
+                add_gate(g) ^ *inv
+            }
+        }
+    }
+}
+
+/// Resubstitution engine, with the simulation values of all nodes
+struct Resubstitution<'a> {
+    /// Network being modified
+    aig: Network,
+    params: &'a AreaParameters,
+    /// Number of references to each node
+    refs: RefCounts,
+    /// Gates using each input
+    input_fanouts: Vec<Vec<usize>>,
+    /// Gates using each node
+    node_fanouts: Vec<Vec<usize>>,
+    /// Simulation values of the inputs, one vector per input
+    input_values: Vec<Vec<u64>>,
+    /// Simulation values of the nodes, one vector per node
+    node_values: Vec<Vec<u64>>,
+    /// Incremental prover for the candidates
+    prover: Prover,
+    rng: SmallRng,
+}
+
+impl<'a> Resubstitution<'a> {
+    fn new(aig: Network, params: &'a AreaParameters) -> Resubstitution<'a> {
+        let mut ret = Resubstitution {
+            refs: RefCounts::new(&aig),
+            input_fanouts: vec![Vec::new(); aig.nb_inputs()],
+            node_fanouts: vec![Vec::new(); aig.nb_nodes()],
+            input_values: vec![Vec::new(); aig.nb_inputs()],
+            node_values: vec![Vec::new(); aig.nb_nodes()],
+            aig,
+            params,
+            prover: Prover::new(),
+            rng: SmallRng::seed_from_u64(1),
+        };
+        for i in 0..ret.aig.nb_nodes() {
+            ret.add_fanouts(i);
+        }
+        ret
+    }
+
+    /// Register a gate in the fanouts of its inputs
+    fn add_fanouts(&mut self, i: usize) {
+        for s in self.aig.gate(i).dependencies() {
+            if s.is_input() {
+                self.input_fanouts[s.input() as usize].push(i);
+            } else if s.is_var() {
+                self.node_fanouts[s.var() as usize].push(i);
+            }
+        }
+    }
+
+    /// Gates using a signal
+    fn fanouts(&self, s: Signal) -> &[usize] {
+        if s.is_input() {
+            &self.input_fanouts[s.input() as usize]
+        } else if s.is_var() {
+            &self.node_fanouts[s.var() as usize]
+        } else {
+            &[]
+        }
+    }
+
+    /// Simulate one word, with the given values for the inputs and the flip-flops
+    ///
+    /// The network must be topologically sorted, so this is only used before any modification.
+    fn add_word(&mut self, inputs: Vec<u64>, dffs: Vec<u64>) {
+        let node_values = SimpleSimulator::simulate_word(&self.aig, &inputs, &dffs);
+        for (v, x) in self.input_values.iter_mut().zip(inputs) {
+            v.push(x);
+        }
+        for (v, x) in self.node_values.iter_mut().zip(node_values) {
+            v.push(x);
+        }
+    }
+
+    /// Simulate a counterexample on the modified network
+    ///
+    /// The network is not topologically sorted anymore, so the nodes are simulated recursively.
+    fn add_counterexample(&mut self, inputs: Vec<bool>, dffs: Vec<bool>) {
+        let word = |b: bool| if b { !0u64 } else { 0u64 };
+        for (v, x) in self.input_values.iter_mut().zip(inputs) {
+            v.push(word(x));
+        }
+        let mut dff_values = dffs.into_iter();
+        let mut done: Vec<bool> = (0..self.aig.nb_nodes())
+            .map(|i| !self.aig.gate(i).is_comb())
+            .collect();
+        for (v, is_dff) in self.node_values.iter_mut().zip(&done) {
+            if *is_dff {
+                v.push(word(dff_values.next().unwrap()));
+            }
+        }
+        for i in 0..self.aig.nb_nodes() {
+            let mut stack = vec![i];
+            while let Some(n) = stack.pop() {
+                if done[n] {
+                    continue;
+                }
+                let pending: Vec<usize> = self
+                    .aig
+                    .gate(n)
+                    .vars()
+                    .map(|v| v as usize)
+                    .filter(|v| !done[*v])
+                    .collect();
+                if pending.is_empty() {
+                    let v = self.eval_last(n);
+                    self.node_values[n].push(v);
+                    done[n] = true;
+                } else {
+                    stack.push(n);
+                    stack.extend(pending);
+                }
+            }
+        }
+    }
+
+    /// Compute the last simulation word of a node from its inputs
+    fn eval_last(&self, i: usize) -> u64 {
+        let g = self.aig.gate(i);
+        let mut net = Network::new();
+        net.add_inputs(g.dependencies().len());
+        // The inversions are taken into account in the input values
+        let local = g.remap_with_ind(|_, ind| net.input(ind));
+        net.add(local);
+        let mut sim = SimpleSimulator::from_aig(&net);
+        let values: Vec<u64> = g
+            .dependencies()
+            .iter()
+            .map(|s| *self.values(*s).last().unwrap())
+            .collect();
+        sim.copy_inputs(&values);
+        sim.run_comb();
+        sim.node_values[0]
+    }
+
+    /// Simulate random words
+    fn add_random_words(&mut self, nb_words: usize) {
+        for (inputs, dffs) in SimpleSimulator::random_words(&self.aig, nb_words, &mut self.rng) {
+            self.add_word(inputs, dffs);
+        }
+    }
+
+    /// Simulation values of a signal
+    fn values(&self, s: Signal) -> Vec<u64> {
+        let nb_words = self.input_values.first().map(|v| v.len());
+        let v = if s.is_constant() {
+            vec![0; nb_words.unwrap_or(self.node_values[0].len())]
+        } else if s.is_input() {
+            self.input_values[s.input() as usize].clone()
+        } else {
+            self.node_values[s.var() as usize].clone()
+        };
+        let inv = if s.is_inverted() { !0 } else { 0 };
+        v.into_iter().map(|x| x ^ inv).collect()
+    }
+
+    /// Simulation values of a 2-input gate
+    fn gate_values(&self, g: &Gate) -> Vec<u64> {
+        let a = self.values(g.dependencies()[0]);
+        let b = self.values(g.dependencies()[1]);
+        if g.is_xor() {
+            a.iter().zip(b).map(|(x, y)| x ^ y).collect()
+        } else {
+            a.iter().zip(b).map(|(x, y)| x & y).collect()
+        }
+    }
+
+    /// Collect the divisors of a node, excluding the logic that would be removed with it
+    ///
+    /// The divisors are the signals of its transitive fanin, and the gates whose inputs are all
+    /// divisors.
+    fn divisors(&self, i: usize, mffc: &[usize]) -> Vec<Signal> {
+        let mut ret = self.fanin_divisors(i, mffc);
+        let max_len = ret.len() + MAX_SIDE_DIVISORS;
+        let mut pos = 0;
+        while pos < ret.len() && ret.len() < max_len {
+            for &j in self.fanouts(ret[pos]) {
+                let g = self.aig.gate(j);
+                let s = self.aig.node(j);
+                let usable = j != i
+                    && g.is_comb()
+                    && !matches!(g, Gate::Buf(_))
+                    && self.refs.get(j) > 0
+                    && !mffc.contains(&j)
+                    && !ret.contains(&s)
+                    && g.dependencies()
+                        .iter()
+                        .all(|d| d.is_constant() || ret.contains(&d.without_inversion()));
+                if usable && ret.len() < max_len {
+                    ret.push(s);
+                }
+            }
+            pos += 1;
+        }
+        ret
+    }
+
+    /// Collect the divisors of a node in its transitive fanin
+    fn fanin_divisors(&self, i: usize, mffc: &[usize]) -> Vec<Signal> {
+        let mut ret: Vec<Signal> = Vec::new();
+        let mut level: Vec<Signal> = vec![self.aig.node(i)];
+        for _ in 0..MAX_LEVELS {
+            let mut next = Vec::new();
+            for s in level {
+                if !s.is_var() || !self.aig.gate(s.var() as usize).is_comb() {
+                    continue;
+                }
+                for d in self.aig.gate(s.var() as usize).dependencies() {
+                    let d = d.without_inversion();
+                    if d.is_constant() || ret.contains(&d) {
+                        continue;
+                    }
+                    next.push(d);
+                    if !d.is_var() || !mffc.contains(&(d.var() as usize)) {
+                        ret.push(d);
+                        if ret.len() >= MAX_DIVISORS {
+                            return ret;
+                        }
+                    }
+                }
+            }
+            level = next;
+        }
+        ret
+    }
+
+    /// Find the cheapest replacement for a node with the divisors, with an area below the limit
+    fn find_resub(&self, target: &[u64], divs: &[Signal], max_cost: usize) -> Option<Resub> {
+        let inv_target: Vec<u64> = target.iter().map(|x| !x).collect();
+        let values: Vec<Vec<u64>> = divs.iter().map(|d| self.values(*d)).collect();
+
+        // Existing signal
+        for s in std::iter::once(Signal::zero()).chain(divs.iter().copied()) {
+            let v = self.values(s);
+            if v == target {
+                return Some(Resub::Copy(s));
+            }
+            if v == inv_target {
+                return Some(Resub::Copy(!s));
+            }
+        }
+
+        // All single gates and their complements, by their simulation values
+        let mut single: FxHashMap<Vec<u64>, (Gate, bool)> = FxHashMap::default();
+        let mut add_single = |v: Vec<u64>, g: Gate| {
+            let inv_v = v.iter().map(|x| !x).collect();
+            single.entry(v).or_insert((g.clone(), false));
+            single.entry(inv_v).or_insert((g, true));
+        };
+        for i in 0..divs.len() {
+            for j in i + 1..divs.len() {
+                let v = values[i]
+                    .iter()
+                    .zip(&values[j])
+                    .map(|(a, b)| a ^ b)
+                    .collect();
+                add_single(v, Gate::xor(divs[i], divs[j]));
+                for (pi, pj) in [(false, false), (false, true), (true, false), (true, true)] {
+                    let mi = if pi { !0 } else { 0 };
+                    let mj = if pj { !0 } else { 0 };
+                    let v = values[i]
+                        .iter()
+                        .zip(&values[j])
+                        .map(|(a, b)| (a ^ mi) & (b ^ mj))
+                        .collect();
+                    add_single(v, Gate::and(divs[i] ^ pi, divs[j] ^ pj));
+                }
+            }
+        }
+        let mut best: Option<(usize, Resub)> = None;
+        let consider = |best: &mut Option<(usize, Resub)>, cost: usize, r: Resub| {
+            if cost < max_cost && best.as_ref().is_none_or(|b| cost < b.0) {
+                *best = Some((cost, r));
+            }
+        };
+        if let Some((g, inv)) = single.get(target) {
+            consider(
+                &mut best,
+                self.params.gate_area(g),
+                Resub::One(g.clone(), *inv),
+            );
+            return best.map(|(_, r)| r);
+        }
+
+        // Two gates: a Xor or And of a divisor and a single gate
+        for (d, v) in divs.iter().zip(&values) {
+            let rest: Vec<u64> = target.iter().zip(v).map(|(a, b)| a ^ b).collect();
+            if let Some((g, inv)) = single.get(&rest) {
+                consider(
+                    &mut best,
+                    self.params.xor + self.params.gate_area(g),
+                    Resub::Two {
+                        is_xor: true,
+                        signal: *d,
+                        inner: g.clone(),
+                        inner_inv: *inv,
+                        inv: false,
+                    },
+                );
+            }
+        }
+        for (t, inv) in [(target, false), (inv_target.as_slice(), true)] {
+            for (d, v) in divs.iter().zip(&values) {
+                for pol in [false, true] {
+                    let m = if pol { !0 } else { 0 };
+                    // The divisor must be true whenever the target is true
+                    if t.iter().zip(v).any(|(a, b)| a & !(b ^ m) != 0) {
+                        continue;
+                    }
+                    for (sv, (g, inner_inv)) in &single {
+                        let matches = t
+                            .iter()
+                            .zip(v)
+                            .zip(sv)
+                            .all(|((a, b), c)| (a ^ c) & (b ^ m) == 0);
+                        if matches {
+                            consider(
+                                &mut best,
+                                self.params.and + self.params.gate_area(g),
+                                Resub::Two {
+                                    is_xor: false,
+                                    signal: *d ^ pol,
+                                    inner: g.clone(),
+                                    inner_inv: *inner_inv,
+                                    inv,
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        best.map(|(_, r)| r)
+    }
+
+    /// Add a gate to the network, with its simulation values
+    fn add_gate(&mut self, g: Gate) -> Signal {
+        let v = self.gate_values(&g);
+        let s = self.aig.add(g);
+        self.node_values.push(v);
+        self.refs.resize(&self.aig);
+        self.node_fanouts.push(Vec::new());
+        self.add_fanouts(s.var() as usize);
+        s
+    }
+
+    /// Try to replace a node by a cheaper function of its divisors
+    fn resub_node(&mut self, i: usize) {
+        for _ in 0..MAX_TRIES {
+            let mffc = self.refs.deref_node(&self.aig, i, &[]);
+            let gain = cone_area(&self.aig, &mffc, self.params);
+            let divs = self.divisors(i, &mffc);
+            self.refs.ref_node(&self.aig, i, &[]);
+            let target = self.node_values[i].clone();
+            let Some(r) = self.find_resub(&target, &divs, gain) else {
+                return;
+            };
+            // The new gates are only added to the prover, so that rejected candidates leave no
+            // logic behind
+            let first = self.aig.nb_nodes();
+            let mut gates = Vec::new();
+            let s = r.build(|g| {
+                gates.push(g);
+                Signal::from_var((first + gates.len() - 1) as u32)
+            });
+            match self
+                .prover
+                .find_difference_with(&self.aig, self.aig.node(i), s, &gates)
+            {
+                None => {
+                    let s = r.build(|g| self.add_gate(g));
+                    self.refs.deref_node(&self.aig, i, &[]);
+                    self.aig.replace(i, Gate::Buf(s));
+                    self.refs.ref_node(&self.aig, i, &[]);
+                    return;
+                }
+                Some((inputs, dffs)) => self.add_counterexample(inputs, dffs),
+            }
+        }
+    }
+
+    fn run(&mut self) {
+        self.add_random_words(NB_RANDOM_WORDS);
+        for i in 0..self.aig.nb_nodes() {
+            let g = self.aig.gate(i);
+            if !g.is_comb() || matches!(g, Gate::Buf(_)) || self.refs.get(i) == 0 {
+                continue;
+            }
+            self.resub_node(i);
+        }
+    }
+}
+
+/// Replace nodes by cheaper functions of existing signals, using simulation and SAT
+///
+/// For each node, divisors are collected in a window of its transitive fanin. Candidate
+/// replacements using at most two new gates are found by simulation, and used if they reduce the
+/// area and are proved equivalent with a SAT solver.
+/// Flip-flops are handled as free variables, so that only combinatorial equivalences are used.
+pub fn resub(aig: &mut Network, params: &AreaParameters) {
+    let mut net = aig.clone();
+    if !net.is_topo_sorted() {
+        net.topo_sort();
+    }
+    let mut r = Resubstitution::new(net, params);
+    r.run();
+    let mut net = r.aig;
+    net.topo_sort();
+    net.make_canonical();
+    net.cleanup();
+    *aig = net;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resub;
+    use crate::equiv::check_equivalence_bounded;
+    use crate::network::area::AreaParameters;
+    use crate::network::generators::adder;
+    use crate::{Gate, Network, Signal};
+
+    #[test]
+    fn test_copy() {
+        // (a & b) & (a & c) is the existing (a & b) & c
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let ab = aig.and(a, b);
+        let abc = aig.and(ab, c);
+        let ac = aig.and(a, c);
+        let x = aig.and(ab, ac);
+        aig.add_output(abc);
+        aig.add_output(x);
+        let mut opt = aig.clone();
+        resub(&mut opt, &AreaParameters::vlsi());
+        assert_eq!(opt.nb_nodes(), 2);
+        assert_eq!(opt.output(0), opt.output(1));
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_one_gate() {
+        // (a | b) & !(a & b) is a ^ b
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let o = !aig.and(!a, !b);
+        let n = aig.and(a, b);
+        let x = aig.and(o, !n);
+        aig.add_output(x);
+        let mut opt = aig.clone();
+        resub(&mut opt, &AreaParameters::vlsi());
+        assert_eq!(opt.nb_nodes(), 1);
+        assert!(opt.gate(0).is_xor());
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_two_gates() {
+        // (a & b) | (a & c) | (a & d) is a & (b | c | d), where b | c exists
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let d = aig.add_input();
+        let bc = !aig.and(!b, !c);
+        let ab = aig.and(a, b);
+        let ac = aig.and(a, c);
+        let ad = aig.and(a, d);
+        let x = aig.add(Gate::andn(&[!ab, !ac, !ad]));
+        aig.add_output(!x);
+        aig.add_output(bc);
+        let mut opt = aig.clone();
+        resub(&mut opt, &AreaParameters::vlsi());
+        assert_eq!(opt.nb_nodes(), 3);
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_sequential() {
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let q = Signal::from_var(0);
+        aig.add(Gate::dff(
+            Signal::from_var(4),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        let o = !aig.and(!a, !q);
+        let n = aig.and(a, q);
+        let x = aig.and(o, !n);
+        let y = aig.and(x, a);
+        aig.add_output(y);
+        let mut opt = aig.clone();
+        resub(&mut opt, &AreaParameters::vlsi());
+        assert!(opt.nb_nodes() < aig.nb_nodes());
+        check_equivalence_bounded(&aig, &opt, 4, false).unwrap();
+    }
+
+    #[test]
+    fn test_adder() {
+        let aig = adder::ripple_carry(8);
+        let mut opt = aig.clone();
+        resub(&mut opt, &AreaParameters::vlsi());
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+    }
+}
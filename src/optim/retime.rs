@@ -0,0 +1,720 @@
+//! Retiming: moving flip-flops across combinatorial gates
+//!
+//! The network is seen as a graph whose vertices are the combinatorial gates, and whose edges
+//! carry a number of flip-flops. Only flip-flops with a constant enable and no reset can be moved;
+//! other flip-flops are kept in place, like the inputs and outputs.
+//!
+//! Flip-flops are only moved forward, from the inputs of a gate to its output. The initial value
+//! of the new flip-flops is then obtained by evaluating the gate on the initial values of the old
+//! ones, so that the zero initial state is preserved exactly.
+
+use std::collections::VecDeque;
+
+use fxhash::FxHashMap;
+
+use crate::network::cuts::eval_gate;
+use crate::network::timing::DelayParameters;
+use crate::{Gate, Network, Signal};
+
+/// Source of an edge of the retiming graph
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Source {
+    /// Constant zero
+    Zero,
+    /// Primary input
+    Input(usize),
+    /// Combinatorial gate or fixed flip-flop
+    Node(usize),
+}
+
+/// Connection through buffers and movable flip-flops
+#[derive(Clone, Debug)]
+struct Edge {
+    source: Source,
+    /// Whether the connection is inverted
+    inv: bool,
+    /// Value of the source before the initial state, as stored in each flip-flop: the first
+    /// element is the value one cycle before, and so on
+    history: Vec<bool>,
+}
+
+/// Minimal max-flow graph, used to compute minimum register cuts
+struct FlowGraph {
+    adj: Vec<Vec<usize>>,
+    to: Vec<usize>,
+    cap: Vec<usize>,
+}
+
+/// Capacity of the edges that cannot be cut
+const INFINITY: usize = usize::MAX / 2;
+
+impl FlowGraph {
+    fn new(nb_nodes: usize) -> FlowGraph {
+        FlowGraph {
+            adj: vec![Vec::new(); nb_nodes],
+            to: Vec::new(),
+            cap: Vec::new(),
+        }
+    }
+
+    fn add_edge(&mut self, a: usize, b: usize, cap: usize) {
+        self.adj[a].push(self.to.len());
+        self.to.push(b);
+        self.cap.push(cap);
+        self.adj[b].push(self.to.len());
+        self.to.push(a);
+        self.cap.push(0);
+    }
+
+    /// Nodes reachable from the source in the residual graph, with the edge used to reach them
+    fn reachable(&self, s: usize) -> Vec<Option<usize>> {
+        let mut pred = vec![None; self.adj.len()];
+        pred[s] = Some(usize::MAX);
+        let mut queue = VecDeque::from([s]);
+        while let Some(n) = queue.pop_front() {
+            for &e in &self.adj[n] {
+                let m = self.to[e];
+                if self.cap[e] > 0 && pred[m].is_none() {
+                    pred[m] = Some(e);
+                    queue.push_back(m);
+                }
+            }
+        }
+        pred
+    }
+
+    /// Augment the flow until it is maximal or reaches the limit, and return it
+    fn max_flow(&mut self, s: usize, t: usize, limit: usize) -> usize {
+        let mut flow = 0;
+        while flow < limit {
+            let pred = self.reachable(s);
+            if pred[t].is_none() {
+                break;
+            }
+            let mut path = Vec::new();
+            let mut n = t;
+            while n != s {
+                let e = pred[n].unwrap();
+                path.push(e);
+                n = self.to[e ^ 1];
+            }
+            let f = path.iter().map(|e| self.cap[*e]).min().unwrap();
+            for e in path {
+                self.cap[e] -= f;
+                self.cap[e ^ 1] += f;
+            }
+            flow += f;
+        }
+        flow
+    }
+}
+
+/// Retiming engine
+struct Retimer<'a> {
+    aig: &'a Network,
+    /// Combinatorial gates, in topological order
+    vertices: Vec<usize>,
+    /// Whether each node is a combinatorial gate of the graph
+    is_vertex: Vec<bool>,
+    /// Delay of each node
+    delay: Vec<usize>,
+    /// Edges to the dependencies of each node, followed by the outputs
+    fanins: Vec<Vec<Edge>>,
+    /// Readers of each source, as the reader and the index of the edge
+    fanouts: Vec<Vec<(usize, usize)>>,
+    /// Number of flip-flops moved forward across each node
+    lag: Vec<usize>,
+}
+
+/// Whether each node is a flip-flop that can be moved
+///
+/// Flip-flops that form a loop without any combinatorial gate are kept in place.
+fn movable_dffs(aig: &Network) -> Vec<bool> {
+    let mut movable: Vec<bool> = (0..aig.nb_nodes())
+        .map(|i| match aig.gate(i) {
+            Gate::Dff([_, en, res]) => *en == Signal::one() && *res == Signal::zero(),
+            _ => false,
+        })
+        .collect();
+    for i in 0..aig.nb_nodes() {
+        if !movable[i] {
+            continue;
+        }
+        let mut s = aig.node(i);
+        for _ in 0..aig.nb_nodes() {
+            if !s.is_var() {
+                break;
+            }
+            let j = s.var() as usize;
+            s = match aig.gate(j) {
+                Gate::Buf(t) => *t,
+                Gate::Dff([d, _, _]) if movable[j] => *d,
+                _ => break,
+            };
+            if s.is_var() && s.var() as usize == i {
+                movable[i] = false;
+                break;
+            }
+        }
+    }
+    movable
+}
+
+/// Follow a signal through buffers and movable flip-flops
+fn follow(aig: &Network, movable: &[bool], s: Signal) -> Edge {
+    let mut s = s;
+    let mut dffs = Vec::new();
+    while s.is_var() {
+        let i = s.var() as usize;
+        match aig.gate(i) {
+            Gate::Buf(t) => s = *t ^ s.is_inverted(),
+            Gate::Dff([d, _, _]) if movable[i] => {
+                dffs.push(s.is_inverted());
+                s = *d ^ s.is_inverted();
+            }
+            _ => break,
+        }
+    }
+    let inv = s.is_inverted();
+    let source = if s.is_constant() {
+        Source::Zero
+    } else if s.is_input() {
+        Source::Input(s.input() as usize)
+    } else {
+        Source::Node(s.var() as usize)
+    };
+    // A flip-flop holding zero means that the source had the value of the inversion in between
+    let history = dffs.iter().rev().map(|d| d ^ inv).collect();
+    Edge {
+        source,
+        inv,
+        history,
+    }
+}
+
+impl<'a> Retimer<'a> {
+    fn new(aig: &'a Network) -> Retimer<'a> {
+        let movable = movable_dffs(aig);
+        let vertices: Vec<usize> = (0..aig.nb_nodes())
+            .filter(|i| {
+                let g = aig.gate(*i);
+                g.is_comb() && !matches!(g, Gate::Buf(_))
+            })
+            .collect();
+        let mut is_vertex = vec![false; aig.nb_nodes()];
+        let mut delay = vec![0; aig.nb_nodes()];
+        let params = DelayParameters::unit();
+        for &v in &vertices {
+            is_vertex[v] = true;
+            delay[v] = params.gate_delay(aig.gate(v));
+        }
+        let mut fanins = vec![Vec::new(); aig.nb_nodes() + aig.nb_outputs()];
+        for i in 0..aig.nb_nodes() {
+            if is_vertex[i] || (!aig.gate(i).is_comb() && !movable[i]) {
+                fanins[i] = aig
+                    .gate(i)
+                    .dependencies()
+                    .iter()
+                    .map(|s| follow(aig, &movable, *s))
+                    .collect();
+            }
+        }
+        for o in 0..aig.nb_outputs() {
+            fanins[aig.nb_nodes() + o] = vec![follow(aig, &movable, aig.output(o))];
+        }
+        let mut ret = Retimer {
+            aig,
+            vertices,
+            is_vertex,
+            delay,
+            fanins: Vec::new(),
+            fanouts: Vec::new(),
+            lag: vec![0; aig.nb_nodes() + aig.nb_outputs()],
+        };
+        let mut fanouts = vec![Vec::new(); aig.nb_nodes() + aig.nb_inputs() + 1];
+        for (i, edges) in fanins.iter().enumerate() {
+            for (j, e) in edges.iter().enumerate() {
+                fanouts[ret.source_id(e.source)].push((i, j));
+            }
+        }
+        ret.fanins = fanins;
+        ret.fanouts = fanouts;
+        ret
+    }
+
+    /// Index of a source in the fanout table
+    fn source_id(&self, s: Source) -> usize {
+        match s {
+            Source::Node(i) => i,
+            Source::Input(i) => self.aig.nb_nodes() + i,
+            Source::Zero => self.aig.nb_nodes() + self.aig.nb_inputs(),
+        }
+    }
+
+    /// Number of flip-flops moved across a source
+    fn source_lag(lag: &[usize], s: Source) -> usize {
+        match s {
+            Source::Node(i) => lag[i],
+            _ => 0,
+        }
+    }
+
+    /// Number of flip-flops on an edge after retiming; negative if the retiming is illegal
+    fn weight(lag: &[usize], reader: usize, e: &Edge) -> isize {
+        let w = e.history.len() as isize;
+        let l = lag[reader] as isize;
+        match e.source {
+            // Constants stay constant when flip-flops are moved across them
+            Source::Zero => (w - l).max(0),
+            _ => w - l + Retimer::source_lag(lag, e.source) as isize,
+        }
+    }
+
+    /// Number of flip-flops on the output of each source after retiming, shared by all readers
+    fn chain_length(&self, lag: &[usize], id: usize) -> usize {
+        self.fanouts[id]
+            .iter()
+            .map(|(r, j)| Retimer::weight(lag, *r, &self.fanins[*r][*j]) as usize)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Number of flip-flops after retiming
+    fn nb_dffs(&self, lag: &[usize]) -> usize {
+        (0..self.fanouts.len())
+            .map(|id| self.chain_length(lag, id))
+            .sum()
+    }
+
+    /// Length of the longest combinatorial path starting at each vertex after retiming
+    ///
+    /// Returns None if the retiming is not legal.
+    fn depths(&self, lag: &[usize]) -> Option<Vec<usize>> {
+        let mut nb_pending = vec![0; self.aig.nb_nodes()];
+        for (r, edges) in self.fanins.iter().enumerate() {
+            for e in edges {
+                let w = Retimer::weight(lag, r, e);
+                if w < 0 {
+                    return None;
+                }
+                if let Source::Node(i) = e.source {
+                    if w == 0 && r < self.aig.nb_nodes() && self.is_vertex[r] {
+                        nb_pending[i] += 1;
+                    }
+                }
+            }
+        }
+        let mut depth = vec![0; self.aig.nb_nodes()];
+        let mut queue: Vec<usize> = self
+            .vertices
+            .iter()
+            .cloned()
+            .filter(|v| nb_pending[*v] == 0)
+            .collect();
+        while let Some(v) = queue.pop() {
+            let mut d = 0;
+            for &(r, j) in &self.fanouts[v] {
+                let e = &self.fanins[r][j];
+                if r < self.aig.nb_nodes() && self.is_vertex[r] && Retimer::weight(lag, r, e) == 0 {
+                    d = d.max(depth[r]);
+                }
+            }
+            depth[v] = d + self.delay[v];
+            for e in &self.fanins[v] {
+                if let Source::Node(i) = e.source {
+                    if self.is_vertex[i] && Retimer::weight(lag, v, e) == 0 {
+                        nb_pending[i] -= 1;
+                        if nb_pending[i] == 0 {
+                            queue.push(i);
+                        }
+                    }
+                }
+            }
+        }
+        Some(depth)
+    }
+
+    /// Find a retiming with the given logic depth, moving flip-flops forward across the vertices
+    /// that are too deep
+    fn feasible(&self, period: usize) -> Option<Vec<usize>> {
+        let mut lag = vec![0; self.lag.len()];
+        for _ in 0..=self.vertices.len() {
+            let depth = self.depths(&lag)?;
+            let mut changed = false;
+            for &v in &self.vertices {
+                if depth[v] > period {
+                    lag[v] += 1;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return Some(lag);
+            }
+        }
+        None
+    }
+
+    /// Minimize the logic depth by binary search on the period
+    fn min_period(&mut self) {
+        let depth = self.depths(&self.lag).unwrap();
+        let lo = self.vertices.iter().map(|v| self.delay[*v]).max();
+        let hi = self.vertices.iter().map(|v| depth[*v]).max();
+        let (Some(mut lo), Some(mut hi)) = (lo, hi) else {
+            return;
+        };
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            match self.feasible(mid) {
+                Some(lag) => {
+                    self.lag = lag;
+                    hi = mid;
+                }
+                None => lo = mid + 1,
+            }
+        }
+    }
+
+    /// Move flip-flops forward by one gate to reduce their number, using a minimum cut
+    ///
+    /// Each source has a chain of flip-flops on its output, shared by all its readers. A source
+    /// that already has flip-flops loses one if all readers of its last flip-flop are moved
+    /// across, and is unchanged otherwise; if it is moved across itself, it loses none, and gains
+    /// one unless all these readers are moved across as well.
+    ///
+    /// Returns whether the number of flip-flops was reduced.
+    fn reduce_area_step(&mut self) -> bool {
+        let nb_sources = self.fanouts.len();
+        let lengths: Vec<usize> = (0..nb_sources)
+            .map(|id| self.chain_length(&self.lag, id))
+            .collect();
+        let current = lengths.iter().filter(|l| **l > 0).count();
+        if current == 0 {
+            return false;
+        }
+        // Each source has an "active" node and a node to cut, and each vertex a "moved" node
+        let moved = |v: usize| 2 * nb_sources + v;
+        let s = 2 * nb_sources + self.aig.nb_nodes();
+        let t = s + 1;
+        let mut graph = FlowGraph::new(t + 1);
+        for (id, &l) in lengths.iter().enumerate() {
+            let is_vertex = id < self.aig.nb_nodes() && self.is_vertex[id];
+            if l == 0 && !is_vertex {
+                continue;
+            }
+            if l > 0 {
+                graph.add_edge(s, 2 * id, INFINITY);
+            }
+            if is_vertex {
+                graph.add_edge(moved(id), 2 * id, INFINITY);
+                if l > 0 {
+                    // Its flip-flops are not removed by moving the readers
+                    graph.add_edge(moved(id), t, 1);
+                }
+            }
+            graph.add_edge(2 * id, 2 * id + 1, 1);
+            for &(r, j) in &self.fanouts[id] {
+                if Retimer::weight(&self.lag, r, &self.fanins[r][j]) as usize != l {
+                    continue;
+                }
+                if r < self.aig.nb_nodes() && self.is_vertex[r] {
+                    graph.add_edge(2 * id + 1, moved(r), INFINITY);
+                } else {
+                    graph.add_edge(2 * id + 1, t, INFINITY);
+                }
+            }
+        }
+        for &v in &self.vertices {
+            for e in &self.fanins[v] {
+                if Retimer::weight(&self.lag, v, e) != 0 {
+                    continue;
+                }
+                match e.source {
+                    Source::Zero => (),
+                    Source::Node(i) if self.is_vertex[i] => {
+                        graph.add_edge(moved(v), moved(i), INFINITY)
+                    }
+                    _ => graph.add_edge(moved(v), t, INFINITY),
+                }
+            }
+        }
+        if graph.max_flow(s, t, current) >= current {
+            return false;
+        }
+        let reachable = graph.reachable(s);
+        for &v in &self.vertices {
+            if reachable[moved(v)].is_some() {
+                self.lag[v] += 1;
+            }
+        }
+        true
+    }
+
+    /// Minimize the number of flip-flops
+    fn min_area(&mut self) {
+        while self.reduce_area_step() {}
+    }
+
+    /// Value of an edge at a cycle before the vertices' lag, from the values of the vertices
+    fn value_at(values: &[Vec<bool>], e: &Edge, cycle: usize) -> bool {
+        let w = e.history.len();
+        let v = if cycle < w {
+            e.history[w - cycle - 1]
+        } else {
+            match e.source {
+                Source::Zero => false,
+                Source::Node(i) => values[i][cycle - w],
+                Source::Input(_) => panic!("Inputs have no initial value"),
+            }
+        };
+        v ^ e.inv
+    }
+
+    /// Values of the vertices on the first cycles of the original network, one for each
+    /// flip-flop moved across them
+    fn initial_values(&self) -> Vec<Vec<bool>> {
+        let mut values = vec![Vec::new(); self.aig.nb_nodes()];
+        let max_lag = self.lag.iter().cloned().max().unwrap_or(0);
+        for cycle in 0..max_lag {
+            for &v in &self.vertices {
+                if self.lag[v] > cycle {
+                    let inputs: Vec<bool> = self.fanins[v]
+                        .iter()
+                        .map(|e| Retimer::value_at(&values, e, cycle))
+                        .collect();
+                    let val = eval_gate(self.aig.gate(v), &inputs);
+                    values[v].push(val);
+                }
+            }
+        }
+        values
+    }
+
+    /// Build the retimed network
+    fn build(&self) -> Network {
+        let values = self.initial_values();
+        let mut ret = Network::new();
+        ret.add_inputs(self.aig.nb_inputs());
+        for i in 0..self.aig.nb_inputs() {
+            if let Some(name) = self.aig.input_name(i) {
+                ret.set_input_name(i, name);
+            }
+        }
+        let mut node_map = vec![Signal::zero(); self.aig.nb_nodes()];
+        for (i, s) in node_map.iter_mut().enumerate() {
+            if !self.fanins[i].is_empty() {
+                *s = ret.add(Gate::Buf(Signal::zero()));
+                if let Some(name) = self.aig.node_name(i) {
+                    ret.set_node_name(s.var() as usize, name);
+                }
+            }
+        }
+        let mut chains = FxHashMap::default();
+        let mut edge_signal = |ret: &mut Network, r: usize, e: &Edge| -> Signal {
+            let l = Retimer::weight(&self.lag, r, e) as usize;
+            let lag = Retimer::source_lag(&self.lag, e.source);
+            let mut s = match e.source {
+                Source::Zero => Signal::zero(),
+                Source::Input(i) => ret.input(i),
+                Source::Node(i) => node_map[i],
+            };
+            let mut init = Vec::new();
+            for j in 1..=l {
+                let b = if j <= lag {
+                    values[self.source_id(e.source)][lag - j]
+                } else {
+                    e.history[j - lag - 1]
+                };
+                init.push(b);
+                // Flip-flops with an initial value of one are stored inverted
+                s = *chains
+                    .entry((e.source, init.clone()))
+                    .or_insert_with(|| ret.dff(s ^ b, Signal::one(), Signal::zero()) ^ b);
+            }
+            s ^ e.inv
+        };
+        for (i, s) in node_map.iter().enumerate() {
+            if self.fanins[i].is_empty() {
+                continue;
+            }
+            let deps: Vec<Signal> = self.fanins[i]
+                .iter()
+                .map(|e| edge_signal(&mut ret, i, e))
+                .collect();
+            let g = self.aig.gate(i).remap_with_ind(|_, j| deps[j]);
+            ret.replace(s.var() as usize, g);
+        }
+        for o in 0..self.aig.nb_outputs() {
+            let r = self.aig.nb_nodes() + o;
+            let s = edge_signal(&mut ret, r, &self.fanins[r][0]);
+            ret.add_output(s);
+            if let Some(name) = self.aig.output_name(o) {
+                ret.set_output_name(o, name);
+            }
+        }
+        ret.topo_sort();
+        ret.make_canonical();
+        ret.cleanup();
+        ret
+    }
+}
+
+/// Retime a network with the given objective, and return the logic depth
+fn retime(aig: &mut Network, min_period: bool) -> usize {
+    if !aig.is_topo_sorted() {
+        aig.topo_sort();
+    }
+    let mut retimer = Retimer::new(aig);
+    let nb_dffs = retimer.nb_dffs(&retimer.lag);
+    if min_period {
+        retimer.min_period();
+    } else {
+        retimer.min_area();
+    }
+    if retimer.lag.iter().any(|l| *l > 0) || retimer.nb_dffs(&retimer.lag) < nb_dffs {
+        *aig = retimer.build();
+    }
+    DelayParameters::unit().max_delay(aig)
+}
+
+/// Retime a network to minimize its logic depth
+///
+/// Flip-flops with a constant enable and no reset are moved forward across combinatorial gates,
+/// for pipeline balancing. The logic depth achieved is returned, with each gate counting as one
+/// level.
+pub fn retime_min_period(aig: &mut Network) -> usize {
+    retime(aig, true)
+}
+
+/// Retime a network to minimize its number of flip-flops
+///
+/// Flip-flops with a constant enable and no reset are moved forward across combinatorial gates,
+/// regardless of the logic depth, until no move reduces their number. The logic depth achieved is
+/// returned, with each gate counting as one level.
+pub fn retime_min_area(aig: &mut Network) -> usize {
+    retime(aig, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{retime_min_area, retime_min_period};
+    use crate::equiv::check_equivalence_bounded;
+    use crate::network::stats::stats;
+    use crate::network::timing::DelayParameters;
+    use crate::{Gate, Network, Signal};
+
+    /// Add a flip-flop without enable or reset
+    fn dff(aig: &mut Network, d: Signal) -> Signal {
+        aig.dff(d, Signal::one(), Signal::zero())
+    }
+
+    #[test]
+    fn test_pipeline_balancing() {
+        // Two stages of flip-flops on the inputs of a chain of gates
+        let mut aig = Network::new();
+        let mut q = Vec::new();
+        for _ in 0..4 {
+            let a = aig.add_input();
+            let q1 = dff(&mut aig, a);
+            q.push(dff(&mut aig, q1));
+        }
+        let x1 = aig.and(q[0], q[1]);
+        let x2 = aig.and(x1, q[2]);
+        let x3 = aig.and(x2, q[3]);
+        aig.add_output(x3);
+        assert_eq!(DelayParameters::unit().max_delay(&aig), 3);
+        let mut opt = aig.clone();
+        let depth = retime_min_period(&mut opt);
+        assert_eq!(depth, 1);
+        assert_eq!(DelayParameters::unit().max_delay(&opt), 1);
+        check_equivalence_bounded(&aig, &opt, 5, false).unwrap();
+    }
+
+    #[test]
+    fn test_initial_value() {
+        // The flip-flops are moved across a Nor, whose value is one in the initial state
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let qa = dff(&mut aig, a);
+        let qb = dff(&mut aig, b);
+        let x = aig.and(!qa, !qb);
+        let y = aig.and(x, c);
+        aig.add_output(y);
+        let mut opt = aig.clone();
+        let depth = retime_min_period(&mut opt);
+        assert_eq!(depth, 1);
+        assert_eq!(stats(&opt).nb_dff, 1);
+        check_equivalence_bounded(&aig, &opt, 4, false).unwrap();
+    }
+
+    #[test]
+    fn test_min_area() {
+        // Three flip-flops merged into one
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let qa = dff(&mut aig, a);
+        let qb = dff(&mut aig, b);
+        let qc = dff(&mut aig, !c);
+        let x = aig.add(Gate::and3(qa, !qb, qc));
+        let y = aig.xor(x, a);
+        aig.add_output(y);
+        let mut opt = aig.clone();
+        let depth = retime_min_area(&mut opt);
+        assert_eq!(depth, 1);
+        assert_eq!(stats(&opt).nb_dff, 1);
+        check_equivalence_bounded(&aig, &opt, 4, false).unwrap();
+    }
+
+    #[test]
+    fn test_fixed() {
+        // Flip-flops with an enable are not moved
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let en = aig.add_input();
+        let qa = aig.dff(a, en, Signal::zero());
+        let qb = aig.dff(b, en, Signal::zero());
+        let x = aig.and(qa, qb);
+        aig.add_output(x);
+        let mut opt = aig.clone();
+        assert_eq!(retime_min_area(&mut opt), 1);
+        assert_eq!(stats(&opt).nb_dff, 2);
+        assert_eq!(retime_min_period(&mut opt), 1);
+        assert_eq!(stats(&opt).nb_dff, 2);
+        check_equivalence_bounded(&aig, &opt, 4, false).unwrap();
+    }
+
+    #[test]
+    fn test_feedback() {
+        // An accumulator and a toggle flip-flop, that loop back on themselves
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let acc = Signal::from_var(0);
+        let toggle = Signal::from_var(1);
+        aig.add(Gate::dff(
+            Signal::from_var(2),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        aig.add(Gate::dff(!toggle, Signal::one(), Signal::zero()));
+        let x = aig.xor(acc, a);
+        let qb = dff(&mut aig, b);
+        let y = aig.and(x, qb);
+        let z = aig.and(y, toggle);
+        aig.add_output(z);
+        for area in [false, true] {
+            let mut opt = aig.clone();
+            if area {
+                retime_min_area(&mut opt);
+            } else {
+                retime_min_period(&mut opt);
+            }
+            assert!(stats(&opt).nb_dff <= 3);
+            check_equivalence_bounded(&aig, &opt, 6, false).unwrap();
+        }
+    }
+}
@@ -0,0 +1,614 @@
+//! Local rewriting of 4-input cuts
+//!
+//! Each node is considered with its 4-input cuts. The function of the cut is matched to its NPN
+//! class, for which a small implementation is available in a precomputed database. The cut is
+//! replaced if the new implementation, taking into account the logic that can be shared with the
+//! rest of the network, has a smaller area than the logic it frees.
+//!
+//! The database in `rewrite/npn4.txt` contains an implementation for each of the 222 NPN classes
+//! of 4-input functions, using And, Xor, Mux and Maj gates. It is the implementation with the
+//! smallest VLSI area among trees where Mux and Maj have at least one literal input.
+
+use fxhash::FxHashMap;
+use itertools::Itertools;
+use volute::Lut;
+
+use crate::network::area::AreaParameters;
+use crate::network::cuts::{Cut, CutParameters};
+use crate::network::{cone_area, Normalization, RefCounts};
+use crate::{Gate, Network, Signal};
+
+/// Precomputed implementations, indexed by the truth table of the canonical function
+const DATABASE: &str = include_str!("rewrite/npn4.txt");
+
+/// Implementation of a 4-input function from the database
+///
+/// Inputs are the 4 variables of the function, and the gates are numbered in order.
+#[derive(Clone, Debug)]
+struct Implementation {
+    gates: Vec<Gate>,
+    output: Signal,
+}
+
+/// Parse a signal of the database: a constant, an input `a` to `d` or a gate `xN`
+fn parse_signal(s: &str) -> Signal {
+    if let Some(t) = s.strip_prefix('!') {
+        return !parse_signal(t);
+    }
+    match s {
+        "0" => Signal::zero(),
+        "1" => Signal::one(),
+        "a" | "b" | "c" | "d" => Signal::from_input((s.as_bytes()[0] - b'a') as u32),
+        _ => Signal::from_var(s[1..].parse().unwrap()),
+    }
+}
+
+/// Parse a gate of the database, such as `&(a,!b)`, `^(x0,c)`, `?(a,b,c)` or `M(a,b,c)`
+fn parse_gate(s: &str) -> Gate {
+    let deps: Vec<Signal> = s[2..s.len() - 1].split(',').map(parse_signal).collect();
+    match &s[..1] {
+        "&" => Gate::and(deps[0], deps[1]),
+        "^" => Gate::xor(deps[0], deps[1]),
+        "?" => Gate::mux(deps[0], deps[1], deps[2]),
+        "M" => Gate::maj(deps[0], deps[1], deps[2]),
+        _ => panic!("Unknown gate {} in rewriting database", s),
+    }
+}
+
+/// Load the database of implementations
+fn load_database() -> FxHashMap<u16, Implementation> {
+    let mut ret = FxHashMap::default();
+    for line in DATABASE.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let function = u16::from_str_radix(tokens[0], 16).unwrap();
+        let gates = tokens[1..tokens.len() - 1]
+            .iter()
+            .map(|t| parse_gate(t))
+            .collect();
+        let output = parse_signal(tokens[tokens.len() - 1]);
+        ret.insert(function, Implementation { gates, output });
+    }
+    ret
+}
+
+/// Truth table of a function with up to 4 inputs, extended to 4 inputs
+fn truth_table(lut: &Lut) -> u16 {
+    let mask = (1 << lut.num_vars()) - 1;
+    let mut ret = 0;
+    for m in 0..16 {
+        if lut.value(m & mask) {
+            ret |= 1 << m;
+        }
+    }
+    ret
+}
+
+/// Apply an input permutation and flips to a 4-input function: `x -> f(z) ^ n[4]` where
+/// `z[i] = x[p[i]] ^ n[i]`
+fn npn_transform(f: u16, perm: &[u8], flips: u32) -> u16 {
+    let mut ret = 0;
+    for m in 0..16 {
+        let mut z = 0;
+        for (i, p) in perm.iter().enumerate() {
+            if ((m >> p) & 1 != 0) ^ ((flips >> i) & 1 != 0) {
+                z |= 1 << i;
+            }
+        }
+        if ((f >> z) & 1 != 0) ^ ((flips >> 4) & 1 != 0) {
+            ret |= 1 << m;
+        }
+    }
+    ret
+}
+
+/// NPN canonization of a 4-input function
+///
+/// Returns the canonical function `c`, the permutation `p` and the flips `n` such that
+/// `f(x) = c(z) ^ n[4]` where `z[i] = x[p[i]] ^ n[i]`.
+fn npn_canonization(f: u16) -> (u16, Vec<u8>, u32) {
+    let mut lut = Lut::zero(4);
+    for m in 0..16 {
+        lut.set_value(m, (f >> m) & 1 != 0);
+    }
+    let (c, perm, flips) = lut.npn_canonization();
+    let c = truth_table(&c);
+    if npn_transform(c, &perm, flips) == f {
+        return (c, perm, flips);
+    }
+    // The transformation returned by volute is not always valid, typically when the function
+    // is already canonical: look for one explicitly
+    for perm in (0..4u8).permutations(4) {
+        for flips in 0..32 {
+            if npn_transform(c, &perm, flips) == f {
+                return (c, perm, flips);
+            }
+        }
+    }
+    unreachable!("No NPN transformation found for function {:04x}", f)
+}
+
+/// Cut-based rewriting with reference counting
+struct Rewriter<'a> {
+    aig: Network,
+    params: &'a AreaParameters,
+    database: FxHashMap<u16, Implementation>,
+    /// Number of users of each node, including outputs
+    refs: RefCounts,
+    /// Existing gates, by their canonical form
+    strash: FxHashMap<Gate, Signal>,
+}
+
+impl<'a> Rewriter<'a> {
+    fn new(aig: Network, params: &'a AreaParameters) -> Rewriter<'a> {
+        let refs = RefCounts::new(&aig);
+        let mut strash = FxHashMap::default();
+        for i in 0..aig.nb_nodes() {
+            let g = aig.gate(i);
+            if g.is_comb() {
+                if let Normalization::Node(g, inv) = g.make_canonical() {
+                    strash.entry(g).or_insert(aig.node(i) ^ inv);
+                }
+            }
+        }
+        Rewriter {
+            aig,
+            params,
+            database: load_database(),
+            refs,
+            strash,
+        }
+    }
+
+    /// Remove a reference to the inputs of a node, and return the area of the logic freed,
+    /// stopping at the leaves
+    fn deref_node(&mut self, i: usize, leaves: &[Signal]) -> usize {
+        let freed = self.refs.deref_node(&self.aig, i, leaves);
+        cone_area(&self.aig, &freed, self.params)
+    }
+
+    /// Add a reference to the inputs of a node, stopping at the leaves; opposite of deref_node
+    fn ref_node(&mut self, i: usize, leaves: &[Signal]) {
+        self.refs.ref_node(&self.aig, i, leaves);
+    }
+
+    /// Inputs of the implementation and its output inversion for a cut
+    fn match_cut(&self, cut: &Cut) -> (u16, Vec<Signal>, bool) {
+        let (c, perm, flips) = npn_canonization(truth_table(cut.function()));
+        let leaf = |i: usize| cut.leaves().get(i).copied().unwrap_or(Signal::zero());
+        let inputs = (0..4)
+            .map(|i| leaf(perm[i] as usize) ^ ((flips >> i) & 1 != 0))
+            .collect();
+        (c, inputs, (flips >> 4) & 1 != 0)
+    }
+
+    /// Create the implementation of a function in the network, reusing existing logic
+    ///
+    /// With `dry_run`, nothing is created and only the area of the new logic is returned.
+    /// Returns None if the implementation would use the root node.
+    fn instantiate(
+        &mut self,
+        function: u16,
+        inputs: &[Signal],
+        root: usize,
+        dry_run: bool,
+    ) -> Option<(Signal, usize)> {
+        let imp = self.database[&function].clone();
+        let mut built: Vec<Signal> = Vec::new();
+        let mut cost = 0;
+        let mut next_placeholder = self.aig.nb_nodes() as u32;
+        let translate = |s: &Signal, built: &[Signal]| -> Signal {
+            if s.is_input() {
+                inputs[s.input() as usize] ^ s.is_inverted()
+            } else if s.is_var() {
+                built[s.var() as usize] ^ s.is_inverted()
+            } else {
+                *s
+            }
+        };
+        for g in &imp.gates {
+            let g = g.remap(|s| translate(s, &built));
+            let s = match g.make_canonical() {
+                Normalization::Copy(s) => s,
+                Normalization::Node(g, inv) => match self.strash.get(&g) {
+                    Some(s) if s.var() as usize == root => return None,
+                    Some(s) if !dry_run || self.refs.get(s.var() as usize) > 0 => *s ^ inv,
+                    _ => {
+                        cost += self.params.gate_area(&g);
+                        if dry_run {
+                            next_placeholder += 1;
+                            Signal::from_var(next_placeholder - 1) ^ inv
+                        } else {
+                            let s = self.aig.add(g.clone());
+                            self.refs.resize(&self.aig);
+                            self.strash.insert(g, s);
+                            s ^ inv
+                        }
+                    }
+                },
+            };
+            built.push(s);
+        }
+        Some((translate(&imp.output, &built), cost))
+    }
+
+    /// Area gained by rewriting a node with a cut, if it can be rewritten
+    fn evaluate(&mut self, root: usize, cut: &Cut) -> Option<isize> {
+        let leaves = cut.leaves();
+        if leaves
+            .iter()
+            .any(|s| s.is_var() && self.refs.get(s.var() as usize) == 0)
+        {
+            // Do not revive logic that was already removed
+            return None;
+        }
+        let (function, inputs, _) = self.match_cut(cut);
+        let removed = self.deref_node(root, leaves);
+        let added = self.instantiate(function, &inputs, root, true);
+        self.ref_node(root, leaves);
+        added.map(|(_, cost)| removed as isize - cost as isize)
+    }
+
+    /// Rewrite a node with a cut
+    fn apply(&mut self, root: usize, cut: &Cut) {
+        let (function, inputs, inv) = self.match_cut(cut);
+        let (s, _) = self.instantiate(function, &inputs, root, false).unwrap();
+        self.deref_node(root, &[]);
+        self.aig.replace(root, Gate::Buf(s ^ inv));
+        self.ref_node(root, &[]);
+    }
+
+    fn run(&mut self) {
+        let cuts = CutParameters {
+            max_size: 4,
+            max_cuts: 8,
+            through_dff: false,
+        }
+        .enumerate(&self.aig);
+        for i in 0..self.aig.nb_nodes() {
+            let g = self.aig.gate(i);
+            if !g.is_comb() || matches!(g, Gate::Buf(_) | Gate::Lut(_)) || self.refs.get(i) == 0 {
+                continue;
+            }
+            let mut best: Option<(isize, &Cut)> = None;
+            for cut in cuts.node_cuts(i) {
+                if cut.is_trivial(self.aig.node(i)) || cut.latency() != 0 {
+                    continue;
+                }
+                if let Some(gain) = self.evaluate(i, cut) {
+                    if gain > best.map(|b| b.0).unwrap_or(0) {
+                        best = Some((gain, cut));
+                    }
+                }
+            }
+            if let Some((_, cut)) = best {
+                self.apply(i, cut);
+            }
+        }
+    }
+}
+
+/// Rewrite the network by replacing small cuts with precomputed implementations
+///
+/// 4-input cuts are enumerated for each node, and replaced by the implementation of their NPN
+/// class in the database if this reduces the area. The area of the logic that can be shared with
+/// the rest of the network is not counted.
+pub fn rewrite(aig: &mut Network, params: &AreaParameters) {
+    let mut net = aig.clone();
+    if !net.is_topo_sorted() {
+        net.topo_sort();
+    }
+    let mut rewriter = Rewriter::new(net, params);
+    rewriter.run();
+    let mut net = rewriter.aig;
+    net.topo_sort();
+    net.make_canonical();
+    net.cleanup();
+    *aig = net;
+}
+
+#[cfg(test)]
+mod tests {
+    use fxhash::FxHashMap;
+    use itertools::Itertools;
+
+    use super::{load_database, npn_canonization, npn_transform, rewrite};
+    use crate::equiv::check_equivalence_bounded;
+    use crate::network::area::AreaParameters;
+    use crate::network::generators::adder;
+    use crate::network::{BinaryType, TernaryType};
+    use crate::{Gate, Network, Signal};
+
+    /// Truth tables of the 4 input variables
+    const VARS: [u16; 4] = [0xAAAA, 0xCCCC, 0xF0F0, 0xFF00];
+
+    /// Truth table of a signal of an implementation
+    fn signal_value(s: Signal, values: &[u16]) -> u16 {
+        let v = if s.is_input() {
+            VARS[s.input() as usize]
+        } else if s.is_var() {
+            values[s.var() as usize]
+        } else {
+            0
+        };
+        if s.is_inverted() {
+            !v
+        } else {
+            v
+        }
+    }
+
+    /// Truth table of a gate, given the truth tables of its inputs
+    fn op_value(g: &Gate, v: &[u16]) -> u16 {
+        match g {
+            Gate::Binary(_, BinaryType::And) => v[0] & v[1],
+            Gate::Binary(_, BinaryType::Xor) => v[0] ^ v[1],
+            Gate::Ternary(_, TernaryType::Mux) => (v[0] & v[1]) | (!v[0] & v[2]),
+            Gate::Ternary(_, TernaryType::Maj) => (v[0] & v[1]) | (v[0] & v[2]) | (v[1] & v[2]),
+            _ => panic!("Unexpected gate in rewriting database"),
+        }
+    }
+
+    /// Truth table of a gate of an implementation
+    fn gate_value(g: &Gate, values: &[u16]) -> u16 {
+        let v: Vec<u16> = g
+            .dependencies()
+            .iter()
+            .map(|s| signal_value(*s, values))
+            .collect();
+        op_value(g, &v)
+    }
+
+    #[test]
+    fn test_database() {
+        let db = load_database();
+        assert_eq!(db.len(), 222);
+        for (f, imp) in db.iter() {
+            assert_eq!(npn_canonization(*f).0, *f);
+            let mut values = Vec::new();
+            for g in &imp.gates {
+                values.push(gate_value(g, &values));
+            }
+            assert_eq!(signal_value(imp.output, &values), *f);
+        }
+    }
+
+    #[test]
+    fn test_canonization() {
+        for f in [
+            0x0000u16, 0xffff, 0x0001, 0x1234, 0x6996, 0x8000, 0xcafe, 0x0f0f,
+        ] {
+            let (c, perm, flips) = npn_canonization(f);
+            assert_eq!(npn_transform(c, &perm, flips), f);
+        }
+    }
+
+    #[test]
+    fn test_redundant_mux() {
+        // (a & b) | (!a & b) is just b
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let x = aig.and(a, b);
+        let y = aig.and(!a, b);
+        let z = aig.and(!x, !y);
+        aig.add_output(!z);
+        let mut rewritten = aig.clone();
+        rewrite(&mut rewritten, &AreaParameters::vlsi());
+        assert_eq!(rewritten.nb_nodes(), 0);
+        assert_eq!(rewritten.output(0), b);
+    }
+
+    #[test]
+    fn test_xor_from_and() {
+        // Xor3 built from And gates becomes smaller
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let xor = |aig: &mut Network, x: Signal, y: Signal| {
+            let n1 = aig.and(x, !y);
+            let n2 = aig.and(!x, y);
+            !aig.and(!n1, !n2)
+        };
+        let x = xor(&mut aig, a, b);
+        let y = xor(&mut aig, x, c);
+        aig.add_output(y);
+        let params = AreaParameters::vlsi();
+        let mut rewritten = aig.clone();
+        rewrite(&mut rewritten, &params);
+        assert!(params.area(&rewritten) < params.area(&aig));
+        check_equivalence_bounded(&aig, &rewritten, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_shared_logic() {
+        // Logic used elsewhere is not removed
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let x = aig.and(a, !b);
+        let y = aig.and(!a, b);
+        let z = aig.and(!x, !y);
+        aig.add_output(z);
+        aig.add_output(x);
+        aig.add_output(y);
+        let params = AreaParameters::vlsi();
+        let mut rewritten = aig.clone();
+        rewrite(&mut rewritten, &params);
+        assert!(params.area(&rewritten) <= params.area(&aig));
+        check_equivalence_bounded(&aig, &rewritten, 1, false).unwrap();
+    }
+
+    #[test]
+    fn test_adder() {
+        let aig = adder::ripple_carry(8);
+        let params = AreaParameters::vlsi();
+        let mut rewritten = aig.clone();
+        rewrite(&mut rewritten, &params);
+        assert!(params.area(&rewritten) <= params.area(&aig));
+        check_equivalence_bounded(&aig, &rewritten, 1, false).unwrap();
+    }
+
+    /// Regenerate the database of implementations in `rewrite/npn4.txt`
+    ///
+    /// For each NPN class, this finds the tree of And, Xor, Mux and Maj gates with the smallest
+    /// VLSI area, where Mux and Maj have at least one literal input.
+    /// Run with `cargo test --release regenerate_database -- --ignored`.
+    /// Best tree for a member of each NPN class: cost, gate and input functions
+    type BestTrees = FxHashMap<u16, (usize, Option<(Gate, Vec<u16>)>)>;
+
+    #[test]
+    #[ignore]
+    fn regenerate_database() {
+        let params = AreaParameters::vlsi();
+        let canon: Vec<u16> = (0..=u16::MAX).map(|f| npn_canonization(f).0).collect();
+        let mut members: FxHashMap<u16, Vec<u16>> = FxHashMap::default();
+        for f in 0..=u16::MAX {
+            members.entry(canon[f as usize]).or_default().push(f);
+        }
+        let mut literals = Vec::new();
+        for v in VARS {
+            literals.push(v);
+            literals.push(!v);
+        }
+
+        let mut best: BestTrees = FxHashMap::default();
+        best.insert(canon[0], (0, None));
+        best.insert(canon[VARS[0] as usize], (0, None));
+        let mut changed = true;
+        while changed {
+            changed = false;
+            let known: Vec<(u16, usize)> = best.iter().map(|(f, (c, _))| (*f, *c)).collect();
+            let mut update = |f: u16, cost: usize, g: Gate, inputs: Vec<u16>| {
+                let c = canon[f as usize];
+                if best.get(&c).map(|b| cost < b.0).unwrap_or(true) {
+                    best.insert(c, (cost, Some((g, inputs))));
+                    changed = true;
+                }
+            };
+            let (s0, s1, s2) = (
+                Signal::from_input(0),
+                Signal::from_input(1),
+                Signal::from_input(2),
+            );
+            for (ca, cost_a) in &known {
+                for a in &members[ca] {
+                    let a = *a;
+                    for (b, cost_b) in &known {
+                        let cost = cost_a + cost_b;
+                        for b in [*b, !*b] {
+                            update(a & b, cost + params.and, Gate::and(s0, s1), vec![a, b]);
+                            update(a ^ b, cost + params.xor, Gate::xor(s0, s1), vec![a, b]);
+                            for s in &literals {
+                                let mux = (s & a) | (!s & b);
+                                let maj = (s & a) | (s & b) | (a & b);
+                                let inputs = vec![*s, a, b];
+                                update(mux, cost + params.mux, Gate::mux(s0, s1, s2), inputs);
+                                let inputs = vec![*s, a, b];
+                                update(maj, cost + params.maj, Gate::maj(s0, s1, s2), inputs);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(best.len(), 222);
+
+        // Rebuild the trees as implementations of the canonical functions
+        fn build(
+            f: u16,
+            canon: &[u16],
+            best: &BestTrees,
+            gates: &mut Vec<Gate>,
+            memo: &mut FxHashMap<u16, Signal>,
+        ) -> Signal {
+            if f == 0 {
+                return Signal::zero();
+            }
+            if f == u16::MAX {
+                return Signal::one();
+            }
+            for (i, v) in VARS.iter().enumerate() {
+                if f == *v || f == !*v {
+                    return Signal::from_input(i as u32) ^ (f != *v);
+                }
+            }
+            if let Some(s) = memo.get(&f) {
+                return *s;
+            }
+            if let Some(s) = memo.get(&!f) {
+                return !*s;
+            }
+            // Find the transformation from the member in the database to this function
+            let (g, inputs) = best[&canon[f as usize]].1.clone().unwrap();
+            let member = op_value(&g, &inputs);
+            for perm in (0..4u8).permutations(4) {
+                for flips in 0..32 {
+                    if npn_transform(member, &perm, flips) != f {
+                        continue;
+                    }
+                    let deps: Vec<Signal> = inputs
+                        .iter()
+                        .map(|h| {
+                            build(
+                                npn_transform(*h, &perm, flips & 15),
+                                canon,
+                                best,
+                                gates,
+                                memo,
+                            )
+                        })
+                        .collect();
+                    let gate = g.remap(|s| deps[s.input() as usize]);
+                    gates.push(gate);
+                    let s = Signal::from_var(gates.len() as u32 - 1) ^ ((flips >> 4) & 1 != 0);
+                    memo.insert(f, s);
+                    return s;
+                }
+            }
+            unreachable!()
+        }
+
+        let fmt_signal = |s: Signal| -> String {
+            if s == Signal::zero() {
+                "0".to_owned()
+            } else if s == Signal::one() {
+                "1".to_owned()
+            } else {
+                let inv = if s.is_inverted() { "!" } else { "" };
+                if s.is_input() {
+                    format!("{}{}", inv, (b'a' + s.input() as u8) as char)
+                } else {
+                    format!("{}x{}", inv, s.var())
+                }
+            }
+        };
+        let mut classes: Vec<u16> = best.keys().copied().collect();
+        classes.sort();
+        let mut out = String::new();
+        out.push_str("# Implementations of the NPN classes of 4-input functions\n");
+        out.push_str("# Generated by regenerate_database in rewrite.rs\n");
+        for f in classes {
+            let mut gates = Vec::new();
+            let mut memo = FxHashMap::default();
+            let output = build(f, &canon, &best, &mut gates, &mut memo);
+            out.push_str(&format!("{:04x}", f));
+            for g in &gates {
+                let deps: Vec<String> = g.dependencies().iter().map(|s| fmt_signal(*s)).collect();
+                let op = match g {
+                    Gate::Binary(_, BinaryType::And) => "&",
+                    Gate::Binary(_, BinaryType::Xor) => "^",
+                    Gate::Ternary(_, TernaryType::Mux) => "?",
+                    _ => "M",
+                };
+                out.push_str(&format!(" {}({})", op, deps.join(",")));
+            }
+            out.push_str(&format!(" {}\n", fmt_signal(output)));
+        }
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/optim/rewrite/npn4.txt");
+        std::fs::write(path, out).unwrap();
+    }
+}
@@ -0,0 +1,445 @@
+//! Optimization scripts: sequences of passes parsed from text
+//!
+//! A script is a list of passes separated by semicolons or newlines, each with optional integer
+//! arguments. Comments start with `#` and extend to the end of the line.
+//!
+//! ```
+//! # use quaigh::Network;
+//! # let mut aig = Network::new();
+//! use quaigh::optim::Script;
+//!
+//! let script = Script::parse("canon; share 64; infer-xor; infer-dffe; share").unwrap();
+//! script.run(&mut aig);
+//! ```
+
+use std::fmt;
+
+use crate::io::{Error, Location};
+use crate::network::area::AreaParameters;
+use crate::Network;
+
+use super::{
+    balance, decompose_luts, fraig, infer_dffe, infer_xor_mux, lower_dffe, map_luts, minimize_esop,
+    minimize_sop, optimize_dont_cares, remove_redundancies, resub, retime_min_area,
+    retime_min_period, rewrite, sequential_sweep, share_logic, to_aig, to_mig, to_xag,
+};
+
+/// Integer argument of a pass
+struct PassArg {
+    /// Name of the argument, for the help message
+    name: &'static str,
+    /// Value used when the argument is not given
+    default: usize,
+    /// Smallest allowed value
+    min: usize,
+    /// Largest allowed value
+    max: usize,
+}
+
+/// Description of an optimization pass in the registry
+struct PassInfo {
+    /// Name of the pass in scripts
+    name: &'static str,
+    /// Optional arguments of the pass
+    args: &'static [PassArg],
+    /// Short description of the pass
+    help: &'static str,
+    /// Run the pass with the value of all its arguments
+    run: fn(&mut Network, &[usize]),
+}
+
+/// Flattening limit for N-ary gates, used by sharing and balancing
+const FLATTENING_LIMIT: &[PassArg] = &[PassArg {
+    name: "max_inputs",
+    default: 64,
+    min: 2,
+    max: usize::MAX,
+}];
+
+/// Registry of the passes available in scripts
+const PASSES: &[PassInfo] = &[
+    PassInfo {
+        name: "canon",
+        args: &[],
+        help: "remove unused logic and put gates in canonical form",
+        run: |aig, _| {
+            aig.cleanup();
+            aig.make_canonical();
+        },
+    },
+    PassInfo {
+        name: "cleanup",
+        args: &[],
+        help: "remove unused logic",
+        run: |aig, _| {
+            aig.cleanup();
+        },
+    },
+    PassInfo {
+        name: "dedup",
+        args: &[],
+        help: "merge structurally identical gates",
+        run: |aig, _| {
+            aig.deduplicate();
+        },
+    },
+    PassInfo {
+        name: "share",
+        args: FLATTENING_LIMIT,
+        help: "share logic between N-ary And and Xor gates",
+        run: |aig, args| share_logic(aig, args[0]),
+    },
+    PassInfo {
+        name: "balance",
+        args: FLATTENING_LIMIT,
+        help: "balance And and Xor trees to minimize the logic depth",
+        run: |aig, args| balance(aig, args[0], true),
+    },
+    PassInfo {
+        name: "rewrite",
+        args: &[],
+        help: "rewrite 4-input cuts with optimal implementations",
+        run: |aig, _| rewrite(aig, &AreaParameters::vlsi()),
+    },
+    PassInfo {
+        name: "resub",
+        args: &[],
+        help: "resubstitute gates with existing signals",
+        run: |aig, _| resub(aig, &AreaParameters::vlsi()),
+    },
+    PassInfo {
+        name: "dont-cares",
+        args: &[],
+        help: "simplify gates using their satisfiability and observability don't cares",
+        run: |aig, _| optimize_dont_cares(aig, &AreaParameters::vlsi()),
+    },
+    PassInfo {
+        name: "sop",
+        args: &[],
+        help: "minimize sums of products heuristically",
+        run: |aig, _| minimize_sop(aig, false),
+    },
+    PassInfo {
+        name: "sop-exact",
+        args: &[],
+        help: "minimize sums of products exactly",
+        run: |aig, _| minimize_sop(aig, true),
+    },
+    PassInfo {
+        name: "esop",
+        args: &[PassArg {
+            name: "max_inputs",
+            default: 6,
+            min: 2,
+            max: 8,
+        }],
+        help: "rewrite cuts as exclusive sums of products",
+        run: |aig, args| minimize_esop(aig, args[0], &AreaParameters::vlsi()),
+    },
+    PassInfo {
+        name: "fraig",
+        args: &[],
+        help: "merge functionally equivalent nodes by SAT sweeping",
+        run: |aig, _| fraig(aig),
+    },
+    PassInfo {
+        name: "remove-redundancies",
+        args: &[],
+        help: "replace connections with untestable faults by constants",
+        run: |aig, _| remove_redundancies(aig),
+    },
+    PassInfo {
+        name: "decompose-luts",
+        args: &[],
+        help: "decompose Lut gates into simpler gates",
+        run: |aig, _| decompose_luts(aig, &AreaParameters::vlsi()),
+    },
+    PassInfo {
+        name: "infer-xor",
+        args: &[],
+        help: "infer Xor and Mux gates from And gates",
+        run: |aig, _| infer_xor_mux(aig),
+    },
+    PassInfo {
+        name: "infer-dffe",
+        args: &[],
+        help: "infer flip-flop enables and resets from the logic",
+        run: |aig, _| infer_dffe(aig),
+    },
+    PassInfo {
+        name: "lower-dffe",
+        args: &[],
+        help: "lower flip-flop enables and resets into logic",
+        run: |aig, _| lower_dffe(aig, true, true),
+    },
+    PassInfo {
+        name: "seq-sweep",
+        args: &[],
+        help: "remove constant flip-flops and merge equivalent flip-flops",
+        run: |aig, _| sequential_sweep(aig),
+    },
+    PassInfo {
+        name: "retime",
+        args: &[],
+        help: "retime flip-flops forward to minimize the logic depth",
+        run: |aig, _| {
+            retime_min_period(aig);
+        },
+    },
+    PassInfo {
+        name: "retime-area",
+        args: &[],
+        help: "retime flip-flops forward to minimize their number",
+        run: |aig, _| {
+            retime_min_area(aig);
+        },
+    },
+    PassInfo {
+        name: "aig",
+        args: &[],
+        help: "convert to an And-Inverter graph",
+        run: |aig, _| to_aig(aig),
+    },
+    PassInfo {
+        name: "mig",
+        args: &[],
+        help: "convert to a Majority-Inverter graph",
+        run: |aig, _| to_mig(aig),
+    },
+    PassInfo {
+        name: "xag",
+        args: &[],
+        help: "convert to a Xor-And graph",
+        run: |aig, _| to_xag(aig),
+    },
+    PassInfo {
+        name: "map-luts",
+        args: &[PassArg {
+            name: "k",
+            default: 6,
+            min: 3,
+            max: 8,
+        }],
+        help: "map to K-input LUTs",
+        run: |aig, args| map_luts(aig, args[0]),
+    },
+];
+
+/// Split a statement into whitespace-separated tokens, with their byte offset
+fn split_tokens(statement: &str) -> Vec<(usize, &str)> {
+    let mut ret = Vec::new();
+    let mut start = None;
+    for (i, c) in statement.char_indices() {
+        if c.is_whitespace() {
+            if let Some(s) = start.take() {
+                ret.push((s, &statement[s..i]));
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        ret.push((s, &statement[s..]));
+    }
+    ret
+}
+
+/// A pass of an optimization script, with the value of its arguments
+#[derive(Clone, Debug)]
+pub struct Pass {
+    /// Index of the pass in the registry
+    index: usize,
+    /// Value of all arguments, including defaults
+    args: Vec<usize>,
+    /// Position of the pass in the script
+    loc: Location,
+}
+
+impl Pass {
+    fn info(&self) -> &'static PassInfo {
+        &PASSES[self.index]
+    }
+
+    /// Name of the pass
+    pub fn name(&self) -> &'static str {
+        self.info().name
+    }
+
+    /// Value of the arguments of the pass, including defaults
+    pub fn args(&self) -> &[usize] {
+        &self.args
+    }
+
+    /// Position of the pass in the script
+    pub fn location(&self) -> &Location {
+        &self.loc
+    }
+
+    /// Run the pass on a network
+    pub fn run(&self, aig: &mut Network) {
+        (self.info().run)(aig, &self.args);
+    }
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())?;
+        for a in &self.args {
+            write!(f, " {}", a)?;
+        }
+        Ok(())
+    }
+}
+
+/// An optimization script, as a sequence of passes
+#[derive(Clone, Debug, Default)]
+pub struct Script {
+    passes: Vec<Pass>,
+}
+
+impl Script {
+    /// Parse a script
+    ///
+    /// Unknown passes, and arguments that are missing a pass, out of range or in excess, are
+    /// reported as syntax errors.
+    pub fn parse(text: &str) -> Result<Script, Error> {
+        let mut passes = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap();
+            let mut offset = 0;
+            for statement in line.split(';') {
+                if let Some(pass) = Script::parse_statement(statement, i + 1, offset)? {
+                    passes.push(pass);
+                }
+                offset += statement.len() + 1;
+            }
+        }
+        Ok(Script { passes })
+    }
+
+    /// Parse a single pass, with its position in the script
+    fn parse_statement(statement: &str, line: usize, offset: usize) -> Result<Option<Pass>, Error> {
+        let mut tokens = split_tokens(statement)
+            .into_iter()
+            .map(|(i, t)| (t, Location::new(line, offset + i + 1)));
+        let Some((name, loc)) = tokens.next() else {
+            return Ok(None);
+        };
+        let Some(index) = PASSES.iter().position(|p| p.name == name) else {
+            let names: Vec<&str> = PASSES.iter().map(|p| p.name).collect();
+            let message = format!("unknown pass (available: {})", names.join(", "));
+            return Err(Error::syntax(loc, name, &message));
+        };
+        let info = &PASSES[index];
+        let mut args = Vec::new();
+        for (token, arg_loc) in tokens {
+            let Some(arg) = info.args.get(args.len()) else {
+                let message = format!("too many arguments for pass {}", name);
+                return Err(Error::syntax(arg_loc, token, &message));
+            };
+            match token.parse::<usize>() {
+                Ok(v) if (arg.min..=arg.max).contains(&v) => args.push(v),
+                Ok(_) => {
+                    let message = if arg.max == usize::MAX {
+                        format!("{} must be at least {}", arg.name, arg.min)
+                    } else {
+                        format!("{} must be between {} and {}", arg.name, arg.min, arg.max)
+                    };
+                    return Err(Error::syntax(arg_loc, token, &message));
+                }
+                Err(_) => {
+                    let message = format!("expected an integer for {}", arg.name);
+                    return Err(Error::syntax(arg_loc, token, &message));
+                }
+            }
+        }
+        args.extend(info.args[args.len()..].iter().map(|a| a.default));
+        Ok(Some(Pass { index, args, loc }))
+    }
+
+    /// Passes of the script, in order
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    /// Run all passes of the script on a network
+    pub fn run(&self, aig: &mut Network) {
+        for pass in &self.passes {
+            pass.run(aig);
+        }
+    }
+
+    /// Description of all available passes and their arguments
+    pub fn help() -> String {
+        let mut ret = String::new();
+        for p in PASSES {
+            let mut usage = p.name.to_owned();
+            for a in p.args {
+                usage.push_str(&format!(" [{}={}]", a.name, a.default));
+            }
+            ret.push_str(&format!("  {:<28}{}\n", usage, p.help));
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Script;
+    use crate::equiv::check_equivalence_bounded;
+    use crate::io::Error;
+    use crate::{Gate, Network};
+
+    #[test]
+    fn test_parse() {
+        let script =
+            Script::parse("canon; share 16\n# comment; ignored\n\nbalance;; map-luts 4 # k")
+                .unwrap();
+        let passes: Vec<String> = script.passes().iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            passes,
+            vec!["canon", "share 16", "balance 64", "map-luts 4"]
+        );
+        let loc = script.passes()[3].location();
+        assert_eq!((loc.line, loc.column), (4, 11));
+        assert!(Script::parse("").unwrap().passes().is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let check = |text: &str, token: &str, line: usize, column: usize| {
+            let err = Script::parse(text).unwrap_err();
+            assert!(matches!(err, Error::Syntax { .. }));
+            assert_eq!(err.token(), Some(token));
+            assert_eq!((err.location().line, err.location().column), (line, column));
+        };
+        check("canon; frobnicate", "frobnicate", 1, 8);
+        check("canon\n  share x", "x", 2, 9);
+        check("share 1", "1", 1, 7);
+        check("map-luts 9", "9", 1, 10);
+        check("canon 3", "3", 1, 7);
+        check("share 8 8", "8", 1, 9);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let x = aig.and(a, b);
+        let y = aig.and(x, c);
+        let z = aig.and(!a, !b);
+        let o = aig.add(Gate::and(!x, !z));
+        aig.add_output(y);
+        aig.add_output(!o);
+        let mut opt = aig.clone();
+        Script::parse("canon; share; infer-xor; infer-dffe; share 64")
+            .unwrap()
+            .run(&mut opt);
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+        Script::parse("aig; map-luts 3").unwrap().run(&mut opt);
+        assert!((0..opt.nb_nodes()).all(|i| matches!(opt.gate(i), Gate::Lut(_))));
+        check_equivalence_bounded(&aig, &opt, 1, false).unwrap();
+    }
+}
@@ -0,0 +1,360 @@
+//! Sequential sweeping: removal of constant flip-flops and merging of equivalent flip-flops
+//!
+//! Candidates are found by simulation from the zero initial state, and proved by induction in the
+//! style of register correspondence: assuming that all candidate equivalences hold in a state,
+//! they must hold in the next state. Candidates that fail are refined until a fixpoint is reached.
+
+use std::collections::HashMap;
+
+use fxhash::FxHashMap;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use crate::equiv::{extend_aig_helper, find_difference, state_values, unroll_state};
+use crate::network::NaryType;
+use crate::sim::SimpleSimulator;
+use crate::{Gate, Network, Signal};
+
+/// Number of cycles simulated from the initial state to find the candidates
+const NB_CYCLES: usize = 64;
+
+/// Number of random 64-bit words simulated on the induction step before calling the SAT solver
+const NB_RANDOM_WORDS: usize = 4;
+
+/// Candidate equivalence classes of flip-flops, given by their position in the list of flip-flops
+struct Classes {
+    /// Flip-flops that may be constant zero
+    constant: Vec<usize>,
+    /// Flip-flops that may be equal; the first of each class is its representative
+    classes: Vec<Vec<usize>>,
+}
+
+impl Classes {
+    /// Group flip-flops with the same value, keeping only groups with several of them
+    fn split(members: &[usize], values: &[u64]) -> Vec<Vec<usize>> {
+        let mut groups: FxHashMap<u64, Vec<usize>> = FxHashMap::default();
+        let mut order = Vec::new();
+        for &m in members {
+            let v = values[m];
+            if !groups.contains_key(&v) {
+                order.push(v);
+            }
+            groups.entry(v).or_default().push(m);
+        }
+        order
+            .into_iter()
+            .map(|v| groups.remove(&v).unwrap())
+            .filter(|g| g.len() > 1)
+            .collect()
+    }
+
+    /// Refine the classes with the values of the flip-flops on a set of states
+    ///
+    /// Returns whether the classes changed.
+    fn refine(&mut self, values: &[u64]) -> bool {
+        let mut changed = false;
+        let mut classes = Vec::new();
+        let (constant, other): (Vec<usize>, Vec<usize>) =
+            self.constant.iter().partition(|m| values[**m] == 0);
+        if !other.is_empty() {
+            changed = true;
+            classes.extend(Classes::split(&other, values));
+        }
+        self.constant = constant;
+        for c in &self.classes {
+            let groups = Classes::split(c, values);
+            if groups.len() != 1 || groups[0].len() != c.len() {
+                changed = true;
+            }
+            classes.extend(groups);
+        }
+        self.classes = classes;
+        changed
+    }
+
+    /// Returns whether there is no candidate left
+    fn is_empty(&self) -> bool {
+        self.constant.is_empty() && self.classes.is_empty()
+    }
+}
+
+/// Sequential sweeping engine
+struct Sweeper<'a> {
+    aig: &'a Network,
+    /// Flip-flops of the network
+    dffs: Vec<usize>,
+    classes: Classes,
+    rng: SmallRng,
+}
+
+impl<'a> Sweeper<'a> {
+    fn new(aig: &'a Network) -> Sweeper<'a> {
+        let dffs: Vec<usize> = (0..aig.nb_nodes())
+            .filter(|i| !aig.gate(*i).is_comb())
+            .collect();
+        Sweeper {
+            aig,
+            classes: Classes {
+                constant: (0..dffs.len()).collect(),
+                classes: Vec::new(),
+            },
+            dffs,
+            rng: SmallRng::seed_from_u64(1),
+        }
+    }
+
+    /// Find the initial candidates by simulation from the zero initial state
+    fn simulate_from_init(&mut self) {
+        let mut sim = SimpleSimulator::from_aig(self.aig);
+        for _ in 0..NB_CYCLES {
+            let inputs: Vec<u64> = (0..self.aig.nb_inputs()).map(|_| self.rng.gen()).collect();
+            sim.copy_inputs(&inputs);
+            sim.run_comb();
+            let values: Vec<u64> = self.dffs.iter().map(|i| sim.node_values[*i]).collect();
+            self.classes.refine(&values);
+            sim.run_dff();
+        }
+    }
+
+    /// Build the induction step: the next state of the flip-flops, from a state where all
+    /// candidates hold
+    ///
+    /// The inputs of the network are the state of the representatives followed by the inputs of
+    /// the original network. The outputs are the next state of all flip-flops.
+    fn step_network(&self) -> Network {
+        let mut state = vec![None; self.dffs.len()];
+        for &m in &self.classes.constant {
+            state[m] = Some(Signal::zero());
+        }
+        let mut net = Network::new();
+        for c in &self.classes.classes {
+            let s = net.add_input();
+            for &m in c {
+                state[m] = Some(s);
+            }
+        }
+        let mut t = HashMap::new();
+        for (i, s) in self.dffs.iter().zip(state) {
+            let s = s.unwrap_or_else(|| net.add_input());
+            t.insert(self.aig.node(*i), s);
+            t.insert(!self.aig.node(*i), !s);
+        }
+        extend_aig_helper(&mut net, self.aig, &mut t, false);
+        let t_next = unroll_state(&mut net, self.aig, Some(&t), false);
+        for s in state_values(self.aig, &t_next) {
+            net.add_output(s);
+        }
+        net
+    }
+
+    /// Refine the classes by random simulation of the induction step
+    ///
+    /// Returns whether the classes changed.
+    fn refine_random(&mut self, net: &Network) -> bool {
+        let mut sim = SimpleSimulator::from_aig(net);
+        let mut changed = false;
+        for _ in 0..NB_RANDOM_WORDS {
+            let inputs: Vec<u64> = (0..net.nb_inputs()).map(|_| self.rng.gen()).collect();
+            let values = sim.run(&vec![inputs]).pop().unwrap();
+            changed |= self.classes.refine(&values);
+        }
+        changed
+    }
+
+    /// Prove the induction step with a SAT solver, or refine the classes with a counterexample
+    ///
+    /// Returns whether the classes changed.
+    fn refine_sat(&mut self, net: &Network) -> bool {
+        let mut net = net.clone();
+        let mut diffs = Vec::new();
+        for &m in &self.classes.constant {
+            diffs.push(net.output(m));
+        }
+        for c in &self.classes.classes {
+            for &m in &c[1..] {
+                let d = net.xor(net.output(c[0]), net.output(m));
+                diffs.push(d);
+            }
+        }
+        let miter = net.add(Gate::Nary(diffs.into(), NaryType::Or));
+        match find_difference(&net, miter, Signal::zero()) {
+            None => false,
+            Some((inputs, _)) => {
+                let inputs = inputs
+                    .into_iter()
+                    .map(|b| if b { !0u64 } else { 0u64 })
+                    .collect();
+                let mut sim = SimpleSimulator::from_aig(&net);
+                let values = sim.run(&vec![inputs]).pop().unwrap();
+                let changed = self.classes.refine(&values);
+                assert!(changed);
+                changed
+            }
+        }
+    }
+
+    /// Refine the candidates until they are proved by induction
+    fn run(&mut self) {
+        self.simulate_from_init();
+        while !self.classes.is_empty() {
+            let net = self.step_network();
+            if self.refine_random(&net) {
+                continue;
+            }
+            if !self.refine_sat(&net) {
+                break;
+            }
+        }
+    }
+}
+
+/// Remove constant flip-flops and merge equivalent flip-flops
+///
+/// Flip-flops that stay at zero from the zero initial state, and flip-flops that always have the
+/// same value, are found by simulation and proved by induction, similar to register
+/// correspondence. Contrary to deduplication, this finds flip-flops that are not structurally
+/// identical.
+pub fn sequential_sweep(aig: &mut Network) {
+    if !aig.is_topo_sorted() {
+        aig.topo_sort();
+    }
+    let mut sweeper = Sweeper::new(aig);
+    sweeper.run();
+    let Sweeper { dffs, classes, .. } = sweeper;
+    for m in classes.constant {
+        aig.replace(dffs[m], Gate::Buf(Signal::zero()));
+    }
+    for c in classes.classes {
+        let repr = aig.node(dffs[c[0]]);
+        for &m in &c[1..] {
+            aig.replace(dffs[m], Gate::Buf(repr));
+        }
+    }
+    aig.topo_sort();
+    aig.make_canonical();
+    aig.cleanup();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sequential_sweep;
+    use crate::equiv::check_equivalence_bounded;
+    use crate::network::stats::stats;
+    use crate::{Gate, Network, Signal};
+
+    #[test]
+    fn test_constant() {
+        // The flip-flop is stuck at its reset value
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let q = Signal::from_var(0);
+        aig.add(Gate::dff(
+            Signal::from_var(1),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        let x = aig.and(q, a);
+        aig.add_output(x);
+        let mut opt = aig.clone();
+        sequential_sweep(&mut opt);
+        assert_eq!(opt.nb_nodes(), 0);
+        assert_eq!(opt.output(0), Signal::zero());
+        check_equivalence_bounded(&aig, &opt, 4, false).unwrap();
+    }
+
+    #[test]
+    fn test_constant_induction() {
+        // Two flip-flops that are constant only because the other one is
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let q1 = Signal::from_var(0);
+        let q2 = Signal::from_var(1);
+        aig.add(Gate::dff(
+            Signal::from_var(3),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        aig.add(Gate::dff(
+            Signal::from_var(2),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        let x = aig.and(q1, a);
+        let y = aig.and(q2, b);
+        aig.add_output(x);
+        aig.add_output(y);
+        let mut opt = aig.clone();
+        sequential_sweep(&mut opt);
+        assert_eq!(stats(&opt).nb_dff, 0);
+        check_equivalence_bounded(&aig, &opt, 4, false).unwrap();
+    }
+
+    #[test]
+    fn test_equivalent() {
+        // Two identical toggle flip-flops, that are not structurally identical
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let q1 = Signal::from_var(0);
+        let q2 = Signal::from_var(1);
+        aig.add(Gate::dff(
+            Signal::from_var(2),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        aig.add(Gate::dff(
+            Signal::from_var(3),
+            Signal::one(),
+            Signal::zero(),
+        ));
+        aig.xor(q1, a);
+        aig.xor(q2, a);
+        let o = aig.and(q1, !q2);
+        aig.add_output(q1);
+        aig.add_output(q2);
+        aig.add_output(o);
+        let mut opt = aig.clone();
+        sequential_sweep(&mut opt);
+        assert_eq!(stats(&opt).nb_dff, 1);
+        assert_eq!(opt.output(2), Signal::zero());
+        check_equivalence_bounded(&aig, &opt, 6, false).unwrap();
+    }
+
+    #[test]
+    fn test_pipeline() {
+        // Duplicated pipeline registers with an enable, whose inputs are computed differently
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let en = aig.add_input();
+        let d1 = aig.xor(a, b);
+        let x = aig.and(a, !b);
+        let y = aig.and(!a, b);
+        let d2 = !aig.and(!x, !y);
+        for d in [d1, d2] {
+            let q1 = aig.add(Gate::dff(d, en, Signal::zero()));
+            let q2 = aig.add(Gate::dff(q1, en, Signal::zero()));
+            aig.add_output(q2);
+        }
+        let mut opt = aig.clone();
+        sequential_sweep(&mut opt);
+        assert_eq!(stats(&opt).nb_dff, 2);
+        assert_eq!(opt.output(0), opt.output(1));
+        check_equivalence_bounded(&aig, &opt, 6, false).unwrap();
+    }
+
+    #[test]
+    fn test_not_equivalent() {
+        // A shift register: no flip-flop can be merged
+        let mut aig = Network::new();
+        let a = aig.add_input();
+        let q1 = aig.add(Gate::dff(a, Signal::one(), Signal::zero()));
+        let q2 = aig.add(Gate::dff(q1, Signal::one(), Signal::zero()));
+        let q3 = aig.add(Gate::dff(q2, Signal::one(), Signal::zero()));
+        aig.add_output(q3);
+        let mut opt = aig.clone();
+        sequential_sweep(&mut opt);
+        assert_eq!(stats(&opt).nb_dff, 3);
+        check_equivalence_bounded(&aig, &opt, 6, false).unwrap();
+    }
+}
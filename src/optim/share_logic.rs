@@ -329,7 +329,7 @@ fn factor_gates<F: Fn(&Gate) -> bool, G: Fn(Signal, Signal) -> Gate>(
 ///
 /// Transform large gates into trees of binary gates, sharing as many inputs as possible.
 /// The optimization is performed greedily by merging the most used pair of inputs at each step.
-/// There is no delay optimization yet.
+/// There is no delay optimization: see [`crate::optim::balance`] for depth-oriented rebuilding.
 pub fn factor_nary(aig: &Network) -> Network {
     let aig1 = factor_gates(aig, |g| g.is_and(), |a, b| Gate::and(a, b));
     let aig2 = factor_gates(&aig1, |g| g.is_xor(), |a, b| Gate::xor(a, b));
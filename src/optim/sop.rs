@@ -0,0 +1,874 @@
+//! Two-level minimization of sums of products
+//!
+//! Covers are minimized either heuristically, with the expand, irredundant and reduce steps of
+//! Espresso, or exactly, by enumerating the prime implicants and solving the set cover problem.
+//!
+//! ```
+//! use quaigh::optim::{Cover, Cube};
+//!
+//! // a.b + a.!b + !a.b
+//! let mut cover = Cover::from_cubes(
+//!     2,
+//!     vec![
+//!         Cube::from_literals(&[(0, true), (1, true)]),
+//!         Cube::from_literals(&[(0, true), (1, false)]),
+//!         Cube::from_literals(&[(0, false), (1, true)]),
+//!     ],
+//! );
+//! cover.minimize();
+//!
+//! // a + b
+//! assert_eq!(cover.nb_cubes(), 2);
+//! assert_eq!(cover.nb_literals(), 2);
+//! ```
+
+use volute::Lut;
+
+use crate::network::stats::count_gate_usage;
+use crate::{Gate, Network, Signal};
+
+/// A product of literals, on up to 64 variables
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Cube {
+    /// Variables that appear as positive literals
+    pub(super) pos: u64,
+    /// Variables that appear as negative literals
+    pub(super) neg: u64,
+}
+
+impl Cube {
+    /// The cube without any literal, that is always true
+    pub fn one() -> Cube {
+        Cube { pos: 0, neg: 0 }
+    }
+
+    /// Create a cube from its literals, given as variable index and polarity
+    pub fn from_literals(literals: &[(usize, bool)]) -> Cube {
+        let mut ret = Cube::one();
+        for (v, pol) in literals {
+            assert!(*v < 64);
+            if *pol {
+                ret.pos |= 1 << v;
+            } else {
+                ret.neg |= 1 << v;
+            }
+        }
+        ret
+    }
+
+    /// Literals of the cube, as variable index and polarity
+    pub fn literals(&self) -> Vec<(usize, bool)> {
+        (0..64)
+            .filter_map(|v| self.literal(v).map(|pol| (v, pol)))
+            .collect()
+    }
+
+    /// Polarity of the literal for this variable, if it appears in the cube
+    pub fn literal(&self, var: usize) -> Option<bool> {
+        if (self.pos >> var) & 1 != 0 {
+            Some(true)
+        } else if (self.neg >> var) & 1 != 0 {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Number of literals in the cube
+    pub fn nb_literals(&self) -> usize {
+        (self.pos | self.neg).count_ones() as usize
+    }
+
+    /// Value of the cube for an assignment of the variables, given as a bitmask
+    pub fn value(&self, mask: u64) -> bool {
+        mask & self.pos == self.pos && mask & self.neg == 0
+    }
+
+    /// Returns whether all minterms of the other cube are in this one
+    pub fn contains(&self, other: &Cube) -> bool {
+        self.pos & !other.pos == 0 && self.neg & !other.neg == 0
+    }
+
+    /// Returns whether the two cubes share a minterm
+    pub fn intersects(&self, other: &Cube) -> bool {
+        self.distance(other) == 0
+    }
+
+    /// Number of variables that appear with opposite polarities in the two cubes
+    fn distance(&self, other: &Cube) -> u32 {
+        ((self.pos & other.neg) | (self.neg & other.pos)).count_ones()
+    }
+
+    /// Variables of the cube as a bitmask
+    pub(super) fn vars(&self) -> u64 {
+        self.pos | self.neg
+    }
+
+    /// Intersection of two intersecting cubes
+    fn intersection(&self, other: &Cube) -> Cube {
+        Cube {
+            pos: self.pos | other.pos,
+            neg: self.neg | other.neg,
+        }
+    }
+
+    /// Smallest cube containing both cubes
+    fn supercube(&self, other: &Cube) -> Cube {
+        Cube {
+            pos: self.pos & other.pos,
+            neg: self.neg & other.neg,
+        }
+    }
+
+    /// Remove the literals for some variables
+    fn without_vars(&self, vars: u64) -> Cube {
+        Cube {
+            pos: self.pos & !vars,
+            neg: self.neg & !vars,
+        }
+    }
+
+    /// Consensus of two cubes at distance 1
+    fn consensus(&self, other: &Cube) -> Cube {
+        let conflict = (self.pos & other.neg) | (self.neg & other.pos);
+        self.intersection(other).without_vars(conflict)
+    }
+}
+
+/// A sum of products, on up to 64 variables
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cover {
+    nb_vars: usize,
+    cubes: Vec<Cube>,
+}
+
+impl Cover {
+    /// Create an empty cover, that is always false
+    pub fn new(nb_vars: usize) -> Cover {
+        assert!(nb_vars <= 64);
+        Cover {
+            nb_vars,
+            cubes: Vec::new(),
+        }
+    }
+
+    /// Create a cover from its cubes
+    pub fn from_cubes(nb_vars: usize, cubes: Vec<Cube>) -> Cover {
+        let mut ret = Cover::new(nb_vars);
+        for c in cubes {
+            ret.add_cube(c);
+        }
+        ret
+    }
+
+    /// Create a cover from the minterms of a truth table
+    pub fn from_lut(lut: &Lut) -> Cover {
+        let n = lut.num_vars();
+        let all = if n == 64 { u64::MAX } else { (1 << n) - 1 };
+        let mut ret = Cover::new(n);
+        for m in 0..lut.num_bits() {
+            if lut.value(m) {
+                let m = m as u64;
+                ret.add_cube(Cube {
+                    pos: m,
+                    neg: all & !m,
+                });
+            }
+        }
+        ret
+    }
+
+    /// Compute the truth table of the cover
+    pub fn to_lut(&self) -> Lut {
+        let mut ret = Lut::zero(self.nb_vars);
+        for m in 0..ret.num_bits() {
+            ret.set_value(m, self.value(m as u64));
+        }
+        ret
+    }
+
+    /// Number of variables
+    pub fn nb_vars(&self) -> usize {
+        self.nb_vars
+    }
+
+    /// Cubes of the cover
+    pub fn cubes(&self) -> &[Cube] {
+        &self.cubes
+    }
+
+    /// Number of cubes
+    pub fn nb_cubes(&self) -> usize {
+        self.cubes.len()
+    }
+
+    /// Total number of literals in the cubes
+    pub fn nb_literals(&self) -> usize {
+        self.cubes.iter().map(|c| c.nb_literals()).sum()
+    }
+
+    /// Add a cube to the cover; empty cubes are ignored
+    pub fn add_cube(&mut self, cube: Cube) {
+        assert!(self.nb_vars == 64 || cube.vars() >> self.nb_vars == 0);
+        if cube.pos & cube.neg == 0 {
+            self.cubes.push(cube);
+        }
+    }
+
+    /// Value of the cover for an assignment of the variables, given as a bitmask
+    pub fn value(&self, mask: u64) -> bool {
+        self.cubes.iter().any(|c| c.value(mask))
+    }
+
+    /// Cost of the cover: number of cubes, then number of literals
+    fn cost(&self) -> (usize, usize) {
+        (self.nb_cubes(), self.nb_literals())
+    }
+
+    /// Cofactor of the cover with respect to a cube
+    fn cofactor(&self, cube: &Cube) -> Cover {
+        let cubes = self
+            .cubes
+            .iter()
+            .filter(|c| c.intersects(cube))
+            .map(|c| c.without_vars(cube.vars()))
+            .collect();
+        Cover {
+            nb_vars: self.nb_vars,
+            cubes,
+        }
+    }
+
+    /// Variable to split on for recursive algorithms: the most binate variable if any,
+    /// otherwise the most frequent
+    fn splitting_var(&self) -> Option<(usize, bool)> {
+        let mut best: Option<(usize, (usize, usize))> = None;
+        for v in 0..self.nb_vars {
+            let mut nb_pos = 0;
+            let mut nb_neg = 0;
+            for c in &self.cubes {
+                match c.literal(v) {
+                    Some(true) => nb_pos += 1,
+                    Some(false) => nb_neg += 1,
+                    None => (),
+                }
+            }
+            if nb_pos + nb_neg == 0 {
+                continue;
+            }
+            let score = (nb_pos.min(nb_neg), nb_pos + nb_neg);
+            if best.map(|b| score > b.1).unwrap_or(true) {
+                best = Some((v, score));
+            }
+        }
+        best.map(|(v, score)| (v, score.0 > 0))
+    }
+
+    /// Returns whether the cover is always true
+    fn is_tautology(&self) -> bool {
+        if self.cubes.iter().any(|c| c.nb_literals() == 0) {
+            return true;
+        }
+        match self.splitting_var() {
+            Some((v, true)) => {
+                self.cofactor(&Cube::from_literals(&[(v, true)]))
+                    .is_tautology()
+                    && self
+                        .cofactor(&Cube::from_literals(&[(v, false)]))
+                        .is_tautology()
+            }
+            // A unate cover is a tautology only if it contains the universal cube
+            _ => false,
+        }
+    }
+
+    /// Returns whether the cube is covered by the cover
+    fn covers(&self, cube: &Cube) -> bool {
+        self.cofactor(cube).is_tautology()
+    }
+
+    /// Compute the complement of the cover
+    pub fn complement(&self) -> Cover {
+        let mut ret = Cover::new(self.nb_vars);
+        if self.cubes.iter().any(|c| c.nb_literals() == 0) {
+            return ret;
+        }
+        if self.cubes.len() == 1 {
+            for (v, pol) in self.cubes[0].literals() {
+                ret.add_cube(Cube::from_literals(&[(v, !pol)]));
+            }
+            return ret;
+        }
+        let Some((v, _)) = self.splitting_var() else {
+            ret.add_cube(Cube::one());
+            return ret;
+        };
+        let lit1 = Cube::from_literals(&[(v, true)]);
+        let lit0 = Cube::from_literals(&[(v, false)]);
+        let c1 = self.cofactor(&lit1).complement();
+        let c0 = self.cofactor(&lit0).complement();
+        // Cubes present in both cofactors do not need the splitting variable
+        for c in &c1.cubes {
+            if c0.cubes.contains(c) {
+                ret.add_cube(*c);
+            } else {
+                ret.add_cube(c.intersection(&lit1));
+            }
+        }
+        for c in &c0.cubes {
+            if !c1.cubes.contains(c) {
+                ret.add_cube(c.intersection(&lit0));
+            }
+        }
+        ret
+    }
+
+    /// Remove cubes that are contained in another cube
+    fn remove_contained(&mut self) {
+        self.cubes.sort_by_key(|c| (c.nb_literals(), *c));
+        self.cubes.dedup();
+        let mut ret: Vec<Cube> = Vec::new();
+        for c in &self.cubes {
+            if !ret.iter().any(|r| r.contains(c)) {
+                ret.push(*c);
+            }
+        }
+        self.cubes = ret;
+    }
+
+    /// Expand each cube to a prime implicant, that does not intersect the off-set,
+    /// and remove the cubes it covers
+    fn expand(&mut self, off: &Cover) {
+        self.cubes.sort_by_key(|c| (c.nb_literals(), *c));
+        let mut ret: Vec<Cube> = Vec::new();
+        for c in &self.cubes {
+            if ret.iter().any(|r| r.contains(c)) {
+                continue;
+            }
+            // Raise the literals that block the fewest cubes of the off-set first
+            let mut literals = c.literals();
+            literals.sort_by_key(|(v, pol)| {
+                off.cubes
+                    .iter()
+                    .filter(|r| r.literal(*v) == Some(!pol))
+                    .count()
+            });
+            let mut e = *c;
+            for (v, _) in literals {
+                let t = e.without_vars(1 << v);
+                if !off.cubes.iter().any(|r| r.intersects(&t)) {
+                    e = t;
+                }
+            }
+            ret.retain(|r| !e.contains(r));
+            ret.push(e);
+        }
+        self.cubes = ret;
+    }
+
+    /// Remove redundant cubes, that are covered by the others
+    fn irredundant(&mut self) {
+        // Cubes with more literals are more likely to be redundant
+        self.cubes
+            .sort_by_key(|c| (std::cmp::Reverse(c.nb_literals()), *c));
+        let mut i = 0;
+        while i < self.cubes.len() {
+            let c = self.cubes[i];
+            let mut rest = self.clone();
+            rest.cubes.remove(i);
+            if rest.covers(&c) {
+                self.cubes.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Reduce each cube to the smallest cube that keeps the cover unchanged
+    fn reduce(&mut self) {
+        self.cubes
+            .sort_by_key(|c| (std::cmp::Reverse(c.nb_literals()), *c));
+        let mut i = 0;
+        while i < self.cubes.len() {
+            let c = self.cubes[i];
+            let mut rest = self.clone();
+            rest.cubes.remove(i);
+            let uncovered = rest.cofactor(&c).complement();
+            if uncovered.cubes.is_empty() {
+                self.cubes.remove(i);
+                continue;
+            }
+            let sc = uncovered.cubes[1..]
+                .iter()
+                .fold(uncovered.cubes[0], |a, b| a.supercube(b));
+            self.cubes[i] = c.intersection(&sc);
+            i += 1;
+        }
+    }
+
+    /// Minimize the cover heuristically, with the expand, irredundant and reduce steps of
+    /// Espresso
+    pub fn minimize(&mut self) {
+        self.remove_contained();
+        let off = self.complement();
+        self.expand(&off);
+        self.irredundant();
+        let mut best = self.clone();
+        loop {
+            self.reduce();
+            self.expand(&off);
+            self.irredundant();
+            if self.cost() < best.cost() {
+                best = self.clone();
+            } else {
+                break;
+            }
+        }
+        *self = best;
+    }
+
+    /// All prime implicants of the cover, obtained by iterated consensus
+    fn primes(&self) -> Vec<Cube> {
+        let mut primes = self.clone();
+        primes.remove_contained();
+        loop {
+            let mut new_cubes: Vec<Cube> = Vec::new();
+            let p = &primes.cubes;
+            for i in 0..p.len() {
+                for j in i + 1..p.len() {
+                    if p[i].distance(&p[j]) != 1 {
+                        continue;
+                    }
+                    let c = p[i].consensus(&p[j]);
+                    if !p.iter().chain(new_cubes.iter()).any(|r| r.contains(&c)) {
+                        new_cubes.push(c);
+                    }
+                }
+            }
+            if new_cubes.is_empty() {
+                return primes.cubes;
+            }
+            primes.cubes.extend(new_cubes);
+            primes.remove_contained();
+        }
+    }
+
+    /// Minimize the cover exactly, by solving a set cover problem on the prime implicants
+    ///
+    /// The minterms of the cover are enumerated, so this falls back to the heuristic for
+    /// more than 16 variables. The search is bounded, and returns the best solution found if
+    /// the bound is reached.
+    pub fn minimize_exact(&mut self) {
+        if self.nb_vars > 16 {
+            self.minimize();
+            return;
+        }
+        let primes = self.primes();
+
+        // Covering matrix, with one row for each minterm of the on-set
+        let mut rows: Vec<Vec<usize>> = Vec::new();
+        for m in 0..(1u64 << self.nb_vars) {
+            if self.value(m) {
+                let row: Vec<usize> = (0..primes.len()).filter(|p| primes[*p].value(m)).collect();
+                rows.push(row);
+            }
+        }
+        rows.sort();
+        rows.dedup();
+
+        // Start from the heuristic solution
+        let mut heuristic = self.clone();
+        heuristic.minimize();
+        let mut solver = SetCover::new(rows, &primes);
+        solver.best_cost = heuristic.nb_cubes() * SetCover::CUBE_COST + heuristic.nb_literals();
+        solver.solve();
+        if let Some(sol) = solver.best {
+            self.cubes = sol.iter().map(|p| primes[*p]).collect();
+        } else {
+            *self = heuristic;
+        }
+    }
+
+    /// Create the logic for the cover in a network, as an Or of Ands of the input signals
+    pub fn build(&self, aig: &mut Network, inputs: &[Signal]) -> Signal {
+        assert_eq!(inputs.len(), self.nb_vars);
+        let mut terms = Vec::new();
+        for c in &self.cubes {
+            let lits: Vec<Signal> = c
+                .literals()
+                .iter()
+                .map(|(v, pol)| inputs[*v] ^ !pol)
+                .collect();
+            terms.push(match lits.len() {
+                0 => Signal::one(),
+                1 => lits[0],
+                _ => aig.add_canonical(Gate::andn(&lits)),
+            });
+        }
+        match terms.len() {
+            0 => Signal::zero(),
+            1 => terms[0],
+            _ => {
+                let inv: Vec<Signal> = terms.iter().map(|s| !s).collect();
+                !aig.add_canonical(Gate::andn(&inv))
+            }
+        }
+    }
+}
+
+/// Branch and bound for the minimum cost set cover of the minterms by prime implicants
+struct SetCover {
+    /// Primes covering each minterm
+    rows: Vec<Vec<usize>>,
+    /// Minterms covered by each prime
+    cols: Vec<Vec<usize>>,
+    /// Cost of each prime
+    costs: Vec<usize>,
+    covered: Vec<usize>,
+    excluded: Vec<bool>,
+    chosen: Vec<usize>,
+    best: Option<Vec<usize>>,
+    best_cost: usize,
+    budget: usize,
+}
+
+impl SetCover {
+    /// Cost of a cube, so that the number of cubes is minimized first
+    const CUBE_COST: usize = 1 << 16;
+
+    fn new(rows: Vec<Vec<usize>>, primes: &[Cube]) -> SetCover {
+        let mut cols = vec![Vec::new(); primes.len()];
+        for (i, row) in rows.iter().enumerate() {
+            for p in row {
+                cols[*p].push(i);
+            }
+        }
+        SetCover {
+            covered: vec![0; rows.len()],
+            rows,
+            cols,
+            costs: primes
+                .iter()
+                .map(|p| SetCover::CUBE_COST + p.nb_literals())
+                .collect(),
+            excluded: vec![false; primes.len()],
+            chosen: Vec::new(),
+            best: None,
+            best_cost: usize::MAX,
+            budget: 100_000,
+        }
+    }
+
+    /// Lower bound on the cost to cover the remaining rows, from rows that share no column
+    fn lower_bound(&self) -> usize {
+        let mut used = vec![false; self.cols.len()];
+        let mut ret = 0;
+        for (i, row) in self.rows.iter().enumerate() {
+            if self.covered[i] != 0 {
+                continue;
+            }
+            let avail = row.iter().filter(|p| !self.excluded[**p]);
+            if avail.clone().any(|p| used[*p]) {
+                continue;
+            }
+            ret += avail.clone().map(|p| self.costs[*p]).min().unwrap_or(0);
+            for p in avail {
+                used[*p] = true;
+            }
+        }
+        ret
+    }
+
+    fn set_chosen(&mut self, p: usize, chosen: bool) {
+        for r in &self.cols[p] {
+            if chosen {
+                self.covered[*r] += 1;
+            } else {
+                self.covered[*r] -= 1;
+            }
+        }
+        if chosen {
+            self.chosen.push(p);
+        } else {
+            self.chosen.pop();
+        }
+    }
+
+    fn search(&mut self, cost: usize) {
+        if self.budget == 0 {
+            return;
+        }
+        self.budget -= 1;
+
+        // Uncovered row with the fewest available columns
+        let mut branch_row = None;
+        let mut min_cols = usize::MAX;
+        for (i, row) in self.rows.iter().enumerate() {
+            if self.covered[i] != 0 {
+                continue;
+            }
+            let nb = row.iter().filter(|p| !self.excluded[**p]).count();
+            if nb < min_cols {
+                min_cols = nb;
+                branch_row = Some(i);
+            }
+        }
+        let Some(r) = branch_row else {
+            if cost < self.best_cost {
+                self.best_cost = cost;
+                self.best = Some(self.chosen.clone());
+            }
+            return;
+        };
+        if min_cols == 0 || cost + self.lower_bound() >= self.best_cost {
+            return;
+        }
+
+        // Try the columns that cover the most rows first
+        let mut cands: Vec<usize> = self.rows[r]
+            .iter()
+            .copied()
+            .filter(|p| !self.excluded[*p])
+            .collect();
+        cands.sort_by_key(|p| {
+            let nb = self.cols[*p]
+                .iter()
+                .filter(|r| self.covered[**r] == 0)
+                .count();
+            (std::cmp::Reverse(nb), self.costs[*p])
+        });
+        for p in &cands {
+            self.set_chosen(*p, true);
+            self.search(cost + self.costs[*p]);
+            self.set_chosen(*p, false);
+            self.excluded[*p] = true;
+        }
+        for p in &cands {
+            self.excluded[*p] = false;
+        }
+    }
+
+    fn solve(&mut self) {
+        self.search(0);
+    }
+}
+
+/// Minimize a cover with the chosen algorithm
+fn minimize_cover(cover: &mut Cover, exact: bool) {
+    if exact {
+        cover.minimize_exact();
+    } else {
+        cover.minimize();
+    }
+}
+
+/// Minimize the two-level logic of a network
+///
+/// Lut gates are converted to sums of products, minimized, and rebuilt as And/Or logic.
+/// Two-level structures, such as those obtained from the cubes of `.names` statements in BLIF
+/// files, are minimized too and rebuilt if this reduces the number of cubes or literals.
+/// They are found as And gates whose inputs are inverted And gates without other users.
+pub fn minimize_sop(aig: &mut Network, exact: bool) {
+    let mut net = aig.clone();
+    if !net.is_topo_sorted() {
+        net.topo_sort();
+    }
+    let usage = count_gate_usage(&net);
+    let nb_nodes = net.nb_nodes();
+    let mut absorbed = vec![false; nb_nodes];
+    for i in (0..nb_nodes).rev() {
+        if absorbed[i] {
+            continue;
+        }
+        let g = net.gate(i).clone();
+        if let Gate::Lut(lut) = &g {
+            let mut on = Cover::from_lut(&lut.lut);
+            let mut off = Cover::from_lut(&!&lut.lut);
+            minimize_cover(&mut on, exact);
+            minimize_cover(&mut off, exact);
+            let s = if off.cost() < on.cost() {
+                !off.build(&mut net, &lut.inputs)
+            } else {
+                on.build(&mut net, &lut.inputs)
+            };
+            net.replace(i, Gate::Buf(s));
+        } else if g.is_and() {
+            // The complement of the And is a sum of products
+            let mut cubes: Vec<Vec<Signal>> = Vec::new();
+            let mut children = Vec::new();
+            for s in g.dependencies() {
+                if s.is_var() && s.is_inverted() {
+                    let v = s.var() as usize;
+                    if usage[v] == 1 && net.gate(v).is_and() {
+                        cubes.push(net.gate(v).dependencies().to_vec());
+                        children.push(v);
+                        continue;
+                    }
+                }
+                cubes.push(vec![!s]);
+            }
+            if children.is_empty() {
+                continue;
+            }
+            let mut leaves: Vec<Signal> = cubes
+                .iter()
+                .flatten()
+                .map(|s| s.without_inversion())
+                .collect();
+            leaves.sort();
+            leaves.dedup();
+            if leaves.len() > 64 {
+                continue;
+            }
+            let mut cover = Cover::new(leaves.len());
+            for c in &cubes {
+                let lits: Vec<(usize, bool)> = c
+                    .iter()
+                    .map(|s| {
+                        let v = leaves.binary_search(&s.without_inversion()).unwrap();
+                        (v, !s.is_inverted())
+                    })
+                    .collect();
+                cover.add_cube(Cube::from_literals(&lits));
+            }
+            let old_cost = cover.cost();
+            minimize_cover(&mut cover, exact);
+            if cover.cost() < old_cost {
+                let s = cover.build(&mut net, &leaves);
+                net.replace(i, Gate::Buf(!s));
+                for c in children {
+                    absorbed[c] = true;
+                }
+            }
+        }
+    }
+    net.topo_sort();
+    net.make_canonical();
+    net.cleanup();
+    *aig = net;
+}
+
+#[cfg(test)]
+mod tests {
+    use volute::Lut;
+
+    use super::{minimize_sop, Cover, Cube};
+    use crate::equiv::check_equivalence_bounded;
+    use crate::{Gate, Network, Signal};
+
+    /// Cover with all minterms of a 3-input function except 3 and 4, whose minimum cover has
+    /// 3 cubes and several solutions
+    fn cyclic_cover() -> Cover {
+        let mut lut = Lut::one(3);
+        lut.set_value(3, false);
+        lut.set_value(4, false);
+        Cover::from_lut(&lut)
+    }
+
+    #[test]
+    fn test_complement() {
+        for _ in 0..20 {
+            let lut = Lut::random(5);
+            let cover = Cover::from_lut(&lut);
+            assert_eq!(cover.to_lut(), lut);
+            assert_eq!(cover.complement().to_lut(), !&lut);
+        }
+    }
+
+    #[test]
+    fn test_tautology() {
+        let c = Cover::from_cubes(
+            2,
+            vec![
+                Cube::from_literals(&[(0, true)]),
+                Cube::from_literals(&[(0, false), (1, true)]),
+                Cube::from_literals(&[(1, false)]),
+            ],
+        );
+        assert!(c.is_tautology());
+        let c = Cover::from_cubes(
+            2,
+            vec![
+                Cube::from_literals(&[(0, true)]),
+                Cube::from_literals(&[(1, true)]),
+            ],
+        );
+        assert!(!c.is_tautology());
+    }
+
+    #[test]
+    fn test_heuristic() {
+        for _ in 0..20 {
+            let lut = Lut::random(6);
+            let mut cover = Cover::from_lut(&lut);
+            cover.minimize();
+            assert_eq!(cover.to_lut(), lut);
+            let minterms = Cover::from_lut(&lut);
+            assert!(cover.nb_cubes() <= minterms.nb_cubes());
+            assert!(cover.nb_literals() <= minterms.nb_literals());
+        }
+    }
+
+    #[test]
+    fn test_exact() {
+        let mut cover = cyclic_cover();
+        let lut = cover.to_lut();
+        cover.minimize_exact();
+        assert_eq!(cover.to_lut(), lut);
+        assert_eq!(cover.nb_cubes(), 3);
+        assert_eq!(cover.nb_literals(), 6);
+
+        for _ in 0..10 {
+            let lut = Lut::random(5);
+            let mut exact = Cover::from_lut(&lut);
+            exact.minimize_exact();
+            assert_eq!(exact.to_lut(), lut);
+            let mut heuristic = Cover::from_lut(&lut);
+            heuristic.minimize();
+            assert!(exact.cost() <= heuristic.cost());
+        }
+    }
+
+    #[test]
+    fn test_lut() {
+        let mut aig = Network::new();
+        let inputs: Vec<Signal> = (0..4).map(|_| aig.add_input()).collect();
+        let x = aig.add(Gate::lut(&inputs, Lut::threshold(4, 3)));
+        let y = aig.add(Gate::lut(
+            &inputs[..2],
+            Lut::nth_var(2, 0) | Lut::nth_var(2, 1),
+        ));
+        aig.add_output(x);
+        aig.add_output(y);
+        for exact in [false, true] {
+            let mut min = aig.clone();
+            minimize_sop(&mut min, exact);
+            assert!((0..min.nb_nodes()).all(|i| !matches!(min.gate(i), Gate::Lut(_))));
+            check_equivalence_bounded(&aig, &min, 1, false).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_blif_cover() {
+        use crate::io::read_blif;
+
+        let example = ".model m
+.inputs a b c
+.outputs y
+.names a b c y
+111 1
+110 1
+101 1
+100 1
+011 1
+.end
+";
+        let aig = read_blif(example.as_bytes()).unwrap();
+        let mut min = aig.clone();
+        min.make_canonical();
+        minimize_sop(&mut min, false);
+        // a + b.c
+        assert_eq!(min.nb_nodes(), 2);
+        check_equivalence_bounded(&aig, &min, 1, false).unwrap();
+    }
+}
@@ -8,6 +8,7 @@ use crate::sim::incremental_sim::IncrementalSimulator;
 use crate::Network;
 
 pub use fault::Fault;
+pub(crate) use simple_sim::{SimpleSimulator, NB_RANDOM_WORDS};
 
 /// Simple conversion to 64b format
 fn bool_to_multi(values: &Vec<Vec<bool>>) -> Vec<Vec<u64>> {
@@ -121,7 +122,7 @@ mod tests {
     use volute::{Lut3, Lut5};
 
     use crate::network::NaryType;
-    use crate::sim::simulate_multi;
+    use crate::sim::{simulate_comb_with_faults, simulate_multi, Fault};
     use crate::{Gate, Network, Signal};
 
     use super::simulate;
@@ -357,4 +358,33 @@ mod tests {
 
         assert_eq!(simulate_multi(&aig, &pattern), expected);
     }
+
+    #[test]
+    fn test_redundant_fault_polarity() {
+        // x drives the And through an inversion, so x stuck at 1 forces y to 0
+        let mut aig = Network::default();
+        let a = aig.add_input();
+        let b = aig.add_input();
+        let c = aig.add_input();
+        let x = aig.and(a, b);
+        let y = aig.and(!x, c);
+        aig.add_output(y);
+        let x_stuck = |value| Fault::OutputStuckAtFault { gate: 0, value };
+        let y_stuck = Fault::OutputStuckAtFault {
+            gate: 1,
+            value: false,
+        };
+        let redundant = Fault::redundant_faults(&aig);
+        assert!(redundant.contains(&x_stuck(true)));
+        assert!(!redundant.contains(&x_stuck(false)));
+
+        let sim = |f: Fault, inputs: &Vec<bool>| simulate_comb_with_faults(&aig, inputs, &vec![f]);
+        let mut x_stuck_0_detected = false;
+        for v in 0..8 {
+            let inputs: Vec<bool> = (0..3).map(|i| (v >> i) & 1 != 0).collect();
+            assert_eq!(sim(x_stuck(true), &inputs), sim(y_stuck, &inputs));
+            x_stuck_0_detected |= sim(x_stuck(false), &inputs) != sim(y_stuck, &inputs);
+        }
+        assert!(x_stuck_0_detected);
+    }
 }
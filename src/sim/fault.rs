@@ -87,9 +87,10 @@ impl Fault {
                         if value == input_inv {
                             ret.push(Fault::InputStuckAtFault { gate, input, value });
                             if is_single_use(s) {
+                                // The equivalent fault on the output takes the inversion into account
                                 ret.push(Fault::OutputStuckAtFault {
                                     gate: s.var() as usize,
-                                    value,
+                                    value: value ^ s.is_inverted(),
                                 });
                             }
                         }
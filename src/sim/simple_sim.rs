@@ -1,3 +1,4 @@
+use rand::Rng;
 use volute::Lut;
 
 use crate::network::{BinaryType, NaryType, TernaryType};
@@ -5,6 +6,9 @@ use crate::{Network, Signal};
 
 use super::Fault;
 
+/// Number of random 64-bit words simulated initially by the simulation-guided optimizations
+pub(crate) const NB_RANDOM_WORDS: usize = 8;
+
 /// Structure for simulation based directly on the network representation
 ///
 /// This is simple to write and relatively efficient, but could be greatly improved
@@ -43,6 +47,63 @@ impl<'a> SimpleSimulator<'a> {
         }
     }
 
+    /// Build a simulator from existing values of the inputs and nodes
+    ///
+    /// The network is not required to be topologically sorted: this is meant to resimulate
+    /// individual gates with [`SimpleSimulator::run_gate`].
+    pub(crate) fn from_values(
+        aig: &'a Network,
+        input_values: Vec<u64>,
+        node_values: Vec<u64>,
+    ) -> SimpleSimulator<'a> {
+        assert_eq!(input_values.len(), aig.nb_inputs());
+        assert_eq!(node_values.len(), aig.nb_nodes());
+        SimpleSimulator {
+            aig,
+            input_values,
+            node_values,
+        }
+    }
+
+    /// Simulate the combinatorial logic for one word, with the given values for the inputs and
+    /// the flip-flops
+    ///
+    /// Returns the values of the nodes.
+    pub(crate) fn simulate_word(aig: &Network, inputs: &[u64], dffs: &[u64]) -> Vec<u64> {
+        let mut sim = SimpleSimulator::from_aig(aig);
+        let mut dff_values = dffs.iter();
+        for i in 0..aig.nb_nodes() {
+            if !aig.gate(i).is_comb() {
+                sim.node_values[i] = *dff_values.next().unwrap();
+            }
+        }
+        assert!(dff_values.next().is_none());
+        sim.copy_inputs(inputs);
+        sim.run_comb();
+        sim.node_values
+    }
+
+    /// Generate random values for the inputs and the flip-flops of a network
+    ///
+    /// Returns one pair of input and flip-flop values per word, to be used with
+    /// [`SimpleSimulator::simulate_word`].
+    pub(crate) fn random_words(
+        aig: &Network,
+        nb_words: usize,
+        rng: &mut impl Rng,
+    ) -> Vec<(Vec<u64>, Vec<u64>)> {
+        let nb_dffs = (0..aig.nb_nodes())
+            .filter(|i| !aig.gate(*i).is_comb())
+            .count();
+        (0..nb_words)
+            .map(|_| {
+                let inputs = (0..aig.nb_inputs()).map(|_| rng.gen()).collect();
+                let dffs = (0..nb_dffs).map(|_| rng.gen()).collect();
+                (inputs, dffs)
+            })
+            .collect()
+    }
+
     /// Run the simulation
     pub fn run(&mut self, input_values: &Vec<Vec<u64>>) -> Vec<Vec<u64>> {
         self.check();